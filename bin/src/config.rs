@@ -1,14 +1,132 @@
 use serde::Deserialize;
 
+use shared::{camera::LightingStrategy, colour::ColourSpace, integrator::MetropolisLightTransport};
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
     image: ConfigImage,
+    #[serde(default)]
+    lighting: Lighting,
+    #[serde(default)]
+    metropolis: Metropolis,
+    #[serde(default)]
+    colour: Colour,
 }
 
 impl Config {
     pub fn get_image(&mut self) -> Option<Image> {
         self.image.get()
     }
+
+    pub fn get_lighting_strategy(&self) -> LightingStrategy {
+        self.lighting.into()
+    }
+
+    pub fn get_metropolis(&self) -> MetropolisLightTransport {
+        self.metropolis.into()
+    }
+
+    pub fn get_working_space(&self) -> ColourSpace {
+        self.colour.working_space.into()
+    }
+
+    pub fn get_output_space(&self) -> ColourSpace {
+        self.colour.output_space.into()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+enum Lighting {
+    #[default]
+    Mixture,
+    Ris {
+        #[serde(default = "Lighting::default_candidates")]
+        candidates: u16,
+        #[serde(default)]
+        spatial_reuse: bool,
+    },
+}
+
+impl Lighting {
+    const fn default_candidates() -> u16 {
+        32
+    }
+}
+
+impl From<Lighting> for LightingStrategy {
+    fn from(value: Lighting) -> Self {
+        match value {
+            Lighting::Mixture => Self::Mixture,
+            Lighting::Ris {
+                candidates,
+                spatial_reuse,
+            } => Self::Ris {
+                candidates,
+                spatial_reuse,
+            },
+        }
+    }
+}
+
+/// Mutation parameters of the Metropolis integrator, missing ones keep their defaults.
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+struct Metropolis {
+    large_step_probability: Option<f64>,
+    sigma: Option<f64>,
+    bootstrap_samples: Option<u32>,
+    chains: Option<u32>,
+}
+
+impl From<Metropolis> for MetropolisLightTransport {
+    fn from(value: Metropolis) -> Self {
+        let mut metropolis = Self::new();
+        if let Some(large_step_probability) = value.large_step_probability {
+            metropolis = metropolis.with_large_step_probability(large_step_probability);
+        }
+        if let Some(sigma) = value.sigma {
+            metropolis = metropolis.with_sigma(sigma);
+        }
+        if let Some(bootstrap_samples) = value.bootstrap_samples {
+            metropolis = metropolis.with_bootstrap_samples(bootstrap_samples);
+        }
+        if let Some(chains) = value.chains {
+            metropolis = metropolis.with_chains(chains);
+        }
+        metropolis
+    }
+}
+
+/// Space the paths are traced in and space of the written image.
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+struct Colour {
+    #[serde(default)]
+    working_space: Space,
+    #[serde(default)]
+    output_space: Space,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+enum Space {
+    #[default]
+    Srgb,
+    DisplayP3,
+    Rec2020,
+    Acescg,
+    Aces2065,
+}
+
+impl From<Space> for ColourSpace {
+    fn from(value: Space) -> Self {
+        match value {
+            Space::Srgb => Self::Srgb,
+            Space::DisplayP3 => Self::DisplayP3,
+            Space::Rec2020 => Self::Rec2020,
+            Space::Acescg => Self::AcesCg,
+            Space::Aces2065 => Self::Aces2065,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
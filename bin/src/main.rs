@@ -1,28 +1,92 @@
 use std::{
     fs::{File, read_to_string},
-    io::{BufWriter, Write as _},
+    io::BufWriter,
+    sync::Arc,
 };
 
 use crate::{
-    cli::{Args, Scenes},
+    cli::{Args, Integrators, Scenes},
     config::{Config, Image},
 };
 
 use clap::Parser;
 
 use scenes::{
-    SceneGenerator, checkered_spheres, cornell_box, debugging_scene, perlin_spheres, plane, simple,
-    simple_light, simple_transform,
+    SceneGenerator, bouncing_spheres, checkered_spheres, cornell_box, debugging_scene,
+    perlin_spheres, plane, simple, simple_light, simple_transform,
+};
+use shared::{
+    image::{write_exr, write_ppm},
+    integrator::{
+        AmbientOcclusion, BidirectionalPathTracer, DebugView, DirectLighting, GuidedPathTracer,
+        Integrator, MetropolisLightTransport, PathTracer, ProgressivePhotonMapper, Whitted,
+    },
+    scene::AnimatedScene,
 };
 
 mod config;
 mod cli {
+    use std::ops::Range;
+
     use clap::{Parser, ValueEnum};
     #[derive(Debug, Parser)]
     pub struct Args {
         pub scene: Scenes,
         #[arg(long)]
         pub debug: bool,
+        #[arg(long, value_enum, default_value_t = Integrators::Path)]
+        pub integrator: Integrators,
+        /// Maximum distance used by the ambient occlusion and depth integrators
+        #[arg(long, default_value_t = 100.)]
+        pub distance: f64,
+        /// Initial gather radius of the photon mapper
+        #[arg(long, default_value_t = 1.)]
+        pub radius: f64,
+        /// Seed of the random numbers of the pixels, to reproduce a render
+        #[arg(long)]
+        pub seed: Option<u64>,
+        /// Trace wavelengths instead of RGB, needed for dispersion
+        #[arg(long)]
+        pub spectral: bool,
+        /// Colour temperature, in Kelvin, which is made white
+        #[arg(long)]
+        pub white_balance: Option<f64>,
+        /// Written as OpenEXR if it ends in `.exr`, otherwise as PPM
+        #[arg(long, default_value = "image.ppm")]
+        pub output: String,
+        /// Renders the frames `start..end` of the animation, numbering the output of each one
+        #[arg(long, value_parser = parse_frames)]
+        pub frames: Option<Range<u32>>,
+        /// Builds the hierarchy of the whole scene for every frame, instead of reusing the one
+        /// of the static objects
+        #[arg(long)]
+        pub rebuild_static: bool,
+    }
+
+    fn parse_frames(frames: &str) -> Result<Range<u32>, String> {
+        let (start, end) = frames
+            .split_once("..")
+            .ok_or_else(|| format!("`{frames}` isn't of the form `start..end`"))?;
+        let start = start.parse().map_err(|err| format!("{err}"))?;
+        let end = end.parse().map_err(|err| format!("{err}"))?;
+        Ok(start..end)
+    }
+
+    #[derive(Debug, ValueEnum, Clone, Copy)]
+    pub enum Integrators {
+        Path,
+        Guided,
+        Bidirectional,
+        PhotonMapping,
+        Metropolis,
+        Direct,
+        Whitted,
+        AmbientOcclusion,
+        Normal,
+        Uv,
+        Albedo,
+        Depth,
+        BvhCost,
     }
 
     #[derive(Debug, ValueEnum, Clone, Copy)]
@@ -35,11 +99,13 @@ mod cli {
         Simple,
         SimpleLight,
         SimpleTransform,
+        BouncingSpheres,
     }
 }
 
-fn get_scene_generator(scene: Scenes) -> &'static dyn SceneGenerator {
-    match scene {
+/// Still scenes are animations which don't change.
+fn get_animation(scene: Scenes) -> AnimatedScene {
+    let scene_generator: &dyn SceneGenerator = match scene {
         cli::Scenes::CornellBox => &cornell_box,
         cli::Scenes::Debug => &debugging_scene,
         cli::Scenes::CheckeredSpheres => &checkered_spheres,
@@ -48,6 +114,41 @@ fn get_scene_generator(scene: Scenes) -> &'static dyn SceneGenerator {
         cli::Scenes::Simple => &simple,
         cli::Scenes::SimpleLight => &simple_light,
         cli::Scenes::SimpleTransform => &simple_transform,
+        cli::Scenes::BouncingSpheres => return bouncing_spheres(),
+    };
+    scene_generator.generate_scene().into()
+}
+
+/// `output` with the frame number before the extension, `image.ppm` becomes `image_0007.ppm`.
+fn frame_path(output: &str, frame: u32) -> String {
+    match output.rsplit_once('.') {
+        Some((stem, extension)) => format!("{stem}_{frame:04}.{extension}"),
+        None => format!("{output}_{frame:04}"),
+    }
+}
+
+fn get_integrator(
+    integrator: Integrators,
+    distance: f64,
+    radius: f64,
+    metropolis: MetropolisLightTransport,
+) -> Arc<dyn Integrator> {
+    match integrator {
+        Integrators::Path => Arc::new(PathTracer),
+        Integrators::Guided => Arc::new(GuidedPathTracer::new()),
+        Integrators::Bidirectional => Arc::new(BidirectionalPathTracer),
+        Integrators::PhotonMapping => Arc::new(ProgressivePhotonMapper::new(radius)),
+        Integrators::Metropolis => Arc::new(metropolis),
+        Integrators::Direct => Arc::new(DirectLighting),
+        Integrators::Whitted => Arc::new(Whitted::new()),
+        Integrators::AmbientOcclusion => Arc::new(AmbientOcclusion::new(distance).with_samples(4)),
+        Integrators::Normal => Arc::new(DebugView::Normal),
+        Integrators::Uv => Arc::new(DebugView::Uv),
+        Integrators::Albedo => Arc::new(DebugView::Albedo),
+        Integrators::Depth => Arc::new(DebugView::Depth {
+            max_distance: distance,
+        }),
+        Integrators::BvhCost => Arc::new(DebugView::BvhCost { max_cost: 64 }),
     }
 }
 
@@ -65,41 +166,63 @@ fn main() {
     } = config.get_image().unwrap();
 
     // World
-    let scene_generator = get_scene_generator(args.scene);
-    let (world, lights, cam) = scene_generator.generate_scene();
+    let animation = get_animation(args.scene).with_static_reuse(!args.rebuild_static);
 
     // Camera
-    let cam = cam
-        .with_vfov(40.)
-        .with_aspect_ratio(aspect_ratio)
-        .with_max_depth(max_depth as _)
-        .with_image_width(image_width)
-        .with_image_height(image_height)
-        .with_samples_per_pixel(samples_per_pixel)
-        .build();
-
-    // Render
-    let out = if args.debug {
-        cam.render_debug(world.as_ref(), lights.as_ref())
-    } else {
-        cam.render(world.as_ref(), lights.as_ref())
+    let animation = animation.map_camera(|cam| {
+        let cam = cam
+            .with_vfov(40.)
+            .with_aspect_ratio(aspect_ratio)
+            .with_max_depth(max_depth as _)
+            .with_image_width(image_width)
+            .with_image_height(image_height)
+            .with_samples_per_pixel(samples_per_pixel)
+            .with_lighting_strategy(config.get_lighting_strategy())
+            .with_integrator(get_integrator(
+                args.integrator,
+                args.distance,
+                args.radius,
+                config.get_metropolis(),
+            ))
+            .with_spectral(args.spectral)
+            .with_working_space(config.get_working_space());
+        let cam = match args.seed {
+            Some(seed) => cam.with_seed(seed),
+            None => cam,
+        };
+        match args.white_balance {
+            Some(temperature) => cam.with_white_balance(temperature),
+            None => cam,
+        }
+    });
+
+    let frames = match &args.frames {
+        Some(frames) => frames
+            .clone()
+            .map(|frame| (frame, frame_path(&args.output, frame)))
+            .collect(),
+        None => vec![(0, args.output.clone())],
     };
+    for (frame, output) in frames {
+        let time = f64::from(frame);
+        let cam = animation.camera_at(time).build();
+        let scene = animation.scene_at(time);
+
+        // Render
+        let out = if args.debug {
+            cam.render_scene_debug(&scene)
+        } else {
+            cam.render_scene(&scene)
+        };
 
-    // Temp
-    let file_name = "image.ppm";
-    let mut file = BufWriter::new(File::create(file_name).unwrap());
-    file.write_fmt(format_args!("P3\n{image_width} {image_height}\n255\n"))
-        .unwrap();
-    #[cfg_attr(not(debug_assertions), expect(unused))]
-    for (j, i, val) in out
-        .into_iter()
-        .enumerate()
-        .rev()
-        .flat_map(|(j, v)| v.into_iter().enumerate().map(move |(i, val)| (j, i, val)))
-    {
-        #[cfg(debug_assertions)]
-        dbg!(j, i);
-
-        file.write_fmt(format_args!("{val}\n")).unwrap();
+        // Output
+        let file = BufWriter::new(File::create(&output).unwrap());
+        let working_space = cam.get_working_space();
+        let output_space = config.get_output_space();
+        if output.ends_with(".exr") {
+            write_exr(file, &out, working_space, output_space).unwrap();
+        } else {
+            write_ppm(file, &out, working_space, output_space).unwrap();
+        }
     }
 }
@@ -34,6 +34,19 @@ pub trait Box3DExt {
     fn compare_by_axis(&self, other: &Self, axis: Axis) -> Ordering {
         self.axis(axis).start().total_cmp(other.axis(axis).start())
     }
+    fn is_finite(&self) -> bool {
+        crate::aaplane::get_axis().into_iter().all(|axis| {
+            let range = self.axis(axis);
+            range.start().is_finite() && range.end().is_finite()
+        })
+    }
+
+    fn has_nan(&self) -> bool {
+        crate::aaplane::get_axis().into_iter().any(|axis| {
+            let range = self.axis(axis);
+            range.start().is_nan() || range.end().is_nan()
+        })
+    }
 }
 
 #[cfg(feature = "euclid")]
@@ -189,6 +202,28 @@ mod inner {
             self.axis(axis).start().total_cmp(other.axis(axis).start())
         }
 
+        /// Whether every side is at a finite distance, which isn't the case for the boxes of
+        /// unbounded objects like planes.
+        #[must_use]
+        pub const fn is_finite(&self) -> bool {
+            self.min.x.is_finite()
+                && self.min.y.is_finite()
+                && self.min.z.is_finite()
+                && self.max.x.is_finite()
+                && self.max.y.is_finite()
+                && self.max.z.is_finite()
+        }
+
+        #[must_use]
+        pub const fn has_nan(&self) -> bool {
+            self.min.x.is_nan()
+                || self.min.y.is_nan()
+                || self.min.z.is_nan()
+                || self.max.x.is_nan()
+                || self.max.y.is_nan()
+                || self.max.z.is_nan()
+        }
+
         /// # Panics
         /// If the iterator is empty this will panic
         pub fn from_points<I>(points: I) -> Self
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use crate::aabox::AABBox;
 #[cfg(feature = "euclid")]
 use crate::vec3::Vec3;
@@ -7,6 +9,16 @@ pub trait Bounded {
     fn get_surface_area(&self) -> f64;
 }
 
+impl<T: Bounded + ?Sized> Bounded for Arc<T> {
+    fn get_aabbox(&self) -> AABBox {
+        (**self).get_aabbox()
+    }
+
+    fn get_surface_area(&self) -> f64 {
+        (**self).get_surface_area()
+    }
+}
+
 #[cfg(feature = "euclid")]
 impl Bounded for Vec3 {
     fn get_aabbox(&self) -> AABBox {
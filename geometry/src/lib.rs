@@ -2,7 +2,9 @@ pub mod aabox;
 pub mod aaplane;
 pub mod bounded;
 pub mod matrix3;
+pub mod matrix4;
 pub mod onb;
+pub mod quaternion;
 pub mod transformations;
 pub mod vec3;
 
@@ -34,6 +34,13 @@ impl Matrix3 {
         let [[a, b, c], [d, e, f], [g, h, i]] = self.0;
         a * (e * i - f * h) + b * (f * g - d * i) + c * (d * h - e * g)
     }
+
+    #[must_use]
+    pub fn transpose(self) -> Self {
+        Self(std::array::from_fn(|i| {
+            std::array::from_fn(|j| self.0[j][i])
+        }))
+    }
 }
 
 impl From<[[f64; 3]; 3]> for Matrix3 {
@@ -0,0 +1,127 @@
+use std::ops::Mul;
+
+use crate::{matrix3::Matrix3, vec3::Vec3};
+
+/// Matrix of a projective transformation acting on column vectors `(x, y, z, w)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4(pub(crate) [[f64; 4]; 4]);
+
+impl Matrix4 {
+    /// Affine transformation applying `linear` and then moving by `translation`.
+    #[must_use]
+    pub fn affine(linear: Matrix3, translation: [f64; 3]) -> Self {
+        Self(std::array::from_fn(|i| {
+            std::array::from_fn(|j| match (i, j) {
+                (3, 3) => 1.,
+                (3, _) => 0.,
+                (_, 3) => translation[i],
+                _ => linear.0[i][j],
+            })
+        }))
+    }
+
+    #[must_use]
+    pub fn linear_part(self) -> Matrix3 {
+        Matrix3(std::array::from_fn(|i| {
+            std::array::from_fn(|j| self.0[i][j])
+        }))
+    }
+
+    #[must_use]
+    pub const fn translation(self) -> [f64; 3] {
+        [self.0[0][3], self.0[1][3], self.0[2][3]]
+    }
+
+    /// Whether it keeps parallel lines parallel, which is the case unless the last row
+    /// isn't `(0, 0, 0, 1)`.
+    // Products of affine matrices keep the last row exactly
+    #[allow(clippy::float_cmp)]
+    #[must_use]
+    pub fn is_affine(self) -> bool {
+        self.0[3] == [0., 0., 0., 1.]
+    }
+
+    #[must_use]
+    pub fn transpose(self) -> Self {
+        Self(std::array::from_fn(|i| {
+            std::array::from_fn(|j| self.0[j][i])
+        }))
+    }
+
+    #[must_use]
+    pub fn det(self) -> f64 {
+        (0..4)
+            .map(|j| {
+                let sign = if j % 2 == 0 { 1. } else { -1. };
+                sign * self.0[0][j] * self.minor(0, j).det()
+            })
+            .sum()
+    }
+
+    fn minor(self, row: usize, column: usize) -> Matrix3 {
+        let skip = |k: usize, skipped: usize| if k < skipped { k } else { k + 1 };
+        Matrix3(std::array::from_fn(|i| {
+            std::array::from_fn(|j| self.0[skip(i, row)][skip(j, column)])
+        }))
+    }
+
+    #[must_use]
+    pub fn inverse(self) -> Option<Self> {
+        if self.is_affine() {
+            let linear = self.linear_part().inverse()?;
+            let [x, y, z] = self.translation();
+            let translation = linear * Vec3::new(x, y, z);
+            return Some(Self::affine(
+                linear,
+                [-translation.x, -translation.y, -translation.z],
+            ));
+        }
+        let det = self.det();
+        if !det.is_normal() {
+            return None;
+        }
+        // Transposed matrix of cofactors over the determinant
+        Some(Self(std::array::from_fn(|i| {
+            std::array::from_fn(|j| {
+                let sign = if (i + j) % 2 == 0 { 1. } else { -1. };
+                sign * self.minor(j, i).det() / det
+            })
+        })))
+    }
+
+    /// Applies the matrix to `(x, y, z, w)`.
+    #[must_use]
+    pub fn apply(self, vector: [f64; 4]) -> [f64; 4] {
+        self.0
+            .map(|row| row.iter().zip(vector).map(|(m, v)| m * v).sum())
+    }
+}
+
+impl Default for Matrix4 {
+    fn default() -> Self {
+        Self::affine(Matrix3::default(), [0.; 3])
+    }
+}
+
+impl From<[[f64; 4]; 4]> for Matrix4 {
+    fn from(value: [[f64; 4]; 4]) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Matrix3> for Matrix4 {
+    fn from(value: Matrix3) -> Self {
+        Self::affine(value, [0.; 3])
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self(std::array::from_fn(|i| {
+            std::array::from_fn(|j| (0..4).map(|k| self.0[i][k] * rhs.0[k][j]).sum())
+        }))
+    }
+}
@@ -0,0 +1,273 @@
+use std::ops::Mul;
+
+use crate::{matrix3::Matrix3, vec3::Vec3};
+
+/// Rotation as a unit quaternion `w + xi + yj + zk`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+/// Rotations around the x, y and z axes in degrees, applied in that order.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct EulerAngles {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    #[must_use]
+    pub const fn identity() -> Self {
+        Self {
+            w: 1.,
+            x: 0.,
+            y: 0.,
+            z: 0.,
+        }
+    }
+
+    /// Rotation of `angle` degrees around `axis`, counterclockwise when looking against it.
+    #[must_use]
+    pub fn from_axis_angle(axis: Vec3, angle: f64) -> Self {
+        let axis = axis.normalize();
+        let (sin, cos) = (angle.to_radians() / 2.).sin_cos();
+        Self {
+            w: cos,
+            x: axis.x * sin,
+            y: axis.y * sin,
+            z: axis.z * sin,
+        }
+    }
+
+    #[must_use]
+    pub fn from_euler(angles: EulerAngles) -> Self {
+        let x = Self::from_axis_angle(Vec3::new(1., 0., 0.), angles.x);
+        let y = Self::from_axis_angle(Vec3::new(0., 1., 0.), angles.y);
+        let z = Self::from_axis_angle(Vec3::new(0., 0., 1.), angles.z);
+        z * y * x
+    }
+
+    /// Angles of [`from_euler`](Quaternion::from_euler) giving this rotation, with the y angle
+    /// in `[-90, 90]`.
+    #[must_use]
+    pub fn to_euler(self) -> EulerAngles {
+        let [[m00, _, _], [m10, m11, m12], [m20, m21, m22]] = self.to_matrix().0;
+        let y = (-m20).clamp(-1., 1.).asin().to_degrees();
+        if m20.abs() < 1. - 1e-12 {
+            EulerAngles {
+                x: m21.atan2(m22).to_degrees(),
+                y,
+                z: m10.atan2(m00).to_degrees(),
+            }
+        } else {
+            // Gimbal lock, only the difference of the x and z angles matters
+            EulerAngles {
+                x: (-m12).atan2(m11).to_degrees(),
+                y,
+                z: 0.,
+            }
+        }
+    }
+
+    /// Rotation of a matrix without scale or reflection, see "Converting a Rotation Matrix to a
+    /// Quaternion" by Day.
+    #[must_use]
+    pub fn from_matrix(matrix: Matrix3) -> Self {
+        let [[m00, m01, m02], [m10, m11, m12], [m20, m21, m22]] = matrix.0;
+        let (t, q) = if m22 < 0. {
+            if m00 > m11 {
+                let t = 1. + m00 - m11 - m22;
+                (t, [m21 - m12, t, m01 + m10, m20 + m02])
+            } else {
+                let t = 1. - m00 + m11 - m22;
+                (t, [m02 - m20, m01 + m10, t, m12 + m21])
+            }
+        } else if m00 < -m11 {
+            let t = 1. - m00 - m11 + m22;
+            (t, [m10 - m01, m20 + m02, m12 + m21, t])
+        } else {
+            let t = 1. + m00 + m11 + m22;
+            (t, [t, m21 - m12, m02 - m20, m10 - m01])
+        };
+        let scale = 0.5 / t.sqrt();
+        Self {
+            w: q[0] * scale,
+            x: q[1] * scale,
+            y: q[2] * scale,
+            z: q[3] * scale,
+        }
+    }
+
+    #[must_use]
+    pub fn to_matrix(self) -> Matrix3 {
+        let Self { w, x, y, z } = self;
+        Matrix3([
+            [
+                1. - 2. * (y * y + z * z),
+                2. * (x * y - w * z),
+                2. * (x * z + w * y),
+            ],
+            [
+                2. * (x * y + w * z),
+                1. - 2. * (x * x + z * z),
+                2. * (y * z - w * x),
+            ],
+            [
+                2. * (x * z - w * y),
+                2. * (y * z + w * x),
+                1. - 2. * (x * x + y * y),
+            ],
+        ])
+    }
+
+    #[must_use]
+    pub fn dot(self, other: Self) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    #[must_use]
+    pub fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    #[must_use]
+    pub fn normalize(self) -> Self {
+        self.scale(self.length().recip())
+    }
+
+    /// Opposite rotation.
+    #[must_use]
+    pub const fn conjugate(self) -> Self {
+        Self {
+            w: self.w,
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+
+    #[must_use]
+    pub fn rotate(self, vector: Vec3) -> Vec3 {
+        self.to_matrix() * vector
+    }
+
+    /// Rotation `t` of the way from `self` to `other` along the shortest arc, at a constant
+    /// angular speed.
+    #[must_use]
+    pub fn slerp(self, other: Self, t: f64) -> Self {
+        // `q` and `-q` are the same rotation, the closest one is the shortest way
+        let (other, cos) = match self.dot(other) {
+            cos if cos < 0. => (other.scale(-1.), -cos),
+            cos => (other, cos),
+        };
+        if cos > 1. - 1e-9 {
+            return self.scale(1. - t).add(other.scale(t)).normalize();
+        }
+        let theta = cos.acos();
+        let sin = theta.sin();
+        self.scale(((1. - t) * theta).sin() / sin)
+            .add(other.scale((t * theta).sin() / sin))
+    }
+
+    const fn scale(self, s: f64) -> Self {
+        Self {
+            w: self.w * s,
+            x: self.x * s,
+            y: self.y * s,
+            z: self.z * s,
+        }
+    }
+
+    const fn add(self, other: Self) -> Self {
+        Self {
+            w: self.w + other.w,
+            x: self.x + other.x,
+            y: self.y + other.y,
+            z: self.z + other.z,
+        }
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// Rotating by `self * rhs` is rotating by `rhs` and then by `self`.
+impl Mul for Quaternion {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self {
+            w: self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            x: self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            y: self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            z: self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::vec3::Vec3;
+
+    use super::{EulerAngles, Quaternion};
+
+    fn rotation() -> impl Strategy<Value = Quaternion> {
+        prop::array::uniform4(-1. ..1.)
+            .prop_filter("too short to normalize", |q| {
+                q.iter().map(|v| v * v).sum::<f64>() > 1e-3
+            })
+            .prop_map(|[w, x, y, z]| Quaternion { w, x, y, z }.normalize())
+    }
+
+    fn assert_same_rotation(a: Quaternion, b: Quaternion) {
+        assert!((a.dot(b).abs() - 1.).abs() < 1e-9, "{a:?} != {b:?}");
+    }
+
+    proptest! {
+        #[test]
+        fn matrices_round_trip(q in rotation()) {
+            assert_same_rotation(Quaternion::from_matrix(q.to_matrix()), q);
+            let v = Vec3::new(0.3, -1., 2.);
+            let rotated = (q * Quaternion { w: 0., x: v.x, y: v.y, z: v.z }) * q.conjugate();
+            let expected = q.rotate(v);
+            assert!((Vec3::new(rotated.x, rotated.y, rotated.z) - expected).length() < 1e-9);
+        }
+
+        #[test]
+        fn euler_angles_round_trip(x in -180. ..180., y in -89. ..89., z in -180. ..180.) {
+            let q = Quaternion::from_euler(EulerAngles { x, y, z });
+            let angles = q.to_euler();
+            assert_same_rotation(Quaternion::from_euler(angles), q);
+            assert!((angles.y - y).abs() < 1e-6);
+        }
+
+        #[test]
+        fn slerp_moves_at_constant_speed(a in rotation(), b in rotation(), t in 0. ..1.) {
+            assert_same_rotation(a.slerp(b, 0.), a);
+            assert_same_rotation(a.slerp(b, 1.), b);
+            let between = a.slerp(b, t);
+            assert!((between.length() - 1.).abs() < 1e-9);
+            let angle = |p: Quaternion, q: Quaternion| p.dot(q).abs().min(1.).acos();
+            assert!((angle(a, between) - t * angle(a, b)).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn gimbal_lock_keeps_the_rotation() {
+        let q = Quaternion::from_euler(EulerAngles {
+            x: 30.,
+            y: 90.,
+            z: 10.,
+        });
+        assert_same_rotation(Quaternion::from_euler(q.to_euler()), q);
+    }
+}
@@ -1,100 +1,95 @@
+use std::sync::Arc;
+
 #[cfg(feature = "euclid")]
 use crate::aabox::Box3DExt as _;
-use crate::{aabox::AABBox, bounded::Bounded, transformations::private::Token};
+use crate::{
+    aabox::AABBox,
+    aaplane::Axis,
+    bounded::Bounded,
+    matrix3::Matrix3,
+    matrix4::Matrix4,
+    quaternion::Quaternion,
+    transformations::private::Token,
+    vec3::{Point3, Vec3},
+};
 #[cfg(feature = "euclid")]
 use euclid::UnknownUnit;
-#[cfg(feature = "euclid")]
-pub use inner::rotation;
+pub use inner::matrix;
 
 #[cfg(feature = "euclid")]
 mod inner {
-    use euclid::Rotation3D;
+    use crate::{matrix3::Matrix3, matrix4::Matrix4, transformations::Transformation};
 
-    use crate::{aaplane::Axis, transformations::Transformation};
+    impl From<Matrix4> for Transformation {
+        fn from(value: Matrix4) -> Self {
+            // euclid multiplies row vectors
+            Self::from_arrays(value.transpose().0)
+        }
+    }
 
-    #[must_use]
-    pub fn rotation(angle: f64, axis: Axis) -> Transformation {
-        let radians = angle.to_radians();
-        match axis {
-            Axis::X => Rotation3D::around_x(euclid::Angle { radians }),
-            Axis::Y => Rotation3D::around_y(euclid::Angle { radians }),
-            Axis::Z => Rotation3D::around_z(euclid::Angle { radians }),
+    impl From<Matrix3> for Transformation {
+        fn from(value: Matrix3) -> Self {
+            Matrix4::from(value).into()
         }
-        .to_transform()
+    }
+
+    /// Matrix of the transformation, applied to column vectors.
+    #[must_use]
+    pub fn matrix(transformation: &Transformation) -> Matrix4 {
+        Matrix4::from(transformation.to_arrays()).transpose()
     }
 }
 
 #[cfg(not(feature = "euclid"))]
 mod inner {
     use crate::{
-        aaplane::Axis,
         matrix3::Matrix3,
+        matrix4::Matrix4,
         vec3::{Point3, Vec3},
     };
 
-    #[must_use]
-    pub fn rotation(angle: f64, axis: Axis) -> Transformation {
-        let angle = angle.to_radians();
-        match axis {
-            Axis::X => [
-                [1., 0., 0.],
-                [0., angle.cos(), -angle.sin()],
-                [0., angle.sin(), angle.cos()],
-            ],
-            Axis::Y => [
-                [angle.cos(), 0., angle.sin()],
-                [0., 1., 0.],
-                [-angle.sin(), 0., angle.cos()],
-            ],
-            Axis::Z => [
-                [angle.cos(), -angle.sin(), 0.],
-                [angle.sin(), angle.cos(), 0.],
-                [0., 0., 1.],
-            ],
-        }
-        .into()
-    }
-
     impl From<[[f64; 3]; 3]> for Transformation {
         fn from(value: [[f64; 3]; 3]) -> Self {
-            Self {
-                rotation: value.into(),
-                ..Default::default()
-            }
+            Matrix3::from(value).into()
         }
     }
 
     impl From<Matrix3> for Transformation {
         fn from(value: Matrix3) -> Self {
-            Self {
-                rotation: value,
-                ..Default::default()
-            }
+            Matrix4::from(value).into()
+        }
+    }
+
+    impl From<Matrix4> for Transformation {
+        fn from(value: Matrix4) -> Self {
+            Self { matrix: value }
         }
     }
 
     impl From<Vec3> for Transformation {
         fn from(value: Vec3) -> Self {
-            Self {
-                translation: value,
-                ..Default::default()
-            }
+            Matrix4::affine(Matrix3::default(), value.to_array()).into()
         }
     }
 
-    #[derive(Debug, Default, Clone, Copy)]
+    /// Matrix of the transformation, applied to column vectors.
+    #[must_use]
+    pub const fn matrix(transformation: &Transformation) -> Matrix4 {
+        transformation.matrix
+    }
+
+    /// Projective transformation of points, affine unless made with
+    /// [`perspective`](super::perspective).
+    #[derive(Debug, Default, Clone, Copy, PartialEq)]
     pub struct Transformation {
-        pub rotation: Matrix3,
-        pub translation: Vec3,
+        matrix: Matrix4,
     }
 
     impl Transformation {
         #[must_use]
         pub fn apply(self, transformation: Self) -> Self {
             Self {
-                rotation: transformation.rotation * self.rotation,
-                translation: transformation.translation
-                    + transformation.rotation * self.translation,
+                matrix: transformation.matrix * self.matrix,
             }
         }
 
@@ -103,30 +98,35 @@ mod inner {
             self.apply(*transformation)
         }
 
+        /// `None` if the point is sent to infinity or behind the viewer of a perspective.
         #[must_use]
         pub fn transform_point3d(self, point: Point3) -> Option<Point3> {
-            Some(self.rotation * point + self.translation)
+            let [x, y, z, w] = self.matrix.apply([point.x, point.y, point.z, 1.]);
+            (w > 0.).then(|| Point3::new(x / w, y / w, z / w))
         }
 
+        /// Ignores the translation and the perspective.
         #[must_use]
         pub fn transform_vector3d(self, vec: Vec3) -> Vec3 {
-            self.rotation * vec + self.translation
+            let [x, y, z, _] = self.matrix.apply([vec.x, vec.y, vec.z, 0.]);
+            Vec3::new(x, y, z)
         }
 
         #[must_use]
         pub fn inverse(self) -> Option<Self> {
-            let rotation = self.rotation.inverse()?;
-            Self {
-                rotation,
-                translation: -(rotation * self.translation),
-            }
-            .into()
+            Some(Self {
+                matrix: self.matrix.inverse()?,
+            })
         }
     }
 
     #[cfg(test)]
     mod tests {
-        use crate::{matrix3::Matrix3, transformations::Transformation, vec3::Vec3};
+        use crate::{
+            matrix3::Matrix3,
+            transformations::{Transformation, matrix},
+            vec3::Vec3,
+        };
 
         #[test]
         fn inverse_times_itself_is_identity() {
@@ -136,13 +136,13 @@ mod inner {
             let trans = Transformation::from(mat);
             let trans = trans.apply(translation.into());
             let inv = trans.inverse().unwrap();
-            let id = trans.apply(inv);
+            let id = matrix(&trans.apply(inv));
             for i in 0..3 {
-                assert!((id.rotation.0[i][i] - 1.).abs() < f64::EPSILON);
+                assert!((id.0[i][i] - 1.).abs() < f64::EPSILON);
                 assert!(
-                    (id.translation.inner()[i]).abs() < f64::EPSILON,
-                    "id.translation.inner()[i] = {:?}",
-                    id.translation.inner()[i]
+                    (id.translation()[i]).abs() < f64::EPSILON,
+                    "id.translation()[i] = {:?}",
+                    id.translation()[i]
                 );
             }
         }
@@ -151,10 +151,10 @@ mod inner {
         fn inverse_of_identity_is_identity() {
             let id = Matrix3::from([[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]]);
             let trans_id = Transformation::from(id);
-            let inv = trans_id.inverse().unwrap();
+            let inv = matrix(&trans_id.inverse().unwrap());
             for i in 0..3 {
                 for j in 0..3 {
-                    assert!((id.0[i][j] - inv.rotation.0[i][j]).abs() < f64::EPSILON);
+                    assert!((id.0[i][j] - inv.0[i][j]).abs() < f64::EPSILON);
                 }
             }
         }
@@ -165,32 +165,279 @@ mod inner {
 pub type Transformation = euclid::Transform3D<f64, UnknownUnit, UnknownUnit>;
 
 #[cfg(not(feature = "euclid"))]
-pub use inner::{Transformation, rotation};
+pub use inner::Transformation;
+
+/// Matrix applied to vectors, without the translation.
+#[must_use]
+pub fn linear_part(transformation: &Transformation) -> Matrix3 {
+    matrix(transformation).linear_part()
+}
+
+#[must_use]
+pub fn translation(offset: Vec3) -> Transformation {
+    Matrix4::affine(Matrix3::default(), offset.to_array()).into()
+}
+
+/// Scales each axis by the matching factor, negative ones mirror it.
+#[must_use]
+pub fn scale(factors: Vec3) -> Transformation {
+    Matrix3::from([
+        [factors.x, 0., 0.],
+        [0., factors.y, 0.],
+        [0., 0., factors.z],
+    ])
+    .into()
+}
+
+/// Moves `x` by `xy` times `y` and `xz` times `z`, and `y` by `yz` times `z`.
+#[must_use]
+pub fn shear(xy: f64, xz: f64, yz: f64) -> Transformation {
+    Matrix3::from([[1., xy, xz], [0., 1., yz], [0., 0., 1.]]).into()
+}
 
-#[derive(Debug)]
+/// Rotation of `angle` degrees around a principal axis.
+#[must_use]
+pub fn rotation(angle: f64, axis: Axis) -> Transformation {
+    let axis = match axis {
+        Axis::X => Vec3::new(1., 0., 0.),
+        Axis::Y => Vec3::new(0., 1., 0.),
+        Axis::Z => Vec3::new(0., 0., 1.),
+    };
+    rotation_around(angle, axis)
+}
+
+/// Rotation of `angle` degrees around `axis`, counterclockwise when looking against it.
+#[must_use]
+pub fn rotation_around(angle: f64, axis: Vec3) -> Transformation {
+    Quaternion::from_axis_angle(axis, angle).into()
+}
+
+impl From<Quaternion> for Transformation {
+    fn from(value: Quaternion) -> Self {
+        value.to_matrix().into()
+    }
+}
+
+/// Places the origin at `from` with the z axis pointing away from `target` and the y axis as
+/// close to `up` as possible, like the camera of
+/// [`CameraBuilder::with_lookat`](../../shared/camera/struct.CameraBuilder.html). Its inverse
+/// takes points to the space of the camera.
+#[must_use]
+pub fn look_at(from: Point3, target: Point3, up: Vec3) -> Transformation {
+    let w = (from - target).normalize();
+    let u = up.cross(w).normalize();
+    let v = w.cross(u);
+    Matrix4::affine(
+        Matrix3::from([[u.x, v.x, w.x], [u.y, v.y, w.y], [u.z, v.z, w.z]]),
+        [from.x, from.y, from.z],
+    )
+    .into()
+}
+
+/// Perspective looking down the z axis from `distance` in front of the origin, which is left
+/// in place. Only points with `z < distance` can be transformed.
+#[must_use]
+pub fn perspective(distance: f64) -> Transformation {
+    Matrix4::from([
+        [1., 0., 0., 0.],
+        [0., 1., 0., 0.],
+        [0., 0., 1., 0.],
+        [0., 0., -distance.recip(), 1.],
+    ])
+    .into()
+}
+
+/// Affine transformation split into a scale, then a shear, then a rotation and then a
+/// translation, which can be interpolated.
+#[derive(Debug, Clone, Copy)]
+pub struct Decomposition {
+    pub translation: Vec3,
+    pub rotation: Quaternion,
+    /// Mirroring is kept in the sign of the z scale
+    pub scale: Vec3,
+    /// Arguments of [`shear`]
+    pub shear: [f64; 3],
+}
+
+impl Default for Decomposition {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::new(0., 0., 0.),
+            rotation: Quaternion::identity(),
+            scale: Vec3::new(1., 1., 1.),
+            shear: [0.; 3],
+        }
+    }
+}
+
+impl Decomposition {
+    /// Splits `transformation`, `None` if it isn't affine or it flattens space.
+    #[must_use]
+    pub fn new(transformation: &Transformation) -> Option<Self> {
+        let matrix = matrix(transformation);
+        if !matrix.is_affine() {
+            return None;
+        }
+        let linear = matrix.linear_part().0;
+        let [c0, c1, c2] =
+            std::array::from_fn(|j| Vec3::new(linear[0][j], linear[1][j], linear[2][j]));
+        // Gram-Schmidt, the linear part is a rotation times an upper triangular matrix
+        let sx = c0.length();
+        let r0 = c0 / sx;
+        let a = r0.dot(c1);
+        let c1 = c1 - r0 * a;
+        let sy = c1.length();
+        let r1 = c1 / sy;
+        let (b, c) = (r0.dot(c2), r1.dot(c2));
+        let c2 = c2 - r0 * b - r1 * c;
+        let sz = c2.length();
+        if !(sx.is_normal() && sy.is_normal() && sz.is_normal()) {
+            return None;
+        }
+        let r2 = c2 / sz;
+        let (r2, sz) = if r0.cross(r1).dot(r2) < 0. {
+            (-r2, -sz)
+        } else {
+            (r2, sz)
+        };
+        let rotation = Matrix3::from([[r0.x, r1.x, r2.x], [r0.y, r1.y, r2.y], [r0.z, r1.z, r2.z]]);
+        let [tx, ty, tz] = matrix.translation();
+        Some(Self {
+            translation: Vec3::new(tx, ty, tz),
+            rotation: Quaternion::from_matrix(rotation),
+            scale: Vec3::new(sx, sy, sz),
+            shear: [a / sy, b / sz, c / sz],
+        })
+    }
+
+    #[must_use]
+    pub fn compose(&self) -> Transformation {
+        let [xy, xz, yz] = self.shear;
+        scale(self.scale)
+            .then(&shear(xy, xz, yz))
+            .then(&self.rotation.into())
+            .then(&translation(self.translation))
+    }
+
+    /// Goes `t` of the way to `other`, rotating along the shortest arc.
+    #[must_use]
+    pub fn lerp(&self, other: &Self, t: f64) -> Self {
+        let lerp = |a: f64, b: f64| a + (b - a) * t;
+        Self {
+            translation: self.translation + (other.translation - self.translation) * t,
+            rotation: self.rotation.slerp(other.rotation, t),
+            scale: self.scale + (other.scale - self.scale) * t,
+            shear: std::array::from_fn(|i| lerp(self.shear[i], other.shear[i])),
+        }
+    }
+}
+
+/// An object placed with a [`Transformation`], the inverse and the matrix used for normals are
+/// computed once when it's placed.
+#[derive(Debug, Clone)]
 pub struct Transformed<T> {
     transformation: Transformation,
+    /// `None` if the transformation can't be undone, like a scale by 0
+    inverse: Option<Transformation>,
+    /// Inverse transpose of the linear part, which keeps normals perpendicular to the surface
+    normal_matrix: Matrix3,
     instance: T,
 }
 
+/// A shared prototype placed in the scene, the prototype is stored once however many times it's
+/// placed.
+pub type Instance<T> = Transformed<Arc<T>>;
+
 impl<T> Transformed<T> {
+    fn new(instance: T, transformation: Transformation) -> Self {
+        let inverse = transformation.inverse();
+        Self {
+            transformation,
+            inverse,
+            normal_matrix: normal_matrix(inverse),
+            instance,
+        }
+    }
+
     pub const fn get_transformation(&self) -> Transformation {
         self.transformation
     }
+
+    pub const fn get_inverse(&self) -> Option<Transformation> {
+        self.inverse
+    }
+
+    pub const fn get_normal_matrix(&self) -> Matrix3 {
+        self.normal_matrix
+    }
+
     pub const fn get_instance(&self) -> &T {
         &self.instance
     }
+
+    /// Moves the instance, replacing its transformation.
+    pub fn set_transformation<U: Into<Transformation>>(&mut self, transformation: U) {
+        let transformation = transformation.into();
+        let inverse = transformation.inverse();
+        self.transformation = transformation;
+        self.inverse = inverse;
+        self.normal_matrix = normal_matrix(inverse);
+    }
+
+    /// Moves a normal of the instance to where it's placed, the result has unit length.
+    pub fn transform_normal(&self, normal: Vec3) -> Vec3 {
+        (self.normal_matrix * normal).normalize()
+    }
+
+    /// Box containing `aabox`, in the space of the instance, once it's placed.
+    ///
+    /// # Panics
+    /// If a corner is sent to infinity, which affine transformations never do.
+    pub fn transform_aabbox(&self, aabox: AABBox) -> AABBox {
+        AABBox::from_points(
+            aabox
+                .get_points()
+                .map(|p| self.transformation.transform_point3d(p).unwrap()),
+        )
+    }
+
+    /// How much areas of the instance are scaled, exact for rotations and uniform scales.
+    pub fn area_scale(&self) -> f64 {
+        self.normal_matrix.det().abs().recip().powf(2. / 3.)
+    }
+
+    /// Whether angles between directions are kept, which is the case unless the scale is
+    /// different along some axis.
+    pub fn is_conformal(&self) -> bool {
+        let m = self.normal_matrix.transpose() * self.normal_matrix;
+        let scale = m.0[0][0];
+        (0..3).all(|i| {
+            (0..3).all(|j| {
+                let expected = if i == j { scale } else { 0. };
+                (m.0[i][j] - expected).abs() <= 1e-9 * scale
+            })
+        })
+    }
+}
+
+fn normal_matrix(inverse: Option<Transformation>) -> Matrix3 {
+    inverse
+        .map(|inverse| linear_part(&inverse).transpose())
+        .unwrap_or_default()
+}
+
+impl<T: ?Sized> Transformed<Arc<T>> {
+    /// Places `prototype` without copying it.
+    pub fn instance<U: Into<Transformation>>(prototype: &Arc<T>, transformation: U) -> Self {
+        Self::new(Arc::clone(prototype), transformation.into())
+    }
 }
 
 impl<T> From<T> for Transformed<T> {
     fn from(value: T) -> Self {
-        Self {
-            transformation: Transformation::default(),
-            instance: value,
-        }
+        Self::new(value, Transformation::default())
     }
 }
-
 mod private {
     pub struct Token {}
 }
@@ -215,10 +462,7 @@ where
 
 impl<T> Transformed<T> {
     fn transform_impl(self, transformation: Transformation) -> Self {
-        Self {
-            transformation: self.transformation.then(&transformation),
-            instance: self.instance,
-        }
+        Self::new(self.instance, self.transformation.then(&transformation))
     }
 }
 
@@ -227,15 +471,132 @@ where
     T: Bounded,
 {
     fn get_aabbox(&self) -> AABBox {
-        AABBox::from_points(
-            self.get_instance()
-                .get_aabbox()
-                .get_points()
-                .map(|p| self.transformation.transform_point3d(p).unwrap()),
-        )
+        self.transform_aabbox(self.get_instance().get_aabbox())
     }
 
     fn get_surface_area(&self) -> f64 {
-        self.get_instance().get_surface_area()
+        self.get_instance().get_surface_area() * self.area_scale()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::{
+        matrix4::Matrix4,
+        quaternion::{EulerAngles, Quaternion},
+        transformations::{Decomposition, look_at, matrix, perspective},
+        vec3::{Point3, Vec3},
+    };
+
+    fn decomposition() -> impl Strategy<Value = Decomposition> {
+        (
+            prop::array::uniform3(-10. ..10.),
+            prop::array::uniform3(-180. ..180.),
+            prop::array::uniform3(0.2..5.),
+            any::<bool>(),
+            prop::array::uniform3(-1. ..1.),
+        )
+            .prop_map(|(translation, [x, y, z], scale, mirror, shear)| {
+                let [sx, sy, sz] = scale;
+                Decomposition {
+                    translation: Vec3::new(translation[0], translation[1], translation[2]),
+                    rotation: Quaternion::from_euler(EulerAngles { x, y, z }),
+                    scale: Vec3::new(sx, sy, if mirror { -sz } else { sz }),
+                    shear,
+                }
+            })
+    }
+
+    fn point() -> impl Strategy<Value = Point3> {
+        prop::array::uniform3(-10. ..10.).prop_map(|[x, y, z]| Point3::new(x, y, z))
+    }
+
+    fn assert_close(a: Point3, b: Point3) {
+        let size = (b - Point3::new(0., 0., 0.)).length();
+        assert!((a - b).length() < 1e-8 * (1. + size), "{a:?} != {b:?}");
+    }
+
+    fn assert_matrices_close(a: Matrix4, b: Matrix4) {
+        for (row_a, row_b) in a.0.iter().zip(b.0) {
+            for (a, b) in row_a.iter().zip(row_b) {
+                assert!((a - b).abs() < 1e-9 * (1. + b.abs()), "{a:?} != {b:?}");
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn inverse_undoes_the_transformation(decomposition in decomposition(), p in point()) {
+            let transformation = decomposition.compose();
+            let inverse = transformation.inverse().unwrap();
+            let moved = transformation.transform_point3d(p).unwrap();
+            assert_close(inverse.transform_point3d(moved).unwrap(), p);
+            assert_matrices_close(
+                matrix(&transformation.then(&inverse)),
+                Matrix4::default(),
+            );
+        }
+
+        #[test]
+        fn composition_applies_in_order(
+            a in decomposition(),
+            b in decomposition(),
+            p in point(),
+        ) {
+            let (a, b) = (a.compose(), b.compose());
+            let both = a.then(&b);
+            let expected = b.transform_point3d(a.transform_point3d(p).unwrap()).unwrap();
+            assert_close(both.transform_point3d(p).unwrap(), expected);
+            assert_matrices_close(matrix(&both), matrix(&b) * matrix(&a));
+        }
+
+        #[test]
+        fn decomposition_round_trips(decomposition in decomposition()) {
+            let transformation = decomposition.compose();
+            let split = Decomposition::new(&transformation).unwrap();
+            assert_matrices_close(matrix(&split.compose()), matrix(&transformation));
+            assert!((split.scale - decomposition.scale).length() < 1e-9);
+            assert!((split.rotation.dot(decomposition.rotation).abs() - 1.).abs() < 1e-9);
+            for (a, b) in split.shear.iter().zip(decomposition.shear) {
+                assert!((a - b).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn look_at_faces_the_target() {
+        let (from, target) = (Point3::new(1., 2., 3.), Point3::new(-2., 0., 5.));
+        let transformation = look_at(from, target, Vec3::new(0., 1., 0.));
+        assert_close(
+            transformation
+                .transform_point3d(Point3::new(0., 0., 0.))
+                .unwrap(),
+            from,
+        );
+        let forward = transformation.transform_vector3d(Vec3::new(0., 0., -1.));
+        assert!((forward.dot((target - from).normalize()) - 1.).abs() < 1e-12);
+        let up = transformation.transform_vector3d(Vec3::new(0., 1., 0.));
+        assert!(up.y > 0. && forward.dot(up).abs() < 1e-12);
+    }
+
+    #[test]
+    fn perspective_divides_by_depth() {
+        let transformation = perspective(2.);
+        let near = transformation
+            .transform_point3d(Point3::new(1., 1., 0.))
+            .unwrap();
+        assert_close(near, Point3::new(1., 1., 0.));
+        let far = transformation
+            .transform_point3d(Point3::new(1., 1., -2.))
+            .unwrap();
+        assert_close(far, Point3::new(0.5, 0.5, -1.));
+        assert!(Decomposition::new(&transformation).is_none());
+        assert!(
+            transformation
+                .transform_point3d(Point3::new(0., 0., 3.))
+                .is_none()
+        );
     }
 }
@@ -127,6 +127,12 @@ impl Vec3 {
         self
     }
 
+    #[inline]
+    #[must_use]
+    pub const fn to_vector(self) -> Self {
+        self
+    }
+
     #[inline]
     #[must_use]
     pub fn component_mul(self, other: Self) -> Self {
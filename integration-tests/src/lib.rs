@@ -1,8 +1,42 @@
 #[cfg(test)]
 mod tests {
-    use geometry::vec3::{Point3, Vec3};
-    use scenes::{cornell_box, debugging_scene, plane, simple, simple_light};
-    use shared::camera::CameraBuilder;
+    use geometry::{
+        aaplane::Axis,
+        bounded::Bounded as _,
+        matrix3::Matrix3,
+        transformations::{Instance, Transformable as _, Transformation, Transformed, rotation},
+        vec3::{Point3, Vec3},
+    };
+    use scenes::{bouncing_spheres, cornell_box, debugging_scene, plane, simple, simple_light};
+    use shared::{
+        camera::{
+            Aperture, CameraBuilder, Exposure, LensDistortion, LightingStrategy, Projection,
+            SensorSize,
+        },
+        colour::{Colour, ColourSpace},
+        entities::{
+            Capsule, Cone, Csg, Cuboid, Cylinder, Disk, Plane, Quad, SdfObject, Sphere, Torus,
+            Triangle,
+        },
+        environment::{
+            Environment, EnvironmentLight, EnvironmentMap, PhysicalSky, SkyGradient, Sun, SunLight,
+        },
+        hittable::{BoundedHittable, Hittable as _},
+        hittable_collections::{
+            bvh::{BoundedVolumeHierarchy, RefittingHierarchy},
+            hittable_list::HittableList,
+        },
+        image::{write_exr, write_ppm},
+        integrator::{
+            AmbientOcclusion, BidirectionalPathTracer, DebugView, DirectLighting, GuidedPathTracer,
+            Integrator, MetropolisLightTransport, PathTracer, ProgressivePhotonMapper, Whitted,
+        },
+        lights::{Brightness, DirectionalLight, Emission, IesProfile, PointLight, SpotLight},
+        material::{Dialectric, DiffuseLight, Dispersion, INHERITED_PTR, Lambertian},
+        ray::Ray,
+        scene::{LightSampling, Scene, SceneNode},
+    };
+    use std::sync::Arc;
 
     #[test]
     fn plane_test() {
@@ -110,4 +144,1122 @@ mod tests {
         // Render
         cam.render_debug(world.as_ref(), lights.as_ref());
     }
+
+    #[test]
+    fn physical_camera_test() {
+        // World
+        let (world, lights, cam) = cornell_box();
+        // Camera
+        let cam = cam
+            .with_image_width(3)
+            .with_image_height(2)
+            .with_samples_per_pixel(50)
+            .with_max_depth(10)
+            .into_physical()
+            .with_focal_length(35.)
+            .with_sensor(SensorSize::APS_C)
+            .with_f_number(2.8)
+            .with_units_per_metre(100.)
+            .with_focus_distance(800.)
+            .with_aperture(Aperture::Polygonal {
+                blades: 6,
+                rotation: 15.,
+            })
+            .with_distortion(LensDistortion::new(-0.1, 0.01))
+            .with_vignetting(0.5)
+            .with_exposure(Exposure::new(100., 1. / 60.))
+            .build();
+
+        // Render
+        cam.render_debug(world.as_ref(), lights.as_ref());
+    }
+
+    #[test]
+    fn projections_test() {
+        // World
+        let (world, lights, cam) = cornell_box();
+        for projection in [
+            Projection::Orthographic { height: 600. },
+            Projection::Fisheye { fov: 180. },
+            Projection::Equirectangular,
+            Projection::CubeMap,
+        ] {
+            // Camera
+            let cam = cam
+                .clone()
+                .with_image_width(3)
+                .with_image_height(2)
+                .with_samples_per_pixel(20)
+                .with_max_depth(10)
+                .with_defocus_angle(0.1)
+                .with_projection(projection)
+                .build();
+
+            // Render
+            cam.render_debug(world.as_ref(), lights.as_ref());
+        }
+    }
+
+    #[test]
+    fn environment_test() {
+        // World
+        let (world, _, cam) = simple();
+        let mut pixels = vec![Colour::new(0.1, 0.1, 0.2); 16 * 8];
+        // Sun
+        pixels[2 * 16 + 5] = Colour::new(1000., 900., 800.);
+        let environments: [Arc<dyn Environment>; 2] = [
+            Arc::new(SkyGradient::default()),
+            Arc::new(
+                EnvironmentMap::new(16, 8, pixels)
+                    .unwrap()
+                    .with_rotation(30.)
+                    .with_intensity(2.),
+            ),
+        ];
+        for environment in environments {
+            let mut lights = HittableList::default();
+            lights.add(EnvironmentLight::new(environment.clone()));
+            // Camera
+            let cam = cam
+                .clone()
+                .with_image_width(3)
+                .with_image_height(2)
+                .with_samples_per_pixel(20)
+                .with_max_depth(5)
+                .with_lookfrom(Point3::new(-13., 2., 3.))
+                .with_lookat(Point3::new(0., 0., 0.))
+                .with_environment(environment)
+                .build();
+
+            // Render
+            cam.render_debug(world.as_ref(), &lights);
+        }
+    }
+
+    #[test]
+    fn physical_sky_test() {
+        // World
+        let (world, _, cam) = simple();
+        let sun = Sun::from_date_time(172, 15.5, 40.);
+        let sky = Arc::new(PhysicalSky::new(sun, 3.));
+
+        let zenith = sky.get_colour(Vec3::new(0., 1., 0.)).into_inner();
+        assert!(zenith.x.is_finite() && zenith.y.is_finite() && zenith.z.is_finite());
+        assert!(zenith.z > zenith.x, "The sky should be blue");
+        let sun_colour = sky.get_colour(sun.get_direction());
+        assert!(sun_colour.luminance() > 1000. * zenith.y);
+
+        let mut lights = HittableList::default();
+        lights.add(SunLight::from(sky.as_ref()));
+        lights.add(EnvironmentLight::new(sky.clone()));
+        // Camera
+        let cam = cam
+            .with_image_width(3)
+            .with_image_height(2)
+            .with_samples_per_pixel(20)
+            .with_max_depth(5)
+            .with_lookfrom(Point3::new(-13., 2., 3.))
+            .with_lookat(Point3::new(0., 0., 0.))
+            .with_environment(sky)
+            .build();
+
+        // Render
+        cam.render_debug(world.as_ref(), &lights);
+    }
+
+    #[test]
+    fn punctual_lights_test() {
+        const IES: &str = "IESNA:LM-63-2002
+[TEST] Small downlight
+TILT=NONE
+1 1000 1 5 1 1 2 0 0 0
+1 1 100
+0 22.5 45 67.5 90
+0
+1000 900 500 100 0
+";
+        let profile = Arc::new(IesProfile::parse(IES).unwrap());
+        assert_eq!(profile.evaluate(0., 0.), 1.);
+        assert_eq!(profile.evaluate(90., 123.), 0.);
+
+        // World
+        let (world, _, cam) = cornell_box();
+        let scene = Scene::new(world)
+            .with_punctual_light(
+                PointLight::new(Point3::new(277.5, 500., 277.5), Colour::new(1e4, 1e4, 1e4))
+                    .with_profile(profile.clone()),
+            )
+            .with_punctual_light(SpotLight::new(
+                Point3::new(100., 500., 100.),
+                Vec3::new(0., -1., 0.),
+                Colour::new(1e4, 8e3, 6e3),
+                20.,
+                30.,
+            ))
+            .with_punctual_light(DirectionalLight::new(
+                Vec3::new(1., -1., 1.),
+                Colour::new(0.5, 0.5, 0.5),
+            ));
+        assert!(
+            scene.get_lights().is_some(),
+            "The ceiling light is emissive"
+        );
+        // Camera
+        let cam = cam
+            .with_image_width(3)
+            .with_image_height(2)
+            .with_samples_per_pixel(20)
+            .with_max_depth(10)
+            .build();
+
+        // Render
+        cam.render_scene_debug(&scene);
+    }
+
+    #[test]
+    fn light_tree_test() {
+        // World
+        let (world, _, cam) = cornell_box();
+        let scene = Scene::new(world).with_light_sampling(LightSampling::Tree);
+        assert!(scene.get_lights().is_some());
+        // Camera
+        let cam = cam
+            .with_image_width(3)
+            .with_image_height(2)
+            .with_samples_per_pixel(20)
+            .with_max_depth(10)
+            .build();
+
+        // Render
+        cam.render_scene_debug(&scene);
+    }
+
+    #[test]
+    fn ris_test() {
+        for spatial_reuse in [false, true] {
+            // World
+            let (world, lights, cam) = cornell_box();
+            // Camera
+            let cam = cam
+                .with_image_width(3)
+                .with_image_height(2)
+                .with_samples_per_pixel(10)
+                .with_max_depth(10)
+                .with_lighting_strategy(LightingStrategy::Ris {
+                    candidates: 8,
+                    spatial_reuse,
+                })
+                .build();
+
+            // Render
+            cam.render_debug(world.as_ref(), lights.as_ref());
+            cam.render_scene_debug(&Scene::new(world));
+        }
+    }
+
+    #[test]
+    fn integrators_test() {
+        const WHITTED: Whitted = Whitted::new().with_ambient(Colour::new(0.1, 0.1, 0.1));
+        const AMBIENT_OCCLUSION: AmbientOcclusion = AmbientOcclusion::new(100.).with_samples(4);
+        const PHOTON_MAPPER: ProgressivePhotonMapper =
+            ProgressivePhotonMapper::new(20.).with_photons_per_pass(1000);
+        const METROPOLIS: MetropolisLightTransport = MetropolisLightTransport::new()
+            .with_bootstrap_samples(1000)
+            .with_chains(4);
+        const GUIDED: GuidedPathTracer = GuidedPathTracer::new().with_training_passes(2);
+        let integrators: [Arc<dyn Integrator>; 13] = [
+            Arc::new(PathTracer),
+            Arc::new(GUIDED),
+            Arc::new(BidirectionalPathTracer),
+            Arc::new(PHOTON_MAPPER),
+            Arc::new(METROPOLIS),
+            Arc::new(DirectLighting),
+            Arc::new(WHITTED),
+            Arc::new(AMBIENT_OCCLUSION),
+            Arc::new(DebugView::Normal),
+            Arc::new(DebugView::Uv),
+            Arc::new(DebugView::Albedo),
+            Arc::new(DebugView::Depth {
+                max_distance: 1000.,
+            }),
+            Arc::new(DebugView::BvhCost { max_cost: 64 }),
+        ];
+        for integrator in integrators {
+            // World
+            let (world, lights, cam) = cornell_box();
+            // Camera
+            let cam = cam
+                .with_image_width(3)
+                .with_image_height(2)
+                .with_samples_per_pixel(10)
+                .with_max_depth(10)
+                .with_integrator(integrator)
+                .build();
+
+            // Render
+            cam.render_debug(world.as_ref(), lights.as_ref());
+        }
+    }
+
+    #[test]
+    fn integrators_agree_test() {
+        const PHOTON_MAPPER: ProgressivePhotonMapper =
+            ProgressivePhotonMapper::new(0.1).with_photons_per_pass(1000);
+        const METROPOLIS: MetropolisLightTransport = MetropolisLightTransport::new()
+            .with_bootstrap_samples(65536)
+            .with_chains(8);
+        // World, a small Cornell box without glass whose light is out of view, so the means
+        // converge quickly
+        let mut world = HittableList::default();
+        let red = Arc::new(Lambertian::new_with_colour(Colour::new(0.65, 0.05, 0.05)));
+        let white = Arc::new(Lambertian::new_with_colour(Colour::new(0.73, 0.73, 0.73)));
+        let green = Arc::new(Lambertian::new_with_colour(Colour::new(0.12, 0.45, 0.15)));
+        let light = Arc::new(DiffuseLight::new_with_colour(Colour::new(15., 15., 15.)));
+        let (x, y, z) = (
+            Vec3::new(2., 0., 0.),
+            Vec3::new(0., 2., 0.),
+            Vec3::new(0., 0., 2.),
+        );
+        world.add(Quad::new(Point3::new(1., 0., -1.), y, z, green));
+        world.add(Quad::new(Point3::new(-1., 0., -1.), y, z, red));
+        world.add(Quad::new(Point3::new(-1., 0., -1.), x, z, white.clone()));
+        world.add(Quad::new(Point3::new(-1., 2., -1.), x, z, white.clone()));
+        world.add(Quad::new(Point3::new(-1., 0., 1.), x, y, white.clone()));
+        world.add(Cuboid::new(
+            Point3::new(-0.6, 0., -0.2),
+            Point3::new(0., 1.2, 0.4),
+            white,
+        ));
+        let light_quad = Quad::new(
+            Point3::new(-0.25, 1.99, -0.25),
+            Vec3::new(0.5, 0., 0.),
+            Vec3::new(0., 0., 0.5),
+            light,
+        );
+        world.add(light_quad.clone());
+        let mut lights = HittableList::default();
+        lights.add(light_quad);
+
+        let mean_radiance = |integrator: Arc<dyn Integrator>, samples: u16| {
+            // Camera
+            let cam = CameraBuilder::new()
+                .with_lookfrom(Point3::new(0., 1., -3.5))
+                .with_lookat(Point3::new(0., 1., 0.))
+                .with_vfov(30.)
+                .with_image_width(4)
+                .with_image_height(4)
+                .with_samples_per_pixel(samples)
+                .with_max_depth(10)
+                .with_integrator(integrator)
+                .with_seed(7)
+                .build();
+
+            // Render
+            let image = cam.render_debug(&world, &lights);
+            let pixels = image.iter().flatten().count() as f64;
+            image
+                .iter()
+                .flatten()
+                .map(|colour| colour.average().luminance())
+                .sum::<f64>()
+                / pixels
+        };
+        let reference = mean_radiance(Arc::new(PathTracer), 4096);
+        let integrators: [(Arc<dyn Integrator>, u16); 3] = [
+            (Arc::new(BidirectionalPathTracer), 1024),
+            (Arc::new(PHOTON_MAPPER), 256),
+            // The mean of the image only depends on the bootstrap samples
+            (Arc::new(METROPOLIS), 16),
+        ];
+        for (integrator, samples) in integrators {
+            let mean = mean_radiance(integrator.clone(), samples);
+            assert!(
+                (mean - reference).abs() < 0.1 * reference,
+                "{integrator:?} has a mean radiance of {mean} instead of {reference}"
+            );
+        }
+    }
+
+    #[test]
+    fn seeded_render_test() {
+        const GUIDED: GuidedPathTracer = GuidedPathTracer::new().with_training_passes(3);
+        const PHOTON_MAPPER: ProgressivePhotonMapper =
+            ProgressivePhotonMapper::new(20.).with_photons_per_pass(1000);
+        const METROPOLIS: MetropolisLightTransport = MetropolisLightTransport::new()
+            .with_bootstrap_samples(1000)
+            .with_chains(4);
+        // Integrators with random numbers which aren't drawn per pixel
+        let integrators: [Arc<dyn Integrator>; 3] = [
+            Arc::new(GUIDED),
+            Arc::new(PHOTON_MAPPER),
+            Arc::new(METROPOLIS),
+        ];
+        for integrator in integrators {
+            let render = || {
+                // World
+                let (world, lights, cam) = cornell_box();
+                // Camera
+                let cam = cam
+                    .with_image_width(4)
+                    .with_image_height(4)
+                    .with_samples_per_pixel(6)
+                    .with_max_depth(10)
+                    .with_integrator(integrator.clone())
+                    .with_seed(42)
+                    .build();
+
+                // Render
+                cam.render_debug(world.as_ref(), lights.as_ref())
+                    .iter()
+                    .flatten()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(render(), render(), "{integrator:?}");
+        }
+    }
+
+    #[test]
+    fn spectral_test() {
+        assert!((Dispersion::BK7.index_of_refraction(587.6) - 1.5168).abs() < 1e-4);
+        assert!(
+            Dispersion::SF11.index_of_refraction(450.) > Dispersion::SF11.index_of_refraction(650.),
+            "Blue light is bent the most"
+        );
+
+        // World
+        let mut world = HittableList::default();
+        let light = Arc::new(DiffuseLight::new_with_colour(Colour::new(4., 4., 4.)));
+        world.add(Sphere::new(
+            Point3::new(0., -1000., 0.),
+            1000.,
+            Arc::new(Lambertian::new_with_colour(Colour::new(0.5, 0.5, 0.5))),
+        ));
+        world.add(Sphere::new(
+            Point3::new(0., 1., 0.),
+            1.,
+            Arc::new(Dialectric::new(1.5).with_dispersion(Dispersion::Cauchy { a: 1.5, b: 0.02 })),
+        ));
+        world.add(Quad::new(
+            Point3::new(-1., 4., -1.),
+            Vec3::new(2., 0., 0.),
+            Vec3::new(0., 0., 2.),
+            light.clone(),
+        ));
+        let mut lights = HittableList::default();
+        lights.add(Quad::new(
+            Point3::new(-1., 4., -1.),
+            Vec3::new(2., 0., 0.),
+            Vec3::new(0., 0., 2.),
+            light,
+        ));
+
+        const METROPOLIS: MetropolisLightTransport = MetropolisLightTransport::new()
+            .with_bootstrap_samples(1000)
+            .with_chains(4);
+        const GUIDED: GuidedPathTracer = GuidedPathTracer::new().with_training_passes(2);
+        const PHOTON_MAPPER: ProgressivePhotonMapper =
+            ProgressivePhotonMapper::new(0.5).with_photons_per_pass(1000);
+        const SPATIAL_REUSE: LightingStrategy = LightingStrategy::Ris {
+            candidates: 4,
+            spatial_reuse: true,
+        };
+        let integrators: [(Arc<dyn Integrator>, LightingStrategy); 6] = [
+            (Arc::new(PathTracer), LightingStrategy::Mixture),
+            (Arc::new(PathTracer), SPATIAL_REUSE),
+            (Arc::new(GUIDED), LightingStrategy::Mixture),
+            (Arc::new(BidirectionalPathTracer), LightingStrategy::Mixture),
+            (Arc::new(PHOTON_MAPPER), LightingStrategy::Mixture),
+            (Arc::new(METROPOLIS), LightingStrategy::Mixture),
+        ];
+        for (integrator, lighting_strategy) in integrators {
+            // Camera
+            let cam = CameraBuilder::new()
+                .with_image_width(3)
+                .with_image_height(2)
+                .with_samples_per_pixel(10)
+                .with_max_depth(10)
+                .with_lookfrom(Point3::new(0., 2., 6.))
+                .with_lookat(Point3::new(0., 1., 0.))
+                .with_integrator(integrator)
+                .with_lighting_strategy(lighting_strategy)
+                .with_spectral(true)
+                .build();
+
+            // Render
+            cam.render_debug(&world, &lights);
+        }
+    }
+
+    #[test]
+    fn photometric_lights_test() {
+        let bulb = Emission::new(2700., Brightness::Lumens(800.));
+        assert!(DirectionalLight::new_with_emission(Vec3::new(0., -1., 0.), bulb).is_none());
+        assert!(
+            DiffuseLight::new_with_emission(Emission::new(5000., Brightness::Lux(1.)), 1.)
+                .is_none()
+        );
+
+        // World
+        let mut world = HittableList::default();
+        world.add(Sphere::new(
+            Point3::new(0., -1000., 0.),
+            1000.,
+            Arc::new(Lambertian::new_with_colour(Colour::new(0.5, 0.5, 0.5))),
+        ));
+        let panel = Arc::new(
+            DiffuseLight::new_with_emission(Emission::new(6500., Brightness::Watts(20.)), 2.)
+                .unwrap(),
+        );
+        world.add(Quad::new(
+            Point3::new(-0.5, 3., -0.5),
+            Vec3::new(1., 0., 0.),
+            Vec3::new(0., 0., 1.),
+            panel.clone(),
+        ));
+        let mut lights = HittableList::default();
+        lights.add(Quad::new(
+            Point3::new(-0.5, 3., -0.5),
+            Vec3::new(1., 0., 0.),
+            Vec3::new(0., 0., 1.),
+            panel,
+        ));
+        let scene = Scene::new(Box::new(world))
+            .with_punctual_light(
+                PointLight::new_with_emission(Point3::new(2., 2., 0.), bulb).unwrap(),
+            )
+            .with_punctual_light(
+                SpotLight::new_with_emission(
+                    Point3::new(-2., 2., 0.),
+                    Vec3::new(0., -1., 0.),
+                    Emission::new(4000., Brightness::Candela(100.)),
+                    20.,
+                    30.,
+                )
+                .unwrap(),
+            )
+            .with_punctual_light(
+                DirectionalLight::new_with_emission(
+                    Vec3::new(1., -1., 0.),
+                    Emission::new(5500., Brightness::Lux(10.)),
+                )
+                .unwrap(),
+            );
+
+        for spectral in [false, true] {
+            // Camera
+            let cam = CameraBuilder::new()
+                .with_image_width(3)
+                .with_image_height(2)
+                .with_samples_per_pixel(10)
+                .with_max_depth(10)
+                .with_lookfrom(Point3::new(0., 2., 6.))
+                .with_lookat(Point3::new(0., 1., 0.))
+                .with_white_balance(2700.)
+                .with_spectral(spectral)
+                .build();
+
+            // Render
+            cam.render_scene_debug(&scene);
+        }
+    }
+
+    #[test]
+    fn colour_spaces_test() {
+        for spectral in [false, true] {
+            // World
+            let (world, lights, cam) = cornell_box();
+            // Camera
+            let cam = cam
+                .with_image_width(3)
+                .with_image_height(2)
+                .with_samples_per_pixel(10)
+                .with_max_depth(10)
+                .with_working_space(ColourSpace::AcesCg)
+                .with_white_balance(5000.)
+                .with_spectral(spectral)
+                .build();
+
+            // Render
+            let out = cam.render_debug(world.as_ref(), lights.as_ref());
+            for output in [
+                ColourSpace::Srgb,
+                ColourSpace::DisplayP3,
+                ColourSpace::Rec2020,
+                ColourSpace::Aces2065,
+            ] {
+                write_ppm(Vec::new(), &out, cam.get_working_space(), output).unwrap();
+                write_exr(Vec::new(), &out, cam.get_working_space(), output).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn instancing_test() {
+        let green = Arc::new(Lambertian::new_with_colour(Colour::new(0.2, 0.5, 0.2)));
+        let apex = Point3::new(0., 2., 0.);
+        let base = [
+            Point3::new(-0.5, 0., -0.5),
+            Point3::new(0.5, 0., -0.5),
+            Point3::new(0.5, 0., 0.5),
+            Point3::new(-0.5, 0., 0.5),
+        ];
+        let mut tree = HittableList::default();
+        for i in 0..4 {
+            let (a, b) = (base[i], base[(i + 1) % 4]);
+            tree.add(Triangle::new(a, b - a, apex - a, green.clone()));
+        }
+        let tree = Arc::new(BoundedVolumeHierarchy::from(tree));
+
+        // A forest of 10000 trees sharing the same mesh
+        let mut forest = HittableList::default();
+        for i in 0..100 {
+            for j in 0..100 {
+                let (x, z) = (f64::from(i) - 50., f64::from(j) - 50.);
+                let placement = Transformation::from(Matrix3::from([
+                    [1., 0., 0.],
+                    [0., 1. + f64::from((i * j) % 3) / 2., 0.],
+                    [0., 0., 1.],
+                ]))
+                .then(&rotation(f64::from(i * 37 + j * 11), Axis::Y))
+                .then(&Vec3::new(x, 0., z).into());
+                forest.add(Instance::instance(&tree, placement));
+            }
+        }
+        assert_eq!(Arc::strong_count(&tree), 10_001);
+
+        // A tilted area light, sampled through its transformation
+        let light = Quad::new(
+            Point3::new(-5., 0., -5.),
+            Vec3::new(10., 0., 0.),
+            Vec3::new(0., 0., 10.),
+            Arc::new(DiffuseLight::new_with_colour(Colour::new(4., 4., 4.))),
+        )
+        .transform(rotation(20., Axis::X))
+        .transform(Vec3::new(0., 20., 0.));
+        let mut lights = HittableList::default();
+        lights.add(light.clone());
+        forest.add(light);
+        let world: Box<dyn BoundedHittable> = Box::new(BoundedVolumeHierarchy::from(forest));
+        let scene = Scene::new(world).with_light_sampling(LightSampling::Tree);
+        assert!(scene.get_lights().is_some());
+
+        let cam = CameraBuilder::new()
+            .with_image_width(8)
+            .with_image_height(6)
+            .with_samples_per_pixel(4)
+            .with_max_depth(4)
+            .with_lookfrom(Point3::new(0., 8., 30.))
+            .with_lookat(Point3::new(0., 0., 0.))
+            .build();
+        let image = cam.render_scene(&scene);
+        let lit = image
+            .iter()
+            .flatten()
+            .map(|pixel| pixel.average().luminance())
+            .sum::<f64>();
+        assert!(lit.is_finite() && lit > 0.);
+        cam.render_debug(scene.get_world(), &lights);
+    }
+
+    #[test]
+    fn scene_graph_test() {
+        let white = Arc::new(Lambertian::new_with_colour(Colour::new(0.73, 0.73, 0.73)));
+        let red = Arc::new(Lambertian::new_with_colour(Colour::new(0.65, 0.05, 0.05)));
+        let lamp = Arc::new(DiffuseLight::new_with_colour(Colour::new(15., 15., 15.)));
+        let unit_box = Cuboid::new(Point3::default(), Point3::new(1., 1., 1.), INHERITED_PTR);
+
+        let room = SceneNode::new("room")
+            .with_material(white)
+            .with_child(
+                SceneNode::new("walls")
+                    .with_object(Quad::new(
+                        Point3::new(0., 0., 0.),
+                        Vec3::new(10., 0., 0.),
+                        Vec3::new(0., 0., 10.),
+                        INHERITED_PTR,
+                    ))
+                    .with_object(Quad::new(
+                        Point3::new(0., 0., 10.),
+                        Vec3::new(10., 0., 0.),
+                        Vec3::new(0., 10., 0.),
+                        INHERITED_PTR,
+                    )),
+            )
+            .with_child(
+                SceneNode::new("lamp")
+                    .with_material(lamp)
+                    .with_transformation(Vec3::new(4., 9., 4.))
+                    .with_object(Quad::new(
+                        Point3::default(),
+                        Vec3::new(2., 0., 0.),
+                        Vec3::new(0., 0., 2.),
+                        INHERITED_PTR,
+                    )),
+            )
+            .with_child(
+                SceneNode::new("table")
+                    .with_transformation(Vec3::new(5., 0., 5.))
+                    .with_child(
+                        SceneNode::new("box")
+                            .with_material(red)
+                            .with_transformation(Vec3::new(-0.5, 0., -0.5))
+                            .with_object(unit_box.clone()),
+                    )
+                    .with_child(
+                        SceneNode::new("crate")
+                            .transform(rotation(45., Axis::Y))
+                            .transform(Vec3::new(2., 0., 2.))
+                            .with_object(unit_box),
+                    ),
+            );
+        let mut room = room;
+        assert!(room.find("crate").is_some() && room.find("chair").is_none());
+
+        // Lift the table, the boxes on it follow
+        let table = room.find_mut("table").unwrap();
+        table.set_transformation(
+            table
+                .get_transformation()
+                .then(&Vec3::new(0., 2., 0.).into()),
+        );
+        let world = room.build();
+        let down = Vec3::new(0., -1., 0.);
+        let hit = world
+            .hit(
+                &Ray::new(Point3::new(5., 5., 5.), down),
+                0.001..=f64::INFINITY,
+            )
+            .unwrap();
+        assert!((hit.get_p().y - 3.).abs() < 1e-9);
+        // The crate inherits the white of the room
+        let crate_hit = world
+            .hit(
+                &Ray::new(Point3::new(7.7, 5., 7.), down),
+                0.001..=f64::INFINITY,
+            )
+            .unwrap();
+        assert!((crate_hit.get_p().y - 3.).abs() < 1e-9);
+        assert!(!crate_hit.get_material().is_inherited());
+        let lamp_hit = world
+            .hit(
+                &Ray::new(Point3::new(5., 5., 5.), -down),
+                0.001..=f64::INFINITY,
+            )
+            .unwrap();
+        assert!(lamp_hit.get_material().is_emissive());
+
+        let scene = Scene::new(Box::new(world));
+        assert!(scene.get_lights().is_some());
+        let cam = CameraBuilder::new()
+            .with_image_width(3)
+            .with_image_height(2)
+            .with_samples_per_pixel(10)
+            .with_max_depth(5)
+            .with_lookfrom(Point3::new(5., 5., -10.))
+            .with_lookat(Point3::new(5., 3., 5.))
+            .build();
+        cam.render_scene_debug(&scene);
+    }
+
+    #[test]
+    fn animation_test() {
+        let animation = bouncing_spheres();
+        // The static objects are built once and shared by every frame
+        assert!(Arc::ptr_eq(
+            &animation.get_static_world(),
+            &animation.get_static_world()
+        ));
+        let rebuilt = bouncing_spheres().with_static_reuse(false);
+
+        let ray = Ray::new(Point3::new(0., 5., 0.), Vec3::new(0., -1., 0.));
+        let top =
+            |world: &dyn BoundedHittable| world.hit(&ray, 0.001..=f64::INFINITY).unwrap().get_p().y;
+        for (time, expected) in [(0., 3.6), (20., 1.2), (24., 0.72), (48., 3.6), (60., 3.6)] {
+            let world = animation.world_at(time);
+            assert!((top(world.as_ref()) - expected).abs() < 1e-9, "{time}");
+            assert!((top(rebuilt.world_at(time).as_ref()) - expected).abs() < 1e-9);
+        }
+        // Half way through the fall the ease in is still slow
+        assert!(top(animation.world_at(10.).as_ref()) > 2.4);
+        // The world of a frame which is still alive isn't refitted for the next one
+        let kept = animation.world_at(0.);
+        let next = animation.world_at(20.);
+        assert!((top(kept.as_ref()) - 3.6).abs() < 1e-9);
+        assert!((top(next.as_ref()) - 1.2).abs() < 1e-9);
+
+        let scene = animation.scene_at(12.);
+        assert!(scene.get_lights().is_some());
+        let cam = animation
+            .camera_at(12.)
+            .with_image_width(4)
+            .with_image_height(3)
+            .with_samples_per_pixel(1)
+            .with_seed(3)
+            .build();
+        cam.render_scene(&scene);
+    }
+
+    #[test]
+    fn bvh_refit_test() {
+        let material = Arc::new(Lambertian::new_with_colour(Colour::new(0.5, 0.5, 0.5)));
+        let sphere = Sphere::new(Point3::default(), 0.4, material);
+        // Off the grid lines, the build doesn't split objects starting at the same coordinate
+        let grid = |i: f64, j: f64| Vec3::new(i * 2. + j * 0.01, 0., j * 2. + i * 0.01);
+        let start: Vec<_> = (0..256)
+            .map(|k| grid(f64::from(k % 16), f64::from(k / 16)))
+            .collect();
+        let mut list = HittableList::default();
+        start
+            .iter()
+            .for_each(|&position| list.add(sphere.clone().transform(position)));
+        let mut refitting = RefittingHierarchy::new(list);
+
+        // Every sphere moves to `f` of where it is
+        let check = |refitting: &mut RefittingHierarchy,
+                     positions: &mut Vec<Vec3>,
+                     f: &dyn Fn(Vec3) -> Vec3| {
+            let rebuilt = refitting.update(|object: &mut Transformed<Sphere>| {
+                let position = object
+                    .get_transformation()
+                    .transform_point3d(Point3::default())
+                    .unwrap();
+                object.set_transformation(f(position));
+            });
+            positions
+                .iter_mut()
+                .for_each(|position| *position = f(*position));
+            let mut expected = HittableList::default();
+            positions
+                .iter()
+                .for_each(|&position| expected.add(sphere.clone().transform(position)));
+            for (i, position) in positions.iter().enumerate().step_by(7) {
+                let ray = Ray::new(*position + Vec3::new(0.1, 5., 0.), Vec3::new(0., -1., 0.02));
+                let got = refitting
+                    .get_hierarchy()
+                    .hit(&ray, 0.001..=f64::INFINITY)
+                    .map(|rec| rec.get_t());
+                let want = expected
+                    .hit(&ray, 0.001..=f64::INFINITY)
+                    .map(|rec| rec.get_t());
+                assert_eq!(got, want, "{i}");
+                assert!(got.is_some());
+            }
+            rebuilt
+        };
+
+        // Small moves keep the tree
+        let mut positions = start.clone();
+        let cost = refitting.get_hierarchy().sah_cost();
+        let wobble = |p: Vec3| p + Vec3::new((p.z * 0.7).sin(), 0.3, (p.x * 1.3).cos()) * 0.1;
+        assert!(!check(&mut refitting, &mut positions, &wobble));
+        assert!(!check(&mut refitting, &mut positions, &wobble));
+        assert_eq!(refitting.rebuilds(), 0);
+        assert!(refitting.get_hierarchy().sah_cost() < cost * 1.5);
+
+        // Shuffling the spheres around the grid makes the refitted tree too slow
+        let scatter = |p: Vec3| {
+            let (i, j) = ((p.x / 2.).round(), (p.z / 2.).round());
+            grid((i + 5. * j) % 16., (3. * i + 2. * j) % 16.)
+        };
+        assert!(check(&mut refitting, &mut positions, &scatter));
+        assert_eq!(refitting.rebuilds(), 1);
+        assert_eq!(refitting.get_hierarchy().len(), 256);
+    }
+
+    #[test]
+    fn bvh_unbounded_test() {
+        let material = Arc::new(Lambertian::new_with_colour(Colour::new(0.5, 0.5, 0.5)));
+        let position =
+            |i: f64| Point3::new((i * 1.37) % 20., 1. + (i * 0.61).sin(), (i * 2.9) % 20.);
+        let list = || {
+            let mut list = HittableList::default();
+            list.add(Plane::new(
+                Point3::default(),
+                Vec3::new(0., 1., 0.),
+                material.clone(),
+            ));
+            (0..100)
+                .for_each(|i| list.add(Sphere::new(position(f64::from(i)), 0.3, material.clone())));
+            list
+        };
+        let bvh = BoundedVolumeHierarchy::from(list());
+        let BoundedVolumeHierarchy::Unbounded {
+            bounded, unbounded, ..
+        } = &bvh
+        else {
+            panic!("the plane should be kept out of the tree");
+        };
+        assert_eq!(unbounded.len(), 1);
+        assert_eq!(bounded.len(), 100);
+        assert!(bounded.get_aabbox().is_finite());
+        assert!(bvh.sah_cost().is_finite());
+
+        let flat = list();
+        for i in 0..100 {
+            let i = f64::from(i);
+            let target = position(i);
+            // From below the rays hit the plane before the spheres
+            let origin = if i % 2. == 0. {
+                Point3::new(target.x + 0.1, 8., target.z)
+            } else {
+                Point3::new(target.x + 0.1, -3., target.z)
+            };
+            let ray = Ray::new(origin, target - origin);
+            let got = bvh.hit(&ray, 0.001..=f64::INFINITY).map(|rec| rec.get_t());
+            let want = flat.hit(&ray, 0.001..=f64::INFINITY).map(|rec| rec.get_t());
+            assert_eq!(got, want, "{i}");
+            assert!(got.is_some());
+        }
+    }
+
+    #[test]
+    fn analytic_primitives_test() {
+        let material = Arc::new(Lambertian::new_with_colour(Colour::new(0.5, 0.5, 0.5)));
+        let axis = Vec3::new(0.2, 1., 0.1);
+        let primitive = |k: u32| -> Arc<dyn BoundedHittable> {
+            let at = Point3::default();
+            match k % 5 {
+                0 => Arc::new(Disk::new(at, axis, 0.4, material.clone())),
+                1 => Arc::new(Cylinder::new(at, at + axis * 0.5, 0.3, material.clone())),
+                2 => Arc::new(Cone::new(at, at + axis * 0.6, 0.4, material.clone())),
+                3 => Arc::new(Torus::new(at, axis, 0.35, 0.1, material.clone())),
+                _ => Arc::new(Capsule::new(at, at + axis * 0.4, 0.2, material.clone())),
+            }
+        };
+        let position = |k: u32| {
+            let k = f64::from(k);
+            Vec3::new((k * 1.37) % 12., (k * 0.61).sin(), (k * 2.9) % 12.)
+        };
+        let list = || {
+            let mut list = HittableList::default();
+            (0..150).for_each(|k| list.add(primitive(k).transform(position(k))));
+            list
+        };
+        let bvh = BoundedVolumeHierarchy::from(list());
+        let flat = list();
+        let mut hits = 0;
+        for k in 0..150 {
+            let target = position(k).to_point();
+            for origin in [Vec3::new(0.13, 4., 0.05), Vec3::new(-3., 0.2, 0.4)] {
+                let origin = target + origin;
+                let ray = Ray::new(origin, target - origin);
+                let got = bvh.hit(&ray, 0.001..=f64::INFINITY).map(|rec| rec.get_t());
+                let want = flat.hit(&ray, 0.001..=f64::INFINITY).map(|rec| rec.get_t());
+                assert_eq!(got, want, "{k}");
+                hits += usize::from(got.is_some());
+            }
+        }
+        assert!(hits > 250, "{hits}");
+
+        // Moving the primitive is the same as making it somewhere else
+        let offset = Vec3::new(1., -2., 0.5);
+        let moved =
+            Torus::new(Point3::default(), axis, 0.35, 0.1, material.clone()).transform(offset);
+        let made = Torus::new(offset.to_point(), axis, 0.35, 0.1, material);
+        let ray = Ray::new(Point3::new(1.3, 3., 0.5), Vec3::new(0., -1., 0.));
+        let (moved, made) = (
+            moved.hit(&ray, 0.001..=f64::INFINITY).unwrap(),
+            made.hit(&ray, 0.001..=f64::INFINITY).unwrap(),
+        );
+        assert!((moved.get_t() - made.get_t()).abs() < 1e-9);
+        assert!((moved.get_normal() - made.get_normal()).length() < 1e-9);
+    }
+
+    #[test]
+    fn sdf_test() {
+        use shared::entities::sdf::{self, SdfExt as _};
+
+        let material = Arc::new(Lambertian::new_with_colour(Colour::new(0.5, 0.5, 0.5)));
+        let hit = |object: &dyn BoundedHittable, origin: Point3, direction: Vec3| {
+            object
+                .hit(&Ray::new(origin, direction), 0.001..=f64::INFINITY)
+                .map(|rec| (rec.get_t(), rec.get_normal()))
+        };
+
+        // Sphere tracing finds the analytic sphere
+        let traced = SdfObject::new(
+            sdf::Sphere::new(1.).translate(Vec3::new(0.5, 0., 0.)),
+            material.clone(),
+        );
+        let sphere = Sphere::new(Point3::new(0.5, 0., 0.), 1., material.clone());
+        for k in 0..50 {
+            let k = f64::from(k);
+            let origin = Point3::new(k.sin() * 4., k.cos() * 3., -4.);
+            let direction = Point3::new(0.5, 0., 0.) - origin + Vec3::new(k.cos(), 0.3, 0.) * 0.5;
+            let (got, want) = (
+                hit(&traced, origin, direction),
+                hit(&sphere, origin, direction),
+            );
+            match (got, want) {
+                (Some((t0, n0)), Some((t1, n1))) => {
+                    assert!((t0 - t1).abs() < 1e-3, "{k}");
+                    assert!((n0 - n1).length() < 1e-3, "{k}");
+                }
+                (None, None) => {}
+                _ => panic!("{k}: {got:?} != {want:?}"),
+            }
+        }
+        // Rays leaving the surface don't hit it again
+        let (t, _) = hit(&traced, Point3::new(0.5, 0., -4.), Vec3::new(0., 0., 1.)).unwrap();
+        let surface = Point3::new(0.5, 0., -4. + t);
+        assert!(hit(&traced, surface, Vec3::new(0.3, 0.2, -1.)).is_none());
+        let (t, _) = hit(&traced, surface, Vec3::new(0., 0., 1.)).unwrap();
+        assert!((t - 2.).abs() < 1e-3);
+
+        // Blending fills the gap between metaballs
+        let ball = || sdf::Sphere::new(0.5);
+        let apart = |k: f64| {
+            SdfObject::new(
+                ball()
+                    .translate(Vec3::new(-0.6, 0., 0.))
+                    .smooth_union(ball().translate(Vec3::new(0.6, 0., 0.)), k),
+                material.clone(),
+            )
+        };
+        let between = (Point3::new(0., 2., 0.), Vec3::new(0., -1., 0.));
+        assert!(hit(&apart(0.), between.0, between.1).is_none());
+        assert!(hit(&apart(0.5), between.0, between.1).is_some());
+
+        // A ball carved out of the side of a box leaves a dent
+        let carved = SdfObject::new(
+            sdf::Cuboid::new(Vec3::new(1., 1., 1.))
+                .with_rounding(0.1)
+                .subtract(ball().translate(Vec3::new(0., 0., -1.))),
+            material.clone(),
+        );
+        let (t, normal) = hit(&carved, Point3::new(0., 0., -3.), Vec3::new(0., 0., 1.)).unwrap();
+        assert!((t - 2.5).abs() < 1e-3);
+        assert!((normal - Vec3::new(0., 0., -1.)).length() < 1e-3);
+        let (t, normal) = hit(&carved, Point3::new(0.3, 0., -3.), Vec3::new(0., 0., 1.)).unwrap();
+        assert!((t - 2.4).abs() < 1e-3);
+        assert!((normal - Vec3::new(-0.6, 0., -0.8)).length() < 1e-3);
+        let (t, normal) = hit(&carved, Point3::new(0.7, 0., -3.), Vec3::new(0., 0., 1.)).unwrap();
+        assert!((t - 2.).abs() < 1e-3);
+        assert!((normal - Vec3::new(0., 0., -1.)).length() < 1e-3);
+
+        // Repeated, twisted and fractal shapes stay inside their boxes
+        let repeated = SdfObject::new(
+            ball().repeat(Vec3::new(2., 0., 2.), [2, 0, 1]),
+            material.clone(),
+        );
+        let (t, _) = hit(&repeated, Point3::new(4., 3., -2.), Vec3::new(0., -1., 0.)).unwrap();
+        assert!((t - 2.5).abs() < 1e-3);
+        assert!(hit(&repeated, Point3::new(6., 3., 0.), Vec3::new(0., -1., 0.)).is_none());
+        let twisted = SdfObject::new(
+            sdf::Cuboid::new(Vec3::new(0.5, 2., 0.2)).twist(0.8),
+            material.clone(),
+        );
+        let bulb = SdfObject::new(sdf::Mandelbulb::default(), material.clone());
+        let mut list = HittableList::default();
+        list.add(repeated);
+        list.add(twisted.transform(Vec3::new(0., 0., 8.)));
+        list.add(bulb.transform(Vec3::new(0., 0., -8.)));
+        let (t, _) = hit(&list, Point3::new(0., 0., -12.), Vec3::new(0., 0., 1.)).unwrap();
+        assert!((4. - 1.2..4.).contains(&t), "{t}");
+        let (t, _) = hit(&list, Point3::new(0., 1., 12.), Vec3::new(0., 0., -1.)).unwrap();
+        assert!((4. - 0.6..4.).contains(&t), "{t}");
+        let bvh = BoundedVolumeHierarchy::from(list);
+        assert!(hit(&bvh, Point3::new(0., 1., 12.), Vec3::new(0., 0., -1.)).is_some());
+    }
+
+    #[test]
+    fn csg_test() {
+        let material = Arc::new(Lambertian::new_with_colour(Colour::new(0.5, 0.5, 0.5)));
+        let light = Arc::new(DiffuseLight::new_with_colour(Colour::new(4., 4., 4.)));
+        let hit = |object: &dyn BoundedHittable, origin: Point3, direction: Vec3, start: f64| {
+            object
+                .hit(&Ray::new(origin, direction), start..=f64::INFINITY)
+                .map(|rec| {
+                    (
+                        rec.get_t(),
+                        rec.get_normal(),
+                        rec.is_front_face(),
+                        rec.get_material().is_emissive(),
+                    )
+                })
+        };
+        let assert_hit = |got: Option<(f64, Vec3, bool, bool)>, t: f64, normal: Vec3, faces| {
+            let (got_t, got_normal, front_face, emissive) = got.unwrap();
+            assert!((got_t - t).abs() < 1e-6, "{got_t} != {t}");
+            assert!((got_normal - normal).length() < 1e-6, "{got_normal:?}");
+            assert_eq!((front_face, emissive), faces);
+        };
+        let (x, z) = (Vec3::new(1., 0., 0.), Vec3::new(0., 0., 1.));
+
+        // A box with a hole through it, whose walls have the material of the cylinder
+        let drilled = Arc::new(Csg::difference(
+            Cuboid::new(
+                Point3::new(-1., -1., -1.),
+                Point3::new(1., 1., 1.),
+                material.clone(),
+            ),
+            Cylinder::new(
+                Point3::new(0., 0., -2.),
+                Point3::new(0., 0., 2.),
+                0.5,
+                light.clone(),
+            ),
+        ));
+        assert!(hit(drilled.as_ref(), Point3::new(0., 0.2, -5.), z, 0.001).is_none());
+        assert_hit(
+            hit(drilled.as_ref(), Point3::new(0.75, 0., -5.), z, 0.001),
+            4.,
+            -z,
+            (true, false),
+        );
+        // Starting inside the solid
+        assert_hit(
+            hit(drilled.as_ref(), Point3::new(0.75, 0., 0.), z, 0.001),
+            1.,
+            -z,
+            (false, false),
+        );
+        // Across the hole, the normals keep facing the ray
+        let origin = Point3::new(-5., 0., 0.);
+        for (t, faces) in [
+            (4., (true, false)),
+            (4.5, (false, true)),
+            (5.5, (true, true)),
+            (6., (false, false)),
+        ] {
+            assert_hit(hit(drilled.as_ref(), origin, x, t - 0.01), t, -x, faces);
+        }
+
+        // Lens made by two spheres, the hole is where none of the children are
+        let lens = Arc::new(Csg::intersection(
+            Sphere::new(Point3::new(-0.5, 0., 0.), 1., material.clone()),
+            Sphere::new(Point3::new(0.5, 0., 0.), 1., light.clone()),
+        ));
+        assert_hit(hit(lens.as_ref(), origin, x, 0.001), 4.5, -x, (true, true));
+        assert_hit(hit(lens.as_ref(), origin, x, 4.6), 5.5, -x, (false, false));
+        let (t, ..) = hit(lens.as_ref(), Point3::new(0., 0., -5.), z, 0.001).unwrap();
+        assert!((t - (5. - 0.75f64.sqrt())).abs() < 1e-9);
+        assert!(hit(lens.as_ref(), Point3::new(-1.2, 0., -5.), z, 0.001).is_none());
+        let merged = Csg::union(
+            Sphere::new(Point3::new(-0.5, 0., 0.), 1., material.clone()),
+            Sphere::new(Point3::new(0.5, 0., 0.), 1., light.clone()),
+        );
+        assert_hit(hit(&merged, origin, x, 0.001), 3.5, -x, (true, false));
+        assert_hit(hit(&merged, origin, x, 3.6), 6.5, -x, (false, true));
+
+        // Nodes can be nested, moved and put in hierarchies
+        let hollow = Csg::difference(
+            lens.clone(),
+            Sphere::new(Point3::default(), 0.3, material.clone()),
+        );
+        assert_hit(hit(&hollow, origin, x, 4.6), 4.7, -x, (false, false));
+        let offset = Vec3::new(0., 10., 3.);
+        let mut list = HittableList::default();
+        list.add(drilled.clone().transform(offset));
+        list.add(hollow.transform(-offset));
+        let bvh = BoundedVolumeHierarchy::from(list);
+        for start in [0.001, 4.1, 4.6, 5.6] {
+            assert_eq!(
+                hit(&bvh, origin + offset, x, start).map(|(t, ..)| t),
+                hit(drilled.as_ref(), origin, x, start).map(|(t, ..)| t),
+            );
+        }
+        assert_hit(hit(&bvh, origin - offset, x, 4.6), 4.7, -x, (false, false));
+    }
 }
@@ -4,17 +4,19 @@ use rand::{Rng as _, SeedableRng, distributions::Standard, rngs::SmallRng, threa
 
 use geometry::{
     aaplane::Axis,
-    transformations::{Transformable as _, rotation},
+    transformations::{Transformable as _, rotation, scale, translation},
     vec3::{Point3, Translation3, Vec3},
 };
 
 use shared::{
+    animation::{Animated, AnimatedCamera, Interpolation},
     camera::CameraBuilder,
     colour::Colour,
     entities::{Cuboid, Plane, Quad, Sphere},
     hittable::BoundedHittable,
     hittable_collections::{bvh::BoundedVolumeHierarchy, hittable_list::HittableList},
     material::{Dialectric, DiffuseLight, INVISIBLE_PTR, Lambertian, Material, Metal},
+    scene::AnimatedScene,
     texture::{CheckerTexture, NoiseTexture},
     utils::random_utils,
 };
@@ -651,3 +653,87 @@ pub fn simple_transform() -> (
         cam,
     )
 }
+
+/// A ball bouncing between spheres under a pulsing light, while the camera circles around and
+/// zooms in, at 24 frames per second.
+pub fn bouncing_spheres() -> AnimatedScene {
+    let ground = Arc::new(Lambertian::new(Arc::new(CheckerTexture::new_with_colours(
+        Colour::new(0.2, 0.3, 0.1),
+        Colour::new(0.9, 0.9, 0.9),
+        0.5,
+    ))));
+    let mut spheres = HittableList::default();
+    for (i, x) in [-4., -2., 2., 4.].into_iter().enumerate() {
+        let colour = [
+            Colour::new(0.8, 0.1, 0.1),
+            Colour::new(0.1, 0.8, 0.1),
+            Colour::new(0.1, 0.1, 0.8),
+            Colour::new(0.8, 0.8, 0.1),
+        ][i];
+        spheres.add(Sphere::new(
+            Point3::new(x, 0.7, 0.),
+            0.7,
+            Arc::new(Lambertian::new_with_colour(colour)),
+        ));
+    }
+
+    // Falls with a quadratic ease in and rises with an ease out, squashing when it lands
+    let up = translation(Vec3::new(0., 3., 0.));
+    let down = translation(Vec3::new(0., 0.6, 0.));
+    let squashed = scale(Vec3::new(1.25, 0.6, 1.25)).then(&translation(Vec3::new(0., 0.36, 0.)));
+    let bounce = Animated::new(up)
+        .with_eased_key(20., down, Interpolation::EASE_IN)
+        .with_key(24., squashed)
+        .with_key(28., down)
+        .with_eased_key(48., up, Interpolation::EASE_OUT);
+    let colour = Animated::new(Colour::new(0.9, 0.9, 0.9))
+        .with_key(24., Colour::new(0.9, 0.3, 0.1))
+        .with_key(48., Colour::new(0.9, 0.9, 0.9));
+
+    let intensity = Animated::new(2.)
+        .with_eased_key(24., 8., Interpolation::EASE_IN_OUT)
+        .with_eased_key(48., 2., Interpolation::EASE_IN_OUT);
+
+    let lookat = Point3::new(0., 1., 0.);
+    let lookfrom = |angle: f64| {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        Point3::new(12. * sin, 4., 12. * cos)
+    };
+    let camera_animation = AnimatedCamera::new()
+        .with_lookfrom(
+            Animated::new(lookfrom(0.))
+                .with_key(16., lookfrom(30.))
+                .with_key(32., lookfrom(60.))
+                .with_key(48., lookfrom(90.)),
+        )
+        .with_vfov(Animated::new(40.).with_eased_key(48., 30., Interpolation::EASE));
+    let cam = CameraBuilder::new()
+        .with_lookat(lookat)
+        .with_focus_dist((lookfrom(0.) - lookat).length())
+        .with_vfov(40.)
+        .with_background(Colour::new(0.05, 0.05, 0.08));
+
+    AnimatedScene::new(cam)
+        .with_static(Plane::new(
+            Point3::new(0., 0., 0.),
+            Vec3::new(0., 1., 0.),
+            ground,
+        ))
+        .with_static(BoundedVolumeHierarchy::from(spheres))
+        .with_animated(move |time| {
+            let material = Arc::new(Lambertian::new_with_colour(colour.at(time)));
+            Sphere::new(Point3::default(), 0.6, material).transform(bounce.at(time))
+        })
+        .with_animated(move |time| {
+            let light = Arc::new(DiffuseLight::new_with_colour(
+                Colour::new(1., 0.9, 0.8) * intensity.at(time),
+            ));
+            Quad::new(
+                Point3::new(-1., 6., -1.),
+                Vec3::new(2., 0., 0.),
+                Vec3::new(0., 0., 2.),
+                light,
+            )
+        })
+        .with_camera_animation(camera_animation)
+}
@@ -0,0 +1,299 @@
+use geometry::{
+    quaternion::Quaternion,
+    transformations::{Decomposition, Transformation},
+    vec3::{Point3, Vec3},
+};
+
+use crate::{camera::CameraBuilder, colour::Colour};
+
+/// Values which can be blended, `t` goes from 0, giving `self`, to 1, giving `other`.
+pub trait Interpolate {
+    #[must_use]
+    fn interpolate(&self, other: &Self, t: f64) -> Self;
+}
+
+impl Interpolate for f64 {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for Vec3 {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        *self + (*other - *self) * t
+    }
+}
+
+#[cfg(feature = "euclid")]
+impl Interpolate for Point3 {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        *self + (*other - *self) * t
+    }
+}
+
+impl Interpolate for Colour {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        *self * (1. - t) + *other * t
+    }
+}
+
+impl Interpolate for Quaternion {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        self.slerp(*other, t)
+    }
+}
+
+impl Interpolate for Decomposition {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        self.lerp(other, t)
+    }
+}
+
+/// Interpolates the [`Decomposition`]s, so rotations stay rotations, transformations which
+/// can't be decomposed jump at the end.
+impl Interpolate for Transformation {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        match (Decomposition::new(self), Decomposition::new(other)) {
+            (Some(start), Some(end)) => start.lerp(&end, t).compose(),
+            _ if t < 1. => *self,
+            _ => *other,
+        }
+    }
+}
+
+/// How the value goes from a keyframe to the next one.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Interpolation {
+    /// Keeps the value of the first keyframe until the next one
+    Step,
+    #[default]
+    Linear,
+    /// Timing curve from `(0, 0)` to `(1, 1)` with control points `(x1, y1)` and `(x2, y2)`,
+    /// like the CSS `cubic-bezier`, the x coordinates are clamped to `[0, 1]`
+    Bezier([f64; 4]),
+}
+
+impl Interpolation {
+    pub const EASE: Self = Self::Bezier([0.25, 0.1, 0.25, 1.]);
+    pub const EASE_IN: Self = Self::Bezier([0.42, 0., 1., 1.]);
+    pub const EASE_OUT: Self = Self::Bezier([0., 0., 0.58, 1.]);
+    pub const EASE_IN_OUT: Self = Self::Bezier([0.42, 0., 0.58, 1.]);
+
+    /// How far the value has gone when `t` of the time between the keyframes has passed.
+    #[must_use]
+    pub fn ease(self, t: f64) -> f64 {
+        match self {
+            Self::Step => 0.,
+            Self::Linear => t,
+            Self::Bezier([x1, y1, x2, y2]) => {
+                let bezier = |p1: f64, p2: f64, s: f64| {
+                    let r = 1. - s;
+                    3. * r * r * s * p1 + 3. * r * s * s * p2 + s * s * s
+                };
+                let (x1, x2) = (x1.clamp(0., 1.), x2.clamp(0., 1.));
+                // x grows with s, so the s giving t can be bisected
+                let (mut low, mut high) = (0., 1.);
+                for _ in 0..48 {
+                    let middle = (low + high) / 2.;
+                    if bezier(x1, x2, middle) < t {
+                        low = middle;
+                    } else {
+                        high = middle;
+                    }
+                }
+                bezier(y1, y2, (low + high) / 2.)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Keyframe<T> {
+    time: f64,
+    value: T,
+    /// How the value reaches this keyframe from the previous one
+    interpolation: Interpolation,
+}
+
+/// Value changing over time, given by keyframes, before the first one and after the last one it
+/// keeps their value.
+#[derive(Debug, Clone)]
+pub struct Animated<T> {
+    /// Sorted by time, there's always at least one
+    keyframes: Vec<Keyframe<T>>,
+}
+
+impl<T> Animated<T>
+where
+    T: Interpolate + Clone,
+{
+    /// Value which doesn't change, keyframes can be added later.
+    pub fn new(value: T) -> Self {
+        Self {
+            keyframes: vec![Keyframe {
+                time: 0.,
+                value,
+                interpolation: Interpolation::Step,
+            }],
+        }
+    }
+
+    /// Keyframe reached linearly from the previous one, replaces the one at the same time.
+    #[must_use]
+    pub fn with_key(self, time: f64, value: T) -> Self {
+        self.with_eased_key(time, value, Interpolation::Linear)
+    }
+
+    #[must_use]
+    pub fn with_eased_key(mut self, time: f64, value: T, interpolation: Interpolation) -> Self {
+        self.add_key(time, value, interpolation);
+        self
+    }
+
+    pub fn add_key(&mut self, time: f64, value: T, interpolation: Interpolation) {
+        let keyframe = Keyframe {
+            time,
+            value,
+            interpolation,
+        };
+        match self
+            .keyframes
+            .binary_search_by(|keyframe| keyframe.time.total_cmp(&time))
+        {
+            Ok(i) => self.keyframes[i] = keyframe,
+            Err(i) => self.keyframes.insert(i, keyframe),
+        }
+    }
+
+    /// Whether it has the same value at every time.
+    pub fn is_static(&self) -> bool {
+        self.keyframes.len() == 1
+    }
+
+    pub fn at(&self, time: f64) -> T {
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        if next == 0 {
+            return self.keyframes[0].value.clone();
+        }
+        let previous = &self.keyframes[next - 1];
+        let Some(next) = self.keyframes.get(next) else {
+            return previous.value.clone();
+        };
+        let t = (time - previous.time) / (next.time - previous.time);
+        previous
+            .value
+            .interpolate(&next.value, next.interpolation.ease(t))
+    }
+}
+
+impl<T> From<T> for Animated<T>
+where
+    T: Interpolate + Clone,
+{
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// Animated settings of a [`CameraBuilder`], the ones which aren't set are left as they are.
+#[derive(Debug, Clone, Default)]
+pub struct AnimatedCamera {
+    lookfrom: Option<Animated<Point3>>,
+    lookat: Option<Animated<Point3>>,
+    vfov: Option<Animated<f64>>,
+    focus_dist: Option<Animated<f64>>,
+}
+
+impl AnimatedCamera {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_lookfrom(self, lookfrom: Animated<Point3>) -> Self {
+        Self {
+            lookfrom: Some(lookfrom),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_lookat(self, lookat: Animated<Point3>) -> Self {
+        Self {
+            lookat: Some(lookat),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_vfov(self, vfov: Animated<f64>) -> Self {
+        Self {
+            vfov: Some(vfov),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_focus_dist(self, focus_dist: Animated<f64>) -> Self {
+        Self {
+            focus_dist: Some(focus_dist),
+            ..self
+        }
+    }
+
+    /// `camera` with the animated settings at `time`.
+    pub fn apply(&self, camera: CameraBuilder, time: f64) -> CameraBuilder {
+        let mut camera = camera;
+        if let Some(lookfrom) = &self.lookfrom {
+            camera = camera.with_lookfrom(lookfrom.at(time));
+        }
+        if let Some(lookat) = &self.lookat {
+            camera = camera.with_lookat(lookat.at(time));
+        }
+        if let Some(vfov) = &self.vfov {
+            camera = camera.with_vfov(vfov.at(time));
+        }
+        if let Some(focus_dist) = &self.focus_dist {
+            camera = camera.with_focus_dist(focus_dist.at(time));
+        }
+        camera
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Animated, Interpolation};
+
+    #[test]
+    fn keyframes_are_interpolated() {
+        let animated =
+            Animated::new(0.)
+                .with_key(10., 5.)
+                .with_eased_key(20., 1., Interpolation::Step);
+        assert!(!animated.is_static());
+        assert!((animated.at(-3.) - 0.).abs() < 1e-12);
+        assert!((animated.at(4.) - 2.).abs() < 1e-12);
+        assert!((animated.at(10.) - 5.).abs() < 1e-12);
+        assert!((animated.at(19.9) - 5.).abs() < 1e-12);
+        assert!((animated.at(25.) - 1.).abs() < 1e-12);
+        // Keys at the same time replace each other
+        let animated = animated.with_key(10., 7.);
+        assert!((animated.at(10.) - 7.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn bezier_eases_in_and_out() {
+        let ease = Interpolation::EASE_IN_OUT;
+        assert!(ease.ease(0.).abs() < 1e-9);
+        assert!((ease.ease(1.) - 1.).abs() < 1e-9);
+        assert!((ease.ease(0.5) - 0.5).abs() < 1e-9);
+        assert!(ease.ease(0.1) < 0.1 && ease.ease(0.9) > 0.9);
+        let samples: Vec<_> = (0..=20).map(|i| ease.ease(f64::from(i) / 20.)).collect();
+        assert!(samples.windows(2).all(|pair| pair[0] <= pair[1]));
+        // Straight control points give back the linear interpolation
+        let linear = Interpolation::Bezier([0.25, 0.25, 0.75, 0.75]);
+        assert!((linear.ease(0.3) - 0.3).abs() < 1e-9);
+    }
+}
@@ -1,7 +1,14 @@
+mod film;
+mod physical;
+mod projection;
+
 use core::f64;
-use std::ops::{Add, Div, Mul, Sub};
 #[cfg(feature = "hit_counters")]
-use std::sync::atomic::{self, AtomicU64, Ordering};
+use std::sync::atomic::{self, Ordering};
+use std::{
+    ops::{Add, Div, Mul, Sub},
+    sync::Arc,
+};
 
 use rand::{
     Rng as _, SeedableRng as _,
@@ -13,105 +20,216 @@ use rayon::iter::{IntoParallelIterator as _, ParallelIterator as _};
 
 use kdam::par_tqdm;
 
+#[cfg(feature = "hit_counters")]
+use crate::integrator::HIT_COUNTER;
 use crate::{
-    colour::{Colour, SampledColour},
+    colour::{Colour, ColourSpace, SampledColour, WhiteBalance},
+    environment::{Background, Environment},
     hittable::Hittable,
-    material::ScatterReflect,
-    pdf::{HittablePdf, MixturePdf, Pdf},
+    integrator::{Integrator, Lighting, PathTracer},
+    lights::PunctualLight,
     ray::Ray,
-    utils::random_utils::UnitDisk,
+    scene::{Scene, SceneLights},
+    spectrum::SampledWavelengths,
 };
 #[cfg(feature = "euclid")]
 use geometry::vec3::Vec3Ext as _;
 use geometry::vec3::{Point3, Vec3};
 
+pub use crate::integrator::LightingStrategy;
+pub use film::Film;
+pub use projection::Projection;
+
+use physical::Lens;
+pub use physical::{
+    Aperture, ApertureImage, Exposure, LensDistortion, PhysicalCameraBuilder, SensorSize,
+};
+
 #[derive(Debug, Clone, Copy)]
+enum FieldOfView {
+    /// In degrees
+    Vertical(f64),
+    /// Tangent of half the vertical angle covered by the sensor
+    Sensor { aspect_ratio: f64, half_height: f64 },
+}
+
+#[derive(Debug, Clone)]
 pub struct CameraBuilder {
     aspect_ratio: Option<f64>,
     image_width: Option<u32>,
     image_height: Option<u32>,
     samples_per_pixel: u16,
     max_depth: u32,
-    background: Colour,
+    background: Background,
     vfov: f64,
     lookfrom: Point3,
     lookat: Point3,
     vup: Vec3,
     defocus_angle: f64,
     focus_dist: f64,
+    projection: Projection,
+    lighting_strategy: LightingStrategy,
+    integrator: Arc<dyn Integrator>,
+    seed: Option<u64>,
+    spectral: bool,
+    white_balance: Option<f64>,
+    working_space: ColourSpace,
 }
 
 impl CameraBuilder {
-    pub const fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             aspect_ratio: None,
             image_width: None,
             image_height: None,
             samples_per_pixel: 10,
             max_depth: 10,
-            background: Colour::new(0., 0., 0.),
+            background: Background::Colour(Colour::new(0., 0., 0.)),
             vfov: 90.,
             lookfrom: Point3::new(0., 0., 0.),
             lookat: Point3::new(0., 0., -1.),
             vup: Vec3::new(0., 1., 0.),
             defocus_angle: 0.,
             focus_dist: 10.,
+            projection: Projection::Perspective,
+            lighting_strategy: LightingStrategy::Mixture,
+            integrator: Arc::new(PathTracer),
+            seed: None,
+            spectral: false,
+            white_balance: None,
+            working_space: ColourSpace::Srgb,
         }
     }
 
-    pub const fn with_aspect_ratio(self, aspect_ratio: f64) -> Self {
+    pub fn with_aspect_ratio(self, aspect_ratio: f64) -> Self {
         Self {
             aspect_ratio: Some(aspect_ratio),
             ..self
         }
     }
-    pub const fn with_image_width(self, image_width: u32) -> Self {
+    pub fn with_image_width(self, image_width: u32) -> Self {
         Self {
             image_width: Some(image_width),
             ..self
         }
     }
-    pub const fn with_image_height(self, image_height: u32) -> Self {
+    pub fn with_image_height(self, image_height: u32) -> Self {
         Self {
             image_height: Some(image_height),
             ..self
         }
     }
-    pub const fn with_samples_per_pixel(self, samples_per_pixel: u16) -> Self {
+    pub fn with_samples_per_pixel(self, samples_per_pixel: u16) -> Self {
         Self {
             samples_per_pixel,
             ..self
         }
     }
-    pub const fn with_max_depth(self, max_depth: u32) -> Self {
+    pub fn with_max_depth(self, max_depth: u32) -> Self {
         Self { max_depth, ..self }
     }
-    pub const fn with_background(self, background: Colour) -> Self {
-        Self { background, ..self }
+    pub fn with_background(self, background: Colour) -> Self {
+        Self {
+            background: Background::Colour(background),
+            ..self
+        }
     }
-    pub const fn with_vfov(self, vfov: f64) -> Self {
+    /// Rays that miss look up the environment, add an
+    /// [`EnvironmentLight`](crate::environment::EnvironmentLight) to the lights to sample it.
+    pub fn with_environment(self, environment: Arc<dyn Environment>) -> Self {
+        Self {
+            background: Background::Environment(environment),
+            ..self
+        }
+    }
+    pub fn with_vfov(self, vfov: f64) -> Self {
         Self { vfov, ..self }
     }
-    pub const fn with_lookfrom(self, lookfrom: Point3) -> Self {
+    pub fn with_lookfrom(self, lookfrom: Point3) -> Self {
         Self { lookfrom, ..self }
     }
-    pub const fn with_lookat(self, lookat: Point3) -> Self {
+    pub fn with_lookat(self, lookat: Point3) -> Self {
         Self { lookat, ..self }
     }
-    pub const fn with_vup(self, vup: Vec3) -> Self {
+    pub fn with_vup(self, vup: Vec3) -> Self {
         Self { vup, ..self }
     }
-    pub const fn with_defocus_angle(self, defocus_angle: f64) -> Self {
+    pub fn with_defocus_angle(self, defocus_angle: f64) -> Self {
         Self {
             defocus_angle,
             ..self
         }
     }
-    pub const fn with_focus_dist(self, focus_dist: f64) -> Self {
+    pub fn with_focus_dist(self, focus_dist: f64) -> Self {
         Self { focus_dist, ..self }
     }
+    pub fn with_projection(self, projection: Projection) -> Self {
+        Self { projection, ..self }
+    }
+    /// Only used by integrators which sample the lights, like the [`PathTracer`].
+    pub fn with_lighting_strategy(self, lighting_strategy: LightingStrategy) -> Self {
+        Self {
+            lighting_strategy,
+            ..self
+        }
+    }
+    pub fn with_integrator(self, integrator: Arc<dyn Integrator>) -> Self {
+        Self { integrator, ..self }
+    }
+    /// Seeds the random numbers of every pixel, photon and Markov chain, so renders can be
+    /// reproduced.
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    /// Traces every sample at a few wavelengths instead of in RGB, which is needed for
+    /// [`Dispersion`](crate::material::Dispersion). Spatial reuse and photon mapping still
+    /// render in RGB.
+    pub fn with_spectral(self, spectral: bool) -> Self {
+        Self { spectral, ..self }
+    }
+
+    /// Makes the light of a blackbody at `temperature` Kelvin white.
+    pub fn with_white_balance(self, temperature: f64) -> Self {
+        Self {
+            white_balance: Some(temperature),
+            ..self
+        }
+    }
+
+    /// Space whose primaries the paths are traced with, the rendered colours are in it too.
+    pub fn with_working_space(self, working_space: ColourSpace) -> Self {
+        Self {
+            working_space,
+            ..self
+        }
+    }
+
+    pub fn into_physical(self) -> PhysicalCameraBuilder {
+        PhysicalCameraBuilder::new(self)
+    }
 
     pub fn build(self) -> Camera {
+        let defocus_radius = self.defocus_angle.div(2.).tan().mul(self.focus_dist);
+        let (vfov, focus_dist) = (self.vfov, self.focus_dist);
+        self.build_inner(
+            FieldOfView::Vertical(vfov),
+            focus_dist,
+            defocus_radius,
+            Lens::default(),
+        )
+    }
+
+    fn build_inner(
+        self,
+        fov: FieldOfView,
+        focus_dist: f64,
+        defocus_radius: f64,
+        lens: Lens,
+    ) -> Camera {
         let CameraBuilder {
             aspect_ratio,
             image_width,
@@ -119,12 +237,19 @@ impl CameraBuilder {
             samples_per_pixel,
             max_depth,
             background,
-            vfov,
+            vfov: _,
             lookfrom,
             lookat,
             vup,
-            defocus_angle,
-            focus_dist,
+            defocus_angle: _,
+            focus_dist: _,
+            projection,
+            lighting_strategy,
+            integrator,
+            seed,
+            spectral,
+            white_balance,
+            working_space,
         } = self;
 
         let (aspect_ratio, image_height, image_width) =
@@ -158,9 +283,22 @@ impl CameraBuilder {
         let pixel_samples_scale = 1. / samples_per_pixel as f64;
         let center = lookfrom;
 
-        let theta = vfov.to_radians();
-        let h = theta.div(2.).tan();
-        let viewport_height = 2. * h * focus_dist;
+        let h = match fov {
+            FieldOfView::Vertical(vfov) => vfov.to_radians().div(2.).tan(),
+            // The sensor is cropped to the image, keeping whichever side is the tightest
+            FieldOfView::Sensor {
+                aspect_ratio: sensor_aspect_ratio,
+                half_height,
+            } if aspect_ratio > sensor_aspect_ratio => {
+                half_height * sensor_aspect_ratio / aspect_ratio
+            }
+            FieldOfView::Sensor { half_height, .. } => half_height,
+        };
+        let vfov = h.atan().mul(2.).to_degrees();
+        let viewport_height = match projection {
+            Projection::Orthographic { height } => height,
+            _ => 2. * h * focus_dist,
+        };
         let viewport_width = viewport_height * aspect_ratio;
 
         let w = {
@@ -180,14 +318,8 @@ impl CameraBuilder {
         let viewport_v = v * viewport_height;
         // dbg!(viewport_u, viewport_v);
 
-        let pixel_delta_u = viewport_u / (image_width as f64);
-        let pixel_delta_v = viewport_v / (image_height as f64);
-
-        let viewport_upper_left_corner =
-            center - (w * focus_dist) - viewport_u / 2. - viewport_v / 2.;
-        let pixel00_loc = viewport_upper_left_corner + (pixel_delta_u + pixel_delta_v) / 2.;
-        // dbg!(viewport_upper_left_corner, pixel00_loc);
-        let defocus_radius = defocus_angle.div(2.).tan().mul(focus_dist);
+        let viewport_center = center - (w * focus_dist);
+        let defocus_angle = defocus_radius.div(focus_dist).atan().mul(2.);
         let defocus_disk_u = u * defocus_radius;
         let defocus_disk_v = v * defocus_radius;
 
@@ -206,14 +338,23 @@ impl CameraBuilder {
             focus_dist,
             pixel_samples_scale,
             center,
-            pixel00_loc,
-            pixel_delta_u,
-            pixel_delta_v,
+            viewport_center,
+            viewport_u,
+            viewport_v,
             u,
             v,
             w,
+            defocus_radius,
             defocus_disk_u,
             defocus_disk_v,
+            lens,
+            projection,
+            lighting_strategy,
+            integrator,
+            seed,
+            spectral,
+            white_balance,
+            working_space,
         }
     }
 }
@@ -232,7 +373,7 @@ pub struct Camera {
     image_height: u32,
     samples_per_pixel: u16,
     max_depth: u32,
-    background: Colour,
+    background: Background,
     #[expect(unused)]
     vfov: f64,
     #[expect(unused)]
@@ -241,23 +382,52 @@ pub struct Camera {
     lookat: Point3,
     #[expect(unused)]
     vup: Vec3,
-    defocus_angle: f64,
     #[expect(unused)]
+    defocus_angle: f64,
     focus_dist: f64,
     #[expect(unused)]
     pixel_samples_scale: f64,
     center: Point3,
-    pixel00_loc: Point3,
-    pixel_delta_u: Vec3,
-    pixel_delta_v: Vec3,
-    #[expect(unused)]
+    viewport_center: Point3,
+    viewport_u: Vec3,
+    viewport_v: Vec3,
     u: Vec3,
-    #[expect(unused)]
     v: Vec3,
-    #[expect(unused)]
     w: Vec3,
+    defocus_radius: f64,
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
+    lens: Lens,
+    projection: Projection,
+    lighting_strategy: LightingStrategy,
+    integrator: Arc<dyn Integrator>,
+    seed: Option<u64>,
+    spectral: bool,
+    white_balance: Option<f64>,
+    working_space: ColourSpace,
+}
+
+/// See [`Camera::importance`].
+#[derive(Debug, Clone, Copy)]
+pub struct Importance {
+    /// Pixel the ray lands on, rows are counted from the bottom like in [`Camera::get_ray`]
+    pub pixel: [usize; 2],
+    /// Includes the exposure and vignetting
+    pub value: f64,
+    /// Density of the origin of the ray on the lens, in area measure
+    pub pdf_position: f64,
+    /// Density of the direction of the ray, in solid angle
+    pub pdf_direction: f64,
+}
+
+/// See [`Camera::sample_importance`].
+#[derive(Debug, Clone, Copy)]
+pub struct ImportanceSample {
+    pub lens_point: Point3,
+    pub pixel: [usize; 2],
+    /// Importance divided by the density of `lens_point` in solid angle, as seen from the point
+    /// being connected
+    pub weight: f64,
 }
 
 pub(crate) enum DebugModes {
@@ -267,33 +437,215 @@ pub(crate) enum DebugModes {
     Miri,
 }
 
-#[cfg(feature = "hit_counters")]
-static HIT_COUNTER: AtomicU64 = AtomicU64::new(0);
 impl Camera {
     // #[inline]
+    /// When the pixel sample falls outside of the projection, like in the corners of a fisheye
+    /// image, the ray looks backwards from the center of the camera, see
+    /// [`Camera::try_get_ray`].
     pub fn get_ray(&self, i: usize, j: usize, rng: &mut dyn rand::RngCore) -> Ray {
+        self.try_get_ray(i, j, rng)
+            .unwrap_or_else(|| Ray::new(self.center, self.w))
+    }
+
+    /// Returns `None` if the pixel sample falls outside of the projection, like the corners of
+    /// a fisheye image.
+    pub fn try_get_ray(&self, i: usize, j: usize, rng: &mut dyn rand::RngCore) -> Option<Ray> {
+        self.sample_ray(i, j, rng).map(|(ray, _)| ray)
+    }
+
+    /// Also returns the weight of the sample on the film, which accounts for vignetting and
+    /// exposure.
+    pub(crate) fn sample_ray(
+        &self,
+        i: usize,
+        j: usize,
+        rng: &mut dyn rand::RngCore,
+    ) -> Option<(Ray, f64)> {
         let dist = Uniform::new_inclusive(-0.5, 0.5);
         let offset = (dist.sample(rng), dist.sample(rng));
-        let pixel_sample = self.pixel00_loc
-            + self.pixel_delta_u * (i as f64 + offset.0)
-            + self.pixel_delta_v * (j as f64 + offset.1);
+        let (width, height) = (self.image_width as f64, self.image_height as f64);
+        // Film coordinates, in [-0.5, 0.5] from left to right and bottom to top
+        let mut s = (i as f64 + 0.5 + offset.0) / width - 0.5;
+        let mut t = (j as f64 + 0.5 + offset.1) / height - 0.5;
+
+        let direction = match self.projection {
+            projection if projection.uses_film_plane() => {
+                if let Some(distortion) = self.lens.distortion {
+                    let aspect_ratio = width / height;
+                    let (x, y) = distortion.apply(2. * s * aspect_ratio, 2. * t);
+                    (s, t) = (x / aspect_ratio / 2., y / 2.);
+                }
+                let pixel_sample = self.viewport_center + self.viewport_u * s + self.viewport_v * t;
 
-        debug_assert!(
-            pixel_sample.x.is_finite() && pixel_sample.y.is_finite() && pixel_sample.z.is_finite()
-        );
+                debug_assert!(
+                    pixel_sample.x.is_finite()
+                        && pixel_sample.y.is_finite()
+                        && pixel_sample.z.is_finite()
+                );
 
-        let origin = if self.defocus_angle <= f64::EPSILON {
-            self.center
+                let (pinhole, weight) = if projection == Projection::Perspective {
+                    let cos_theta = self.focus_dist / (pixel_sample - self.center).length();
+                    (self.center, self.lens.vignetting_weight(cos_theta))
+                } else {
+                    (pixel_sample + self.w * self.focus_dist, 1.)
+                };
+                let origin = pinhole + self.sample_lens(rng);
+                return Some((
+                    Ray::new(origin, pixel_sample - origin),
+                    weight * self.lens.exposure,
+                ));
+            }
+            Projection::Fisheye { fov } => {
+                let radius = width.min(height) / 2.;
+                Projection::fisheye_direction(fov, s * width / radius, t * height / radius)?
+            }
+            Projection::Equirectangular => Projection::equirectangular_direction(s + 0.5, t + 0.5),
+            _ => Projection::cube_map_direction(s + 0.5, t + 0.5),
+        };
+        let direction = self.u * direction[0] + self.v * direction[1] - self.w * direction[2];
+
+        // Depth of field only makes sense when the lens faces the scene
+        let origin = if matches!(self.projection, Projection::Fisheye { .. }) {
+            self.center + self.sample_lens(rng)
         } else {
-            let p = rng.sample(UnitDisk);
-            self.center + self.defocus_disk_u * p.x + self.defocus_disk_v * p.z
+            self.center
         };
-        let direction = pixel_sample - origin;
-        Ray::new(origin, direction)
+        let focus_point = self.center + direction * self.focus_dist;
+        Some((Ray::new(origin, focus_point - origin), self.lens.exposure))
+    }
+
+    /// Random numbers of the `pass`-th sample of the pixel at `index`, reproducible if the
+    /// camera has a seed. Integrators which don't sample per pixel use it for their photons or
+    /// chains.
+    pub(crate) fn pixel_rng(&self, pass: u64, index: usize) -> SmallRng {
+        match self.seed {
+            Some(seed) => SmallRng::seed_from_u64(seed ^ pass.rotate_right(24) ^ index as u64),
+            None => SmallRng::from_rng(thread_rng()).unwrap(),
+        }
+    }
+
+    /// Wavelengths carried by a sample, `None` when rendering in RGB.
+    pub(crate) fn sample_wavelengths(
+        &self,
+        rng: &mut dyn rand::RngCore,
+    ) -> Option<SampledWavelengths> {
+        self.spectral
+            .then(|| SampledWavelengths::sample(rng.r#gen()))
+    }
+
+    /// Light found by `trace` for a sample, in RGB. The sample is traced with `lighting` at
+    /// wavelengths drawn from `rng` when rendering spectrally.
+    pub(crate) fn trace_sample<R: rand::RngCore>(
+        &self,
+        rng: &mut R,
+        lighting: &Lighting<'_>,
+        trace: impl FnOnce(&mut R, &Lighting<'_>) -> Colour,
+    ) -> Colour {
+        let lighting = lighting.with_wavelengths(self.sample_wavelengths(rng));
+        lighting.to_rgb(trace(rng, &lighting))
+    }
+
+    pub const fn get_working_space(&self) -> ColourSpace {
+        self.working_space
+    }
+
+    /// Width and height of the image in pixels.
+    pub const fn get_image_size(&self) -> (usize, usize) {
+        (self.image_width as usize, self.image_height as usize)
+    }
+
+    pub const fn get_samples_per_pixel(&self) -> u16 {
+        self.samples_per_pixel
+    }
+
+    pub const fn get_max_depth(&self) -> u32 {
+        self.max_depth
+    }
+
+    fn sample_lens(&self, rng: &mut dyn rand::RngCore) -> Vec3 {
+        if self.defocus_radius <= f64::EPSILON {
+            Vec3::new(0., 0., 0.)
+        } else {
+            let p = rng.sample(&self.lens.aperture);
+            self.defocus_disk_u * p.x + self.defocus_disk_v * p.z
+        }
+    }
+
+    /// Area of the lens, 1 for a pinhole.
+    fn lens_area(&self) -> Option<f64> {
+        if self.defocus_radius <= f64::EPSILON {
+            Some(1.)
+        } else {
+            let area = self.lens.aperture.area()?;
+            Some(area * self.defocus_radius * self.defocus_radius)
+        }
+    }
+
+    /// Importance carried by a ray leaving the lens, `None` if it doesn't reach the film.
+    ///
+    /// Only perspective cameras without lens distortion or image apertures are supported.
+    pub fn importance(&self, r: &Ray) -> Option<Importance> {
+        if self.projection != Projection::Perspective || self.lens.distortion.is_some() {
+            return None;
+        }
+        let lens_area = self.lens_area()?;
+        let direction = r.get_direction().normalize();
+        let cos_theta = -direction.dot(self.w);
+        if cos_theta <= 0. {
+            return None;
+        }
+        // The viewport is on the focus plane
+        let focus_point = r.get_origin() + direction * (self.focus_dist / cos_theta);
+        let offset = focus_point - self.viewport_center;
+        let s = offset.dot(self.viewport_u) / self.viewport_u.square_length();
+        let t = offset.dot(self.viewport_v) / self.viewport_v.square_length();
+        if s.abs() > 0.5 || t.abs() > 0.5 {
+            return None;
+        }
+        let pixel = [
+            (((s + 0.5) * self.image_width as f64) as usize).min(self.image_width as usize - 1),
+            (((t + 0.5) * self.image_height as f64) as usize).min(self.image_height as usize - 1),
+        ];
+        // Area of the viewport at unit distance
+        let film_area = self.viewport_u.length() * self.viewport_v.length()
+            / (self.focus_dist * self.focus_dist);
+        let cos2 = cos_theta * cos_theta;
+        // Same weight as `sample_ray`
+        let weight = self
+            .lens
+            .vignetting_weight(self.focus_dist / (focus_point - self.center).length())
+            * self.lens.exposure;
+        Some(Importance {
+            pixel,
+            value: weight / (film_area * lens_area * cos2 * cos2),
+            pdf_position: lens_area.recip(),
+            pdf_direction: (film_area * cos2 * cos_theta).recip(),
+        })
+    }
+
+    /// Samples a point on the lens to connect `p` to the film.
+    pub fn sample_importance(
+        &self,
+        p: Point3,
+        rng: &mut dyn rand::RngCore,
+    ) -> Option<ImportanceSample> {
+        let lens_area = self.lens_area()?;
+        let lens_point = self.center + self.sample_lens(rng);
+        let to_point = p - lens_point;
+        let importance = self.importance(&Ray::new(lens_point, to_point))?;
+        let distance_squared = to_point.square_length();
+        let cos_theta = -to_point.dot(self.w) / distance_squared.sqrt();
+        let pdf = distance_squared / (cos_theta * lens_area);
+        Some(ImportanceSample {
+            lens_point,
+            pixel: importance.pixel,
+            weight: importance.value / pdf,
+        })
     }
 
     pub fn render(&self, world: &dyn Hittable, lights: &dyn Hittable) -> Vec<Vec<SampledColour>> {
-        self.render_internal(world, lights, DebugModes::Off)
+        let lighting = self.lighting(world, Some(lights), &[]);
+        self.render_internal(&lighting, DebugModes::Off)
     }
 
     pub fn render_debug(
@@ -301,6 +653,47 @@ impl Camera {
         world: &dyn Hittable,
         lights: &dyn Hittable,
     ) -> Vec<Vec<SampledColour>> {
+        let lighting = self.lighting(world, Some(lights), &[]);
+        self.render_internal(&lighting, self.debug_mode())
+    }
+
+    pub fn render_scene(&self, scene: &Scene) -> Vec<Vec<SampledColour>> {
+        let lights = scene.get_lights();
+        let lighting = self.lighting(
+            scene.get_world(),
+            lights.as_ref().map(SceneLights::as_hittable),
+            scene.get_punctual_lights(),
+        );
+        self.render_internal(&lighting, DebugModes::Off)
+    }
+
+    pub fn render_scene_debug(&self, scene: &Scene) -> Vec<Vec<SampledColour>> {
+        let lights = scene.get_lights();
+        let lighting = self.lighting(
+            scene.get_world(),
+            lights.as_ref().map(SceneLights::as_hittable),
+            scene.get_punctual_lights(),
+        );
+        self.render_internal(&lighting, self.debug_mode())
+    }
+
+    const fn lighting<'a>(
+        &'a self,
+        world: &'a dyn Hittable,
+        lights: Option<&'a dyn Hittable>,
+        punctual_lights: &'a [Box<dyn PunctualLight>],
+    ) -> Lighting<'a> {
+        Lighting::new(
+            world,
+            lights,
+            punctual_lights,
+            &self.background,
+            self.lighting_strategy,
+            self,
+        )
+    }
+
+    fn debug_mode(&self) -> DebugModes {
         #[cfg(debug_assertions)]
         dbg!(self);
 
@@ -308,49 +701,25 @@ impl Camera {
         const DEBUG_MODE: DebugModes = DebugModes::Normal;
         #[cfg(miri)]
         const DEBUG_MODE: DebugModes = DebugModes::Miri;
-        self.render_internal(world, lights, DEBUG_MODE)
+        DEBUG_MODE
     }
 
     #[inline]
     fn render_internal(
         &self,
-        world: &dyn Hittable,
-        lights: &dyn Hittable,
+        lighting: &Lighting<'_>,
         debug_mode: DebugModes,
     ) -> Vec<Vec<SampledColour>> {
-        // Render
-        let render_lambda = move |(j, i, v, mut rng): (_, _, &mut Colour, _)| {
-            *v = (0..self.samples_per_pixel)
-                .map(|_| {
-                    let r = self.get_ray(i, j, &mut rng);
-                    Self::ray_colour_call(
-                        &r,
-                        &self.background,
-                        world,
-                        lights,
-                        &mut rng,
-                        self.max_depth,
-                    )
-                })
-                .fold(Colour::default(), |acc, val| acc + val);
-        };
-        let mut out: Vec<Vec<_>> = (0..self.image_height)
-            .map(|_| (0..self.image_width).map(|_| Colour::default()).collect())
-            .collect();
-        let process: Vec<_> = out
-            .iter_mut()
-            .enumerate()
-            .flat_map(|(j, vec)| {
-                vec.iter_mut()
-                    .enumerate()
-                    .map(move |(i, v)| (j, i, v, SmallRng::from_rng(thread_rng()).unwrap()))
-            })
-            .collect();
-
+        let film = Film::new(self.image_width as usize, self.image_height as usize);
+        let render = || self.integrator.render(self, lighting, &film);
         if matches!(debug_mode, DebugModes::Miri | DebugModes::Normal) {
-            process.into_iter().for_each(render_lambda);
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(1)
+                .build()
+                .unwrap();
+            pool.install(render);
         } else {
-            par_tqdm!(process.into_par_iter()).for_each(render_lambda);
+            render();
         }
 
         #[cfg(feature = "hit_counters")]
@@ -378,146 +747,53 @@ impl Camera {
                 light_counter
             );
         }
-        out.into_iter()
-            .map(|vec| {
-                vec.into_iter()
+        let white_balance = self
+            .white_balance
+            .map(|temperature| WhiteBalance::new(temperature).in_space(self.working_space));
+        (0..self.image_height as usize)
+            .map(|j| {
+                (0..self.image_width as usize)
+                    .map(|i| film.get_pixel(i, j))
+                    .map(|colour| match &white_balance {
+                        Some(white_balance) => white_balance.apply(colour),
+                        None => colour,
+                    })
                     .map(|colour| SampledColour::from((colour, self.samples_per_pixel as _)))
                     .collect()
             })
             .collect()
     }
 
-    #[allow(dead_code)]
-    fn ray_colour(
-        r: &Ray,
-        background: &Colour,
-        world: &dyn Hittable,
-        lights: &dyn Hittable,
-        rng: &mut dyn rand::RngCore,
-        depth: u32,
-    ) -> Colour {
-        if depth == 0 {
-            return Colour::default();
-        }
-        let Some(rec) = world.hit(r, (f64::EPSILON)..=f64::INFINITY) else {
-            return *background;
-        };
-
-        #[cfg(feature = "hit_counters")]
-        HIT_COUNTER.fetch_add(1, Ordering::Relaxed);
-
-        let colour_from_emission =
-            rec.get_material()
-                .emitted(rec.get_u(), rec.get_v(), rec.get_p());
-
-        let Some(srec) = rec.get_material().scatter(r, &rec, rng) else {
-            return colour_from_emission;
-        };
-
-        let pdf_ptr = match srec.scatter_reflect {
-            ScatterReflect::Reflect(ray) => {
-                return srec.attenuation
-                    * Self::ray_colour(&ray, background, world, lights, rng, depth - 1);
-            }
-            ScatterReflect::Scatter(pdf) => pdf,
-        };
-
-        let light_pdf = HittablePdf::new(lights, rec.get_p());
-        let p = MixturePdf::new(&light_pdf, pdf_ptr.as_ref());
-
-        let scattered_ray = Ray::new(rec.get_p(), p.generate(rng));
-        let pdf_value = p.value(&scattered_ray.get_direction());
-
-        let scattering_pdf = rec.get_material().scattering_pdf(r, &rec, &scattered_ray);
-
-        let sample_colour =
-            Self::ray_colour(&scattered_ray, background, world, lights, rng, depth - 1).fix_nan();
-        let colour_from_scatter = (srec.attenuation * scattering_pdf * sample_colour) / pdf_value;
-        colour_from_emission + colour_from_scatter
-    }
-
-    fn ray_colour_call(
-        r: &Ray,
-        background: &Colour,
-        world: &dyn Hittable,
-        lights: &dyn Hittable,
-        rng: &mut dyn rand::RngCore,
-        depth: u32,
-    ) -> Colour {
-        Self::ray_colour_tail_call(
-            r.clone(),
-            background,
-            world,
-            lights,
-            rng,
-            Colour::from_array([1., 1., 1.]),
-            Colour::default(),
-            depth,
-        )
-    }
-
-    #[allow(clippy::too_many_arguments)]
-    fn ray_colour_tail_call(
-        r: Ray,
-        background: &Colour,
-        world: &dyn Hittable,
-        lights: &dyn Hittable,
-        rng: &mut dyn rand::RngCore,
-        mult: Colour,
-        res: Colour,
-        depth: u32,
-    ) -> Colour {
-        if depth == 0 {
-            return Colour::default() + res;
-        }
-        let Some(rec) = world.hit(&r, (f64::EPSILON)..=f64::INFINITY) else {
-            return mult * *background + res;
-        };
-
-        #[cfg(feature = "hit_counters")]
-        HIT_COUNTER.fetch_add(1, Ordering::Relaxed);
-
-        let colour_from_emission =
-            rec.get_material()
-                .emitted(rec.get_u(), rec.get_v(), rec.get_p());
-
-        let Some(srec) = rec.get_material().scatter(&r, &rec, rng) else {
-            return mult * colour_from_emission + res;
-        };
-
-        let pdf_ptr = match srec.scatter_reflect {
-            ScatterReflect::Reflect(ray) => {
-                return Self::ray_colour_tail_call(
-                    ray,
-                    background,
-                    world,
-                    lights,
-                    rng,
-                    mult * srec.attenuation,
-                    res,
-                    depth - 1,
-                );
-            }
-            ScatterReflect::Scatter(pdf) => pdf,
+    /// Renders every pixel on its own, tracing a ray per sample with the `integrator`, see
+    /// [`Integrator::render`].
+    pub(crate) fn render_pixels(
+        &self,
+        integrator: &(impl Integrator + ?Sized),
+        lighting: &Lighting<'_>,
+        film: &Film,
+    ) {
+        let render_lambda = |(j, i): (usize, usize)| {
+            let mut rng = self.pixel_rng(0, j * self.image_width as usize + i);
+            let colour = (0..self.samples_per_pixel)
+                .map(|_| {
+                    let Some((r, weight)) = self.sample_ray(i, j, &mut rng) else {
+                        return Colour::default();
+                    };
+                    self.trace_sample(&mut rng, lighting, |rng, lighting| {
+                        integrator.radiance(&r, lighting, film, rng, self.max_depth)
+                    }) * weight
+                })
+                .fold(Colour::default(), |acc, val| acc + val);
+            film.add_pixel([i, j], colour);
         };
+        let process: Vec<_> = (0..self.image_height as usize)
+            .flat_map(|j| (0..self.image_width as usize).map(move |i| (j, i)))
+            .collect();
 
-        let light_pdf = HittablePdf::new(lights, rec.get_p());
-        let p = MixturePdf::new(&light_pdf, pdf_ptr.as_ref());
-
-        let scattered_ray = Ray::new(rec.get_p(), p.generate(rng));
-        let pdf_value = p.value(&scattered_ray.get_direction());
-
-        let scattering_pdf = rec.get_material().scattering_pdf(&r, &rec, &scattered_ray);
-
-        Self::ray_colour_tail_call(
-            scattered_ray,
-            background,
-            world,
-            lights,
-            rng,
-            mult * (srec.attenuation * scattering_pdf / pdf_value),
-            res + mult * colour_from_emission,
-            depth - 1,
-        )
+        // Miri can't draw the progress bar
+        #[cfg(miri)]
+        process.into_par_iter().for_each(render_lambda);
+        #[cfg(not(miri))]
+        par_tqdm!(process.into_par_iter()).for_each(render_lambda);
     }
 }
@@ -0,0 +1,61 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::colour::Colour;
+
+/// Light landing on the pixels of the image being rendered, summed over their samples.
+///
+/// Integrators add the samples of each pixel, while light which can land on any pixel, like the
+/// contributions of paths traced from the lights, is splatted. Once the render is done
+/// everything is divided by the samples per pixel.
+#[derive(Debug)]
+pub struct Film {
+    width: usize,
+    height: usize,
+    /// Bits of the `f64` channels, so they can be added to from any thread
+    pixels: Vec<[AtomicU64; 3]>,
+}
+
+impl Film {
+    pub(crate) fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: (0..width * height)
+                .map(|_| std::array::from_fn(|_| AtomicU64::new(0.0f64.to_bits())))
+                .collect(),
+        }
+    }
+
+    /// Rows are counted from the bottom, like the pixels of [`Camera::get_ray`](super::Camera::get_ray).
+    /// Light found while rendering spectrally has to be turned into RGB first, see
+    /// [`Lighting::to_rgb`](crate::integrator::Lighting::to_rgb).
+    pub fn add_splat(&self, [i, j]: [usize; 2], colour: Colour) {
+        if i >= self.width || j >= self.height {
+            return;
+        }
+        self.add_pixel([i, j], colour);
+    }
+
+    /// Adds RGB light to a pixel, like the sum of its samples.
+    pub(crate) fn add_pixel(&self, [i, j]: [usize; 2], colour: Colour) {
+        let colour = colour.fix_nan().into_inner();
+        for (channel, value) in self.pixels[j * self.width + i]
+            .iter()
+            .zip([colour.x, colour.y, colour.z])
+        {
+            // Can't fail as the closure always returns `Some`
+            let _ = channel.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + value).to_bits())
+            });
+        }
+    }
+
+    pub(crate) fn get_pixel(&self, i: usize, j: usize) -> Colour {
+        let [r, g, b] = &self.pixels[j * self.width + i];
+        Colour::new(
+            f64::from_bits(r.load(Ordering::Relaxed)),
+            f64::from_bits(g.load(Ordering::Relaxed)),
+            f64::from_bits(b.load(Ordering::Relaxed)),
+        )
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    f64::consts::{PI, TAU},
+    sync::Arc,
+};
+
+use rand::{
+    Rng,
+    distributions::{Distribution, Open01},
+};
+
+use geometry::vec3::Vec3;
+
+use crate::{
+    camera::{Camera, CameraBuilder, FieldOfView},
+    utils::random_utils::UnitDisk,
+};
+
+/// Physical size of the sensor (or film gate) in millimetres.
+#[derive(Debug, Clone, Copy)]
+pub struct SensorSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl SensorSize {
+    pub const FULL_FRAME: Self = Self::new(36., 24.);
+    pub const APS_C: Self = Self::new(23.6, 15.6);
+    pub const MICRO_FOUR_THIRDS: Self = Self::new(17.3, 13.);
+    pub const SUPER_35: Self = Self::new(24.89, 18.66);
+
+    pub const fn new(width: f64, height: f64) -> Self {
+        Self { width, height }
+    }
+
+    fn aspect_ratio(self) -> f64 {
+        self.width / self.height
+    }
+}
+
+/// Shape of the lens opening, which is also the shape of out of focus highlights.
+#[derive(Debug, Clone, Default)]
+pub enum Aperture {
+    #[default]
+    Circular,
+    /// Regular polygon with `blades` sides, `rotation` is in degrees.
+    Polygonal {
+        blades: u8,
+        rotation: f64,
+    },
+    Image(Arc<ApertureImage>),
+}
+
+impl Aperture {
+    /// Area of the shape, which fits in the unit disk. `None` for images, which aren't sampled
+    /// uniformly.
+    pub(crate) fn area(&self) -> Option<f64> {
+        match *self {
+            Aperture::Polygonal { blades, .. } if blades >= 3 => {
+                let blades = f64::from(blades);
+                Some(blades / 2. * (TAU / blades).sin())
+            }
+            Aperture::Circular | Aperture::Polygonal { .. } => Some(PI),
+            Aperture::Image(_) => None,
+        }
+    }
+}
+
+impl Distribution<Vec3> for Aperture {
+    /// Samples a point inside the unit disk on the xz plane, same as [`UnitDisk`].
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec3 {
+        match self {
+            Aperture::Circular => rng.sample(UnitDisk),
+            &Aperture::Polygonal { blades, rotation } if blades >= 3 => {
+                let blades = blades as usize;
+                let k = rng.gen_range(0..blades);
+                let angle = |k: usize| rotation.to_radians() + TAU * (k as f64) / (blades as f64);
+                let (a, b) = (angle(k), angle(k + 1));
+                let mut r1 = rng.sample::<f64, _>(Open01);
+                let mut r2 = rng.sample::<f64, _>(Open01);
+                if r1 + r2 > 1. {
+                    r1 = 1. - r1;
+                    r2 = 1. - r2;
+                }
+                Vec3::new(r1 * a.cos() + r2 * b.cos(), 0., r1 * a.sin() + r2 * b.sin())
+            }
+            Aperture::Polygonal { .. } => rng.sample(UnitDisk),
+            Aperture::Image(image) => image.sample(rng),
+        }
+    }
+}
+
+/// Grayscale mask used as a custom aperture, brighter pixels let more light through.
+#[derive(Debug, Clone)]
+pub struct ApertureImage {
+    width: usize,
+    height: usize,
+    cdf: Vec<f64>,
+}
+
+impl ApertureImage {
+    /// `weights` is in row major order, starting from the top left corner.
+    /// Returns `None` if the sizes don't match or no pixel lets any light through.
+    pub fn new(width: usize, height: usize, weights: &[f64]) -> Option<Self> {
+        (width * height == weights.len() && !weights.is_empty()).then_some(())?;
+        let cdf: Vec<f64> = weights
+            .iter()
+            .scan(0., |accum, &w| {
+                *accum += w.max(0.);
+                Some(*accum)
+            })
+            .collect();
+        let total = *cdf.last()?;
+        (total > 0.).then(|| Self {
+            width,
+            height,
+            cdf: cdf.into_iter().map(|v| v / total).collect(),
+        })
+    }
+
+    pub fn from_fn(width: usize, height: usize, f: impl Fn(f64, f64) -> f64) -> Option<Self> {
+        let weights: Vec<f64> = (0..height)
+            .flat_map(|j| (0..width).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                let x = 2. * (i as f64 + 0.5) / (width as f64) - 1.;
+                let y = 1. - 2. * (j as f64 + 0.5) / (height as f64);
+                f(x, y)
+            })
+            .collect();
+        Self::new(width, height, &weights)
+    }
+
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec3 {
+        let u: f64 = rng.sample(Open01);
+        let index = self.cdf.partition_point(|&v| v < u).min(self.cdf.len() - 1);
+        let (i, j) = (index % self.width, index / self.width);
+        let x = 2. * (i as f64 + rng.sample::<f64, _>(Open01)) / (self.width as f64) - 1.;
+        let y = 1. - 2. * (j as f64 + rng.sample::<f64, _>(Open01)) / (self.height as f64);
+        Vec3::new(x, 0., y)
+    }
+}
+
+/// Brown-Conrady radial distortion, positive `k1` gives pincushion and negative gives barrel.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LensDistortion {
+    pub k1: f64,
+    pub k2: f64,
+}
+
+impl LensDistortion {
+    pub const fn new(k1: f64, k2: f64) -> Self {
+        Self { k1, k2 }
+    }
+
+    /// `x` and `y` are normalized so that the top and bottom of the image are at `y = ±1`.
+    pub(crate) fn apply(self, x: f64, y: f64) -> (f64, f64) {
+        let r2 = x * x + y * y;
+        let factor = 1. + self.k1 * r2 + self.k2 * r2 * r2;
+        (x * factor, y * factor)
+    }
+}
+
+/// Sensitivity and shutter time, together with the f-number they determine the exposure.
+#[derive(Debug, Clone, Copy)]
+pub struct Exposure {
+    pub iso: f64,
+    /// In seconds
+    pub shutter_speed: f64,
+}
+
+impl Exposure {
+    pub const fn new(iso: f64, shutter_speed: f64) -> Self {
+        Self { iso, shutter_speed }
+    }
+
+    pub fn ev100(self, f_number: f64) -> f64 {
+        (f_number * f_number / self.shutter_speed).log2() - (self.iso / 100.).log2()
+    }
+
+    /// Scale from scene luminance to sensor value, uses the saturation based sensitivity
+    /// convention so a luminance of `1.2 * 2^EV100` maps to 1.
+    pub fn scale(self, f_number: f64) -> f64 {
+        (1.2 * self.ev100(f_number).exp2()).recip()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Lens {
+    pub(crate) aperture: Aperture,
+    pub(crate) distortion: Option<LensDistortion>,
+    pub(crate) vignetting: f64,
+    pub(crate) exposure: f64,
+}
+
+impl Default for Lens {
+    fn default() -> Self {
+        Self {
+            aperture: Aperture::Circular,
+            distortion: None,
+            vignetting: 0.,
+            exposure: 1.,
+        }
+    }
+}
+
+impl Lens {
+    /// Natural vignetting follows `cos^4` of the angle to the optical axis,
+    /// `vignetting` blends between no falloff and the full falloff.
+    pub(crate) fn vignetting_weight(&self, cos_theta: f64) -> f64 {
+        let cos2 = cos_theta * cos_theta;
+        1. - self.vignetting * (1. - cos2 * cos2)
+    }
+}
+
+/// Alternative to [`CameraBuilder`] which describes the camera with photographic quantities,
+/// the position and image settings are taken from the builder it was created from.
+#[derive(Debug, Clone)]
+pub struct PhysicalCameraBuilder {
+    base: CameraBuilder,
+    /// In millimetres
+    focal_length: f64,
+    sensor: SensorSize,
+    f_number: f64,
+    /// In scene units
+    focus_distance: f64,
+    units_per_metre: f64,
+    exposure: Option<Exposure>,
+    aperture: Aperture,
+    distortion: Option<LensDistortion>,
+    vignetting: f64,
+}
+
+impl PhysicalCameraBuilder {
+    pub fn new(base: CameraBuilder) -> Self {
+        Self {
+            focal_length: 50.,
+            sensor: SensorSize::FULL_FRAME,
+            f_number: 8.,
+            focus_distance: base.focus_dist,
+            units_per_metre: 1.,
+            exposure: None,
+            aperture: Aperture::Circular,
+            distortion: None,
+            vignetting: 0.,
+            base,
+        }
+    }
+
+    pub fn with_focal_length(self, focal_length: f64) -> Self {
+        Self {
+            focal_length,
+            ..self
+        }
+    }
+    pub fn with_sensor(self, sensor: SensorSize) -> Self {
+        Self { sensor, ..self }
+    }
+    pub fn with_f_number(self, f_number: f64) -> Self {
+        Self { f_number, ..self }
+    }
+    pub fn with_focus_distance(self, focus_distance: f64) -> Self {
+        Self {
+            focus_distance,
+            ..self
+        }
+    }
+    pub fn with_units_per_metre(self, units_per_metre: f64) -> Self {
+        Self {
+            units_per_metre,
+            ..self
+        }
+    }
+    pub fn with_exposure(self, exposure: Exposure) -> Self {
+        Self {
+            exposure: Some(exposure),
+            ..self
+        }
+    }
+    pub fn with_aperture(self, aperture: Aperture) -> Self {
+        Self { aperture, ..self }
+    }
+    pub fn with_distortion(self, distortion: LensDistortion) -> Self {
+        Self {
+            distortion: Some(distortion),
+            ..self
+        }
+    }
+    pub fn with_vignetting(self, vignetting: f64) -> Self {
+        Self { vignetting, ..self }
+    }
+
+    pub fn build(self) -> Camera {
+        let mm_per_unit = 1000. / self.units_per_metre;
+        let focus_distance_mm = self.focus_distance * mm_per_unit;
+        // Thin lens equation, focusing closer moves the sensor away from the lens
+        let image_distance = if focus_distance_mm > self.focal_length {
+            self.focal_length * focus_distance_mm / (focus_distance_mm - self.focal_length)
+        } else {
+            self.focal_length
+        };
+        let lens_radius = self.focal_length / self.f_number / 2. / mm_per_unit;
+        let lens = Lens {
+            aperture: self.aperture,
+            distortion: self.distortion,
+            vignetting: self.vignetting.clamp(0., 1.),
+            exposure: self.exposure.map_or(1., |e| e.scale(self.f_number)),
+        };
+        self.base.build_inner(
+            FieldOfView::Sensor {
+                aspect_ratio: self.sensor.aspect_ratio(),
+                half_height: self.sensor.height / 2. / image_distance,
+            },
+            self.focus_distance,
+            lens_radius,
+            lens,
+        )
+    }
+}
+
+impl From<CameraBuilder> for PhysicalCameraBuilder {
+    fn from(value: CameraBuilder) -> Self {
+        Self::new(value)
+    }
+}
@@ -0,0 +1,74 @@
+use std::f64::consts::{PI, TAU};
+
+/// How pixels are mapped to rays leaving the camera.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Projection {
+    #[default]
+    Perspective,
+    /// Parallel rays, `height` is the height of the view in scene units.
+    Orthographic { height: f64 },
+    /// Equidistant fisheye inscribed in the image, `fov` is the full angle of the circle
+    /// in degrees. Pixels outside of the circle don't produce rays.
+    Fisheye { fov: f64 },
+    /// Full sphere with the longitude along the width and the latitude along the height,
+    /// the center of the image looks at `lookat`.
+    Equirectangular,
+    /// The six faces of a cube in a 3x2 grid, the bottom row is front, right and back
+    /// and the top row is left, up and down.
+    CubeMap,
+}
+
+impl Projection {
+    /// Whether rays are generated from a film plane, in which case lens distortion,
+    /// vignetting and depth of field use the film plane.
+    pub(crate) const fn uses_film_plane(self) -> bool {
+        matches!(self, Self::Perspective | Self::Orthographic { .. })
+    }
+
+    /// Direction in camera space (right, up, forward) for the normalized position `(x, y)` in
+    /// the fisheye circle, where the circle has radius 1.
+    pub(crate) fn fisheye_direction(fov: f64, x: f64, y: f64) -> Option<[f64; 3]> {
+        let r = x.hypot(y);
+        if r > 1. {
+            return None;
+        }
+        let theta = r * fov.to_radians() / 2.;
+        let (sin, cos) = theta.sin_cos();
+        if r <= f64::EPSILON {
+            Some([0., 0., 1.])
+        } else {
+            Some([x / r * sin, y / r * sin, cos])
+        }
+    }
+
+    /// Direction in camera space (right, up, forward), `s` and `t` are in `[0, 1]`.
+    pub(crate) fn equirectangular_direction(s: f64, t: f64) -> [f64; 3] {
+        let phi = (s - 0.5) * TAU;
+        let theta = (t - 0.5) * PI;
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        [cos_theta * sin_phi, sin_theta, cos_theta * cos_phi]
+    }
+
+    /// Direction in camera space (right, up, forward), `s` and `t` are in `[0, 1]`.
+    pub(crate) fn cube_map_direction(s: f64, t: f64) -> [f64; 3] {
+        let (s, t) = (s * 3., t * 2.);
+        let (column, row) = (s.floor().clamp(0., 2.), t.floor().clamp(0., 1.));
+        let a = 2. * (s - column) - 1.;
+        let b = 2. * (t - row) - 1.;
+        match (row as u8, column as u8) {
+            // Front
+            (0, 0) => [a, b, 1.],
+            // Right
+            (0, 1) => [1., b, -a],
+            // Back
+            (0, _) => [-a, b, -1.],
+            // Left
+            (_, 0) => [-1., b, a],
+            // Up
+            (_, 1) => [a, 1., -b],
+            // Down
+            (_, _) => [a, -1., b],
+        }
+    }
+}
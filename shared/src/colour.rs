@@ -1,8 +1,14 @@
+mod space;
+mod temperature;
+
 use core::ops::{Add, AddAssign, Mul};
 use std::ops::{Div, DivAssign, MulAssign};
 
 use geometry::prelude::*;
 
+pub use space::ColourSpace;
+pub use temperature::{WhiteBalance, blackbody, luminous_efficacy};
+
 #[derive(Debug, Default, Clone, Copy)]
 pub struct Colour(Vec3);
 
@@ -56,6 +62,11 @@ impl Colour {
                 .map(|v| if v.is_nan() { 0.0 } else { v }),
         )
     }
+
+    /// Relative luminance with Rec. 709 primaries.
+    pub fn luminance(self) -> f64 {
+        0.2126 * self.0.x + 0.7152 * self.0.y + 0.0722 * self.0.z
+    }
 }
 
 impl From<Vec3> for Colour {
@@ -135,6 +146,13 @@ impl DivAssign for Colour {
 
 pub struct SampledColour(Colour, i32);
 
+impl SampledColour {
+    /// Average of the samples.
+    pub fn average(&self) -> Colour {
+        self.0 / f64::from(self.1)
+    }
+}
+
 impl From<(Colour, i32)> for SampledColour {
     fn from((c, s): (Colour, i32)) -> Self {
         SampledColour(c, s)
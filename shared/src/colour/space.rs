@@ -0,0 +1,167 @@
+use std::sync::LazyLock;
+
+use geometry::{matrix3::Matrix3, vec3::Vec3};
+
+use super::Colour;
+
+/// D65, the white of sRGB, Display P3 and Rec. 2020
+const D65: [f64; 2] = [0.3127, 0.3290];
+/// White of the ACES colour spaces, close to D60
+const ACES_WHITE: [f64; 2] = [0.32168, 0.33767];
+
+/// Cone responses used by the Bradford chromatic adaptation
+const XYZ_TO_BRADFORD: [[f64; 3]; 3] = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+const SPACES: [ColourSpace; 5] = [
+    ColourSpace::Srgb,
+    ColourSpace::DisplayP3,
+    ColourSpace::Rec2020,
+    ColourSpace::AcesCg,
+    ColourSpace::Aces2065,
+];
+
+/// Conversions between every pair of spaces, indexed by their position in [`SPACES`].
+static CONVERSIONS: LazyLock<[[Matrix3; 5]; 5]> = LazyLock::new(|| {
+    SPACES.map(|from| {
+        SPACES.map(|to| {
+            to.from_xyz() * chromatic_adaptation(from.white(), to.white()) * from.to_xyz()
+        })
+    })
+});
+
+/// Primaries and white of linear RGB colours.
+///
+/// Colours are given in linear sRGB unless stated otherwise, while paths are traced in the
+/// working space set by [`CameraBuilder::with_working_space`](crate::camera::CameraBuilder::with_working_space),
+/// which the [`PathColours`](crate::spectrum::PathColours) of the path converts them to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColourSpace {
+    /// Rec. 709 primaries
+    #[default]
+    Srgb,
+    DisplayP3,
+    Rec2020,
+    /// ACES AP1 primaries, usual for rendering and compositing
+    AcesCg,
+    /// ACES2065-1, with the AP0 primaries which cover every colour, used to exchange images
+    Aces2065,
+}
+
+impl ColourSpace {
+    /// Chromaticities of the red, green and blue primaries and of the white.
+    pub const fn chromaticities(self) -> [[f64; 2]; 4] {
+        match self {
+            ColourSpace::Srgb => [[0.64, 0.33], [0.30, 0.60], [0.15, 0.06], D65],
+            ColourSpace::DisplayP3 => [[0.680, 0.320], [0.265, 0.690], [0.150, 0.060], D65],
+            ColourSpace::Rec2020 => [[0.708, 0.292], [0.170, 0.797], [0.131, 0.046], D65],
+            ColourSpace::AcesCg => [[0.713, 0.293], [0.165, 0.830], [0.128, 0.044], ACES_WHITE],
+            ColourSpace::Aces2065 => [[0.7347, 0.2653], [0.0, 1.0], [0.0001, -0.0770], ACES_WHITE],
+        }
+    }
+
+    fn white(self) -> Vec3 {
+        xy_to_xyz(self.chromaticities()[3])
+    }
+
+    /// Matrix from this space to CIE XYZ.
+    pub fn to_xyz(self) -> Matrix3 {
+        let [r, g, b, _] = self.chromaticities().map(xy_to_xyz);
+        let primaries = Matrix3::from([[r.x, g.x, b.x], [r.y, g.y, b.y], [r.z, g.z, b.z]]);
+        // Scaled so (1, 1, 1) is the white
+        let scale = primaries.inverse().unwrap() * self.white();
+        primaries * Matrix3::from([[scale.x, 0., 0.], [0., scale.y, 0.], [0., 0., scale.z]])
+    }
+
+    /// Matrix from CIE XYZ to this space.
+    pub fn from_xyz(self) -> Matrix3 {
+        self.to_xyz().inverse().unwrap()
+    }
+
+    /// Matrix from this space to `target`, adapting the white if they have different ones.
+    pub fn conversion(self, target: ColourSpace) -> Matrix3 {
+        CONVERSIONS[self as usize][target as usize]
+    }
+
+    /// Transfer function of the space when shown on a display, linear spaces are left as they
+    /// are. `value` is linear.
+    pub fn encode(self, value: f64) -> f64 {
+        match self {
+            ColourSpace::Srgb | ColourSpace::DisplayP3 => {
+                if value <= 0.0031308 {
+                    12.92 * value
+                } else {
+                    1.055 * value.powf(2.4_f64.recip()) - 0.055
+                }
+            }
+            ColourSpace::Rec2020 => {
+                const ALPHA: f64 = 1.099_296_826_809_44;
+                const BETA: f64 = 0.018_053_968_510_807;
+                if value < BETA {
+                    4.5 * value
+                } else {
+                    ALPHA * value.powf(0.45) - (ALPHA - 1.)
+                }
+            }
+            ColourSpace::AcesCg | ColourSpace::Aces2065 => value,
+        }
+    }
+}
+
+/// `XYZ` with `Y = 1` of the chromaticity `xy`.
+fn xy_to_xyz([x, y]: [f64; 2]) -> Vec3 {
+    Vec3::new(x / y, 1., (1. - x - y) / y)
+}
+
+/// Bradford transform making colours seen under the `source` white look as they would under
+/// the `target` one, both in CIE XYZ.
+pub(crate) fn chromatic_adaptation(source: Vec3, target: Vec3) -> Matrix3 {
+    let to_cone = Matrix3::from(XYZ_TO_BRADFORD);
+    let (source, target) = (to_cone * source, to_cone * target);
+    let scale = Matrix3::from([
+        [target.x / source.x, 0., 0.],
+        [0., target.y / source.y, 0.],
+        [0., 0., target.z / source.z],
+    ]);
+    to_cone.inverse().unwrap() * scale * to_cone
+}
+
+impl Colour {
+    /// Converts a colour given in `from` into `to`.
+    pub fn convert(self, from: ColourSpace, to: ColourSpace) -> Self {
+        if from == to {
+            return self;
+        }
+        Colour::from_vec3(from.conversion(to) * self.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColourSpace, SPACES};
+    use crate::colour::Colour;
+
+    #[test]
+    fn conversions_round_trip() {
+        let colour = Colour::new(0.8, 0.4, 0.1);
+        for from in SPACES {
+            for to in SPACES {
+                let white = Colour::new(1., 1., 1.).convert(from, to).into_inner();
+                assert!(
+                    [white.x, white.y, white.z]
+                        .iter()
+                        .all(|v| (v - 1.).abs() < 1e-3),
+                    "White from {from:?} to {to:?} is {white:?}"
+                );
+                let back = colour.convert(from, to).convert(to, from).into_inner();
+                assert!((back - colour.into_inner()).length() < 1e-9);
+            }
+        }
+        // The usual matrix, see http://www.brucelindbloom.com/Eqn_RGB_XYZ_Matrix.html
+        let red = ColourSpace::Srgb.to_xyz() * Colour::new(1., 0., 0.).into_inner();
+        assert!((red.x - 0.4124564).abs() < 1e-4 && (red.y - 0.2126729).abs() < 1e-4);
+    }
+}
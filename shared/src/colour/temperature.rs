@@ -0,0 +1,92 @@
+use std::f64::consts::PI;
+
+use geometry::{matrix3::Matrix3, vec3::Vec3};
+
+use crate::spectrum::{colour_matching, integrate, xyz_to_linear_srgb};
+
+use super::{Colour, ColourSpace, space::chromatic_adaptation};
+
+const PLANCK: f64 = 6.626_070_15e-34;
+const SPEED_OF_LIGHT: f64 = 299_792_458.;
+const BOLTZMANN: f64 = 1.380_649e-23;
+const STEFAN_BOLTZMANN: f64 = 5.670_374_419e-8;
+/// Lumens per watt of light at 555 nm, where the eye is most sensitive
+const MAX_LUMINOUS_EFFICACY: f64 = 683.;
+
+/// Spectral radiance of a blackbody at `temperature` Kelvin, in W/(m² sr nm). The wavelength
+/// is in nanometres.
+pub fn blackbody(wavelength: f64, temperature: f64) -> f64 {
+    let lambda = wavelength * 1e-9;
+    let exponent = PLANCK * SPEED_OF_LIGHT / (lambda * BOLTZMANN * temperature);
+    2. * PLANCK * SPEED_OF_LIGHT * SPEED_OF_LIGHT / (lambda.powi(5) * exponent.exp_m1()) * 1e-9
+}
+
+fn blackbody_xyz(temperature: f64) -> [f64; 3] {
+    integrate(|lambda| colour_matching(lambda).map(|v| v * blackbody(lambda, temperature)))
+}
+
+/// Lumens per watt radiated by a blackbody at `temperature` Kelvin, counting the light the eye
+/// can't see.
+pub fn luminous_efficacy(temperature: f64) -> f64 {
+    let radiance = STEFAN_BOLTZMANN * temperature.powi(4) / PI;
+    MAX_LUMINOUS_EFFICACY * blackbody_xyz(temperature)[1] / radiance
+}
+
+impl Colour {
+    /// Colour of a blackbody at `temperature` Kelvin with a luminance of 1. Around 2700 K is an
+    /// incandescent bulb and 6500 K is white.
+    pub fn from_temperature(temperature: f64) -> Self {
+        let [x, y, z] = blackbody_xyz(temperature);
+        // Very warm colours fall outside of sRGB
+        let colour = Colour::from_array(xyz_to_linear_srgb([x / y, 1., z / y]).map(|v| v.max(0.)));
+        colour / colour.luminance()
+    }
+}
+
+/// Turns the colour of a blackbody at some temperature into white, like a camera whose white
+/// balance is set to it. Uses the Bradford chromatic adaptation.
+#[derive(Debug, Clone, Copy)]
+pub struct WhiteBalance(Matrix3);
+
+impl WhiteBalance {
+    /// `temperature` is in Kelvin, the balanced colours are in linear sRGB.
+    pub fn new(temperature: f64) -> Self {
+        let to_xyz = ColourSpace::Srgb.to_xyz();
+        let source = to_xyz * Colour::from_temperature(temperature).into_inner();
+        let target = to_xyz * Vec3::new(1., 1., 1.);
+        Self(ColourSpace::Srgb.from_xyz() * chromatic_adaptation(source, target) * to_xyz)
+    }
+
+    /// Balances colours in `space` instead.
+    pub fn in_space(self, space: ColourSpace) -> Self {
+        Self(ColourSpace::Srgb.conversion(space) * self.0 * space.conversion(ColourSpace::Srgb))
+    }
+
+    pub fn apply(&self, colour: Colour) -> Colour {
+        Colour::from_vec3(self.0 * colour.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Colour, WhiteBalance, luminous_efficacy};
+
+    #[test]
+    fn blackbodies_are_white_balanced() {
+        // Incandescent bulbs are close to a blackbody
+        assert!((10. ..20.).contains(&luminous_efficacy(2700.)));
+        assert!((80. ..100.).contains(&luminous_efficacy(6500.)));
+
+        let warm = Colour::from_temperature(2700.).into_inner();
+        assert!((warm.x / warm.z) > 2., "{warm:?} isn't orange");
+        for temperature in [2700., 4000., 6500., 10_000.] {
+            let colour = Colour::from_temperature(temperature);
+            assert!((colour.luminance() - 1.).abs() < 1e-9);
+            let balanced = WhiteBalance::new(temperature).apply(colour).into_inner();
+            assert!(
+                (balanced.x - balanced.y).abs() < 1e-6 && (balanced.z - balanced.y).abs() < 1e-6,
+                "{balanced:?} isn't grey at {temperature} K"
+            );
+        }
+    }
+}
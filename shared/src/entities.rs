@@ -1,13 +1,28 @@
+mod capsule;
+mod cone;
+mod csg;
 mod cuboid;
+mod cylinder;
+mod disk;
+mod frame;
 mod plane;
 mod quadrilateral;
+pub mod sdf;
 mod sphere;
+mod torus;
 pub mod transformations;
 mod triangles;
+pub use capsule::Capsule;
+pub use cone::Cone;
+pub use csg::{Csg, CsgOperation};
 pub use cuboid::Cuboid;
+pub use cylinder::Cylinder;
+pub use disk::Disk;
 pub use plane::Plane;
 pub use quadrilateral::Quad;
+pub use sdf::SdfObject;
 pub use sphere::Sphere;
+pub use torus::Torus;
 pub use triangles::Triangle;
 
 // #[cfg(feature = "hit_counters")]
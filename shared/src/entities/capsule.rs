@@ -0,0 +1,169 @@
+use std::{
+    f64::consts::{PI, TAU},
+    fmt::Debug,
+    ops::RangeInclusive,
+};
+
+use rand::{Rng as _, distributions::Standard};
+
+use geometry::{
+    aabox::AABBox,
+    bounded::Bounded,
+    vec3::{Point3, Vec3},
+};
+
+use crate::{
+    hittable::{BoundedHittable, HitRecord, Hittable, SurfacePoint},
+    hittable_collections::light_tree::LightBounds,
+    material::DynMaterial,
+    ray::Ray,
+    utils::polynomial::solve_quadratic,
+};
+
+use super::{
+    cylinder::hit_side,
+    frame::{Frame, LocalHit, LocalPoint, area_pdf_value, turn_fraction},
+};
+
+/// Points closer than `radius` to the segment from `start` to `end`, a cylinder closed by two
+/// half spheres.
+///
+/// `u` goes around the axis and `v` from the start to the end along it.
+#[derive(Debug, Clone)]
+pub struct Capsule {
+    frame: Frame,
+    radius: f64,
+    length: f64,
+    mat_ptr: DynMaterial,
+    aabox: AABBox,
+}
+
+impl Capsule {
+    pub fn new<T>(start: Point3, end: Point3, radius: f64, mat_ptr: T) -> Self
+    where
+        T: TryInto<DynMaterial>,
+        <T as TryInto<DynMaterial>>::Error: Debug,
+    {
+        let extent = Vec3::new(radius, radius, radius);
+        Self {
+            frame: Frame::new(start, end - start),
+            radius,
+            length: (end - start).length(),
+            mat_ptr: mat_ptr.try_into().unwrap(),
+            aabox: AABBox::from_points([
+                start - extent,
+                start + extent,
+                end - extent,
+                end + extent,
+            ]),
+        }
+    }
+
+    fn side_area(&self) -> f64 {
+        TAU * self.radius * self.length
+    }
+
+    /// Hits with the half sphere at `height` on the axis, bulging towards `side`.
+    fn hit_end(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        height: f64,
+        side: f64,
+    ) -> impl Iterator<Item = LocalHit> {
+        let center = Vec3::new(0., 0., height);
+        let oc = origin - center;
+        let roots = solve_quadratic(
+            direction.square_length(),
+            2. * oc.dot(direction),
+            oc.square_length() - self.radius * self.radius,
+        );
+        let (radius, total) = (self.radius, self.length + 2. * self.radius);
+        roots.into_iter().flatten().filter_map(move |t| {
+            let point = origin + direction * t;
+            ((point.z - height) * side >= 0.).then(|| LocalHit {
+                t,
+                normal: (point - center) / radius,
+                u: turn_fraction(point),
+                v: (point.z + radius) / total,
+            })
+        })
+    }
+}
+
+impl Hittable for Capsule {
+    fn hit(&self, r: &Ray, range: RangeInclusive<f64>) -> Option<HitRecord<'_>> {
+        let (origin, direction) = self.frame.local_ray(r);
+        let total = self.length + 2. * self.radius;
+        let side =
+            hit_side(origin, direction, self.radius, 0.0..=self.length).map(|hit| LocalHit {
+                // Along the whole capsule rather than the side
+                v: (hit.v * self.length + self.radius) / total,
+                ..hit
+            });
+        let hits = side
+            .chain(self.hit_end(origin, direction, 0., -1.))
+            .chain(self.hit_end(origin, direction, self.length, 1.));
+        self.frame
+            .closest_hit(r, &range, hits, self.mat_ptr.as_ref())
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        area_pdf_value(self, self.get_surface_area(), origin, direction)
+    }
+
+    fn sample_point(&self, rng: &mut dyn rand::RngCore) -> Option<SurfacePoint<'_>> {
+        let choice = rng.sample::<f64, _>(Standard) * self.get_surface_area();
+        let phi = TAU * rng.sample::<f64, _>(Standard);
+        let (normal, height) = if choice < self.side_area() {
+            let z = self.length * rng.sample::<f64, _>(Standard);
+            (Vec3::new(phi.cos(), phi.sin(), 0.), z)
+        } else {
+            // Uniform over a sphere, each half goes to its end
+            let z = 1. - 2. * rng.sample::<f64, _>(Standard);
+            let ring = (1. - z * z).max(0.).sqrt();
+            let normal = Vec3::new(ring * phi.cos(), ring * phi.sin(), z);
+            (normal, if z > 0. { self.length } else { 0. })
+        };
+        let point = normal * self.radius + Vec3::new(0., 0., height);
+        let local = LocalPoint {
+            point,
+            normal,
+            u: turn_fraction(point),
+            v: (point.z + self.radius) / (self.length + 2. * self.radius),
+        };
+        Some(self.frame.surface_point(local, self.mat_ptr.as_ref()))
+    }
+
+    fn emissive_objects<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+        if self.mat_ptr.as_ref().is_emissive() {
+            lights.push(self);
+        }
+    }
+
+    fn light_power(&self) -> Option<f64> {
+        let material = self.mat_ptr.as_ref();
+        material
+            .is_emissive()
+            .then(|| material.emitted_power() * self.get_surface_area())
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds::omnidirectional(
+            self.aabox,
+            self.light_power()?,
+        ))
+    }
+}
+
+impl Bounded for Capsule {
+    fn get_aabbox(&self) -> AABBox {
+        self.aabox
+    }
+
+    fn get_surface_area(&self) -> f64 {
+        self.side_area() + 4. * PI * self.radius * self.radius
+    }
+}
+
+impl BoundedHittable for Capsule {}
@@ -0,0 +1,187 @@
+use std::{
+    f64::consts::{PI, TAU},
+    fmt::Debug,
+    ops::RangeInclusive,
+};
+
+use rand::{Rng as _, distributions::Standard};
+
+use geometry::{
+    aabox::AABBox,
+    bounded::Bounded,
+    vec3::{Point3, Vec3},
+};
+
+use crate::{
+    hittable::{BoundedHittable, HitRecord, Hittable, SurfacePoint},
+    hittable_collections::light_tree::LightBounds,
+    material::DynMaterial,
+    ray::Ray,
+    utils::polynomial::solve_quadratic,
+};
+
+use super::{
+    disk::{hit_disk, sample_disk},
+    frame::{Frame, LocalHit, LocalPoint, area_pdf_value, turn_fraction},
+};
+
+/// Cone with a circular base of `radius` and its tip at `apex`, the base is closed by a disk
+/// unless it's made without caps.
+///
+/// On the side `u` goes around the axis and `v` from the base to the apex, on the base they're
+/// the ones of a [`Disk`](super::Disk).
+#[derive(Debug, Clone)]
+pub struct Cone {
+    frame: Frame,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    mat_ptr: DynMaterial,
+    aabox: AABBox,
+}
+
+impl Cone {
+    pub fn new<T>(base: Point3, apex: Point3, radius: f64, mat_ptr: T) -> Self
+    where
+        T: TryInto<DynMaterial>,
+        <T as TryInto<DynMaterial>>::Error: Debug,
+    {
+        let frame = Frame::new(base, apex - base);
+        let extent = frame.circle_extent(radius);
+        Self {
+            frame,
+            radius,
+            height: (apex - base).length(),
+            capped: true,
+            mat_ptr: mat_ptr.try_into().unwrap(),
+            aabox: AABBox::from_points([base - extent, base + extent, apex]),
+        }
+    }
+
+    /// Whether the base is closed.
+    #[must_use]
+    pub fn with_caps(self, capped: bool) -> Self {
+        Self { capped, ..self }
+    }
+
+    fn side_area(&self) -> f64 {
+        PI * self.radius * self.radius.hypot(self.height)
+    }
+
+    fn hit_side(&self, origin: Vec3, direction: Vec3) -> impl Iterator<Item = LocalHit> {
+        // x^2 + y^2 = (k (h - z))^2
+        let k2 = (self.radius / self.height).powi(2);
+        let (h, height) = (self.height - origin.z, self.height);
+        let roots = solve_quadratic(
+            direction.x * direction.x + direction.y * direction.y - k2 * direction.z * direction.z,
+            2. * (origin.x * direction.x + origin.y * direction.y + k2 * h * direction.z),
+            origin.x * origin.x + origin.y * origin.y - k2 * h * h,
+        );
+        roots.into_iter().flatten().filter_map(move |t| {
+            let point = origin + direction * t;
+            (0. ..=height).contains(&point.z).then(|| {
+                let normal = Vec3::new(point.x, point.y, k2 * (height - point.z));
+                LocalHit {
+                    t,
+                    // The apex has no normal, the axis is used
+                    normal: if normal.square_length() > 0. {
+                        normal.normalize()
+                    } else {
+                        Vec3::new(0., 0., 1.)
+                    },
+                    u: turn_fraction(point),
+                    v: point.z / height,
+                }
+            })
+        })
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, r: &Ray, range: RangeInclusive<f64>) -> Option<HitRecord<'_>> {
+        let (origin, direction) = self.frame.local_ray(r);
+        let base = self
+            .capped
+            .then(|| hit_disk(origin, direction, 0., self.radius))
+            .flatten()
+            .map(|hit| LocalHit {
+                normal: -hit.normal,
+                ..hit
+            });
+        self.frame.closest_hit(
+            r,
+            &range,
+            self.hit_side(origin, direction).chain(base),
+            self.mat_ptr.as_ref(),
+        )
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        area_pdf_value(self, self.get_surface_area(), origin, direction)
+    }
+
+    fn sample_point(&self, rng: &mut dyn rand::RngCore) -> Option<SurfacePoint<'_>> {
+        let choice = rng.sample::<f64, _>(Standard) * self.get_surface_area();
+        let local = if choice < self.side_area() {
+            // The area grows with the square of the distance to the apex
+            let s = rng.sample::<f64, _>(Standard).sqrt();
+            let phi = TAU * rng.sample::<f64, _>(Standard);
+            let (sin, cos) = phi.sin_cos();
+            let point = Vec3::new(
+                s * self.radius * cos,
+                s * self.radius * sin,
+                self.height * (1. - s),
+            );
+            LocalPoint {
+                point,
+                // Leans away from the axis by the slope of the side
+                normal: Vec3::new(self.height * cos, self.height * sin, self.radius).normalize(),
+                u: turn_fraction(point),
+                v: 1. - s,
+            }
+        } else {
+            let base = sample_disk(self.radius, rng);
+            LocalPoint {
+                normal: -base.normal,
+                ..base
+            }
+        };
+        Some(self.frame.surface_point(local, self.mat_ptr.as_ref()))
+    }
+
+    fn emissive_objects<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+        if self.mat_ptr.as_ref().is_emissive() {
+            lights.push(self);
+        }
+    }
+
+    fn light_power(&self) -> Option<f64> {
+        let material = self.mat_ptr.as_ref();
+        material
+            .is_emissive()
+            .then(|| material.emitted_power() * self.get_surface_area())
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds::omnidirectional(
+            self.aabox,
+            self.light_power()?,
+        ))
+    }
+}
+
+impl Bounded for Cone {
+    fn get_aabbox(&self) -> AABBox {
+        self.aabox
+    }
+
+    fn get_surface_area(&self) -> f64 {
+        if self.capped {
+            self.side_area() + PI * self.radius * self.radius
+        } else {
+            self.side_area()
+        }
+    }
+}
+
+impl BoundedHittable for Cone {}
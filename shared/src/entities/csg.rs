@@ -0,0 +1,171 @@
+use std::ops::RangeInclusive;
+
+#[cfg(feature = "euclid")]
+use geometry::aabox::Box3DExt as _;
+use geometry::{aabox::AABBox, aaplane::get_axis, bounded::Bounded, vec3::Point3};
+
+use crate::{
+    hittable::{BoundedHittable, HitInterval, HitRecord, Hittable},
+    ray::Ray,
+};
+
+/// How the insides of the children of a [`Csg`] are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    /// The part of the first child outside of the second one.
+    Difference,
+}
+
+impl CsgOperation {
+    const fn is_inside(self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            Self::Union => in_a || in_b,
+            Self::Intersection => in_a && in_b,
+            Self::Difference => in_a && !in_b,
+        }
+    }
+}
+
+/// Solid made by combining the insides of two closed objects, each part of its surface keeps the
+/// material of the child it comes from.
+///
+/// Rays are intersected with the children along their whole line, using
+/// [`intervals`](Hittable::intervals), so the children must be closed and can't overlap
+/// themselves. It can't be sampled as a light.
+#[derive(Debug, Clone)]
+pub struct Csg<A, B> {
+    a: A,
+    b: B,
+    operation: CsgOperation,
+    aabox: AABBox,
+}
+
+impl<A: BoundedHittable, B: BoundedHittable> Csg<A, B> {
+    pub fn new(a: A, b: B, operation: CsgOperation) -> Self {
+        let (a_box, b_box) = (a.get_aabbox(), b.get_aabbox());
+        let aabox = match operation {
+            CsgOperation::Union => a_box.enclose(&b_box),
+            CsgOperation::Intersection => {
+                let overlap = get_axis().map(|axis| {
+                    let (a_axis, b_axis) = (a_box.axis(axis), b_box.axis(axis));
+                    let start = a_axis.start().max(*b_axis.start());
+                    // Boxes which don't overlap are left flat
+                    (start, a_axis.end().min(*b_axis.end()).max(start))
+                });
+                AABBox::from_points([
+                    Point3::from(overlap.map(|(start, _)| start)),
+                    Point3::from(overlap.map(|(_, end)| end)),
+                ])
+            }
+            CsgOperation::Difference => a_box,
+        };
+        Self {
+            a,
+            b,
+            operation,
+            aabox,
+        }
+    }
+
+    pub fn union(a: A, b: B) -> Self {
+        Self::new(a, b, CsgOperation::Union)
+    }
+
+    pub fn intersection(a: A, b: B) -> Self {
+        Self::new(a, b, CsgOperation::Intersection)
+    }
+
+    /// The part of `a` outside of `b`.
+    pub fn difference(a: A, b: B) -> Self {
+        Self::new(a, b, CsgOperation::Difference)
+    }
+
+    pub const fn get_operation(&self) -> CsgOperation {
+        self.operation
+    }
+}
+
+/// Intervals of `object` along the line of `r`, skipping it if its box isn't hit.
+fn child_intervals<'a>(object: &'a dyn BoundedHittable, r: &Ray) -> Vec<HitInterval<'a>> {
+    if object.is_aabbox_hit(r, f64::NEG_INFINITY..=f64::INFINITY) {
+        object.intervals(r)
+    } else {
+        Vec::new()
+    }
+}
+
+impl<A: BoundedHittable, B: BoundedHittable> Hittable for Csg<A, B> {
+    fn hit(&self, r: &Ray, range: RangeInclusive<f64>) -> Option<HitRecord<'_>> {
+        self.intervals(r)
+            .into_iter()
+            .flat_map(|HitInterval { enter, exit }| [enter, exit])
+            .find(|record| range.contains(&record.get_t()))
+    }
+
+    /// Costs of the children, which are both walked along the whole ray.
+    fn hit_cost(&self, r: &Ray, range: RangeInclusive<f64>) -> u32 {
+        self.a.bounded_hit_cost(r, range.clone()) + self.b.bounded_hit_cost(r, range)
+    }
+
+    fn intervals(&self, r: &Ray) -> Vec<HitInterval<'_>> {
+        let a = child_intervals(&self.a, r);
+        if a.is_empty() && self.operation != CsgOperation::Union {
+            return Vec::new();
+        }
+        let b = child_intervals(&self.b, r);
+        // Every place where the ray goes in or out of a child, sorted along it
+        let mut crossings = [(true, a), (false, b)]
+            .into_iter()
+            .flat_map(|(from_a, intervals)| {
+                intervals
+                    .into_iter()
+                    .flat_map(move |HitInterval { enter, exit }| {
+                        [(from_a, true, enter), (from_a, false, exit)]
+                    })
+            })
+            .collect::<Vec<_>>();
+        crossings.sort_by(|(.., x), (.., y)| x.get_t().total_cmp(&y.get_t()));
+
+        let (mut in_a, mut in_b) = (false, false);
+        let mut enter = None;
+        let mut intervals = Vec::new();
+        for (from_a, entering, mut record) in crossings {
+            let was_inside = self.operation.is_inside(in_a, in_b);
+            if from_a {
+                in_a = entering;
+            } else {
+                in_b = entering;
+            }
+            let inside = self.operation.is_inside(in_a, in_b);
+            if inside == was_inside {
+                continue;
+            }
+            // The side is the one of the solid, like going out of `b` into a difference
+            record.set_front_face(inside);
+            if inside {
+                enter = Some(record);
+            } else if let Some(enter) = enter.take() {
+                intervals.push(HitInterval {
+                    enter,
+                    exit: record,
+                });
+            }
+        }
+        intervals
+    }
+}
+
+impl<A: BoundedHittable, B: BoundedHittable> Bounded for Csg<A, B> {
+    fn get_aabbox(&self) -> AABBox {
+        self.aabox
+    }
+
+    /// The one of both children, which is more than the one of the solid.
+    fn get_surface_area(&self) -> f64 {
+        self.a.get_surface_area() + self.b.get_surface_area()
+    }
+}
+
+impl<A: BoundedHittable, B: BoundedHittable> BoundedHittable for Csg<A, B> {}
@@ -3,6 +3,8 @@ use std::{fmt::Debug, ops::RangeInclusive};
 #[cfg(feature = "euclid")]
 use geometry::aabox::Box3DExt as _;
 
+use rand::{Rng as _, distributions::Standard};
+
 use geometry::{
     aabox::AABBox,
     aaplane::get_axis,
@@ -11,7 +13,7 @@ use geometry::{
 };
 
 use crate::{
-    hittable::{BoundedHittable, HitRecord, Hittable},
+    hittable::{BoundedHittable, HitRecord, Hittable, SurfacePoint},
     material::DynMaterial,
     ray::Ray,
 };
@@ -56,6 +58,38 @@ impl Hittable for Cuboid {
             .filter_map(|q| q.hit(r, range.clone()))
             .min_by(|hit1, hit2| hit1.get_t().total_cmp(&hit2.get_t()))
     }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        // Faces are picked proportionally to their area, so the surface is sampled uniformly
+        let area = self.get_surface_area();
+        self.quads
+            .iter()
+            .map(|quad| quad.get_surface_area() / area * quad.pdf_value(origin, direction))
+            .sum()
+    }
+
+    fn sample_point(&self, rng: &mut dyn rand::RngCore) -> Option<SurfacePoint<'_>> {
+        let mut remaining = rng.sample::<f64, _>(Standard) * self.get_surface_area();
+        let quad = self
+            .quads
+            .iter()
+            .find(|quad| {
+                remaining -= quad.get_surface_area();
+                remaining < 0.
+            })
+            .unwrap_or(&self.quads[5]);
+        quad.sample_point(rng)
+    }
+
+    fn emissive_objects<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+        self.quads
+            .iter()
+            .for_each(|quad| quad.emissive_objects(lights));
+    }
+
+    fn light_power(&self) -> Option<f64> {
+        self.quads.iter().map(Quad::light_power).sum()
+    }
 }
 
 impl Bounded for Cuboid {
@@ -0,0 +1,185 @@
+use std::{
+    f64::consts::{PI, TAU},
+    fmt::Debug,
+    ops::RangeInclusive,
+};
+
+use rand::{Rng as _, distributions::Standard};
+
+use geometry::{
+    aabox::AABBox,
+    bounded::Bounded,
+    vec3::{Point3, Vec3},
+};
+
+use crate::{
+    hittable::{BoundedHittable, HitRecord, Hittable, SurfacePoint},
+    hittable_collections::light_tree::LightBounds,
+    material::DynMaterial,
+    ray::Ray,
+    utils::polynomial::solve_quadratic,
+};
+
+use super::{
+    disk::{hit_disk, sample_disk},
+    frame::{Frame, LocalHit, LocalPoint, area_pdf_value, turn_fraction},
+};
+
+/// Cylinder from `base` to `top`, closed by a disk at each end unless it's made without caps.
+///
+/// On the side `u` goes around the axis and `v` from the base to the top, on the caps they're
+/// the ones of a [`Disk`](super::Disk).
+#[derive(Debug, Clone)]
+pub struct Cylinder {
+    frame: Frame,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    mat_ptr: DynMaterial,
+    aabox: AABBox,
+}
+
+impl Cylinder {
+    pub fn new<T>(base: Point3, top: Point3, radius: f64, mat_ptr: T) -> Self
+    where
+        T: TryInto<DynMaterial>,
+        <T as TryInto<DynMaterial>>::Error: Debug,
+    {
+        let frame = Frame::new(base, top - base);
+        let extent = frame.circle_extent(radius);
+        Self {
+            frame,
+            radius,
+            height: (top - base).length(),
+            capped: true,
+            mat_ptr: mat_ptr.try_into().unwrap(),
+            aabox: AABBox::from_points([base - extent, base + extent, top - extent, top + extent]),
+        }
+    }
+
+    /// Whether the ends are closed.
+    #[must_use]
+    pub fn with_caps(self, capped: bool) -> Self {
+        Self { capped, ..self }
+    }
+
+    fn side_area(&self) -> f64 {
+        TAU * self.radius * self.height
+    }
+}
+
+/// Hits of the ray `(origin, direction)` with the side of the cylinder of `radius` around the
+/// `z` axis between the heights in `along`, in the space of a [`Frame`].
+pub(super) fn hit_side(
+    origin: Vec3,
+    direction: Vec3,
+    radius: f64,
+    along: RangeInclusive<f64>,
+) -> impl Iterator<Item = LocalHit> {
+    let roots = solve_quadratic(
+        direction.x * direction.x + direction.y * direction.y,
+        2. * (origin.x * direction.x + origin.y * direction.y),
+        origin.x * origin.x + origin.y * origin.y - radius * radius,
+    );
+    roots.into_iter().flatten().filter_map(move |t| {
+        let point = origin + direction * t;
+        along.contains(&point.z).then(|| LocalHit {
+            t,
+            normal: Vec3::new(point.x, point.y, 0.) / radius,
+            u: turn_fraction(point),
+            v: (point.z - along.start()) / (along.end() - along.start()),
+        })
+    })
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, r: &Ray, range: RangeInclusive<f64>) -> Option<HitRecord<'_>> {
+        let (origin, direction) = self.frame.local_ray(r);
+        let side = hit_side(origin, direction, self.radius, 0.0..=self.height);
+        let caps = self
+            .capped
+            .then(|| {
+                [
+                    hit_disk(origin, direction, 0., self.radius).map(|hit| LocalHit {
+                        normal: -hit.normal,
+                        ..hit
+                    }),
+                    hit_disk(origin, direction, self.height, self.radius),
+                ]
+            })
+            .into_iter()
+            .flatten()
+            .flatten();
+        self.frame
+            .closest_hit(r, &range, side.chain(caps), self.mat_ptr.as_ref())
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        area_pdf_value(self, self.get_surface_area(), origin, direction)
+    }
+
+    fn sample_point(&self, rng: &mut dyn rand::RngCore) -> Option<SurfacePoint<'_>> {
+        let cap_area = PI * self.radius * self.radius;
+        let choice = rng.sample::<f64, _>(Standard) * self.get_surface_area();
+        let local = if choice < self.side_area() {
+            let phi = TAU * rng.sample::<f64, _>(Standard);
+            let v = rng.sample::<f64, _>(Standard);
+            let normal = Vec3::new(phi.cos(), phi.sin(), 0.);
+            LocalPoint {
+                point: normal * self.radius + Vec3::new(0., 0., self.height * v),
+                normal,
+                u: turn_fraction(normal),
+                v,
+            }
+        } else if choice < self.side_area() + cap_area {
+            let base = sample_disk(self.radius, rng);
+            LocalPoint {
+                normal: -base.normal,
+                ..base
+            }
+        } else {
+            let top = sample_disk(self.radius, rng);
+            LocalPoint {
+                point: top.point + Vec3::new(0., 0., self.height),
+                ..top
+            }
+        };
+        Some(self.frame.surface_point(local, self.mat_ptr.as_ref()))
+    }
+
+    fn emissive_objects<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+        if self.mat_ptr.as_ref().is_emissive() {
+            lights.push(self);
+        }
+    }
+
+    fn light_power(&self) -> Option<f64> {
+        let material = self.mat_ptr.as_ref();
+        material
+            .is_emissive()
+            .then(|| material.emitted_power() * self.get_surface_area())
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds::omnidirectional(
+            self.aabox,
+            self.light_power()?,
+        ))
+    }
+}
+
+impl Bounded for Cylinder {
+    fn get_aabbox(&self) -> AABBox {
+        self.aabox
+    }
+
+    fn get_surface_area(&self) -> f64 {
+        if self.capped {
+            self.side_area() + 2. * PI * self.radius * self.radius
+        } else {
+            self.side_area()
+        }
+    }
+}
+
+impl BoundedHittable for Cylinder {}
@@ -0,0 +1,136 @@
+use std::{
+    f64::consts::{PI, TAU},
+    fmt::Debug,
+    ops::RangeInclusive,
+};
+
+use rand::{Rng as _, distributions::Standard};
+
+use geometry::{
+    aabox::AABBox,
+    bounded::Bounded,
+    vec3::{Point3, Vec3},
+};
+
+use crate::{
+    hittable::{BoundedHittable, HitRecord, Hittable, SurfacePoint},
+    hittable_collections::light_tree::LightBounds,
+    material::DynMaterial,
+    ray::Ray,
+};
+
+use super::frame::{Frame, LocalHit, LocalPoint, area_pdf_value, turn_fraction};
+
+/// Flat circle facing `normal`, `u` goes around it and `v` from the centre to the edge.
+#[derive(Debug, Clone)]
+pub struct Disk {
+    frame: Frame,
+    radius: f64,
+    mat_ptr: DynMaterial,
+    aabox: AABBox,
+}
+
+impl Disk {
+    pub fn new<T>(center: Point3, normal: Vec3, radius: f64, mat_ptr: T) -> Self
+    where
+        T: TryInto<DynMaterial>,
+        <T as TryInto<DynMaterial>>::Error: Debug,
+    {
+        let frame = Frame::new(center, normal);
+        let extent = frame.circle_extent(radius);
+        Self {
+            frame,
+            radius,
+            mat_ptr: mat_ptr.try_into().unwrap(),
+            aabox: AABBox::from_points([center - extent, center + extent]),
+        }
+    }
+}
+
+/// Hit of the ray `(origin, direction)` with the disk of `radius` around the `z` axis at
+/// `height`, in the space of a [`Frame`].
+pub(super) fn hit_disk(
+    origin: Vec3,
+    direction: Vec3,
+    height: f64,
+    radius: f64,
+) -> Option<LocalHit> {
+    (direction.z.abs() > f64::EPSILON).then_some(())?;
+    let t = (height - origin.z) / direction.z;
+    let point = origin + direction * t;
+    let distance = point.x.hypot(point.y);
+    (distance <= radius).then(|| LocalHit {
+        t,
+        normal: Vec3::new(0., 0., 1.),
+        u: turn_fraction(point),
+        v: distance / radius,
+    })
+}
+
+/// Point sampled uniformly on the disk of `radius` around the `z` axis, facing up.
+pub(super) fn sample_disk(radius: f64, rng: &mut dyn rand::RngCore) -> LocalPoint {
+    let v = rng.sample::<f64, _>(Standard).sqrt();
+    let phi = TAU * rng.sample::<f64, _>(Standard);
+    let point = Vec3::new(radius * v * phi.cos(), radius * v * phi.sin(), 0.);
+    LocalPoint {
+        point,
+        normal: Vec3::new(0., 0., 1.),
+        u: turn_fraction(point),
+        v,
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, range: RangeInclusive<f64>) -> Option<HitRecord<'_>> {
+        let (origin, direction) = self.frame.local_ray(r);
+        self.frame.closest_hit(
+            r,
+            &range,
+            hit_disk(origin, direction, 0., self.radius),
+            self.mat_ptr.as_ref(),
+        )
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        area_pdf_value(self, self.get_surface_area(), origin, direction)
+    }
+
+    fn sample_point(&self, rng: &mut dyn rand::RngCore) -> Option<SurfacePoint<'_>> {
+        let local = sample_disk(self.radius, rng);
+        Some(self.frame.surface_point(local, self.mat_ptr.as_ref()))
+    }
+
+    fn emissive_objects<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+        if self.mat_ptr.as_ref().is_emissive() {
+            lights.push(self);
+        }
+    }
+
+    fn light_power(&self) -> Option<f64> {
+        let material = self.mat_ptr.as_ref();
+        material
+            .is_emissive()
+            .then(|| material.emitted_power() * self.get_surface_area())
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds::planar(
+            self.aabox,
+            self.light_power()?,
+            self.frame.get_axis(),
+            self.mat_ptr.as_ref().is_two_sided(),
+        ))
+    }
+}
+
+impl Bounded for Disk {
+    fn get_aabbox(&self) -> AABBox {
+        self.aabox
+    }
+
+    fn get_surface_area(&self) -> f64 {
+        PI * self.radius * self.radius
+    }
+}
+
+impl BoundedHittable for Disk {}
@@ -0,0 +1,255 @@
+use std::{f64::consts::TAU, ops::RangeInclusive};
+
+use geometry::{
+    onb::Onb,
+    vec3::{Point3, Vec3},
+};
+
+use crate::{
+    hittable::{HitRecord, Hittable, SurfacePoint},
+    material::Material,
+    ray::Ray,
+};
+
+/// Orthonormal frame at `origin` whose `z` axis is the axis of a primitive, so it can be hit in
+/// its own space.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Frame {
+    origin: Point3,
+    onb: Onb,
+}
+
+/// Hit in the space of a [`Frame`].
+pub(super) struct LocalHit {
+    pub t: f64,
+    pub normal: Vec3,
+    pub u: f64,
+    pub v: f64,
+}
+
+/// Point sampled on a primitive in the space of a [`Frame`].
+pub(super) struct LocalPoint {
+    pub point: Vec3,
+    pub normal: Vec3,
+    pub u: f64,
+    pub v: f64,
+}
+
+impl Frame {
+    pub fn new(origin: Point3, axis: Vec3) -> Self {
+        Self {
+            origin,
+            onb: Onb::new(axis),
+        }
+    }
+
+    pub const fn get_axis(&self) -> Vec3 {
+        self.onb.get_w()
+    }
+
+    /// Origin and direction of `r` in this frame, distances along it stay the same.
+    pub fn local_ray(&self, r: &Ray) -> (Vec3, Vec3) {
+        let local = |v: Vec3| {
+            Vec3::new(
+                v.dot(self.onb.get_u()),
+                v.dot(self.onb.get_v()),
+                v.dot(self.onb.get_w()),
+            )
+        };
+        (
+            local(r.get_origin() - self.origin),
+            local(r.get_direction()),
+        )
+    }
+
+    pub fn point_to_world(&self, point: Vec3) -> Point3 {
+        self.origin + self.onb.transform(point)
+    }
+
+    pub fn vector_to_world(&self, vector: Vec3) -> Vec3 {
+        self.onb.transform(vector)
+    }
+
+    /// `local` in world space.
+    pub fn surface_point<'a>(
+        &self,
+        local: LocalPoint,
+        material: &'a dyn Material,
+    ) -> SurfacePoint<'a> {
+        SurfacePoint {
+            p: self.point_to_world(local.point),
+            normal: self.vector_to_world(local.normal).normalize(),
+            u: local.u,
+            v: local.v,
+            material,
+        }
+    }
+
+    /// Half of the size along each axis of a circle of `radius` around the `z` axis.
+    pub fn circle_extent(&self, radius: f64) -> Vec3 {
+        let w = self.get_axis();
+        Vec3::new(
+            radius * (1. - w.x * w.x).max(0.).sqrt(),
+            radius * (1. - w.y * w.y).max(0.).sqrt(),
+            radius * (1. - w.z * w.z).max(0.).sqrt(),
+        )
+    }
+
+    /// The closest of `hits` in `range`.
+    pub fn closest_hit<'a>(
+        &self,
+        r: &Ray,
+        range: &RangeInclusive<f64>,
+        hits: impl IntoIterator<Item = LocalHit>,
+        mat_ptr: &'a dyn Material,
+    ) -> Option<HitRecord<'a>> {
+        let hit = hits
+            .into_iter()
+            .filter(|hit| range.contains(&hit.t))
+            .min_by(|a, b| a.t.total_cmp(&b.t))?;
+        Some(HitRecord::new(
+            r,
+            hit.t,
+            self.vector_to_world(hit.normal).normalize(),
+            hit.u,
+            hit.v,
+            mat_ptr,
+        ))
+    }
+}
+
+/// Angle of `point` around the `z` axis as a fraction of a turn.
+pub(super) fn turn_fraction(point: Vec3) -> f64 {
+    (point.y.atan2(point.x) / TAU).rem_euclid(1.)
+}
+
+/// Density over directions from `origin` of sampling points uniformly over the surface of
+/// `object`, which adds up every point along `direction`.
+pub(super) fn area_pdf_value(
+    object: &dyn Hittable,
+    area: f64,
+    origin: Point3,
+    direction: Vec3,
+) -> f64 {
+    let ray = Ray::new(origin, direction);
+    let mut start = 0.;
+    let mut pdf = 0.;
+    // No primitive is crossed more than 4 times by a ray
+    for _ in 0..4 {
+        let Some(record) = object.hit(&ray, start..=f64::INFINITY) else {
+            break;
+        };
+        let distance_squared = record.get_t() * record.get_t() * direction.square_length();
+        let cosine = direction
+            .dot(record.get_normal())
+            .abs()
+            .max(f64::EPSILON * direction.length())
+            / direction.length();
+        pdf += distance_squared / (cosine * area);
+        start = record.get_t() * (1. + 1e-9) + 1e-9;
+    }
+    pdf
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{f64::consts::PI, sync::Arc};
+
+    use rand::{SeedableRng as _, rngs::SmallRng};
+
+    use geometry::{
+        aabox::AABBox,
+        vec3::{Point3, Vec3},
+    };
+
+    use crate::{
+        colour::Colour,
+        entities::{Capsule, Cone, Cylinder, Disk, Torus},
+        hittable::BoundedHittable,
+        material::Lambertian,
+        ray::Ray,
+    };
+
+    fn contains(aabox: &AABBox, point: Point3) -> bool {
+        let (min, max) = (aabox.get_points()[0], aabox.get_points()[7]);
+        (min.x..=max.x).contains(&point.x)
+            && (min.y..=max.y).contains(&point.y)
+            && (min.z..=max.z).contains(&point.z)
+    }
+
+    fn primitives() -> Vec<Box<dyn BoundedHittable>> {
+        let material = Arc::new(Lambertian::new_with_colour(Colour::new(0.5, 0.5, 0.5)));
+        let (center, axis) = (Point3::new(0.5, -0.2, 0.3), Vec3::new(0.3, 1., -0.2));
+        vec![
+            Box::new(Disk::new(center, axis, 1., material.clone())),
+            Box::new(Cylinder::new(center, center + axis, 0.7, material.clone())),
+            Box::new(Cylinder::new(center, center + axis, 0.7, material.clone()).with_caps(false)),
+            Box::new(Cone::new(center, center + axis, 0.8, material.clone())),
+            Box::new(Torus::new(center, axis, 1., 0.3, material.clone())),
+            Box::new(Capsule::new(center, center + axis, 0.5, material)),
+        ]
+    }
+
+    #[test]
+    fn sampled_points_are_on_the_surface() {
+        let primitives = primitives();
+        let origin = Point3::new(0.2, 3., -3.);
+        let mut rng = SmallRng::seed_from_u64(3);
+        // Directions spread evenly over the sphere
+        let n = 200_000;
+        let golden_angle = PI * (3. - 5f64.sqrt());
+        let directions = (0..n).map(|i| {
+            let z = 1. - (2. * f64::from(i) + 1.) / f64::from(n);
+            let (sin, cos) = (f64::from(i) * golden_angle).sin_cos();
+            let ring = (1. - z * z).sqrt();
+            Vec3::new(ring * cos, ring * sin, z)
+        });
+        for (i, primitive) in primitives.iter().enumerate() {
+            let aabox = primitive.get_aabbox();
+            for _ in 0..1000 {
+                let direction = primitive.random(origin, &mut rng);
+                let point = origin + direction;
+                assert!(contains(&aabox, point), "{i}: {point:?}");
+                let record = primitive
+                    .hit(&Ray::new(origin, direction), 0.001..=1. + 1e-6)
+                    .unwrap();
+                assert!((record.get_normal().length() - 1.).abs() < 1e-9);
+                assert!((0. ..=1.).contains(&record.get_u()), "{i}");
+                assert!((0. ..=1.).contains(&record.get_v()), "{i}");
+                assert!(primitive.pdf_value(origin, direction) > 0.);
+            }
+            // The density over directions integrates to 1
+            let integral = directions
+                .clone()
+                .map(|direction| primitive.pdf_value(origin, direction))
+                .sum::<f64>()
+                * 4.
+                * PI
+                / f64::from(n);
+            assert!((integral - 1.).abs() < 0.05, "{i}: {integral}");
+        }
+    }
+
+    #[test]
+    fn sampled_records_match_hits() {
+        let mut rng = SmallRng::seed_from_u64(5);
+        for (i, primitive) in primitives().iter().enumerate() {
+            for _ in 0..1000 {
+                let sampled = primitive.sample_surface(&mut rng).unwrap();
+                let (p, normal) = (sampled.get_p(), sampled.get_normal());
+                // Hit again from just outside the surface
+                let r = Ray::new(p + normal * 1e-4, -normal);
+                let Some(hit) = primitive.hit(&r, (0.)..=2e-4) else {
+                    panic!("{i}: {p:?} wasn't hit");
+                };
+                assert!((hit.get_p() - p).length() < 1e-9, "{i}: {p:?}");
+                assert!((hit.get_normal() - normal).length() < 1e-6, "{i}: {p:?}");
+                assert!(hit.is_front_face(), "{i}: {p:?}");
+                // `u` goes around, so it wraps at 1
+                let du = (hit.get_u() - sampled.get_u()).abs();
+                assert!(du.min(1. - du) < 1e-6, "{i}: {p:?}");
+                assert!((hit.get_v() - sampled.get_v()).abs() < 1e-6, "{i}: {p:?}");
+            }
+        }
+    }
+}
@@ -14,7 +14,8 @@ use geometry::{
 };
 
 use crate::{
-    hittable::{BoundedHittable, HitRecord, Hittable},
+    hittable::{BoundedHittable, HitRecord, Hittable, SurfacePoint},
+    hittable_collections::light_tree::LightBounds,
     material::DynMaterial,
     ray::Ray,
 };
@@ -111,10 +112,37 @@ impl Hittable for Quad {
         }
     }
 
-    fn random(&self, origin: Point3, rng: &mut dyn rand::RngCore) -> Vec3 {
-        let p =
-            self.q + self.u * rng.sample::<f64, _>(Open01) + self.v * rng.sample::<f64, _>(Open01);
-        p - origin
+    fn sample_point(&self, rng: &mut dyn rand::RngCore) -> Option<SurfacePoint<'_>> {
+        let (u, v) = (rng.sample::<f64, _>(Open01), rng.sample::<f64, _>(Open01));
+        Some(SurfacePoint {
+            p: self.q + self.u * u + self.v * v,
+            normal: self.normal,
+            u,
+            v,
+            material: self.mat_ptr.as_ref(),
+        })
+    }
+
+    fn emissive_objects<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+        if self.mat_ptr.as_ref().is_emissive() {
+            lights.push(self);
+        }
+    }
+
+    fn light_power(&self) -> Option<f64> {
+        let material = self.mat_ptr.as_ref();
+        material
+            .is_emissive()
+            .then(|| material.emitted_power() * self.area)
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds::planar(
+            self.aabbox,
+            self.light_power()?,
+            self.normal,
+            self.mat_ptr.as_ref().is_two_sided(),
+        ))
     }
 }
 
@@ -0,0 +1,271 @@
+use std::{fmt::Debug, ops::RangeInclusive, sync::Arc};
+
+#[cfg(feature = "euclid")]
+use geometry::aabox::Box3DExt as _;
+use geometry::{
+    aabox::AABBox,
+    aaplane::get_axis,
+    bounded::Bounded,
+    vec3::{Point3, Vec3},
+};
+
+use crate::{
+    hittable::{BoundedHittable, HitRecord, Hittable},
+    material::DynMaterial,
+    ray::Ray,
+};
+
+mod operations;
+mod shapes;
+pub use operations::{
+    Repeated, SmoothIntersection, SmoothSubtraction, SmoothUnion, Translated, Twisted,
+};
+pub use shapes::{Capsule, Cuboid, Mandelbulb, Sphere, Torus};
+
+/// Signed distance field, negative inside the shape.
+pub trait Sdf: Debug + Send + Sync {
+    /// Distance from `point` to the surface, which can be too big by at most
+    /// [`lipschitz`](Sdf::lipschitz) times.
+    fn distance(&self, point: Point3) -> f64;
+
+    /// Box enclosing the shape.
+    fn bounds(&self) -> AABBox;
+
+    /// How much faster than the distance to the surface the field can change, the steps of
+    /// sphere tracing are shortened by it.
+    fn lipschitz(&self) -> f64 {
+        1.
+    }
+}
+
+impl<T: Sdf + ?Sized> Sdf for Arc<T> {
+    fn distance(&self, point: Point3) -> f64 {
+        (**self).distance(point)
+    }
+
+    fn bounds(&self) -> AABBox {
+        (**self).bounds()
+    }
+
+    fn lipschitz(&self) -> f64 {
+        (**self).lipschitz()
+    }
+}
+
+impl<T: Sdf + ?Sized> Sdf for Box<T> {
+    fn distance(&self, point: Point3) -> f64 {
+        (**self).distance(point)
+    }
+
+    fn bounds(&self) -> AABBox {
+        (**self).bounds()
+    }
+
+    fn lipschitz(&self) -> f64 {
+        (**self).lipschitz()
+    }
+}
+
+/// Ways of combining and deforming fields, the smooth operations blend the shapes over a
+/// distance of about `k`.
+pub trait SdfExt: Sdf + Sized {
+    fn translate(self, offset: Vec3) -> Translated<Self> {
+        Translated::new(self, offset)
+    }
+
+    fn union<B: Sdf>(self, other: B) -> SmoothUnion<Self, B> {
+        self.smooth_union(other, 0.)
+    }
+
+    fn smooth_union<B: Sdf>(self, other: B, k: f64) -> SmoothUnion<Self, B> {
+        SmoothUnion::new(self, other, k)
+    }
+
+    /// The part of `self` outside `other`.
+    fn subtract<B: Sdf>(self, other: B) -> SmoothSubtraction<Self, B> {
+        self.smooth_subtract(other, 0.)
+    }
+
+    fn smooth_subtract<B: Sdf>(self, other: B, k: f64) -> SmoothSubtraction<Self, B> {
+        SmoothSubtraction::new(self, other, k)
+    }
+
+    fn intersect<B: Sdf>(self, other: B) -> SmoothIntersection<Self, B> {
+        self.smooth_intersect(other, 0.)
+    }
+
+    fn smooth_intersect<B: Sdf>(self, other: B, k: f64) -> SmoothIntersection<Self, B> {
+        SmoothIntersection::new(self, other, k)
+    }
+
+    /// Copies every `period`, `count` of them on each side of the original along each axis,
+    /// axes with a period of 0 aren't repeated.
+    fn repeat(self, period: Vec3, count: [u32; 3]) -> Repeated<Self> {
+        Repeated::new(self, period, count)
+    }
+
+    /// Turns the shape around the `y` axis by `rate` radians per unit of height.
+    fn twist(self, rate: f64) -> Twisted<Self> {
+        Twisted::new(self, rate)
+    }
+}
+
+impl<T: Sdf> SdfExt for T {}
+
+/// Corners of `aabox` with the lowest and the highest coordinates.
+fn min_max(aabox: &AABBox) -> [Point3; 2] {
+    [
+        Point3::from(get_axis().map(|axis| *aabox.axis(axis).start())),
+        Point3::from(get_axis().map(|axis| *aabox.axis(axis).end())),
+    ]
+}
+
+/// `aabox` grown by `amount` on every side.
+fn pad(aabox: AABBox, amount: f64) -> AABBox {
+    let [min, max] = min_max(&aabox);
+    let amount = Vec3::new(amount, amount, amount);
+    AABBox::from_points([min - amount, max + amount])
+}
+
+/// Entity whose surface is where a [`Sdf`] is 0, hit by sphere tracing inside the box of the
+/// field, with the normals given by finite differences.
+///
+/// It can't be sampled as a light, and its surface area is estimated by the one of its box.
+#[derive(Debug, Clone)]
+pub struct SdfObject<S> {
+    sdf: S,
+    mat_ptr: DynMaterial,
+    aabox: AABBox,
+    max_steps: u32,
+    epsilon: f64,
+}
+
+impl<S: Sdf> SdfObject<S> {
+    pub fn new<T>(sdf: S, mat_ptr: T) -> Self
+    where
+        T: TryInto<DynMaterial>,
+        <T as TryInto<DynMaterial>>::Error: Debug,
+    {
+        const EPSILON: f64 = 1e-4;
+        Self {
+            aabox: pad(sdf.bounds(), 2. * EPSILON),
+            sdf,
+            mat_ptr: mat_ptr.try_into().unwrap(),
+            max_steps: 256,
+            epsilon: EPSILON,
+        }
+    }
+
+    /// Steps taken before giving up on a ray, which then misses.
+    #[must_use]
+    pub fn with_max_steps(self, max_steps: u32) -> Self {
+        Self { max_steps, ..self }
+    }
+
+    /// Distance to the surface at which it's considered hit.
+    #[must_use]
+    pub fn with_epsilon(self, epsilon: f64) -> Self {
+        Self {
+            aabox: pad(self.sdf.bounds(), 2. * epsilon),
+            epsilon,
+            ..self
+        }
+    }
+
+    pub const fn get_sdf(&self) -> &S {
+        &self.sdf
+    }
+
+    /// Part of `range` where `r` is inside the box.
+    fn box_range(&self, r: &Ray, range: &RangeInclusive<f64>) -> Option<(f64, f64)> {
+        let (origin, direction) = (r.get_origin(), r.get_direction());
+        let (origin, direction) = (origin.to_array(), direction.to_array());
+        get_axis()
+            .into_iter()
+            .zip(origin.into_iter().zip(direction))
+            .try_fold(
+                (*range.start(), *range.end()),
+                |(start, end), (axis, (origin, direction))| {
+                    let bounds = self.aabox.axis(axis);
+                    let t0 = (bounds.start() - origin) / direction;
+                    let t1 = (bounds.end() - origin) / direction;
+                    let (start, end) = if t0.is_nan() || t1.is_nan() {
+                        // Parallel to the sides and on one of them
+                        (start, end)
+                    } else {
+                        (start.max(t0.min(t1)), end.min(t0.max(t1)))
+                    };
+                    (start <= end).then_some((start, end))
+                },
+            )
+    }
+
+    /// Distance along `r` to the surface and the number of steps taken.
+    fn march(&self, r: &Ray, range: &RangeInclusive<f64>) -> (Option<f64>, u32) {
+        let Some((start, end)) = self.box_range(r, range) else {
+            return (None, 0);
+        };
+        let speed = r.get_direction().length() * self.sdf.lipschitz();
+        let mut t = start;
+        for step in 0..self.max_steps {
+            let distance = self.sdf.distance(r.at(t)).abs();
+            if distance < self.epsilon {
+                // The box keeps the surface away from where rays enter it, so only rays leaving
+                // the surface start on it, they're moved away first
+                if step == 0 {
+                    t += 2. * self.epsilon / speed;
+                    continue;
+                }
+                return (Some(t), step + 1);
+            }
+            t += distance / speed;
+            if t > end {
+                return (None, step + 1);
+            }
+        }
+        (None, self.max_steps)
+    }
+
+    /// Gradient of the field at `point`, by central differences.
+    pub fn normal(&self, point: Point3) -> Vec3 {
+        let h = self.epsilon;
+        let difference =
+            |offset: Vec3| self.sdf.distance(point + offset) - self.sdf.distance(point - offset);
+        let normal = Vec3::new(
+            difference(Vec3::new(h, 0., 0.)),
+            difference(Vec3::new(0., h, 0.)),
+            difference(Vec3::new(0., 0., h)),
+        );
+        if normal.square_length() > 0. {
+            normal.normalize()
+        } else {
+            Vec3::new(0., 1., 0.)
+        }
+    }
+}
+
+impl<S: Sdf> Hittable for SdfObject<S> {
+    fn hit(&self, r: &Ray, range: RangeInclusive<f64>) -> Option<HitRecord<'_>> {
+        let t = self.march(r, &range).0?;
+        let normal = self.normal(r.at(t));
+        let (u, v) = super::Sphere::get_sphere_uv(normal.to_point());
+        Some(HitRecord::new(r, t, normal, u, v, self.mat_ptr.as_ref()))
+    }
+
+    /// Steps taken by sphere tracing.
+    fn hit_cost(&self, r: &Ray, range: RangeInclusive<f64>) -> u32 {
+        self.march(r, &range).1
+    }
+}
+
+impl<S: Sdf> Bounded for SdfObject<S> {
+    fn get_aabbox(&self) -> AABBox {
+        self.aabox
+    }
+
+    fn get_surface_area(&self) -> f64 {
+        self.aabox.get_surface_area()
+    }
+}
+
+impl<S: Sdf> BoundedHittable for SdfObject<S> {}
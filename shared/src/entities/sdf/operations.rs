@@ -0,0 +1,225 @@
+#[cfg(feature = "euclid")]
+use geometry::aabox::Box3DExt as _;
+use geometry::{
+    aabox::AABBox,
+    aaplane::Axis,
+    vec3::{Point3, Vec3},
+};
+
+use super::{Sdf, min_max, pad};
+
+/// Smooth minimum of `a` and `b`, which differs from the minimum by at most `k / 4` where they're
+/// closer than `k`, see "Smooth minimum" by Quilez.
+fn smooth_min(a: f64, b: f64, k: f64) -> f64 {
+    if k <= 0. {
+        return a.min(b);
+    }
+    let h = (k - (a - b).abs()).max(0.) / k;
+    a.min(b) - h * h * k / 4.
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Translated<S> {
+    sdf: S,
+    offset: Vec3,
+}
+
+impl<S> Translated<S> {
+    pub const fn new(sdf: S, offset: Vec3) -> Self {
+        Self { sdf, offset }
+    }
+}
+
+impl<S: Sdf> Sdf for Translated<S> {
+    fn distance(&self, point: Point3) -> f64 {
+        self.sdf.distance(point - self.offset)
+    }
+
+    fn bounds(&self) -> AABBox {
+        let [min, max] = min_max(&self.sdf.bounds());
+        AABBox::from_points([min + self.offset, max + self.offset])
+    }
+
+    fn lipschitz(&self) -> f64 {
+        self.sdf.lipschitz()
+    }
+}
+
+/// Union of two shapes, blended where they're closer than `k`.
+#[derive(Debug, Clone, Copy)]
+pub struct SmoothUnion<A, B> {
+    a: A,
+    b: B,
+    k: f64,
+}
+
+impl<A, B> SmoothUnion<A, B> {
+    pub const fn new(a: A, b: B, k: f64) -> Self {
+        Self { a, b, k }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothUnion<A, B> {
+    fn distance(&self, point: Point3) -> f64 {
+        smooth_min(self.a.distance(point), self.b.distance(point), self.k)
+    }
+
+    fn bounds(&self) -> AABBox {
+        // The blend fills the space between the shapes
+        pad(
+            self.a.bounds().enclose(&self.b.bounds()),
+            self.k.max(0.) / 4.,
+        )
+    }
+
+    fn lipschitz(&self) -> f64 {
+        self.a.lipschitz().max(self.b.lipschitz())
+    }
+}
+
+/// The part of `a` outside of `b`, blended where they're closer than `k`.
+#[derive(Debug, Clone, Copy)]
+pub struct SmoothSubtraction<A, B> {
+    a: A,
+    b: B,
+    k: f64,
+}
+
+impl<A, B> SmoothSubtraction<A, B> {
+    pub const fn new(a: A, b: B, k: f64) -> Self {
+        Self { a, b, k }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothSubtraction<A, B> {
+    fn distance(&self, point: Point3) -> f64 {
+        -smooth_min(-self.a.distance(point), self.b.distance(point), self.k)
+    }
+
+    fn bounds(&self) -> AABBox {
+        self.a.bounds()
+    }
+
+    fn lipschitz(&self) -> f64 {
+        self.a.lipschitz().max(self.b.lipschitz())
+    }
+}
+
+/// The part of `a` inside of `b`, blended where they're closer than `k`.
+#[derive(Debug, Clone, Copy)]
+pub struct SmoothIntersection<A, B> {
+    a: A,
+    b: B,
+    k: f64,
+}
+
+impl<A, B> SmoothIntersection<A, B> {
+    pub const fn new(a: A, b: B, k: f64) -> Self {
+        Self { a, b, k }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothIntersection<A, B> {
+    fn distance(&self, point: Point3) -> f64 {
+        -smooth_min(-self.a.distance(point), -self.b.distance(point), self.k)
+    }
+
+    fn bounds(&self) -> AABBox {
+        self.a.bounds()
+    }
+
+    fn lipschitz(&self) -> f64 {
+        self.a.lipschitz().max(self.b.lipschitz())
+    }
+}
+
+/// Copies of a shape every `period`, `count` of them on each side of the original along each
+/// axis, the shape should fit in a period for the distance to be right.
+#[derive(Debug, Clone, Copy)]
+pub struct Repeated<S> {
+    sdf: S,
+    period: Vec3,
+    count: [u32; 3],
+}
+
+impl<S> Repeated<S> {
+    pub const fn new(sdf: S, period: Vec3, count: [u32; 3]) -> Self {
+        Self { sdf, period, count }
+    }
+}
+
+impl<S: Sdf> Sdf for Repeated<S> {
+    fn distance(&self, point: Point3) -> f64 {
+        let [x, y, z] = [
+            (point.x, self.period.x, self.count[0]),
+            (point.y, self.period.y, self.count[1]),
+            (point.z, self.period.z, self.count[2]),
+        ]
+        .map(|(coordinate, period, count)| {
+            if period > 0. {
+                let count = f64::from(count);
+                coordinate - period * (coordinate / period).round().clamp(-count, count)
+            } else {
+                coordinate
+            }
+        });
+        self.sdf.distance(Point3::new(x, y, z))
+    }
+
+    fn bounds(&self) -> AABBox {
+        let [min, max] = min_max(&self.sdf.bounds());
+        let spread = Vec3::new(
+            self.period.x.max(0.) * f64::from(self.count[0]),
+            self.period.y.max(0.) * f64::from(self.count[1]),
+            self.period.z.max(0.) * f64::from(self.count[2]),
+        );
+        AABBox::from_points([min - spread, max + spread])
+    }
+
+    fn lipschitz(&self) -> f64 {
+        self.sdf.lipschitz()
+    }
+}
+
+/// Shape turned around the `y` axis by `rate` radians per unit of height.
+#[derive(Debug, Clone, Copy)]
+pub struct Twisted<S> {
+    sdf: S,
+    rate: f64,
+    /// Furthest distance of the shape from the `y` axis
+    radius: f64,
+}
+
+impl<S: Sdf> Twisted<S> {
+    pub fn new(sdf: S, rate: f64) -> Self {
+        let radius = sdf
+            .bounds()
+            .get_points()
+            .iter()
+            .map(|point| point.x.hypot(point.z))
+            .fold(0., f64::max);
+        Self { sdf, rate, radius }
+    }
+}
+
+impl<S: Sdf> Sdf for Twisted<S> {
+    fn distance(&self, point: Point3) -> f64 {
+        let (sin, cos) = (-self.rate * point.y).sin_cos();
+        self.sdf.distance(Point3::new(
+            cos * point.x - sin * point.z,
+            point.y,
+            sin * point.x + cos * point.z,
+        ))
+    }
+
+    fn bounds(&self) -> AABBox {
+        let y = self.sdf.bounds().axis(Axis::Y);
+        let r = self.radius;
+        AABBox::from_points([Point3::new(-r, *y.start(), -r), Point3::new(r, *y.end(), r)])
+    }
+
+    /// Twisting stretches the field the most at the furthest points from the axis
+    fn lipschitz(&self) -> f64 {
+        self.sdf.lipschitz() * (self.rate * self.radius).hypot(1.)
+    }
+}
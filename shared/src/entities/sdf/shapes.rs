@@ -0,0 +1,188 @@
+use geometry::{
+    aabox::AABBox,
+    vec3::{Point3, Vec3},
+};
+
+use super::Sdf;
+
+/// Sphere of `radius` around the origin.
+#[derive(Debug, Clone, Copy)]
+pub struct Sphere {
+    radius: f64,
+}
+
+impl Sphere {
+    pub const fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+}
+
+impl Sdf for Sphere {
+    fn distance(&self, point: Point3) -> f64 {
+        point.to_vector().length() - self.radius
+    }
+
+    fn bounds(&self) -> AABBox {
+        let r = self.radius;
+        AABBox::from_points([Point3::new(-r, -r, -r), Point3::new(r, r, r)])
+    }
+}
+
+/// Box centred at the origin reaching `half_size` along each axis, with its edges rounded by
+/// `rounding`, which is taken out of its size.
+#[derive(Debug, Clone, Copy)]
+pub struct Cuboid {
+    half_size: Vec3,
+    rounding: f64,
+}
+
+impl Cuboid {
+    pub const fn new(half_size: Vec3) -> Self {
+        Self {
+            half_size,
+            rounding: 0.,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_rounding(self, rounding: f64) -> Self {
+        Self { rounding, ..self }
+    }
+}
+
+impl Sdf for Cuboid {
+    fn distance(&self, point: Point3) -> f64 {
+        let inner = self.half_size - Vec3::new(self.rounding, self.rounding, self.rounding);
+        let q = Vec3::new(
+            point.x.abs() - inner.x,
+            point.y.abs() - inner.y,
+            point.z.abs() - inner.z,
+        );
+        let outside = Vec3::new(q.x.max(0.), q.y.max(0.), q.z.max(0.)).length();
+        let inside = q.x.max(q.y).max(q.z).min(0.);
+        outside + inside - self.rounding
+    }
+
+    fn bounds(&self) -> AABBox {
+        let h = self.half_size;
+        AABBox::from_points([Point3::new(-h.x, -h.y, -h.z), Point3::new(h.x, h.y, h.z)])
+    }
+}
+
+/// Ring around the `y` axis whose tube of `minor_radius` follows a circle of `major_radius`.
+#[derive(Debug, Clone, Copy)]
+pub struct Torus {
+    major_radius: f64,
+    minor_radius: f64,
+}
+
+impl Torus {
+    pub const fn new(major_radius: f64, minor_radius: f64) -> Self {
+        Self {
+            major_radius,
+            minor_radius,
+        }
+    }
+}
+
+impl Sdf for Torus {
+    fn distance(&self, point: Point3) -> f64 {
+        let radial = point.x.hypot(point.z) - self.major_radius;
+        radial.hypot(point.y) - self.minor_radius
+    }
+
+    fn bounds(&self) -> AABBox {
+        let (outer, minor) = (self.major_radius + self.minor_radius, self.minor_radius);
+        AABBox::from_points([
+            Point3::new(-outer, -minor, -outer),
+            Point3::new(outer, minor, outer),
+        ])
+    }
+}
+
+/// Points closer than `radius` to the segment from `start` to `end`.
+#[derive(Debug, Clone, Copy)]
+pub struct Capsule {
+    start: Point3,
+    end: Point3,
+    radius: f64,
+}
+
+impl Capsule {
+    pub const fn new(start: Point3, end: Point3, radius: f64) -> Self {
+        Self { start, end, radius }
+    }
+}
+
+impl Sdf for Capsule {
+    fn distance(&self, point: Point3) -> f64 {
+        let (pa, ba) = (point - self.start, self.end - self.start);
+        let h = (pa.dot(ba) / ba.square_length()).clamp(0., 1.);
+        (pa - ba * h).length() - self.radius
+    }
+
+    fn bounds(&self) -> AABBox {
+        let extent = Vec3::new(self.radius, self.radius, self.radius);
+        AABBox::from_points([
+            self.start - extent,
+            self.start + extent,
+            self.end - extent,
+            self.end + extent,
+        ])
+    }
+}
+
+/// Three dimensional version of the Mandelbrot set, the one usually shown has a `power` of 8.
+///
+/// The distance is estimated from the growth of the iterated point, see "Distance Estimated 3D
+/// Fractals" by Christensen.
+#[derive(Debug, Clone, Copy)]
+pub struct Mandelbulb {
+    power: f64,
+    iterations: u32,
+}
+
+impl Mandelbulb {
+    pub const fn new(power: f64, iterations: u32) -> Self {
+        Self { power, iterations }
+    }
+}
+
+impl Default for Mandelbulb {
+    fn default() -> Self {
+        Self::new(8., 12)
+    }
+}
+
+impl Sdf for Mandelbulb {
+    fn distance(&self, point: Point3) -> f64 {
+        let mut z = point.to_vector();
+        let mut dr = 1.;
+        let mut r = z.length();
+        for _ in 0..self.iterations {
+            if !(f64::EPSILON..=2.).contains(&r) {
+                break;
+            }
+            let theta = (z.z / r).acos() * self.power;
+            let phi = z.y.atan2(z.x) * self.power;
+            dr = r.powf(self.power - 1.) * self.power * dr + 1.;
+            let zr = r.powf(self.power);
+            z = Vec3::new(
+                theta.sin() * phi.cos(),
+                theta.sin() * phi.sin(),
+                theta.cos(),
+            ) * zr
+                + point.to_vector();
+            r = z.length();
+        }
+        if r < f64::EPSILON {
+            return 0.;
+        }
+        0.5 * r.ln() * r / dr
+    }
+
+    fn bounds(&self) -> AABBox {
+        // Points further than this escape in the first iterations for the usual powers
+        AABBox::from_points([Point3::new(-1.2, -1.2, -1.2), Point3::new(1.2, 1.2, 1.2)])
+    }
+}
@@ -16,8 +16,10 @@ use geometry::{
 };
 
 use crate::{
-    hittable::{BoundedHittable, HitRecord, Hittable},
+    hittable::{BoundedHittable, HitInterval, HitRecord, Hittable, SurfacePoint},
+    hittable_collections::light_tree::LightBounds,
     material::DynMaterial,
+    pdf::{Pdf as _, SpherePdf},
     ray::Ray,
 };
 
@@ -52,6 +54,12 @@ impl Sphere {
             point.y.acos().div(PI),
         )
     }
+
+    fn record(&self, r: &Ray, t: f64) -> HitRecord<'_> {
+        let outward_normal = (r.at(t) - self.center) / self.radius;
+        let (u, v) = Sphere::get_sphere_uv(outward_normal.to_point());
+        HitRecord::new(r, t, outward_normal, u, v, self.mat_ptr.as_ref())
+    }
 }
 
 #[cfg(feature = "hit_counters")]
@@ -79,23 +87,13 @@ impl Hittable for Sphere {
             }
         };
 
-        let p = r.at(t);
-        let outward_normal = (p - self.center) / self.radius;
-        let (u, v) = Sphere::get_sphere_uv(outward_normal.to_point());
         // dbg!("Sphere hit!", self, r, t);
 
         #[cfg(feature = "hit_counters")]
         {
             SPHERE_HIT_COUNTER.fetch_add(1, atomic::Ordering::Relaxed);
         }
-        Some(HitRecord::new(
-            r,
-            t,
-            outward_normal,
-            u,
-            v,
-            self.mat_ptr.as_ref(),
-        ))
+        Some(self.record(r, t))
     }
 
     fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
@@ -125,6 +123,54 @@ impl Hittable for Sphere {
         let y = phi.sin() * (1. - z * z).sqrt();
         uvw.transform(Vec3::new(x, y, z))
     }
+
+    fn sample_point(&self, rng: &mut dyn rand::RngCore) -> Option<SurfacePoint<'_>> {
+        let normal = SpherePdf.generate(rng);
+        let (u, v) = Sphere::get_sphere_uv(normal.to_point());
+        Some(SurfacePoint {
+            p: self.center + normal * self.radius,
+            normal,
+            u,
+            v,
+            material: self.mat_ptr.as_ref(),
+        })
+    }
+
+    fn emissive_objects<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+        if self.mat_ptr.as_ref().is_emissive() {
+            lights.push(self);
+        }
+    }
+
+    fn light_power(&self) -> Option<f64> {
+        let material = self.mat_ptr.as_ref();
+        material
+            .is_emissive()
+            .then(|| material.emitted_power() * self.get_surface_area())
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds::omnidirectional(
+            self.aabox,
+            self.light_power()?,
+        ))
+    }
+
+    fn intervals(&self, r: &Ray) -> Vec<HitInterval<'_>> {
+        let oc = r.get_origin() - self.center;
+        let a = r.get_direction().square_length();
+        let half_b = r.get_direction().dot(oc);
+        let c = oc.square_length() - self.radius * self.radius;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant <= 0. {
+            return Vec::new();
+        }
+        let sqrt_discriminant = discriminant.sqrt();
+        vec![HitInterval {
+            enter: self.record(r, (-half_b - sqrt_discriminant) / a),
+            exit: self.record(r, (-half_b + sqrt_discriminant) / a),
+        }]
+    }
 }
 
 impl Bounded for Sphere {
@@ -0,0 +1,177 @@
+use std::{
+    f64::consts::{PI, TAU},
+    fmt::Debug,
+    ops::RangeInclusive,
+};
+
+use rand::{Rng as _, distributions::Standard};
+
+use geometry::{
+    aabox::AABBox,
+    bounded::Bounded,
+    vec3::{Point3, Vec3},
+};
+
+use crate::{
+    hittable::{BoundedHittable, HitRecord, Hittable, SurfacePoint},
+    hittable_collections::light_tree::LightBounds,
+    material::DynMaterial,
+    ray::Ray,
+    utils::polynomial::{roots_in, solve_quadratic},
+};
+
+use super::frame::{Frame, LocalHit, LocalPoint, area_pdf_value, turn_fraction};
+
+/// Ring around `axis` whose tube of `minor_radius` follows a circle of `major_radius`, `u` goes
+/// around the axis and `v` around the tube.
+#[derive(Debug, Clone)]
+pub struct Torus {
+    frame: Frame,
+    major_radius: f64,
+    minor_radius: f64,
+    mat_ptr: DynMaterial,
+    aabox: AABBox,
+}
+
+impl Torus {
+    pub fn new<T>(
+        center: Point3,
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        mat_ptr: T,
+    ) -> Self
+    where
+        T: TryInto<DynMaterial>,
+        <T as TryInto<DynMaterial>>::Error: Debug,
+    {
+        let frame = Frame::new(center, axis);
+        let extent =
+            frame.circle_extent(major_radius) + Vec3::new(minor_radius, minor_radius, minor_radius);
+        Self {
+            frame,
+            major_radius,
+            minor_radius,
+            mat_ptr: mat_ptr.try_into().unwrap(),
+            aabox: AABBox::from_points([center - extent, center + extent]),
+        }
+    }
+
+    fn local_hits(&self, origin: Vec3, direction: Vec3, range: &RangeInclusive<f64>) -> Vec<f64> {
+        let (big, small) = (self.major_radius, self.minor_radius);
+        let length = direction.length();
+        let direction = direction / length;
+        // Only the part of the ray inside the bounding sphere is searched, starting from
+        // there keeps the coefficients small
+        let Some([enter, exit]) = solve_quadratic(
+            1.,
+            2. * origin.dot(direction),
+            origin.square_length() - (big + small).powi(2),
+        ) else {
+            return Vec::new();
+        };
+        let start = enter.max(range.start() * length);
+        let end = exit.min(range.end() * length);
+        if start > end {
+            return Vec::new();
+        }
+        let o = origin + direction * start;
+        let (d, big2) = (direction, big * big);
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) with p = o + s d and |d| = 1
+        let g = o.square_length() + big2 - small * small;
+        let h = o.dot(d);
+        let coefficients = [
+            g * g - 4. * big2 * (o.x * o.x + o.y * o.y),
+            4. * h * g - 8. * big2 * (o.x * d.x + o.y * d.y),
+            4. * h * h + 2. * g - 4. * big2 * (d.x * d.x + d.y * d.y),
+            4. * h,
+            1.,
+        ];
+        roots_in(&coefficients, 0. ..=end - start)
+            .into_iter()
+            .map(|s| (start + s) / length)
+            .collect()
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: &Ray, range: RangeInclusive<f64>) -> Option<HitRecord<'_>> {
+        let (origin, direction) = self.frame.local_ray(r);
+        let hits = self
+            .local_hits(origin, direction, &range)
+            .into_iter()
+            .map(|t| {
+                let point = origin + direction * t;
+                let ring = Vec3::new(point.x, point.y, 0.).normalize() * self.major_radius;
+                let normal = (point - ring) / self.minor_radius;
+                let radial = point.x.hypot(point.y) - self.major_radius;
+                LocalHit {
+                    t,
+                    normal,
+                    u: turn_fraction(point),
+                    v: (point.z.atan2(radial) / TAU).rem_euclid(1.),
+                }
+            });
+        self.frame
+            .closest_hit(r, &range, hits, self.mat_ptr.as_ref())
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        area_pdf_value(self, self.get_surface_area(), origin, direction)
+    }
+
+    fn sample_point(&self, rng: &mut dyn rand::RngCore) -> Option<SurfacePoint<'_>> {
+        let (big, small) = (self.major_radius, self.minor_radius);
+        // The outside of the tube is larger than the inside, the angle around it is picked
+        // by rejection
+        let theta = loop {
+            let theta = TAU * rng.sample::<f64, _>(Standard);
+            if rng.sample::<f64, _>(Standard) * (big + small) <= big + small * theta.cos() {
+                break theta;
+            }
+        };
+        let phi = TAU * rng.sample::<f64, _>(Standard);
+        let (sin, cos) = phi.sin_cos();
+        let distance = big + small * theta.cos();
+        let point = Vec3::new(distance * cos, distance * sin, small * theta.sin());
+        let local = LocalPoint {
+            point,
+            normal: Vec3::new(theta.cos() * cos, theta.cos() * sin, theta.sin()),
+            u: turn_fraction(point),
+            v: theta / TAU,
+        };
+        Some(self.frame.surface_point(local, self.mat_ptr.as_ref()))
+    }
+
+    fn emissive_objects<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+        if self.mat_ptr.as_ref().is_emissive() {
+            lights.push(self);
+        }
+    }
+
+    fn light_power(&self) -> Option<f64> {
+        let material = self.mat_ptr.as_ref();
+        material
+            .is_emissive()
+            .then(|| material.emitted_power() * self.get_surface_area())
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds::omnidirectional(
+            self.aabox,
+            self.light_power()?,
+        ))
+    }
+}
+
+impl Bounded for Torus {
+    fn get_aabbox(&self) -> AABBox {
+        self.aabox
+    }
+
+    fn get_surface_area(&self) -> f64 {
+        4. * PI * PI * self.major_radius * self.minor_radius
+    }
+}
+
+impl BoundedHittable for Torus {}
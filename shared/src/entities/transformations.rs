@@ -1,32 +1,236 @@
 use std::ops::RangeInclusive;
 
-use geometry::transformations::Transformed;
+use geometry::{
+    transformations::Transformed,
+    vec3::{Point3, Vec3},
+};
 
 use crate::{
-    hittable::{BoundedHittable, HitRecord, Hittable},
+    hittable::{BoundedHittable, HitInterval, HitRecord, Hittable, SurfacePoint},
+    hittable_collections::light_tree::LightBounds,
     ray::Ray,
 };
 
+/// `r` in the space of the instance, `None` if the transformation can't be undone.
+fn local_ray<T>(transformed: &Transformed<T>, r: &Ray) -> Option<Ray> {
+    let inverse = transformed.get_inverse()?;
+    let origin = inverse.transform_point3d(r.get_origin())?;
+    let direction = inverse.transform_vector3d(r.get_direction());
+    Some(Ray::new(origin, direction))
+}
+
+/// `rec` of the instance hit by the local ray of `r` moved back to world space.
+fn world_record<'a, T>(
+    transformed: &Transformed<T>,
+    r: &Ray,
+    mut rec: HitRecord<'a>,
+) -> HitRecord<'a> {
+    *rec.get_mut_p() = r.at(rec.get_t());
+    // The normal already faces the ray, which stays true once both are transformed
+    *rec.get_mut_normal() = transformed.transform_normal(rec.get_normal());
+    rec
+}
+
 impl<T> Hittable for Transformed<T>
 where
     T: Hittable,
 {
     fn hit(&self, r: &Ray, range: RangeInclusive<f64>) -> Option<HitRecord<'_>> {
         // For simplicity if there's no inverse just say it's not hit.
-        let inv = self.get_transformation().inverse()?;
-        let origin = inv.transform_point3d(r.get_origin())?;
-        let direction = inv.transform_vector3d(r.get_direction());
-        let offsetted_ray = Ray::new(origin, direction);
+        let local_ray = local_ray(self, r)?;
+        // The direction isn't normalized, so `t` is the same in both spaces
+        self.get_instance()
+            .hit(&local_ray, range)
+            .map(|rec| world_record(self, r, rec))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let Some(local_ray) = local_ray(self, &Ray::new(origin, direction.normalize())) else {
+            return 0.;
+        };
+        let local_direction = local_ray.get_direction();
+        let stretch = local_direction.length();
+        // Change of solid angle of the inverse, whose determinant is the one of the normal
+        // matrix
+        let jacobian = self.get_normal_matrix().det().abs() / stretch.powi(3);
+        self.get_instance()
+            .pdf_value(local_ray.get_origin(), local_direction / stretch)
+            * jacobian
+    }
+
+    fn random(&self, origin: Point3, rng: &mut dyn rand::RngCore) -> Vec3 {
+        let Some(local_origin) = self
+            .get_inverse()
+            .and_then(|inverse| inverse.transform_point3d(origin))
+        else {
+            return Vec3::new(1., 0., 0.);
+        };
+        // The vector from the origin to the sampled point
+        self.get_transformation()
+            .transform_vector3d(self.get_instance().random(local_origin, rng))
+    }
+
+    fn sample_point(&self, rng: &mut dyn rand::RngCore) -> Option<SurfacePoint<'_>> {
+        // Stretching some directions more than others makes uniform points stop being uniform
+        self.is_conformal().then_some(())?;
+        let local = self.get_instance().sample_point(rng)?;
+        Some(SurfacePoint {
+            p: self.get_transformation().transform_point3d(local.p)?,
+            normal: self.transform_normal(local.normal),
+            ..local
+        })
+    }
+
+    fn sample_surface(&self, rng: &mut dyn rand::RngCore) -> Option<HitRecord<'_>> {
+        self.is_conformal().then_some(())?;
+        let mut rec = self.get_instance().sample_surface(rng)?;
+        *rec.get_mut_p() = self.get_transformation().transform_point3d(rec.get_p())?;
+        *rec.get_mut_normal() = self.transform_normal(rec.get_normal());
+        Some(rec)
+    }
+
+    fn emissive_objects<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+        // The emitters of the instance are in its own space, so it's sampled as a whole
+        let mut emissive = Vec::new();
+        self.get_instance().emissive_objects(&mut emissive);
+        if !emissive.is_empty() {
+            lights.push(self);
+        }
+    }
+
+    /// Only known if the transformation is conformal, otherwise the area isn't scaled evenly.
+    fn light_power(&self) -> Option<f64> {
+        self.is_conformal().then_some(())?;
+        Some(self.get_instance().light_power()? * self.area_scale())
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        let bounds = self.get_instance().light_bounds()?;
+        Some(bounds.moved(
+            self.transform_aabbox(bounds.get_aabbox()),
+            self.light_power()?,
+            |axis| self.transform_normal(axis),
+        ))
+    }
+
+    fn hit_cost(&self, r: &Ray, range: RangeInclusive<f64>) -> u32 {
+        local_ray(self, r).map_or(0, |local_ray| {
+            self.get_instance().hit_cost(&local_ray, range)
+        })
+    }
+
+    fn intervals(&self, r: &Ray) -> Vec<HitInterval<'_>> {
+        let Some(local_ray) = local_ray(self, r) else {
+            return Vec::new();
+        };
         self.get_instance()
-            .hit(&offsetted_ray, range)
-            .map(|mut rec| {
-                *rec.get_mut_p() = self
-                    .get_transformation()
-                    .transform_point3d(rec.get_p())
-                    .unwrap();
-                rec
+            .intervals(&local_ray)
+            .into_iter()
+            .map(|HitInterval { enter, exit }| HitInterval {
+                enter: world_record(self, r, enter),
+                exit: world_record(self, r, exit),
             })
+            .collect()
     }
 }
 
 impl<T> BoundedHittable for Transformed<T> where T: BoundedHittable {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rand::{SeedableRng as _, rngs::SmallRng};
+
+    use geometry::{
+        aaplane::Axis,
+        matrix3::Matrix3,
+        transformations::{Instance, Transformable as _, Transformation, rotation},
+        vec3::{Point3, Vec3},
+    };
+
+    use crate::{
+        colour::Colour,
+        entities::{Quad, Sphere},
+        hittable::Hittable,
+        material::{DiffuseLight, Lambertian},
+        ray::Ray,
+    };
+
+    fn stretch() -> Transformation {
+        Transformation::from(Matrix3::from([[2., 0., 0.], [0., 1., 0.], [0., 0., 0.5]]))
+            .then(&rotation(30., Axis::Y))
+            .then(&Vec3::new(1., 2., 3.).into())
+    }
+
+    #[test]
+    fn normals_are_transformed() {
+        let material = Arc::new(Lambertian::new_with_colour(Colour::new(0.5, 0.5, 0.5)));
+        let sphere = Sphere::new(Point3::default(), 1., material).transform(stretch());
+        let ray = Ray::new(Point3::new(1., 2., -10.), Vec3::new(0., 0., 1.));
+        let record = sphere.hit(&ray, 0.001..=f64::INFINITY).unwrap();
+        assert!(record.is_front_face());
+        assert!((record.get_normal().length() - 1.).abs() < 1e-9);
+        // The normal is the gradient of the implicit surface at the hit point
+        let inverse = sphere.get_inverse().unwrap();
+        let local = inverse.transform_point3d(record.get_p()).unwrap();
+        assert!((local.length() - 1.).abs() < 1e-9);
+        let expected = sphere.transform_normal(local);
+        assert!((record.get_normal() - expected).length() < 1e-9);
+    }
+
+    #[test]
+    fn transformed_lights_are_sampled() {
+        let light = Arc::new(DiffuseLight::new_with_colour(Colour::new(1., 1., 1.)));
+        let (q, u, v) = (
+            Point3::new(-0.5, 0., -0.5),
+            Vec3::new(1., 0., 0.),
+            Vec3::new(0., 0., 1.),
+        );
+        let prototype = Arc::new(Quad::new(q, u, v, light.clone()));
+        let instance = Instance::instance(&prototype, stretch());
+        let transformation = stretch();
+        // Scaled evenly so the power is known
+        let uniform =
+            Transformation::from(Matrix3::from([[2., 0., 0.], [0., 2., 0.], [0., 0., 2.]]))
+                .then(&rotation(30., Axis::Y));
+        let uniform_instance = Instance::instance(&prototype, uniform);
+        let uniform_quad = Quad::new(
+            uniform.transform_point3d(q).unwrap(),
+            uniform.transform_vector3d(u),
+            uniform.transform_vector3d(v),
+            light.clone(),
+        );
+        let world_quad = Quad::new(
+            transformation.transform_point3d(q).unwrap(),
+            transformation.transform_vector3d(u),
+            transformation.transform_vector3d(v),
+            light,
+        );
+
+        let mut lights = Vec::new();
+        instance.emissive_objects(&mut lights);
+        assert_eq!(lights.len(), 1);
+        let power = uniform_instance.light_power().unwrap();
+        assert!((power - uniform_quad.light_power().unwrap()).abs() < 1e-9);
+        let mut rng = SmallRng::seed_from_u64(5);
+        assert!(uniform_instance.sample_surface(&mut rng).is_some());
+        // The stretch changes areas differently depending on the orientation
+        assert!(instance.light_power().is_none());
+        assert!(instance.light_bounds().is_none());
+        assert!(instance.sample_surface(&mut rng).is_none());
+
+        // Both sample the same quad uniformly by area
+        let origin = Point3::new(0., 0., 0.);
+        for _ in 0..16 {
+            let direction = instance.random(origin, &mut rng);
+            let expected = world_quad.pdf_value(origin, direction);
+            assert!(expected > 0.);
+            let pdf = instance.pdf_value(origin, direction);
+            assert!(
+                (pdf - expected).abs() < 1e-9 * expected,
+                "{pdf} should be {expected}"
+            );
+        }
+    }
+}
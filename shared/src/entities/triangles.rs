@@ -12,7 +12,8 @@ use geometry::{
 use rand::Rng as _;
 use rand::distributions::Open01;
 
-use crate::hittable::{BoundedHittable, HitRecord, Hittable};
+use crate::hittable::{BoundedHittable, HitRecord, Hittable, SurfacePoint};
+use crate::hittable_collections::light_tree::LightBounds;
 use crate::material::DynMaterial;
 use crate::ray::Ray;
 
@@ -105,15 +106,42 @@ impl Hittable for Triangle {
         }
     }
 
-    fn random(&self, origin: Point3, rng: &mut dyn rand::RngCore) -> Vec3 {
+    fn sample_point(&self, rng: &mut dyn rand::RngCore) -> Option<SurfacePoint<'_>> {
         let mut r1 = rng.sample::<f64, _>(Open01);
         let mut r2 = rng.sample::<f64, _>(Open01);
         if r1 + r2 > 1. {
             r1 = 1. - r1;
             r2 = 1. - r2;
         }
-        let p = self.q + self.u * r1 + self.v * r2;
-        p - origin
+        Some(SurfacePoint {
+            p: self.q + self.u * r1 + self.v * r2,
+            normal: self.normal,
+            u: r1,
+            v: r2,
+            material: self.mat_ptr.as_ref(),
+        })
+    }
+
+    fn emissive_objects<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+        if self.mat_ptr.as_ref().is_emissive() {
+            lights.push(self);
+        }
+    }
+
+    fn light_power(&self) -> Option<f64> {
+        let material = self.mat_ptr.as_ref();
+        material
+            .is_emissive()
+            .then(|| material.emitted_power() * self.area)
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds::planar(
+            self.aabbox,
+            self.light_power()?,
+            self.normal,
+            self.mat_ptr.as_ref().is_two_sided(),
+        ))
     }
 }
 
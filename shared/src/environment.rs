@@ -0,0 +1,266 @@
+mod hdr;
+mod sky;
+pub use sky::{PhysicalSky, Sun, SunLight};
+
+use std::{
+    f64::consts::{PI, TAU},
+    fmt::Debug,
+    fs::File,
+    io::{self, BufReader},
+    ops::RangeInclusive,
+    path::Path,
+    sync::Arc,
+};
+
+use rand::{Rng as _, distributions::Standard};
+
+use geometry::{
+    aabox::AABBox,
+    bounded::Bounded,
+    vec3::{Point3, Vec3},
+};
+
+use crate::{
+    colour::{Colour, ColourSpace},
+    hittable::{BoundedHittable, HitRecord, Hittable},
+    ray::Ray,
+    utils::distribution::Distribution2D,
+};
+
+/// Radiance arriving from infinitely far away, looked up by direction.
+pub trait Environment: Sync + Send + Debug {
+    fn get_colour(&self, direction: Vec3) -> Colour;
+
+    /// Size of the latitude-longitude grid used by [`EnvironmentLight`] to importance sample
+    /// this environment.
+    fn sampling_resolution(&self) -> (usize, usize) {
+        (64, 32)
+    }
+}
+
+impl Environment for Colour {
+    fn get_colour(&self, _direction: Vec3) -> Colour {
+        *self
+    }
+
+    fn sampling_resolution(&self) -> (usize, usize) {
+        (1, 1)
+    }
+}
+
+/// What a ray which doesn't hit anything sees.
+#[derive(Debug, Clone)]
+pub enum Background {
+    Colour(Colour),
+    Environment(Arc<dyn Environment>),
+}
+
+impl Background {
+    pub fn get_colour(&self, direction: Vec3) -> Colour {
+        match self {
+            Background::Colour(colour) => *colour,
+            Background::Environment(environment) => environment.get_colour(direction),
+        }
+    }
+}
+
+/// Maps a direction to latitude-longitude coordinates in `[0, 1]`, `v = 0` is straight up
+/// and `u = 0.5` looks towards `-z`.
+fn direction_to_uv(direction: Vec3) -> (f64, f64) {
+    let direction = direction.normalize();
+    let u = 0.5 + libm::atan2(direction.x, -direction.z) / TAU;
+    let v = direction.y.clamp(-1., 1.).acos() / PI;
+    (u, v)
+}
+
+fn uv_to_direction(u: f64, v: f64) -> Vec3 {
+    let phi = (u - 0.5) * TAU;
+    let theta = v * PI;
+    Vec3::new(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        -theta.sin() * phi.cos(),
+    )
+}
+
+/// Vertical gradient between the horizon and the zenith, the ground mirrors the sky.
+#[derive(Debug, Clone, Copy)]
+pub struct SkyGradient {
+    pub horizon: Colour,
+    pub zenith: Colour,
+}
+
+impl SkyGradient {
+    pub const fn new(horizon: Colour, zenith: Colour) -> Self {
+        Self { horizon, zenith }
+    }
+}
+
+impl Default for SkyGradient {
+    fn default() -> Self {
+        Self::new(Colour::new(1., 1., 1.), Colour::new(0.5, 0.7, 1.))
+    }
+}
+
+impl Environment for SkyGradient {
+    fn get_colour(&self, direction: Vec3) -> Colour {
+        let a = direction.normalize().y.abs();
+        self.horizon * (1. - a) + self.zenith * a
+    }
+}
+
+/// Equirectangular image of the environment, the top row is straight up and the center of the
+/// image looks towards `-z`.
+#[derive(Debug, Clone)]
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    pixels: Vec<Colour>,
+    /// Around the y axis, in radians
+    rotation: f64,
+    intensity: f64,
+}
+
+impl EnvironmentMap {
+    /// `pixels` is in row major order, starting from the top left corner.
+    pub fn new(width: usize, height: usize, pixels: Vec<Colour>) -> Option<Self> {
+        (width > 0 && height > 0 && width * height == pixels.len()).then_some(Self {
+            width,
+            height,
+            pixels,
+            rotation: 0.,
+            intensity: 1.,
+        })
+    }
+
+    /// Loads a Radiance `.hdr` file.
+    pub fn from_hdr(path: impl AsRef<Path>) -> io::Result<Self> {
+        let (width, height, pixels) = hdr::read_hdr(BufReader::new(File::open(path)?))?;
+        Self::new(width, height, pixels)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Empty image"))
+    }
+
+    /// Rotation around the y axis in degrees.
+    pub fn with_rotation(self, rotation: f64) -> Self {
+        Self {
+            rotation: rotation.to_radians(),
+            ..self
+        }
+    }
+
+    pub fn with_intensity(self, intensity: f64) -> Self {
+        Self { intensity, ..self }
+    }
+
+    /// The pixels are in `space` instead of linear sRGB.
+    pub fn with_colour_space(self, space: ColourSpace) -> Self {
+        let conversion = space.conversion(ColourSpace::Srgb);
+        Self {
+            pixels: self
+                .pixels
+                .into_iter()
+                .map(|pixel| Colour::from_vec3(conversion * pixel.into_inner()))
+                .collect(),
+            ..self
+        }
+    }
+}
+
+impl Environment for EnvironmentMap {
+    fn get_colour(&self, direction: Vec3) -> Colour {
+        let (sin, cos) = self.rotation.sin_cos();
+        let direction = Vec3::new(
+            cos * direction.x - sin * direction.z,
+            direction.y,
+            sin * direction.x + cos * direction.z,
+        );
+        let (u, v) = direction_to_uv(direction);
+        let i = ((u * self.width as f64) as usize).min(self.width - 1);
+        let j = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[j * self.width + i] * self.intensity
+    }
+
+    fn sampling_resolution(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+}
+
+/// Light wrapping an [`Environment`] so it can be added to the lights, it's never hit and it
+/// samples directions proportionally to the brightness of the environment.
+///
+/// The camera should use the same environment as its background.
+#[derive(Debug)]
+pub struct EnvironmentLight {
+    environment: Arc<dyn Environment>,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentLight {
+    pub fn new(environment: Arc<dyn Environment>) -> Self {
+        let (width, height) = environment.sampling_resolution();
+        let (width, height) = (width.max(1), height.max(1));
+        let weights: Vec<f64> = (0..height)
+            .flat_map(|j| (0..width).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                let u = (i as f64 + 0.5) / width as f64;
+                let v = (j as f64 + 0.5) / height as f64;
+                // Rows near the poles cover less solid angle
+                environment.get_colour(uv_to_direction(u, v)).luminance() * (v * PI).sin()
+            })
+            .collect();
+        Self {
+            environment,
+            distribution: Distribution2D::new(&weights, width),
+        }
+    }
+
+    pub fn get_environment(&self) -> &dyn Environment {
+        self.environment.as_ref()
+    }
+}
+
+impl Hittable for EnvironmentLight {
+    fn hit(&self, _r: &Ray, _range: RangeInclusive<f64>) -> Option<HitRecord<'_>> {
+        None
+    }
+
+    fn pdf_value(&self, _origin: Point3, direction: Vec3) -> f64 {
+        let (u, v) = direction_to_uv(direction);
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0. {
+            return 0.;
+        }
+        self.distribution.pdf([u, v]) / (2. * PI * PI * sin_theta)
+    }
+
+    fn random(&self, _origin: Point3, rng: &mut dyn rand::RngCore) -> Vec3 {
+        let ([u, v], _) = self
+            .distribution
+            .sample([rng.sample(Standard), rng.sample(Standard)]);
+        uv_to_direction(u, v)
+    }
+}
+
+/// Lights at infinity aren't bounded, they are never hit so they don't affect intersections.
+fn infinite_aabbox() -> AABBox {
+    AABBox::new(
+        Point3::new(-f64::INFINITY, -f64::INFINITY, -f64::INFINITY),
+        Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+    )
+}
+
+impl Bounded for EnvironmentLight {
+    fn get_aabbox(&self) -> AABBox {
+        infinite_aabbox()
+    }
+
+    fn get_surface_area(&self) -> f64 {
+        f64::INFINITY
+    }
+}
+
+impl BoundedHittable for EnvironmentLight {
+    fn is_aabbox_hit(&self, _r: &Ray, _range: RangeInclusive<f64>) -> bool {
+        false
+    }
+}
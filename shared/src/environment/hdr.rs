@@ -0,0 +1,140 @@
+use std::io::{self, BufRead, Read};
+
+use crate::colour::Colour;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads a Radiance RGBE (`.hdr`) image, returning its width, height and pixels in row major
+/// order starting from the top left corner.
+///
+/// Only the standard `-Y height +X width` orientation is supported, with either flat or new
+/// style run length encoded scanlines.
+pub(crate) fn read_hdr(mut reader: impl BufRead) -> io::Result<(usize, usize, Vec<Colour>)> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if !line.starts_with("#?") {
+        return Err(invalid_data("Missing Radiance header"));
+    }
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid_data("Unexpected end of header"));
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=")
+            && format != "32-bit_rle_rgbe"
+        {
+            return Err(invalid_data("Only 32-bit_rle_rgbe is supported"));
+        }
+    }
+
+    line.clear();
+    reader.read_line(&mut line)?;
+    let (height, width) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", height, "+X", width] => (
+            height.parse().map_err(|_| invalid_data("Invalid height"))?,
+            width.parse().map_err(|_| invalid_data("Invalid width"))?,
+        ),
+        _ => return Err(invalid_data("Unsupported resolution string")),
+    };
+
+    let mut pixels = Vec::with_capacity(width * height);
+    let mut scanline = vec![[0u8; 4]; width];
+    for _ in 0..height {
+        read_scanline(&mut reader, &mut scanline)?;
+        pixels.extend(scanline.iter().map(|&rgbe| rgbe_to_colour(rgbe)));
+    }
+    Ok((width, height, pixels))
+}
+
+fn read_scanline(reader: &mut impl Read, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let width = scanline.len();
+    if width == 0 {
+        return Ok(());
+    }
+    let mut first = [0u8; 4];
+    reader.read_exact(&mut first)?;
+    let is_rle = (8..0x8000).contains(&width) && first[0] == 2 && first[1] == 2 && first[2] < 128;
+    if !is_rle {
+        scanline[0] = first;
+        for pixel in &mut scanline[1..] {
+            reader.read_exact(pixel)?;
+        }
+        return Ok(());
+    }
+    if usize::from(first[2]) << 8 | usize::from(first[3]) != width {
+        return Err(invalid_data("Scanline width mismatch"));
+    }
+    for channel in 0..4 {
+        let mut i = 0;
+        while i < width {
+            let mut byte = [0u8; 1];
+            reader.read_exact(&mut byte)?;
+            let (run, count) = if byte[0] > 128 {
+                (true, usize::from(byte[0] - 128))
+            } else {
+                (false, usize::from(byte[0]))
+            };
+            if count == 0 || i + count > width {
+                return Err(invalid_data("Invalid run length"));
+            }
+            if run {
+                reader.read_exact(&mut byte)?;
+                scanline[i..i + count]
+                    .iter_mut()
+                    .for_each(|pixel| pixel[channel] = byte[0]);
+            } else {
+                for pixel in &mut scanline[i..i + count] {
+                    reader.read_exact(&mut byte)?;
+                    pixel[channel] = byte[0];
+                }
+            }
+            i += count;
+        }
+    }
+    Ok(())
+}
+
+fn rgbe_to_colour([r, g, b, e]: [u8; 4]) -> Colour {
+    if e == 0 {
+        return Colour::default();
+    }
+    let f = (f64::from(e) - 136.).exp2();
+    Colour::new(
+        (f64::from(r) + 0.5) * f,
+        (f64::from(g) + 0.5) * f,
+        (f64::from(b) + 0.5) * f,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_hdr;
+
+    #[test]
+    fn flat_and_rle_scanlines() {
+        let width = 8;
+        let mut data = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 8\n".to_vec();
+        // Flat scanline
+        (0..width).for_each(|_| data.extend([128, 64, 0, 129]));
+        // Run length encoded scanline, every channel is a single run
+        data.extend([2, 2, 0, width]);
+        [128, 64, 0, 129]
+            .into_iter()
+            .for_each(|v| data.extend([128 + width, v]));
+
+        let (w, h, pixels) = read_hdr(data.as_slice()).unwrap();
+        assert_eq!((w, h, pixels.len()), (8, 2, 16));
+        pixels.iter().for_each(|pixel| {
+            let pixel = pixel.into_inner();
+            assert!((pixel.x - 1.00390625).abs() < 1e-12);
+            assert!((pixel.y - 0.50390625).abs() < 1e-12);
+            assert!((pixel.z - 0.00390625).abs() < 1e-12);
+        });
+    }
+}
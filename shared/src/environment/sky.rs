@@ -0,0 +1,324 @@
+use std::{
+    f64::consts::{FRAC_PI_2, PI, TAU},
+    ops::RangeInclusive,
+};
+
+use rand::{Rng as _, distributions::Standard};
+
+use geometry::{
+    aabox::AABBox,
+    bounded::Bounded,
+    onb::Onb,
+    vec3::{Point3, Vec3},
+};
+
+use crate::{
+    colour::Colour,
+    environment::{Environment, infinite_aabbox},
+    hittable::{BoundedHittable, HitRecord, Hittable},
+    ray::Ray,
+};
+
+/// Position and size of the sun, the azimuth is measured from north (`-z`) towards east (`+x`).
+#[derive(Debug, Clone, Copy)]
+pub struct Sun {
+    /// In radians
+    elevation: f64,
+    /// In radians
+    azimuth: f64,
+    /// In radians
+    angular_radius: f64,
+    /// Radiance of the sun before going through the atmosphere, relative to the zenith of the
+    /// sky
+    intensity: f64,
+}
+
+impl Sun {
+    /// `elevation` and `azimuth` are in degrees.
+    pub fn new(elevation: f64, azimuth: f64) -> Self {
+        Self {
+            elevation: elevation.to_radians(),
+            azimuth: azimuth.to_radians(),
+            angular_radius: 0.2665_f64.to_radians(),
+            intensity: 1e5,
+        }
+    }
+
+    /// Approximate position of the sun, `day` is the day of the year starting from 1, `hour`
+    /// is the local solar time (12 is noon) and `latitude` is in degrees.
+    pub fn from_date_time(day: u16, hour: f64, latitude: f64) -> Self {
+        let latitude = latitude.to_radians();
+        let declination = -23.44_f64.to_radians() * (TAU / 365. * (f64::from(day) + 10.)).cos();
+        let hour_angle = (15. * (hour - 12.)).to_radians();
+        let sin_elevation = latitude.sin() * declination.sin()
+            + latitude.cos() * declination.cos() * hour_angle.cos();
+        let elevation = sin_elevation.clamp(-1., 1.).asin();
+        let cos_azimuth = (declination.sin() - sin_elevation * latitude.sin())
+            / (elevation.cos() * latitude.cos());
+        let azimuth = cos_azimuth.clamp(-1., 1.).acos();
+        // In the afternoon the sun is west of the meridian
+        let azimuth = if hour_angle > 0. {
+            TAU - azimuth
+        } else {
+            azimuth
+        };
+        Self::new(elevation.to_degrees(), azimuth.to_degrees())
+    }
+
+    /// Angular radius in degrees.
+    pub fn with_angular_radius(self, angular_radius: f64) -> Self {
+        Self {
+            angular_radius: angular_radius.to_radians(),
+            ..self
+        }
+    }
+
+    pub const fn with_intensity(self, intensity: f64) -> Self {
+        Self { intensity, ..self }
+    }
+
+    pub fn get_direction(&self) -> Vec3 {
+        let (sin_elevation, cos_elevation) = self.elevation.sin_cos();
+        let (sin_azimuth, cos_azimuth) = self.azimuth.sin_cos();
+        Vec3::new(
+            cos_elevation * sin_azimuth,
+            sin_elevation,
+            -cos_elevation * cos_azimuth,
+        )
+    }
+
+    fn cos_angular_radius(&self) -> f64 {
+        self.angular_radius.cos()
+    }
+
+    /// Rough spectral attenuation through the atmosphere, using the Kasten-Young air mass.
+    fn transmittance(&self, turbidity: f64) -> Colour {
+        let elevation = self.elevation.to_degrees().max(0.);
+        let air_mass =
+            (self.elevation.max(0.).sin() + 0.50572 * (elevation + 6.07995).powf(-1.6364)).recip();
+        let rayleigh = [0.0596, 0.1324, 0.3310];
+        let mie = 0.01 * turbidity;
+        Colour::from_array(rayleigh.map(|beta: f64| (-(beta + mie) * air_mass).exp()))
+    }
+}
+
+/// Perez distribution coefficients for one of the components of the sky.
+#[derive(Debug, Clone, Copy)]
+struct Perez([f64; 5]);
+
+impl Perez {
+    fn new(turbidity: f64, coefficients: [[f64; 2]; 5]) -> Self {
+        Self(coefficients.map(|[a, b]| a * turbidity + b))
+    }
+
+    fn evaluate(self, cos_theta: f64, gamma: f64) -> f64 {
+        let [a, b, c, d, e] = self.0;
+        (1. + a * (b / cos_theta).exp()) * (1. + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+    }
+}
+
+/// Preetham analytic daylight model with a visible sun disk, see "A Practical Analytic Model
+/// for Daylight" by Preetham, Shirley and Smits.
+///
+/// The sky is normalized so the zenith has a luminance of `intensity`. Add a [`SunLight`] to the
+/// lights so the sun is sampled directly.
+#[derive(Debug, Clone, Copy)]
+pub struct PhysicalSky {
+    sun: Sun,
+    sun_radiance: Colour,
+    turbidity: f64,
+    intensity: f64,
+    ground: Colour,
+    /// Perez coefficients for `Y`, `x` and `y`
+    perez: [Perez; 3],
+    /// `Y`, `x` and `y` at the zenith divided by the Perez function at the zenith
+    zenith: [f64; 3],
+}
+
+impl PhysicalSky {
+    /// `turbidity` goes from 2 for a very clear sky to around 10 for a hazy one.
+    pub fn new(sun: Sun, turbidity: f64) -> Self {
+        let t = turbidity;
+        let theta_s = (FRAC_PI_2 - sun.elevation).clamp(0., FRAC_PI_2 - 1e-3);
+        let (theta_s2, theta_s3) = (theta_s * theta_s, theta_s * theta_s * theta_s);
+        let chromaticity = |c: [[f64; 4]; 3]| {
+            [t * t, t, 1.]
+                .iter()
+                .zip(c)
+                .map(|(t, [a, b, c, d])| t * (a * theta_s3 + b * theta_s2 + c * theta_s + d))
+                .sum::<f64>()
+        };
+        let x_zenith = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let y_zenith = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        let perez = [
+            Perez::new(
+                t,
+                [
+                    [0.1787, -1.4630],
+                    [-0.3554, 0.4275],
+                    [-0.0227, 5.3251],
+                    [0.1206, -2.5771],
+                    [-0.0670, 0.3703],
+                ],
+            ),
+            Perez::new(
+                t,
+                [
+                    [-0.0193, -0.2592],
+                    [-0.0665, 0.0008],
+                    [-0.0004, 0.2125],
+                    [-0.0641, -0.8989],
+                    [-0.0033, 0.0452],
+                ],
+            ),
+            Perez::new(
+                t,
+                [
+                    [-0.0167, -0.2608],
+                    [-0.0950, 0.0092],
+                    [-0.0079, 0.2102],
+                    [-0.0441, -1.6537],
+                    [-0.0109, 0.0529],
+                ],
+            ),
+        ];
+        let zenith = [1., x_zenith, y_zenith];
+        let zenith = [0, 1, 2].map(|i| zenith[i] / perez[i].evaluate(1., theta_s));
+
+        Self {
+            sun,
+            sun_radiance: sun.transmittance(turbidity) * sun.intensity,
+            turbidity,
+            intensity: 1.,
+            ground: Colour::new(0.1, 0.1, 0.1),
+            perez,
+            zenith,
+        }
+    }
+
+    pub const fn with_intensity(self, intensity: f64) -> Self {
+        Self { intensity, ..self }
+    }
+
+    pub const fn with_ground(self, ground: Colour) -> Self {
+        Self { ground, ..self }
+    }
+
+    pub const fn get_sun(&self) -> Sun {
+        self.sun
+    }
+
+    pub const fn get_turbidity(&self) -> f64 {
+        self.turbidity
+    }
+
+    fn sky_colour(&self, direction: Vec3) -> Colour {
+        let cos_theta = direction.y.max(1e-2);
+        let gamma = direction
+            .dot(self.sun.get_direction())
+            .clamp(-1., 1.)
+            .acos();
+        let [luminance, x, y] =
+            [0, 1, 2].map(|i| self.zenith[i] * self.perez[i].evaluate(cos_theta, gamma));
+        // xyY to XYZ to linear sRGB
+        let big_x = x / y * luminance;
+        let big_z = (1. - x - y) / y * luminance;
+        Colour::new(
+            3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+            -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+            0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+        )
+        .into_inner()
+        .to_array()
+        .map(|v| v.max(0.) * self.intensity)
+        .into()
+    }
+}
+
+impl Environment for PhysicalSky {
+    fn get_colour(&self, direction: Vec3) -> Colour {
+        let direction = direction.normalize();
+        if direction.y <= 0. {
+            return self.ground * self.intensity;
+        }
+        let sky = self.sky_colour(direction);
+        if direction.dot(self.sun.get_direction()) >= self.sun.cos_angular_radius() {
+            sky + self.sun_radiance * self.intensity
+        } else {
+            sky
+        }
+    }
+}
+
+/// Light which samples the disk of the sun uniformly, use it together with a [`PhysicalSky`]
+/// with the same [`Sun`] as the background.
+#[derive(Debug, Clone, Copy)]
+pub struct SunLight {
+    direction: Vec3,
+    cos_angular_radius: f64,
+}
+
+impl SunLight {
+    pub fn new(sun: Sun) -> Self {
+        Self {
+            direction: sun.get_direction(),
+            cos_angular_radius: sun.cos_angular_radius(),
+        }
+    }
+}
+
+impl From<&PhysicalSky> for SunLight {
+    fn from(value: &PhysicalSky) -> Self {
+        Self::new(value.get_sun())
+    }
+}
+
+impl Hittable for SunLight {
+    fn hit(&self, _r: &Ray, _range: RangeInclusive<f64>) -> Option<HitRecord<'_>> {
+        None
+    }
+
+    fn pdf_value(&self, _origin: Point3, direction: Vec3) -> f64 {
+        if direction.normalize().dot(self.direction) >= self.cos_angular_radius {
+            1. / (TAU * (1. - self.cos_angular_radius))
+        } else {
+            0.
+        }
+    }
+
+    fn random(&self, _origin: Point3, rng: &mut dyn rand::RngCore) -> Vec3 {
+        let uvw = Onb::new(self.direction);
+        let r1: f64 = rng.sample(Standard);
+        let r2: f64 = rng.sample(Standard);
+        let z = 1. + r1 * (self.cos_angular_radius - 1.);
+        let phi = 2. * PI * r2;
+        let x = phi.cos() * (1. - z * z).sqrt();
+        let y = phi.sin() * (1. - z * z).sqrt();
+        uvw.transform(Vec3::new(x, y, z))
+    }
+}
+
+impl Bounded for SunLight {
+    fn get_aabbox(&self) -> AABBox {
+        infinite_aabbox()
+    }
+
+    fn get_surface_area(&self) -> f64 {
+        f64::INFINITY
+    }
+}
+
+impl BoundedHittable for SunLight {
+    fn is_aabbox_hit(&self, _r: &Ray, _range: RangeInclusive<f64>) -> bool {
+        false
+    }
+}
@@ -1,7 +1,7 @@
 use core::ops::RangeInclusive;
-use std::fmt::Debug;
+use std::{fmt::Debug, iter, sync::Arc};
 
-use crate::{material::Material, ray::Ray};
+use crate::{hittable_collections::light_tree::LightBounds, material::Material, ray::Ray};
 
 use geometry::{
     bounded::Bounded,
@@ -167,6 +167,55 @@ impl<'a> HitRecord<'a> {
     pub(crate) const fn get_mut_p(&mut self) -> &mut Point3 {
         &mut self.p
     }
+
+    #[inline]
+    pub(crate) const fn get_mut_normal(&mut self) -> &mut Vec3 {
+        &mut self.normal
+    }
+
+    #[inline]
+    pub(crate) const fn set_material(&mut self, mat_ptr: &'a dyn Material) {
+        self.mat_ptr = mat_ptr;
+    }
+
+    /// Sets which side of the surface was hit, the normal keeps facing the ray.
+    #[inline]
+    pub(crate) const fn set_front_face(&mut self, front_face: bool) {
+        self.front_face = front_face;
+    }
+}
+
+/// Part of a ray inside a closed object, from the hit where it goes in to the one where it comes
+/// out.
+pub struct HitInterval<'a> {
+    pub enter: HitRecord<'a>,
+    pub exit: HitRecord<'a>,
+}
+
+/// Point sampled on the surface of a primitive, see [`Hittable::sample_point`].
+#[derive(Debug, Clone, Copy)]
+pub struct SurfacePoint<'a> {
+    pub p: Point3,
+    /// Pointing out of the primitive
+    pub normal: Vec3,
+    pub u: f64,
+    pub v: f64,
+    pub material: &'a dyn Material,
+}
+
+impl<'a> From<SurfacePoint<'a>> for HitRecord<'a> {
+    /// The record faces the outside, as if the point was hit from there.
+    fn from(point: SurfacePoint<'a>) -> Self {
+        Self {
+            p: point.p,
+            normal: point.normal,
+            t: 0.,
+            front_face: true,
+            mat_ptr: point.material,
+            u: point.u,
+            v: point.v,
+        }
+    }
 }
 
 pub trait Hittable: Sync + Send + Debug {
@@ -176,8 +225,67 @@ pub trait Hittable: Sync + Send + Debug {
         0.
     }
 
-    fn random(&self, _origin: Point3, _rng: &mut dyn rand::RngCore) -> Vec3 {
-        Vec3::from([1., 0., 0.])
+    /// Direction from `origin` to a point sampled with [`pdf_value`](Hittable::pdf_value), by
+    /// default one from [`sample_point`](Hittable::sample_point).
+    fn random(&self, origin: Point3, rng: &mut dyn rand::RngCore) -> Vec3 {
+        self.sample_point(rng)
+            .map_or(Vec3::from([1., 0., 0.]), |point| point.p - origin)
+    }
+
+    /// Adds the objects with an emissive material which can be sampled with
+    /// [`pdf_value`](Hittable::pdf_value) and [`random`](Hittable::random).
+    fn emissive_objects<'a>(&'a self, _lights: &mut Vec<&'a dyn Hittable>) {}
+
+    /// Power emitted by the object, used to sample brighter and bigger lights more often. `None`
+    /// if it isn't known, like for guides with a non emissive material or infinite lights.
+    fn light_power(&self) -> Option<f64> {
+        None
+    }
+
+    /// Bounds used to place the object in a
+    /// [`LightTree`](crate::hittable_collections::light_tree::LightTree), `None` if it's unbounded.
+    /// Their box has to contain the object, as the tree only looks for hits inside it.
+    fn light_bounds(&self) -> Option<LightBounds> {
+        None
+    }
+
+    /// Point on the surface of a primitive sampled uniformly by area, `None` if it can't be
+    /// sampled like this.
+    fn sample_point(&self, _rng: &mut dyn rand::RngCore) -> Option<SurfacePoint<'_>> {
+        None
+    }
+
+    /// Record of a point on the surface sampled uniformly by area, collections pick the object
+    /// by [`light_power`](Hittable::light_power). Used to start paths on the lights, `None` if
+    /// the object can't be sampled like this.
+    fn sample_surface(&self, rng: &mut dyn rand::RngCore) -> Option<HitRecord<'_>> {
+        self.sample_point(rng).map(HitRecord::from)
+    }
+
+    /// Number of bounding boxes and primitives tested by [`hit`](Hittable::hit), used to
+    /// visualize the cost of acceleration structures.
+    fn hit_cost(&self, _r: &Ray, _range: RangeInclusive<f64>) -> u32 {
+        1
+    }
+
+    /// Parts of the whole line of `r` inside the object sorted along it, which only makes sense
+    /// for closed objects. By default every hit is found one after the other and they're paired
+    /// in turns.
+    fn intervals(&self, r: &Ray) -> Vec<HitInterval<'_>> {
+        let mut start = f64::NEG_INFINITY;
+        let mut hits = iter::from_fn(|| {
+            let record = self.hit(r, start..=f64::INFINITY)?;
+            // Just past the hit, so it isn't found again
+            start = record.get_t() + 1e-9 * record.get_t().abs().max(1.);
+            Some(record)
+        });
+        iter::from_fn(|| {
+            Some(HitInterval {
+                enter: hits.next()?,
+                exit: hits.next()?,
+            })
+        })
+        .collect()
     }
 }
 
@@ -194,8 +302,66 @@ pub trait BoundedHittable: Hittable + Bounded + Debug {
             .then(|| self.hit(r, range))
             .flatten()
     }
+
+    /// [`hit_cost`](Hittable::hit_cost) of [`bounded_hit`](BoundedHittable::bounded_hit).
+    #[inline]
+    fn bounded_hit_cost(&self, r: &Ray, range: RangeInclusive<f64>) -> u32 {
+        1 + if self.get_aabbox().is_hit(r, range.clone()) {
+            self.hit_cost(r, range)
+        } else {
+            0
+        }
+    }
+}
+
+impl<T> Hittable for Arc<T>
+where
+    T: Hittable + ?Sized,
+{
+    #[inline]
+    fn hit(&self, r: &Ray, range: RangeInclusive<f64>) -> Option<HitRecord<'_>> {
+        (**self).hit(r, range)
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        (**self).pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3, rng: &mut dyn rand::RngCore) -> Vec3 {
+        (**self).random(origin, rng)
+    }
+
+    fn emissive_objects<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+        (**self).emissive_objects(lights);
+    }
+
+    fn light_power(&self) -> Option<f64> {
+        (**self).light_power()
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        (**self).light_bounds()
+    }
+
+    fn sample_point(&self, rng: &mut dyn rand::RngCore) -> Option<SurfacePoint<'_>> {
+        (**self).sample_point(rng)
+    }
+
+    fn sample_surface(&self, rng: &mut dyn rand::RngCore) -> Option<HitRecord<'_>> {
+        (**self).sample_surface(rng)
+    }
+
+    fn hit_cost(&self, r: &Ray, range: RangeInclusive<f64>) -> u32 {
+        (**self).hit_cost(r, range)
+    }
+
+    fn intervals(&self, r: &Ray) -> Vec<HitInterval<'_>> {
+        (**self).intervals(r)
+    }
 }
 
+impl<T> BoundedHittable for Arc<T> where T: BoundedHittable + ?Sized {}
+
 impl<T> Hittable for &[T]
 where
     T: BoundedHittable,
@@ -208,4 +374,10 @@ where
             .filter_map(|obj| obj.bounded_hit(r, start..=end))
             .min_by(|a, b| a.get_t().total_cmp(&b.get_t()))
     }
+
+    fn hit_cost(&self, r: &Ray, range: RangeInclusive<f64>) -> u32 {
+        self.iter()
+            .map(|obj| obj.bounded_hit_cost(r, range.clone()))
+            .sum()
+    }
 }
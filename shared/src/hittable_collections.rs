@@ -1,2 +1,33 @@
 pub mod bvh;
 pub mod hittable_list;
+pub mod light_list;
+pub mod light_tree;
+pub mod top_level;
+
+/// Weights used to pick each item when sampling lights, given their
+/// [`light_power`](crate::hittable::Hittable::light_power) and how many objects they contain.
+///
+/// Items with unknown power get the average power per object of the rest, if no power is known
+/// they are weighted by their count, which is the same as picking objects uniformly. The second
+/// value is whether any power was known.
+pub(crate) fn light_weights(
+    items: impl IntoIterator<Item = (Option<f64>, usize)>,
+) -> (Vec<f64>, bool) {
+    let items: Vec<_> = items.into_iter().collect();
+    let (known_power, known_count) = items
+        .iter()
+        .filter_map(|&(power, count)| power.map(|power| (power, count)))
+        .fold((0., 0), |(power, count), (p, c)| (power + p, count + c));
+    if known_count == 0 {
+        return (
+            items.iter().map(|&(_, count)| count as f64).collect(),
+            false,
+        );
+    }
+    let average = known_power / known_count as f64;
+    let weights = items
+        .iter()
+        .map(|&(power, count)| power.unwrap_or(average * count as f64))
+        .collect();
+    (weights, true)
+}
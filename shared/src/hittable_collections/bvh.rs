@@ -1,7 +1,7 @@
-pub use plane_divided::BoundedVolumeHierarchy;
+pub use plane_divided::{BoundedVolumeHierarchy, RefittingHierarchy};
 
 mod plane_divided {
-    use std::ops::RangeInclusive;
+    use std::{any::Any, ops::RangeInclusive};
 
     #[cfg(feature = "euclid")]
     use geometry::aabox::Box3DExt as _;
@@ -11,11 +11,15 @@ mod plane_divided {
         bounded::Bounded,
         vec3::{Point3, Vec3},
     };
-    use rand::Rng;
+    use rand::{Rng as _, distributions::Standard};
 
     use crate::{
-        hittable::{AABoxHit as _, BoundedHittable, HitRecord, Hittable},
-        hittable_collections::hittable_list::HittableList,
+        hittable::{
+            AABoxHit as _, BoundedHittable, HitInterval, HitRecord, Hittable, SurfacePoint,
+        },
+        hittable_collections::{
+            hittable_list::HittableList, light_tree::LightBounds, light_weights,
+        },
         ray::Ray,
     };
 
@@ -28,9 +32,22 @@ mod plane_divided {
         Node {
             left: Box<BoundedVolumeHierarchy>,
             right: Box<BoundedVolumeHierarchy>,
+            aabox: AABBox,
             len: usize,
+            power: Option<f64>,
+            /// Of sampling a light from `left`, see [`Hittable::sample_surface`]
+            left_probability: f64,
             dividing_plane: AAPlane,
         },
+        /// Root keeping the objects with infinite bounding boxes, like planes, out of the tree,
+        /// they're tested along with it
+        Unbounded {
+            bounded: Box<BoundedVolumeHierarchy>,
+            unbounded: HittableList,
+            power: Option<f64>,
+            /// Of sampling a light from `bounded`
+            bounded_probability: f64,
+        },
     }
 
     impl BoundedVolumeHierarchy {
@@ -40,6 +57,7 @@ mod plane_divided {
                 BoundedVolumeHierarchy::Node { left, right, .. } => {
                     left.depth().max(right.depth()) + 1
                 }
+                BoundedVolumeHierarchy::Unbounded { bounded, .. } => bounded.depth() + 1,
             }
         }
 
@@ -49,6 +67,7 @@ mod plane_divided {
                 BoundedVolumeHierarchy::Node { left, right, .. } => {
                     left.node_count() + right.node_count() + 1
                 }
+                BoundedVolumeHierarchy::Unbounded { bounded, .. } => bounded.node_count() + 1,
             }
         }
 
@@ -56,6 +75,9 @@ mod plane_divided {
             match self {
                 BoundedVolumeHierarchy::Leaf(hittable_list) => hittable_list.len(),
                 BoundedVolumeHierarchy::Node { len, .. } => *len,
+                BoundedVolumeHierarchy::Unbounded {
+                    bounded, unbounded, ..
+                } => bounded.len() + unbounded.len(),
             }
         }
 
@@ -64,47 +86,134 @@ mod plane_divided {
             self.len() == 0
         }
 
-        fn aux_pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        /// The objects of type `T`, the bounding boxes aren't updated until
+        /// [`refit`](BoundedVolumeHierarchy::refit) is called.
+        pub fn for_each_mut<T: Any>(&mut self, mut f: impl FnMut(&mut T)) {
+            self.for_each_mut_internal(&mut f);
+        }
+
+        fn for_each_mut_internal<T: Any>(&mut self, f: &mut impl FnMut(&mut T)) {
             match self {
-                BoundedVolumeHierarchy::Leaf(hittable_list) => {
-                    hittable_list.pdf_value(origin, direction) * (hittable_list.len() as f64)
-                }
+                BoundedVolumeHierarchy::Leaf(list) => list.iter_mut().for_each(f),
                 BoundedVolumeHierarchy::Node { left, right, .. } => {
-                    left.aux_pdf_value(origin, direction) + right.aux_pdf_value(origin, direction)
+                    left.for_each_mut_internal(f);
+                    right.for_each_mut_internal(f);
+                }
+                BoundedVolumeHierarchy::Unbounded {
+                    bounded, unbounded, ..
+                } => {
+                    bounded.for_each_mut_internal(f);
+                    unbounded.iter_mut().for_each(f);
                 }
             }
         }
 
-        fn aux_random(&self, index: usize, origin: Point3, rng: &mut dyn rand::RngCore) -> Vec3 {
+        /// Updates the bounding boxes from the leaves up after the objects moved, the tree is
+        /// kept as it is, so it gets slower the more the objects are mixed up, see
+        /// [`sah_cost`](BoundedVolumeHierarchy::sah_cost).
+        pub fn refit(&mut self) {
             match self {
-                BoundedVolumeHierarchy::Leaf(hittable_list) => hittable_list
-                    .iter_hittable()
-                    .nth(index)
-                    .unwrap()
-                    .random(origin, rng),
-                BoundedVolumeHierarchy::Node { left, right, .. } => match left.len().cmp(&index) {
-                    std::cmp::Ordering::Less => left.aux_random(index, origin, rng),
-                    std::cmp::Ordering::Equal | std::cmp::Ordering::Greater => {
-                        right.aux_random(index - left.len(), origin, rng)
-                    }
-                },
+                BoundedVolumeHierarchy::Leaf(list) => list.refit(),
+                BoundedVolumeHierarchy::Node {
+                    left,
+                    right,
+                    aabox,
+                    power,
+                    left_probability,
+                    ..
+                } => {
+                    left.refit();
+                    right.refit();
+                    *aabox = left.get_aabbox().enclose(&right.get_aabbox());
+                    (*power, *left_probability) = light_split([
+                        (left.light_power(), left.len()),
+                        (right.light_power(), right.len()),
+                    ]);
+                }
+                BoundedVolumeHierarchy::Unbounded {
+                    bounded,
+                    unbounded,
+                    power,
+                    bounded_probability,
+                } => {
+                    bounded.refit();
+                    unbounded.refit();
+                    (*power, *bounded_probability) = light_split([
+                        (bounded.light_power(), bounded.len()),
+                        (unbounded.light_power(), unbounded.len()),
+                    ]);
+                }
             }
         }
 
-        // fn inner_into_iter(&self) -> impl Iterator<Item = &'_ HittableList> + '_ {
-        //     match self {
-        //         BoundedVolumeHierarchy::Leaf(hittable_list) => [hittable_list].into_iter(),
-        //         BoundedVolumeHierarchy::Node {
-        //             left,
-        //             right,
-        //             dividing_plane: _,
-        //         } => left.inner_into_iter().chain(right.inner_into_iter()),
-        //     }
-        // }
-    }
+        /// Builds the tree again from its objects.
+        pub fn rebuild(&mut self) {
+            let old = std::mem::replace(self, Self::Leaf(HittableList::default()));
+            *self = old.into_list().into();
+        }
 
-    impl From<HittableList> for BoundedVolumeHierarchy {
-        fn from(value: HittableList) -> Self {
+        pub fn into_list(self) -> HittableList {
+            match self {
+                BoundedVolumeHierarchy::Leaf(list) => list,
+                BoundedVolumeHierarchy::Node { left, right, .. } => {
+                    let mut list = left.into_list();
+                    list.append(right.into_list());
+                    list
+                }
+                BoundedVolumeHierarchy::Unbounded {
+                    bounded, unbounded, ..
+                } => {
+                    let mut list = bounded.into_list();
+                    list.append(unbounded);
+                    list
+                }
+            }
+        }
+
+        /// Expected cost of hitting the tree with a ray, relative to hitting an object, given by
+        /// the surface area heuristic: a ray crossing the tree goes through a node with a
+        /// probability proportional to its surface area.
+        pub fn sah_cost(&self) -> f64 {
+            if let BoundedVolumeHierarchy::Unbounded {
+                bounded, unbounded, ..
+            } = self
+            {
+                // Every ray is tested against the unbounded objects
+                return bounded.sah_cost() + unbounded.len() as f64;
+            }
+            let root_area = self.get_aabbox().get_surface_area();
+            let cost = self.unnormalized_sah_cost();
+            if root_area > 0. {
+                cost / root_area
+            } else {
+                self.len() as f64
+            }
+        }
+
+        fn unnormalized_sah_cost(&self) -> f64 {
+            match self {
+                BoundedVolumeHierarchy::Leaf(list) => {
+                    list.get_aabbox().get_surface_area() * list.len() as f64
+                }
+                BoundedVolumeHierarchy::Node {
+                    left, right, aabox, ..
+                } => {
+                    aabox.get_surface_area() * TRAVERSAL_COST
+                        + left.unnormalized_sah_cost()
+                        + right.unnormalized_sah_cost()
+                }
+                BoundedVolumeHierarchy::Unbounded { .. } => {
+                    unreachable!("unbounded objects are only kept at the root")
+                }
+            }
+        }
+
+        /// Tree over objects with finite bounding boxes.
+        fn build(value: HittableList) -> Self {
+            debug_assert!(
+                value.get_aabbox().is_finite(),
+                "objects with infinite bounding boxes should be kept out of the tree"
+            );
             if value.len() <= 5 {
                 Self::Leaf(value)
             } else {
@@ -128,25 +237,73 @@ mod plane_divided {
                 } else if len == right.len() {
                     Self::Leaf(right)
                 } else {
-                    let left: Box<Self> = Box::new(left.into());
-                    let right: Box<Self> = Box::new(right.into());
+                    let left: Box<Self> = Box::new(Self::build(left));
+                    let right: Box<Self> = Box::new(Self::build(right));
                     let len = left.len() + right.len();
+                    let (power, left_probability) = light_split([
+                        (left.light_power(), left.len()),
+                        (right.light_power(), right.len()),
+                    ]);
                     Self::Node {
+                        aabox: left.get_aabbox().enclose(&right.get_aabbox()),
                         left,
                         right,
                         len,
+                        power,
+                        left_probability,
                         dividing_plane,
                     }
                 }
             }
         }
+
+        // fn inner_into_iter(&self) -> impl Iterator<Item = &'_ HittableList> + '_ {
+        //     match self {
+        //         BoundedVolumeHierarchy::Leaf(hittable_list) => [hittable_list].into_iter(),
+        //         BoundedVolumeHierarchy::Node {
+        //             left,
+        //             right,
+        //             dividing_plane: _,
+        //         } => left.inner_into_iter().chain(right.inner_into_iter()),
+        //     }
+        // }
+    }
+
+    /// Objects with infinite bounding boxes are kept out of the tree.
+    impl From<HittableList> for BoundedVolumeHierarchy {
+        fn from(value: HittableList) -> Self {
+            let (bounded, unbounded) = value.partition(|aabox| aabox.is_finite());
+            debug_assert!(
+                unbounded
+                    .iter_bounded()
+                    .all(|object| !object.get_aabbox().has_nan()),
+                "objects with NaN bounding boxes can't be placed in a hierarchy"
+            );
+            let bounded = Self::build(bounded);
+            if unbounded.is_empty() {
+                return bounded;
+            }
+            let (power, bounded_probability) = light_split([
+                (bounded.light_power(), bounded.len()),
+                (unbounded.light_power(), unbounded.len()),
+            ]);
+            Self::Unbounded {
+                bounded: Box::new(bounded),
+                unbounded,
+                power,
+                bounded_probability,
+            }
+        }
     }
 
     impl Bounded for BoundedVolumeHierarchy {
         fn get_aabbox(&self) -> AABBox {
             match self {
                 Self::Leaf(value) => value.get_aabbox(),
-                Self::Node { left, right, .. } => left.get_aabbox().enclose(&right.get_aabbox()),
+                Self::Node { aabox, .. } => *aabox,
+                Self::Unbounded {
+                    bounded, unbounded, ..
+                } => bounded.get_aabbox().enclose(&unbounded.get_aabbox()),
             }
         }
 
@@ -156,6 +313,9 @@ mod plane_divided {
                 BoundedVolumeHierarchy::Node { left, right, .. } => {
                     left.get_surface_area() + right.get_surface_area()
                 }
+                BoundedVolumeHierarchy::Unbounded {
+                    bounded, unbounded, ..
+                } => bounded.get_surface_area() + unbounded.get_surface_area(),
             }
         }
     }
@@ -184,24 +344,278 @@ mod plane_divided {
                             .min_by(|a, b| a.get_t().total_cmp(&b.get_t())),
                     }
                 }
+                BoundedVolumeHierarchy::Unbounded {
+                    bounded, unbounded, ..
+                } => {
+                    let hit = bounded
+                        .get_aabbox()
+                        .is_hit(r, range.clone())
+                        .then(|| bounded.hit(r, range.clone()))
+                        .flatten();
+                    let end = hit.as_ref().map_or(*range.end(), HitRecord::get_t);
+                    unbounded.hit(r, *range.start()..=end).or(hit)
+                }
             }
         }
 
-        //TODO: Implement this using iterators
         fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
-            let len = self.len();
-            self.aux_pdf_value(origin, direction) / (len as f64)
+            match self {
+                BoundedVolumeHierarchy::Leaf(hittable_list) => {
+                    hittable_list.pdf_value(origin, direction)
+                }
+                BoundedVolumeHierarchy::Node {
+                    left,
+                    right,
+                    left_probability: probability,
+                    ..
+                } => {
+                    *probability * left.pdf_value(origin, direction)
+                        + (1. - *probability) * right.pdf_value(origin, direction)
+                }
+                BoundedVolumeHierarchy::Unbounded {
+                    bounded,
+                    unbounded,
+                    bounded_probability: probability,
+                    ..
+                } => {
+                    *probability * bounded.pdf_value(origin, direction)
+                        + (1. - *probability) * unbounded.pdf_value(origin, direction)
+                }
+            }
         }
 
-        //TODO: Implement this using iterators
         fn random(&self, origin: Point3, rng: &mut dyn rand::RngCore) -> Vec3 {
-            let len = self.len();
-            let index = rng.gen_range(0..len);
-            self.aux_random(index, origin, rng)
+            match self {
+                BoundedVolumeHierarchy::Leaf(hittable_list) => hittable_list.random(origin, rng),
+                BoundedVolumeHierarchy::Node {
+                    left,
+                    right,
+                    left_probability: probability,
+                    ..
+                } => {
+                    let u: f64 = rng.sample(Standard);
+                    if u < *probability {
+                        left.random(origin, rng)
+                    } else {
+                        right.random(origin, rng)
+                    }
+                }
+                BoundedVolumeHierarchy::Unbounded {
+                    bounded,
+                    unbounded,
+                    bounded_probability: probability,
+                    ..
+                } => {
+                    let u: f64 = rng.sample(Standard);
+                    if u < *probability {
+                        bounded.random(origin, rng)
+                    } else {
+                        unbounded.random(origin, rng)
+                    }
+                }
+            }
+        }
+
+        fn sample_surface(&self, rng: &mut dyn rand::RngCore) -> Option<HitRecord<'_>> {
+            match self {
+                BoundedVolumeHierarchy::Leaf(hittable_list) => hittable_list.sample_surface(rng),
+                BoundedVolumeHierarchy::Node {
+                    left,
+                    right,
+                    left_probability: probability,
+                    ..
+                } => {
+                    let u: f64 = rng.sample(Standard);
+                    if u < *probability {
+                        left.sample_surface(rng)
+                    } else {
+                        right.sample_surface(rng)
+                    }
+                }
+                BoundedVolumeHierarchy::Unbounded {
+                    bounded,
+                    unbounded,
+                    bounded_probability: probability,
+                    ..
+                } => {
+                    let u: f64 = rng.sample(Standard);
+                    if u < *probability {
+                        bounded.sample_surface(rng)
+                    } else {
+                        unbounded.sample_surface(rng)
+                    }
+                }
+            }
+        }
+
+        fn emissive_objects<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+            match self {
+                BoundedVolumeHierarchy::Leaf(hittable_list) => {
+                    hittable_list.emissive_objects(lights);
+                }
+                BoundedVolumeHierarchy::Node { left, right, .. } => {
+                    left.emissive_objects(lights);
+                    right.emissive_objects(lights);
+                }
+                BoundedVolumeHierarchy::Unbounded {
+                    bounded, unbounded, ..
+                } => {
+                    bounded.emissive_objects(lights);
+                    unbounded.emissive_objects(lights);
+                }
+            }
+        }
+
+        fn light_power(&self) -> Option<f64> {
+            match self {
+                BoundedVolumeHierarchy::Leaf(hittable_list) => hittable_list.light_power(),
+                BoundedVolumeHierarchy::Node { power, .. }
+                | BoundedVolumeHierarchy::Unbounded { power, .. } => *power,
+            }
+        }
+
+        fn hit_cost(&self, r: &Ray, range: RangeInclusive<f64>) -> u32 {
+            match self {
+                BoundedVolumeHierarchy::Leaf(list) => list.hit_cost(r, range),
+                BoundedVolumeHierarchy::Node { left, right, .. } => {
+                    left.bounded_hit_cost(r, range.clone()) + right.bounded_hit_cost(r, range)
+                }
+                BoundedVolumeHierarchy::Unbounded {
+                    bounded, unbounded, ..
+                } => bounded.bounded_hit_cost(r, range.clone()) + unbounded.hit_cost(r, range),
+            }
         }
     }
 
     impl BoundedHittable for BoundedVolumeHierarchy {}
+
+    /// Cost of visiting a node relative to hitting an object
+    const TRAVERSAL_COST: f64 = 0.125;
+
+    /// Power of both `items` together and the probability of sampling the first one, when they're
+    /// used as a list of lights. Items are given by their power and how many objects they contain.
+    fn light_split(items: [(Option<f64>, usize); 2]) -> (Option<f64>, f64) {
+        let (weights, known) = light_weights(items);
+        let total = weights[0] + weights[1];
+        let probability = if total > 0. {
+            weights[0] / total
+        } else {
+            items[0].1 as f64 / (items[0].1 + items[1].1) as f64
+        };
+        (known.then_some(total), probability)
+    }
+
+    /// [`BoundedVolumeHierarchy`] over objects which move, it's refitted after they move and
+    /// built again once refitting has made it too slow.
+    #[derive(Debug)]
+    pub struct RefittingHierarchy {
+        hierarchy: BoundedVolumeHierarchy,
+        /// Surface area heuristic cost right after the last build
+        built_cost: f64,
+        max_degradation: f64,
+        rebuilds: usize,
+    }
+
+    impl RefittingHierarchy {
+        pub fn new(list: HittableList) -> Self {
+            let hierarchy = BoundedVolumeHierarchy::from(list);
+            Self {
+                built_cost: hierarchy.sah_cost(),
+                hierarchy,
+                max_degradation: 1.5,
+                rebuilds: 0,
+            }
+        }
+
+        /// Ratio between the cost of the refitted tree and its cost when it was built above
+        /// which it's built again, 1.5 by default.
+        #[must_use]
+        pub fn with_max_degradation(self, max_degradation: f64) -> Self {
+            Self {
+                max_degradation,
+                ..self
+            }
+        }
+
+        pub const fn get_hierarchy(&self) -> &BoundedVolumeHierarchy {
+            &self.hierarchy
+        }
+
+        /// Times the tree has been built again.
+        pub const fn rebuilds(&self) -> usize {
+            self.rebuilds
+        }
+
+        /// Lets `f` move the objects of type `T` and then refits the tree, or builds it again if
+        /// it got too slow, returns whether it was built again.
+        pub fn update<T: Any>(&mut self, f: impl FnMut(&mut T)) -> bool {
+            self.hierarchy.for_each_mut(f);
+            self.hierarchy.refit();
+            let cost = self.hierarchy.sah_cost();
+            // Unbounded objects are kept out of the tree, so the cost stays finite
+            if cost <= self.built_cost * self.max_degradation {
+                return false;
+            }
+            self.hierarchy.rebuild();
+            self.built_cost = self.hierarchy.sah_cost();
+            self.rebuilds += 1;
+            true
+        }
+    }
+
+    impl Bounded for RefittingHierarchy {
+        fn get_aabbox(&self) -> AABBox {
+            self.hierarchy.get_aabbox()
+        }
+
+        fn get_surface_area(&self) -> f64 {
+            self.hierarchy.get_surface_area()
+        }
+    }
+
+    impl Hittable for RefittingHierarchy {
+        fn hit(&self, r: &Ray, range: RangeInclusive<f64>) -> Option<HitRecord<'_>> {
+            self.hierarchy.hit(r, range)
+        }
+
+        fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+            self.hierarchy.pdf_value(origin, direction)
+        }
+
+        fn random(&self, origin: Point3, rng: &mut dyn rand::RngCore) -> Vec3 {
+            self.hierarchy.random(origin, rng)
+        }
+
+        fn emissive_objects<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+            self.hierarchy.emissive_objects(lights);
+        }
+
+        fn light_power(&self) -> Option<f64> {
+            self.hierarchy.light_power()
+        }
+
+        fn light_bounds(&self) -> Option<LightBounds> {
+            self.hierarchy.light_bounds()
+        }
+
+        fn sample_point(&self, rng: &mut dyn rand::RngCore) -> Option<SurfacePoint<'_>> {
+            self.hierarchy.sample_point(rng)
+        }
+
+        fn sample_surface(&self, rng: &mut dyn rand::RngCore) -> Option<HitRecord<'_>> {
+            self.hierarchy.sample_surface(rng)
+        }
+
+        fn hit_cost(&self, r: &Ray, range: RangeInclusive<f64>) -> u32 {
+            self.hierarchy.hit_cost(r, range)
+        }
+
+        fn intervals(&self, r: &Ray) -> Vec<HitInterval<'_>> {
+            self.hierarchy.intervals(r)
+        }
+    }
+
+    impl BoundedHittable for RefittingHierarchy {}
 }
 
 #[expect(unused)]
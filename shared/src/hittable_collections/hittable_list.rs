@@ -5,7 +5,7 @@ pub use vector_based::HittableList;
 
 #[allow(dead_code)]
 mod hash_map_based {
-    use rand::seq::IteratorRandom as _;
+    use rand::{Rng as _, distributions::Standard};
 
     #[cfg(feature = "euclid")]
     use geometry::aabox::Box3DExt as _;
@@ -18,8 +18,9 @@ mod hash_map_based {
 
     use crate::{
         hittable::{BoundedHittable, HitRecord, Hittable},
-        hittable_collections::hittable_list::RawHittableVec,
+        hittable_collections::{hittable_list::RawHittableVec, light_weights},
         ray::Ray,
+        utils::distribution::Distribution1D,
     };
 
     use std::{
@@ -27,6 +28,7 @@ mod hash_map_based {
         collections::HashMap,
         fmt::Debug,
         ops::RangeInclusive,
+        sync::OnceLock,
     };
 
     #[derive(Default, Debug)]
@@ -34,6 +36,8 @@ mod hash_map_based {
         pub(in crate::hittable_collections) values: HashMap<TypeId, RawHittableVec>,
         pub(in crate::hittable_collections) len: usize,
         pub(in crate::hittable_collections) aabox: Option<AABBox>,
+        /// Built the first time it's used to sample a light, and again once the objects change.
+        distribution: OnceLock<Distribution1D>,
     }
 
     impl HittableList {
@@ -41,6 +45,7 @@ mod hash_map_based {
             self.values.clear();
             self.len = 0;
             self.aabox = None;
+            self.distribution = OnceLock::new();
         }
 
         pub const fn len(&self) -> usize {
@@ -69,6 +74,7 @@ mod hash_map_based {
                     .add(object);
             }
             self.len += 1;
+            self.distribution = OnceLock::new();
         }
 
         pub fn split_by(self, plane: AAPlane) -> (Self, Self) {
@@ -162,6 +168,15 @@ mod hash_map_based {
             self.values.values().flat_map(|v| v.iter_hittable())
         }
 
+        /// Distribution used to pick which object to sample when used as a list of lights.
+        fn light_distribution(&self) -> &Distribution1D {
+            self.distribution.get_or_init(|| {
+                let (weights, _) =
+                    light_weights(self.iter_hittable().map(|obj| (obj.light_power(), 1)));
+                Distribution1D::new(weights)
+            })
+        }
+
         pub fn iter_debug(&self) -> impl Iterator<Item = &'_ dyn Debug> + '_ {
             self.values.values().flat_map(|v| v.iter_debug())
         }
@@ -182,16 +197,46 @@ mod hash_map_based {
         }
 
         fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
-            self.iter_hittable().fold(0., move |accum, val| {
-                accum + val.pdf_value(origin, direction)
-            }) / (self.len() as f64)
+            if self.is_empty() {
+                return 0.;
+            }
+            let distribution = self.light_distribution();
+            self.iter_hittable()
+                .enumerate()
+                .map(|(i, obj)| distribution.discrete_pdf(i) * obj.pdf_value(origin, direction))
+                .sum()
         }
 
         fn random(&self, origin: Point3, rng: &mut dyn rand::RngCore) -> Vec3 {
+            assert!(!self.is_empty(), "HittableList shouldn't be empty");
+            let (_, _, index) = self.light_distribution().sample(rng.sample(Standard));
+            self.iter_hittable().nth(index).unwrap().random(origin, rng)
+        }
+
+        fn sample_surface(&self, rng: &mut dyn rand::RngCore) -> Option<HitRecord<'_>> {
+            if self.is_empty() {
+                return None;
+            }
+            let (_, _, index) = self.light_distribution().sample(rng.sample(Standard));
+            self.iter_hittable().nth(index)?.sample_surface(rng)
+        }
+
+        fn emissive_objects<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
             self.iter_hittable()
-                .choose(rng)
-                .expect("HittableList shouldn't be empty")
-                .random(origin, rng)
+                .for_each(|obj| obj.emissive_objects(lights));
+        }
+
+        fn light_power(&self) -> Option<f64> {
+            let (weights, known) =
+                light_weights(self.iter_hittable().map(|obj| (obj.light_power(), 1)));
+            known.then(|| weights.iter().sum())
+        }
+
+        fn hit_cost(&self, r: &Ray, range: RangeInclusive<f64>) -> u32 {
+            self.values
+                .values()
+                .map(|obj| obj.bounded_hit_cost(r, range.clone()))
+                .sum()
         }
     }
 
@@ -221,7 +266,7 @@ mod hash_map_based {
 }
 
 mod vector_based {
-    use rand::seq::IteratorRandom as _;
+    use rand::{Rng as _, distributions::Standard};
 
     #[cfg(feature = "euclid")]
     use geometry::aabox::Box3DExt as _;
@@ -234,14 +279,16 @@ mod vector_based {
 
     use crate::{
         hittable::{BoundedHittable, HitRecord, Hittable},
-        hittable_collections::hittable_list::RawHittableVec,
+        hittable_collections::{hittable_list::RawHittableVec, light_weights},
         ray::Ray,
+        utils::distribution::Distribution1D,
     };
 
     use std::{
         any::{Any, TypeId},
         fmt::Debug,
         ops::RangeInclusive,
+        sync::OnceLock,
     };
 
     #[derive(Default, Debug)]
@@ -249,6 +296,8 @@ mod vector_based {
         pub(in crate::hittable_collections) values: Vec<(TypeId, RawHittableVec)>,
         pub(in crate::hittable_collections) len: usize,
         pub(in crate::hittable_collections) aabbox: Option<AABBox>,
+        /// Built the first time it's used to sample a light, and again once the objects change.
+        distribution: OnceLock<Distribution1D>,
     }
 
     impl HittableList {
@@ -256,6 +305,7 @@ mod vector_based {
             self.values.clear();
             self.len = 0;
             self.aabbox = None;
+            self.distribution = OnceLock::new();
         }
 
         pub const fn len(&self) -> usize {
@@ -291,28 +341,29 @@ mod vector_based {
                 }
             }
             self.len += 1;
+            self.distribution = OnceLock::new();
         }
 
         pub fn split_by(self, plane: AAPlane) -> (Self, Self) {
-            let (mut left, mut right) = (Self::default(), Self::default());
+            let (left, right) = self.partition(|aabox| !aabox.right_of(plane));
+            (right, left)
+        }
+
+        /// Splits into the objects whose bounding box matches `predicate` and the rest.
+        pub fn partition(self, predicate: impl Fn(&AABBox) -> bool) -> (Self, Self) {
+            let (mut matching, mut rest) = (Self::default(), Self::default());
             self.values.into_iter().for_each(|(id, obj)| {
-                let (obj_left, obj_right) = obj.split_by(plane);
-                if obj_right.len() > 0 {
-                    let aabox = obj_right.get_aabbox();
-                    right.len += obj_right.len();
-                    right.values.push((id, obj_right));
-                    right.aabbox = right.aabbox.map_or(aabox, |b| b.enclose(&aabox)).into();
-                }
-                if obj_left.len() > 0 {
-                    let aabox = obj_left.get_aabbox();
-                    left.len += obj_left.len();
-                    left.values.push((id, obj_left));
-                    left.aabbox = left.aabbox.map_or(aabox, |b| b.enclose(&aabox)).into();
+                let (obj_matching, obj_rest) = obj.partition(&predicate);
+                for (list, obj) in [(&mut matching, obj_matching), (&mut rest, obj_rest)] {
+                    if obj.len() > 0 {
+                        let aabox = obj.get_aabbox();
+                        list.len += obj.len();
+                        list.values.push((id, obj));
+                        list.aabbox = list.aabbox.map_or(aabox, |b| b.enclose(&aabox)).into();
+                    }
                 }
             });
-            right.values.sort_unstable_by_key(|(k, _)| *k);
-            left.values.sort_unstable_by_key(|(k, _)| *k);
-            (right, left)
+            (matching, rest)
         }
 
         pub fn best_split(self) -> (Self, Self, AAPlane) {
@@ -386,9 +437,55 @@ mod vector_based {
             self.values.iter().flat_map(|(_, v)| v.iter_hittable())
         }
 
+        /// Distribution used to pick which object to sample when used as a list of lights.
+        fn light_distribution(&self) -> &Distribution1D {
+            self.distribution.get_or_init(|| {
+                let (weights, _) =
+                    light_weights(self.iter_hittable().map(|obj| (obj.light_power(), 1)));
+                Distribution1D::new(weights)
+            })
+        }
+
         pub fn iter_debug(&self) -> impl Iterator<Item = &'_ dyn Debug> + '_ {
             self.values.iter().flat_map(|(_, v)| v.iter_debug())
         }
+
+        /// The objects of type `T`, the bounding box isn't updated until
+        /// [`refit`](HittableList::refit) is called.
+        pub fn iter_mut<T: Any>(&mut self) -> impl Iterator<Item = &mut T> {
+            // Their power can change too
+            self.distribution = OnceLock::new();
+            let key = TypeId::of::<T>();
+            self.values
+                .iter_mut()
+                .filter(move |(k, _)| *k == key)
+                // SAFETY: the objects kept under the TypeId of T are of type T
+                .flat_map(|(_, v)| unsafe { v.as_mut_slice::<T>() }.iter_mut())
+        }
+
+        /// Computes the bounding box again after the objects moved.
+        pub fn refit(&mut self) {
+            self.aabbox = self
+                .values
+                .iter()
+                .map(|(_, v)| v.get_aabbox())
+                .reduce(|a, b| a.enclose(&b));
+        }
+
+        /// Moves the objects of `other` into this list.
+        pub fn append(&mut self, other: Self) {
+            self.distribution = OnceLock::new();
+            for (key, vec) in other.values {
+                let aabox = vec.get_aabbox();
+                self.aabbox = self.aabbox.map_or(aabox, |b| b.enclose(&aabox)).into();
+                self.len += vec.len();
+                // SAFETY: both vectors are kept under the TypeId of their type
+                match self.values.binary_search_by_key(&key, |(k, _)| *k) {
+                    Ok(i) => unsafe { self.values[i].1.append(vec) },
+                    Err(i) => self.values.insert(i, (key, vec)),
+                }
+            }
+        }
     }
 
     impl Hittable for HittableList {
@@ -406,16 +503,46 @@ mod vector_based {
         }
 
         fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
-            self.iter_hittable().fold(0., move |accum, val| {
-                accum + val.pdf_value(origin, direction)
-            }) / (self.len() as f64)
+            if self.is_empty() {
+                return 0.;
+            }
+            let distribution = self.light_distribution();
+            self.iter_hittable()
+                .enumerate()
+                .map(|(i, obj)| distribution.discrete_pdf(i) * obj.pdf_value(origin, direction))
+                .sum()
         }
 
         fn random(&self, origin: Point3, rng: &mut dyn rand::RngCore) -> Vec3 {
+            assert!(!self.is_empty(), "HittableList shouldn't be empty");
+            let (_, _, index) = self.light_distribution().sample(rng.sample(Standard));
+            self.iter_hittable().nth(index).unwrap().random(origin, rng)
+        }
+
+        fn sample_surface(&self, rng: &mut dyn rand::RngCore) -> Option<HitRecord<'_>> {
+            if self.is_empty() {
+                return None;
+            }
+            let (_, _, index) = self.light_distribution().sample(rng.sample(Standard));
+            self.iter_hittable().nth(index)?.sample_surface(rng)
+        }
+
+        fn emissive_objects<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
             self.iter_hittable()
-                .choose(rng)
-                .expect("HittableList shouldn't be empty")
-                .random(origin, rng)
+                .for_each(|obj| obj.emissive_objects(lights));
+        }
+
+        fn light_power(&self) -> Option<f64> {
+            let (weights, known) =
+                light_weights(self.iter_hittable().map(|obj| (obj.light_power(), 1)));
+            known.then(|| weights.iter().sum())
+        }
+
+        fn hit_cost(&self, r: &Ray, range: RangeInclusive<f64>) -> u32 {
+            self.values
+                .iter()
+                .map(|(_, obj)| obj.bounded_hit_cost(r, range.clone()))
+                .sum()
         }
     }
 
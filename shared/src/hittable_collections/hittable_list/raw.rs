@@ -4,6 +4,7 @@ mod type_shit {
     #[cfg(feature = "euclid")]
     use geometry::aabox::Box3DExt as _;
     use geometry::{
+        aabox::AABBox,
         aaplane::{AAPlane, Axis},
         bounded::Bounded,
     };
@@ -15,6 +16,8 @@ mod type_shit {
 
     use super::RawHittableVec;
 
+    pub type BoxPredicate<'a> = dyn Fn(&AABBox) -> bool + 'a;
+
     pub struct Functions {
         pub(crate) slice_into_hittable: unsafe fn(*const Slice<u8>) -> *const dyn Hittable,
         pub(crate) slice_into_bounded: unsafe fn(*const Slice<u8>) -> *const dyn Bounded,
@@ -26,6 +29,9 @@ mod type_shit {
         pub(crate) drop_shim: unsafe fn(*mut u8, usize, usize),
         pub(crate) split_by: fn(*mut u8, usize, usize, AAPlane) -> (RawHittableVec, RawHittableVec),
         pub(crate) sort_by_axis: fn(*mut u8, usize, Axis),
+        pub(crate) append: fn(*mut u8, usize, &mut RawHittableVec),
+        pub(crate) partition:
+            fn(*mut u8, usize, &BoxPredicate<'_>) -> (RawHittableVec, RawHittableVec),
     }
 
     pub trait RawHittableVecFns {
@@ -111,6 +117,31 @@ mod type_shit {
                         .total_cmp(b.get_aabbox().axis(axis).start())
                 });
             },
+            append: |ptr, len, target| {
+                for i in 0..len {
+                    let val = unsafe { std::ptr::read(ptr.cast::<T>().cast_const().add(i)) };
+                    unsafe {
+                        target.add(val);
+                    }
+                }
+            },
+            partition: |ptr, len, predicate| {
+                let (mut matching, mut rest) =
+                    (RawHittableVec::new::<T>(), RawHittableVec::new::<T>());
+                for i in 0..len {
+                    let val = unsafe { std::ptr::read(ptr.cast::<T>().cast_const().add(i)) };
+                    if predicate(&val.get_aabbox()) {
+                        unsafe {
+                            matching.add(val);
+                        }
+                    } else {
+                        unsafe {
+                            rest.add(val);
+                        }
+                    }
+                }
+                (matching, rest)
+            },
         };
     }
 }
@@ -139,7 +170,7 @@ use self::{
     bounded_iterator::RawHittableVecBoundedIterator,
     debug_iterator::RawVecDebugIterator,
     hittable_iterator::RawVecHittableIterator,
-    type_shit::{Functions, RawHittableVecFns},
+    type_shit::{BoxPredicate, Functions, RawHittableVecFns},
 };
 
 #[repr(C)]
@@ -190,11 +221,33 @@ impl RawHittableVec {
         self.len = vec.len();
         self.cap = vec.capacity();
         std::mem::forget(vec);
-        let bbox = self
-            .cached_aabox
-            .load()
-            .map_or(bbox, |value| value.enclose(&bbox));
-        self.cached_aabox.store(Some(bbox));
+        let bbox = match self.cached_aabox.load() {
+            Some(value) => Some(value.enclose(&bbox)),
+            // The box of the other objects is out of date, it's computed again when needed
+            None if self.len > 1 => None,
+            None => Some(bbox),
+        };
+        self.cached_aabox.store(bbox);
+    }
+
+    /// Splits into the objects whose bounding box matches `predicate` and the rest.
+    pub fn partition(mut self, predicate: &BoxPredicate<'_>) -> (Self, Self) {
+        let out = (self.fns.partition)(self.ptr, self.len, predicate);
+        self.len = 0;
+        out
+    }
+
+    /// Moves the objects of `other`, which has to hold the same type, to the end.
+    pub unsafe fn append(&mut self, mut other: Self) {
+        (other.fns.append)(other.ptr, other.len, self);
+        other.len = 0;
+    }
+
+    /// The objects, which have to be of type `T`, the bounding box is computed again after they
+    /// can be moved.
+    pub unsafe fn as_mut_slice<T>(&mut self) -> &mut [T] {
+        self.cached_aabox.store(None);
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.cast(), self.len) }
     }
 
     pub fn split_by(mut self, plane: AAPlane) -> (Self, Self) {
@@ -272,6 +325,18 @@ impl Hittable for RawHittableVec {
         }
         .hit(r, range)
     }
+
+    fn hit_cost(&self, r: &Ray, range: RangeInclusive<f64>) -> u32 {
+        unsafe {
+            (self.fns.slice_into_hittable)(std::mem::transmute::<
+                *const RawHittableVec,
+                *const Slice<u8>,
+            >(self as *const _))
+            .as_ref()
+            .unwrap()
+        }
+        .hit_cost(r, range)
+    }
 }
 
 impl Bounded for RawHittableVec {
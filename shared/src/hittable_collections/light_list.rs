@@ -0,0 +1,94 @@
+use std::ops::RangeInclusive;
+
+use rand::{Rng as _, distributions::Standard};
+
+use geometry::vec3::{Point3, Vec3};
+
+use crate::{
+    hittable::{HitRecord, Hittable},
+    hittable_collections::light_weights,
+    ray::Ray,
+    utils::distribution::Distribution1D,
+};
+
+/// Lights borrowed from the world, so they don't have to be duplicated in a second collection.
+///
+/// Lights are picked proportionally to their [`light_power`](Hittable::light_power).
+#[derive(Debug, Default)]
+pub struct LightList<'a> {
+    lights: Vec<&'a dyn Hittable>,
+    distribution: Option<Distribution1D>,
+}
+
+impl<'a> LightList<'a> {
+    /// Collects every emissive object of `world`.
+    pub fn from_world(world: &'a dyn Hittable) -> Self {
+        let mut lights = Vec::new();
+        world.emissive_objects(&mut lights);
+        let mut out = Self {
+            lights,
+            distribution: None,
+        };
+        out.update_distribution();
+        out
+    }
+
+    pub fn add(&mut self, light: &'a dyn Hittable) {
+        self.lights.push(light);
+        self.update_distribution();
+    }
+
+    fn update_distribution(&mut self) {
+        let (weights, _) = light_weights(self.lights.iter().map(|light| (light.light_power(), 1)));
+        self.distribution = (!weights.is_empty()).then(|| Distribution1D::new(weights));
+    }
+
+    pub const fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Hittable for LightList<'_> {
+    fn hit(&self, r: &Ray, range: RangeInclusive<f64>) -> Option<HitRecord<'_>> {
+        self.lights
+            .iter()
+            .filter_map(|light| light.hit(r, range.clone()))
+            .min_by(|a, b| a.get_t().total_cmp(&b.get_t()))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let Some(distribution) = &self.distribution else {
+            return 0.;
+        };
+        self.lights
+            .iter()
+            .enumerate()
+            .map(|(i, light)| distribution.discrete_pdf(i) * light.pdf_value(origin, direction))
+            .sum()
+    }
+
+    fn random(&self, origin: Point3, rng: &mut dyn rand::RngCore) -> Vec3 {
+        let distribution = self
+            .distribution
+            .as_ref()
+            .expect("LightList shouldn't be empty");
+        let (_, _, index) = distribution.sample(rng.sample(Standard));
+        self.lights[index].random(origin, rng)
+    }
+
+    fn sample_surface(&self, rng: &mut dyn rand::RngCore) -> Option<HitRecord<'_>> {
+        let (_, _, index) = self.distribution.as_ref()?.sample(rng.sample(Standard));
+        self.lights[index].sample_surface(rng)
+    }
+
+    fn light_power(&self) -> Option<f64> {
+        let (weights, known) =
+            light_weights(self.lights.iter().map(|light| (light.light_power(), 1)));
+        known.then(|| weights.iter().sum())
+    }
+}
@@ -0,0 +1,498 @@
+use std::{
+    f64::consts::{FRAC_PI_2, PI},
+    ops::RangeInclusive,
+};
+
+use rand::{Rng as _, distributions::Standard, seq::SliceRandom as _};
+
+#[cfg(feature = "euclid")]
+use geometry::aabox::Box3DExt as _;
+use geometry::{
+    aabox::AABBox,
+    aaplane::{Axis, get_axis},
+    vec3::{Point3, Vec3},
+};
+
+use crate::{
+    hittable::{AABoxHit as _, HitRecord, Hittable},
+    ray::Ray,
+};
+
+/// Where and in which directions an object emits light, see "Importance Sampling of Many Lights
+/// with Adaptive Tree Splitting" by Conty Estevez and Kulla.
+#[derive(Debug, Clone, Copy)]
+pub struct LightBounds {
+    aabox: AABBox,
+    power: f64,
+    /// Average direction of emission
+    axis: Vec3,
+    /// Angle around `axis` containing every normal of the light, in radians
+    theta_o: f64,
+    /// Angle past the normals in which light is still emitted, in radians
+    theta_e: f64,
+}
+
+impl LightBounds {
+    pub fn new(aabox: AABBox, power: f64, axis: Vec3, theta_o: f64, theta_e: f64) -> Self {
+        Self {
+            aabox,
+            power,
+            axis: axis.normalize(),
+            theta_o: theta_o.clamp(0., PI),
+            theta_e: theta_e.clamp(0., FRAC_PI_2),
+        }
+    }
+
+    /// Light emitted in every direction, like a sphere.
+    pub fn omnidirectional(aabox: AABBox, power: f64) -> Self {
+        Self::new(aabox, power, Vec3::new(0., 0., 1.), PI, FRAC_PI_2)
+    }
+
+    /// Light emitted by a flat surface, in the hemisphere around `normal` unless it's two sided.
+    pub fn planar(aabox: AABBox, power: f64, normal: Vec3, two_sided: bool) -> Self {
+        let theta_o = if two_sided { PI } else { 0. };
+        Self::new(aabox, power, normal, theta_o, FRAC_PI_2)
+    }
+
+    /// Bounds of a copy of the light placed somewhere else, whose emission is turned by `turn`
+    /// keeping the angles between directions.
+    #[must_use]
+    pub fn moved(self, aabox: AABBox, power: f64, turn: impl Fn(Vec3) -> Vec3) -> Self {
+        Self::new(aabox, power, turn(self.axis), self.theta_o, self.theta_e)
+    }
+
+    pub const fn get_aabbox(&self) -> AABBox {
+        self.aabox
+    }
+
+    pub const fn get_power(&self) -> f64 {
+        self.power
+    }
+
+    #[must_use]
+    pub fn union(self, other: Self) -> Self {
+        let (axis, theta_o) = cone_union((self.axis, self.theta_o), (other.axis, other.theta_o));
+        Self {
+            aabox: self.aabox.enclose(&other.aabox),
+            power: self.power + other.power,
+            axis,
+            theta_o,
+            theta_e: self.theta_e.max(other.theta_e),
+        }
+    }
+
+    /// Conservative estimate of the light arriving at `point`, up to a constant.
+    pub fn importance(&self, point: Point3) -> f64 {
+        let radius = half_diagonal(&self.aabox);
+        let to_point = point - center(&self.aabox);
+        let distance_squared = to_point.square_length();
+        if distance_squared <= radius * radius {
+            return self.power / (radius * radius).max(f64::EPSILON);
+        }
+        let distance = distance_squared.sqrt();
+        let theta_w = self.axis.dot(to_point / distance).clamp(-1., 1.).acos();
+        // Angle subtended by the bounding sphere of the box
+        let theta_b = (radius / distance).asin();
+        let theta = (theta_w - self.theta_o - theta_b).max(0.);
+        if theta >= self.theta_e {
+            return 0.;
+        }
+        self.power * theta.cos() / distance_squared
+    }
+}
+
+/// Smallest cone containing both cones, each given by its axis and spread.
+fn cone_union(a: (Vec3, f64), b: (Vec3, f64)) -> (Vec3, f64) {
+    let ((axis_a, theta_a), (axis_b, theta_b)) = if b.1 > a.1 { (b, a) } else { (a, b) };
+    let theta_d = axis_a.dot(axis_b).clamp(-1., 1.).acos();
+    if (theta_d + theta_b).min(PI) <= theta_a {
+        return (axis_a, theta_a);
+    }
+    let theta_o = (theta_a + theta_d + theta_b) / 2.;
+    let rotation_axis = axis_a.cross(axis_b);
+    if theta_o >= PI || rotation_axis.square_length() < f64::EPSILON {
+        return (axis_a, PI);
+    }
+    // Rodrigues' rotation formula, `rotation_axis` is perpendicular to `axis_a`
+    let rotation_axis = rotation_axis.normalize();
+    let (sin, cos) = (theta_o - theta_a).sin_cos();
+    let axis = axis_a * cos + rotation_axis.cross(axis_a) * sin;
+    (axis.normalize(), theta_o)
+}
+
+fn coordinate(point: Point3, axis: Axis) -> f64 {
+    match axis {
+        Axis::X => point.x,
+        Axis::Y => point.y,
+        Axis::Z => point.z,
+    }
+}
+
+fn center(aabox: &AABBox) -> Point3 {
+    let [x, y, z] = get_axis().map(|axis| {
+        let range = aabox.axis(axis);
+        (range.start() + range.end()) / 2.
+    });
+    Point3::new(x, y, z)
+}
+
+fn half_diagonal(aabox: &AABBox) -> f64 {
+    get_axis()
+        .map(|axis| {
+            let range = aabox.axis(axis);
+            (range.end() - range.start()) / 2.
+        })
+        .iter()
+        .map(|v| v * v)
+        .sum::<f64>()
+        .sqrt()
+}
+
+#[derive(Debug)]
+enum LightNode {
+    Leaf {
+        bounds: LightBounds,
+        light: usize,
+    },
+    Node {
+        bounds: LightBounds,
+        children: Box<[LightNode; 2]>,
+    },
+}
+
+impl LightNode {
+    /// Splits the lights by the median of their centers along the longest axis.
+    fn new(lights: &mut [(usize, LightBounds)]) -> Self {
+        if let [(light, bounds)] = lights {
+            return Self::Leaf {
+                bounds: *bounds,
+                light: *light,
+            };
+        }
+        let centroids = AABBox::from_points(lights.iter().map(|(_, bounds)| center(&bounds.aabox)));
+        let extent = |axis| {
+            let range = centroids.axis(axis);
+            range.end() - range.start()
+        };
+        let axis = get_axis()
+            .into_iter()
+            .max_by(|&a, &b| extent(a).total_cmp(&extent(b)))
+            .unwrap();
+
+        let middle = lights.len() / 2;
+        lights.select_nth_unstable_by(middle, |(_, a), (_, b)| {
+            coordinate(center(&a.aabox), axis).total_cmp(&coordinate(center(&b.aabox), axis))
+        });
+        let (left, right) = lights.split_at_mut(middle);
+        let children = Box::new([Self::new(left), Self::new(right)]);
+        Self::Node {
+            bounds: children[0].get_bounds().union(*children[1].get_bounds()),
+            children,
+        }
+    }
+
+    const fn get_bounds(&self) -> &LightBounds {
+        match self {
+            LightNode::Leaf { bounds, .. } | LightNode::Node { bounds, .. } => bounds,
+        }
+    }
+
+    /// Probability of going to the left child when sampling from `point`.
+    fn left_probability(children: &[LightNode; 2], point: Point3) -> f64 {
+        let [left, right] = children
+            .each_ref()
+            .map(|child| child.get_bounds().importance(point));
+        if left + right > 0. {
+            left / (left + right)
+        } else {
+            0.5
+        }
+    }
+
+    /// Closest hit with the lights under this node, the boxes of the bounds are used like a BVH
+    /// to skip the nodes missed by `r`.
+    fn hit<'a>(
+        &self,
+        lights: &[&'a dyn Hittable],
+        r: &Ray,
+        range: RangeInclusive<f64>,
+    ) -> Option<HitRecord<'a>> {
+        if !self.get_bounds().aabox.is_hit(r, range.clone()) {
+            return None;
+        }
+        match self {
+            LightNode::Leaf { light, .. } => lights[*light].hit(r, range),
+            LightNode::Node { children, .. } => {
+                let left = children[0].hit(lights, r, range.clone());
+                let end = left.as_ref().map_or(*range.end(), HitRecord::get_t);
+                children[1].hit(lights, r, *range.start()..=end).or(left)
+            }
+        }
+    }
+
+    fn pdf_value(&self, lights: &[&dyn Hittable], origin: Point3, direction: Vec3) -> f64 {
+        match self {
+            LightNode::Leaf { light, .. } => lights[*light].pdf_value(origin, direction),
+            LightNode::Node { children, .. } => {
+                let left = Self::left_probability(children, origin);
+                let ray = Ray::new(origin, direction);
+                [left, 1. - left]
+                    .into_iter()
+                    .zip(children.iter())
+                    // Lights only have a density in directions in which they can be hit
+                    .filter(|(probability, child)| {
+                        *probability > 0.
+                            && child.get_bounds().aabox.is_hit(&ray, (0.)..=f64::INFINITY)
+                    })
+                    .map(|(probability, child)| {
+                        probability * child.pdf_value(lights, origin, direction)
+                    })
+                    .sum()
+            }
+        }
+    }
+
+    fn random(
+        &self,
+        lights: &[&dyn Hittable],
+        origin: Point3,
+        rng: &mut dyn rand::RngCore,
+    ) -> Vec3 {
+        match self {
+            LightNode::Leaf { light, .. } => lights[*light].random(origin, rng),
+            LightNode::Node { children, .. } => {
+                let left = Self::left_probability(children, origin);
+                let u: f64 = rng.sample(Standard);
+                children[usize::from(u >= left)].random(lights, origin, rng)
+            }
+        }
+    }
+
+    /// Samples a point on a light, picking the children by their power.
+    fn sample_surface<'a>(
+        &self,
+        lights: &[&'a dyn Hittable],
+        rng: &mut dyn rand::RngCore,
+    ) -> Option<HitRecord<'a>> {
+        match self {
+            LightNode::Leaf { light, .. } => lights[*light].sample_surface(rng),
+            LightNode::Node { bounds, children } => {
+                let left = if bounds.power > 0. {
+                    children[0].get_bounds().power / bounds.power
+                } else {
+                    0.5
+                };
+                let u: f64 = rng.sample(Standard);
+                children[usize::from(u >= left)].sample_surface(lights, rng)
+            }
+        }
+    }
+}
+
+/// Picks lights depending on how much they contribute to the point being shaded, so scenes with
+/// many small lights don't waste samples on far away or back facing ones.
+///
+/// Lights without [`light_bounds`](Hittable::light_bounds) are kept outside the tree and picked
+/// uniformly, with the tree counting as one choice per light in it.
+#[derive(Debug, Default)]
+pub struct LightTree<'a> {
+    root: Option<LightNode>,
+    lights: Vec<&'a dyn Hittable>,
+    unbounded: Vec<&'a dyn Hittable>,
+}
+
+impl<'a> LightTree<'a> {
+    pub fn new(lights: impl IntoIterator<Item = &'a dyn Hittable>) -> Self {
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = lights
+            .into_iter()
+            .map(|light| (light, light.light_bounds()))
+            .partition(|(_, bounds)| bounds.is_some());
+        let mut bounds: Vec<_> = bounded
+            .iter()
+            .filter_map(|(_, bounds)| *bounds)
+            .enumerate()
+            .collect();
+        Self {
+            root: (!bounds.is_empty()).then(|| LightNode::new(&mut bounds)),
+            lights: bounded.into_iter().map(|(light, _)| light).collect(),
+            unbounded: unbounded.into_iter().map(|(light, _)| light).collect(),
+        }
+    }
+
+    /// Builds the tree from every emissive object of `world`.
+    pub fn from_world(world: &'a dyn Hittable) -> Self {
+        let mut lights = Vec::new();
+        world.emissive_objects(&mut lights);
+        Self::new(lights)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len() + self.unbounded.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn depth(&self) -> usize {
+        fn depth(node: &LightNode) -> usize {
+            match node {
+                LightNode::Leaf { .. } => 1,
+                LightNode::Node { children, .. } => {
+                    depth(&children[0]).max(depth(&children[1])) + 1
+                }
+            }
+        }
+        self.root.as_ref().map_or(0, depth)
+    }
+
+    /// Probability of sampling a light from the tree instead of an unbounded one.
+    fn tree_probability(&self) -> f64 {
+        self.lights.len() as f64 / self.len() as f64
+    }
+}
+
+impl Hittable for LightTree<'_> {
+    fn hit(&self, r: &Ray, range: RangeInclusive<f64>) -> Option<HitRecord<'_>> {
+        let tree = self
+            .root
+            .as_ref()
+            .and_then(|root| root.hit(&self.lights, r, range.clone()));
+        self.unbounded
+            .iter()
+            .filter_map(|light| light.hit(r, range.clone()))
+            .chain(tree)
+            .min_by(|a, b| a.get_t().total_cmp(&b.get_t()))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        if self.is_empty() {
+            return 0.;
+        }
+        let tree = self.root.as_ref().map_or(0., |root| {
+            self.tree_probability() * root.pdf_value(&self.lights, origin, direction)
+        });
+        let unbounded = self
+            .unbounded
+            .iter()
+            .map(|light| light.pdf_value(origin, direction))
+            .sum::<f64>()
+            / self.len() as f64;
+        tree + unbounded
+    }
+
+    fn random(&self, origin: Point3, rng: &mut dyn rand::RngCore) -> Vec3 {
+        let u: f64 = rng.sample(Standard);
+        match &self.root {
+            Some(root) if u < self.tree_probability() => root.random(&self.lights, origin, rng),
+            _ => self
+                .unbounded
+                .choose(rng)
+                .expect("LightTree shouldn't be empty")
+                .random(origin, rng),
+        }
+    }
+
+    /// Unbounded lights don't have a known power, so nothing is sampled if there are any.
+    fn sample_surface(&self, rng: &mut dyn rand::RngCore) -> Option<HitRecord<'_>> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.root.as_ref()?.sample_surface(&self.lights, rng)
+    }
+
+    fn light_power(&self) -> Option<f64> {
+        self.unbounded
+            .is_empty()
+            .then(|| self.root.as_ref().map(|root| root.get_bounds().power))
+            .flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rand::{SeedableRng as _, rngs::SmallRng};
+
+    use geometry::vec3::{Point3, Vec3};
+
+    use crate::{
+        colour::Colour,
+        entities::{Quad, Sphere},
+        hittable::Hittable,
+        material::DiffuseLight,
+        ray::Ray,
+    };
+
+    use super::LightTree;
+
+    #[test]
+    fn sampled_directions_have_density() {
+        let light =
+            Arc::new(DiffuseLight::new_with_colour(Colour::new(4., 4., 4.)).with_two_sided(false));
+        let dim_light = Arc::new(DiffuseLight::new_with_colour(Colour::new(0.1, 0.1, 0.1)));
+        let quads: Vec<_> = (0..16)
+            .map(|i| {
+                Quad::new(
+                    Point3::new(f64::from(i % 4) * 2., 5., f64::from(i / 4) * 2.),
+                    Vec3::new(0., 0., 1.),
+                    Vec3::new(1., 0., 0.),
+                    light.clone(),
+                )
+            })
+            .collect();
+        let sphere = Sphere::new(Point3::new(0., 1., -5.), 0.5, dim_light);
+        let tree = LightTree::new(
+            quads
+                .iter()
+                .map(|quad| quad as &dyn Hittable)
+                .chain([&sphere as &dyn Hittable]),
+        );
+        assert_eq!(tree.len(), 17);
+        assert!(tree.depth() >= 5);
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let origin = Point3::new(3., 0., 3.);
+        for _ in 0..1000 {
+            let direction = tree.random(origin, &mut rng);
+            let pdf = tree.pdf_value(origin, direction);
+            assert!(
+                pdf.is_finite() && pdf > 0.,
+                "{direction:?} has a density of {pdf}"
+            );
+        }
+        // Behind every light
+        assert_eq!(tree.pdf_value(origin, Vec3::new(0., -1., 0.)), 0.);
+    }
+
+    #[test]
+    fn hits_are_the_closest_light() {
+        let light = Arc::new(DiffuseLight::new_with_colour(Colour::new(1., 1., 1.)));
+        let spheres: Vec<_> = (0..27)
+            .map(|i| {
+                let center = Point3::new(f64::from(i % 3), f64::from(i / 3 % 3), f64::from(i / 9));
+                Sphere::new(center, 0.3 + 0.05 * f64::from(i % 4), light.clone())
+            })
+            .collect();
+        let lights: Vec<_> = spheres
+            .iter()
+            .map(|sphere| sphere as &dyn Hittable)
+            .collect();
+        let tree = LightTree::new(lights.iter().copied());
+
+        let mut rng = SmallRng::seed_from_u64(1);
+        let origin = Point3::new(-2., -1., -3.);
+        for _ in 0..1000 {
+            let ray = Ray::new(origin, tree.random(origin, &mut rng));
+            let expected = lights
+                .iter()
+                .filter_map(|light| light.hit(&ray, (0.)..=f64::INFINITY))
+                .map(|rec| rec.get_t())
+                .min_by(f64::total_cmp);
+            let t = tree.hit(&ray, (0.)..=f64::INFINITY).map(|rec| rec.get_t());
+            assert_eq!(t, expected);
+        }
+    }
+}
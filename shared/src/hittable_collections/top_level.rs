@@ -0,0 +1,411 @@
+use std::ops::{Range, RangeInclusive};
+
+use arrayvec::ArrayVec;
+use rand::{Rng as _, distributions::Standard};
+
+#[cfg(feature = "euclid")]
+use geometry::aabox::Box3DExt as _;
+use geometry::{
+    aabox::AABBox,
+    aaplane::{Axis, get_axis},
+    bounded::Bounded,
+    transformations::Instance,
+    vec3::{Point3, Vec3},
+};
+
+use crate::{
+    hittable::{AABoxHit as _, BoundedHittable, HitRecord, Hittable},
+    hittable_collections::{bvh::BoundedVolumeHierarchy, light_weights},
+    ray::Ray,
+    utils::distribution::Distribution1D,
+};
+
+/// Most instances kept in a leaf
+const LEAF_SIZE: usize = 2;
+/// Nodes waiting to be visited, halving the instances at each level leaves room for a lot more
+/// than fit in memory
+const STACK_SIZE: usize = 64;
+
+/// Top level of a two level acceleration structure, a hierarchy over [`Instance`]s of prototypes
+/// which keep their own hierarchy, usually a [`BoundedVolumeHierarchy`] built once per mesh.
+///
+/// Rays are moved into the space of each instance they reach, so moving instances only rebuilds
+/// this level, see [`update`](TopLevelHierarchy::update).
+#[derive(Debug)]
+pub struct TopLevelHierarchy<T: ?Sized = BoundedVolumeHierarchy> {
+    instances: Vec<Instance<T>>,
+    /// Instances sorted so the ones in each leaf are next to each other
+    order: Vec<usize>,
+    /// Left children follow their parent
+    nodes: Vec<Node>,
+    /// Instances with infinite bounding boxes, kept out of the nodes and tested by every ray
+    unbounded: Vec<usize>,
+    /// Instances which emit light, see [`Hittable::emissive_objects`]
+    emitters: Vec<usize>,
+    light_distribution: Option<Distribution1D>,
+    power: Option<f64>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Leaf {
+        aabox: AABBox,
+        /// Range of `order`
+        instances: Range<usize>,
+    },
+    Inner {
+        aabox: AABBox,
+        right: usize,
+    },
+}
+
+impl Node {
+    const fn get_aabbox(&self) -> AABBox {
+        match self {
+            Node::Leaf { aabox, .. } | Node::Inner { aabox, .. } => *aabox,
+        }
+    }
+}
+
+impl<T> TopLevelHierarchy<T>
+where
+    T: BoundedHittable + ?Sized,
+{
+    pub fn new(instances: Vec<Instance<T>>) -> Self {
+        let mut out = Self {
+            instances,
+            order: Vec::new(),
+            nodes: Vec::new(),
+            unbounded: Vec::new(),
+            emitters: Vec::new(),
+            light_distribution: None,
+            power: None,
+        };
+        out.build();
+        out
+    }
+
+    pub fn get_instances(&self) -> &[Instance<T>] {
+        &self.instances
+    }
+
+    /// Lets `f` move, add or remove instances and then rebuilds the top level, the hierarchies
+    /// of the prototypes are left as they are.
+    pub fn update(&mut self, f: impl FnOnce(&mut Vec<Instance<T>>)) {
+        f(&mut self.instances);
+        self.build();
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn build(&mut self) {
+        let boxes: Vec<_> = self.instances.iter().map(Bounded::get_aabbox).collect();
+        (self.order, self.unbounded) =
+            (0..self.instances.len()).partition(|&i| boxes[i].is_finite());
+        self.nodes.clear();
+        if !self.order.is_empty() {
+            build_node(&boxes, &mut self.order, 0, &mut self.nodes);
+        }
+
+        self.emitters = (0..self.instances.len())
+            .filter(|&i| {
+                let mut emissive = Vec::new();
+                self.instances[i].emissive_objects(&mut emissive);
+                !emissive.is_empty()
+            })
+            .collect();
+        let (weights, known) = light_weights(
+            self.emitters
+                .iter()
+                .map(|&i| (self.instances[i].light_power(), 1)),
+        );
+        self.power = known.then(|| weights.iter().sum());
+        self.light_distribution = (!weights.is_empty()).then(|| Distribution1D::new(weights));
+    }
+}
+
+impl<T> FromIterator<Instance<T>> for TopLevelHierarchy<T>
+where
+    T: BoundedHittable + ?Sized,
+{
+    fn from_iter<I: IntoIterator<Item = Instance<T>>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect())
+    }
+}
+
+fn centre(aabox: &AABBox, axis: Axis) -> f64 {
+    let range = aabox.axis(axis);
+    (range.start() + range.end()) / 2.
+}
+
+fn extent(aabox: &AABBox, axis: Axis) -> f64 {
+    let range = aabox.axis(axis);
+    range.end() - range.start()
+}
+
+/// Adds the nodes containing `order`, which starts at `offset` in the whole order, splitting
+/// at the median along the longest axis.
+fn build_node(boxes: &[AABBox], order: &mut [usize], offset: usize, nodes: &mut Vec<Node>) {
+    let aabox = order
+        .iter()
+        .map(|&i| boxes[i])
+        .reduce(|a, b| a.enclose(&b))
+        .unwrap();
+    debug_assert!(
+        aabox.is_finite(),
+        "instances with infinite bounding boxes should be kept out of the nodes"
+    );
+    if order.len() <= LEAF_SIZE {
+        nodes.push(Node::Leaf {
+            aabox,
+            instances: offset..offset + order.len(),
+        });
+        return;
+    }
+    let axis = get_axis()
+        .into_iter()
+        .max_by(|&a, &b| extent(&aabox, a).total_cmp(&extent(&aabox, b)))
+        .unwrap();
+    let middle = order.len() / 2;
+    order.select_nth_unstable_by(middle, |&a, &b| {
+        centre(&boxes[a], axis).total_cmp(&centre(&boxes[b], axis))
+    });
+    let index = nodes.len();
+    nodes.push(Node::Inner { aabox, right: 0 });
+    let (left, right) = order.split_at_mut(middle);
+    build_node(boxes, left, offset, nodes);
+    let right_index = nodes.len();
+    if let Node::Inner { right, .. } = &mut nodes[index] {
+        *right = right_index;
+    }
+    build_node(boxes, right, offset + middle, nodes);
+}
+
+impl<T> Hittable for TopLevelHierarchy<T>
+where
+    T: BoundedHittable + ?Sized,
+{
+    fn hit(&self, r: &Ray, range: RangeInclusive<f64>) -> Option<HitRecord<'_>> {
+        let (&start, &(mut end)) = (range.start(), range.end());
+        let mut closest = None;
+        let mut stack = ArrayVec::<usize, STACK_SIZE>::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.get_aabbox().is_hit(r, start..=end) {
+                continue;
+            }
+            match node {
+                Node::Leaf { instances, .. } => {
+                    for &i in &self.order[instances.clone()] {
+                        // Closer hits shorten the range of the next instances
+                        if let Some(record) = self.instances[i].bounded_hit(r, start..=end) {
+                            end = record.get_t();
+                            closest = Some(record);
+                        }
+                    }
+                }
+                Node::Inner { right, .. } => {
+                    stack.push(*right);
+                    stack.push(index + 1);
+                }
+            }
+        }
+        for &i in &self.unbounded {
+            if let Some(record) = self.instances[i].hit(r, start..=end) {
+                end = record.get_t();
+                closest = Some(record);
+            }
+        }
+        closest
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let Some(distribution) = &self.light_distribution else {
+            return 0.;
+        };
+        self.emitters
+            .iter()
+            .enumerate()
+            .map(|(k, &i)| {
+                distribution.discrete_pdf(k) * self.instances[i].pdf_value(origin, direction)
+            })
+            .sum()
+    }
+
+    fn random(&self, origin: Point3, rng: &mut dyn rand::RngCore) -> Vec3 {
+        let distribution = self
+            .light_distribution
+            .as_ref()
+            .expect("TopLevelHierarchy should have an emissive instance");
+        let (_, _, k) = distribution.sample(rng.sample(Standard));
+        self.instances[self.emitters[k]].random(origin, rng)
+    }
+
+    fn sample_surface(&self, rng: &mut dyn rand::RngCore) -> Option<HitRecord<'_>> {
+        let (_, _, k) = self
+            .light_distribution
+            .as_ref()?
+            .sample(rng.sample(Standard));
+        self.instances[self.emitters[k]].sample_surface(rng)
+    }
+
+    fn emissive_objects<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+        self.emitters
+            .iter()
+            .for_each(|&i| self.instances[i].emissive_objects(lights));
+    }
+
+    fn light_power(&self) -> Option<f64> {
+        self.power
+    }
+
+    fn hit_cost(&self, r: &Ray, range: RangeInclusive<f64>) -> u32 {
+        let mut cost = 0;
+        let mut stack = ArrayVec::<usize, STACK_SIZE>::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            cost += 1;
+            if !node.get_aabbox().is_hit(r, range.clone()) {
+                continue;
+            }
+            match node {
+                Node::Leaf { instances, .. } => {
+                    cost += self.order[instances.clone()]
+                        .iter()
+                        .map(|&i| self.instances[i].bounded_hit_cost(r, range.clone()))
+                        .sum::<u32>();
+                }
+                Node::Inner { right, .. } => {
+                    stack.push(*right);
+                    stack.push(index + 1);
+                }
+            }
+        }
+        cost + self
+            .unbounded
+            .iter()
+            .map(|&i| self.instances[i].hit_cost(r, range.clone()))
+            .sum::<u32>()
+    }
+}
+
+impl<T> Bounded for TopLevelHierarchy<T>
+where
+    T: BoundedHittable + ?Sized,
+{
+    fn get_aabbox(&self) -> AABBox {
+        self.unbounded
+            .iter()
+            .map(|&i| self.instances[i].get_aabbox())
+            .chain(self.nodes.first().map(Node::get_aabbox))
+            .reduce(|a, b| a.enclose(&b))
+            .unwrap_or_else(AABBox::zero)
+    }
+
+    fn get_surface_area(&self) -> f64 {
+        self.instances.iter().map(Bounded::get_surface_area).sum()
+    }
+}
+
+impl<T> BoundedHittable for TopLevelHierarchy<T> where T: BoundedHittable + ?Sized {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rand::{Rng as _, SeedableRng as _, rngs::SmallRng};
+
+    use geometry::{
+        aaplane::Axis,
+        transformations::{Instance, Transformation, rotation},
+        vec3::{Point3, Vec3},
+    };
+
+    use crate::{
+        colour::Colour,
+        entities::{Cuboid, Sphere},
+        hittable::Hittable,
+        hittable_collections::{bvh::BoundedVolumeHierarchy, hittable_list::HittableList},
+        material::Lambertian,
+        ray::Ray,
+    };
+
+    use super::TopLevelHierarchy;
+
+    fn placement(i: usize, offset: f64) -> Transformation {
+        let i = i as f64;
+        rotation(i * 23., Axis::Y)
+            .then(&Vec3::new((i % 8.) * 3. + offset, 0., (i / 8.).floor() * 3.).into())
+    }
+
+    #[test]
+    fn hits_match_a_flat_hierarchy() {
+        let material = Arc::new(Lambertian::new_with_colour(Colour::new(0.5, 0.5, 0.5)));
+        let mut prototype = HittableList::default();
+        prototype.add(Cuboid::new(
+            Point3::new(-1., 0., -0.5),
+            Point3::new(1., 1., 0.5),
+            material.clone(),
+        ));
+        prototype.add(Sphere::new(Point3::new(0., 1.5, 0.), 0.5, material));
+        let prototype = Arc::new(BoundedVolumeHierarchy::from(prototype));
+
+        let mut top_level: TopLevelHierarchy = (0..64)
+            .map(|i| Instance::instance(&prototype, placement(i, 0.)))
+            .collect();
+        assert!(top_level.node_count() >= 63);
+
+        let mut rng = SmallRng::seed_from_u64(11);
+        let mut check = |top_level: &TopLevelHierarchy, offset: f64| {
+            let mut flat = HittableList::default();
+            (0..64).for_each(|i| flat.add(Instance::instance(&prototype, placement(i, offset))));
+            let flat = BoundedVolumeHierarchy::from(flat);
+            let mut hits = 0;
+            for _ in 0..256 {
+                let origin = Point3::new(rng.gen_range(-5. ..30.), 10., rng.gen_range(-5. ..30.));
+                let direction = Vec3::new(rng.gen_range(-1. ..1.), -1., rng.gen_range(-1. ..1.));
+                let ray = Ray::new(origin, direction);
+                let expected = flat.hit(&ray, 0.001..=f64::INFINITY).map(|rec| rec.get_t());
+                let got = top_level
+                    .hit(&ray, 0.001..=f64::INFINITY)
+                    .map(|rec| rec.get_t());
+                assert_eq!(got, expected);
+                hits += usize::from(got.is_some());
+            }
+            assert!(hits > 0);
+        };
+        check(&top_level, 0.);
+
+        // Moving the instances only rebuilds the top level
+        top_level.update(|instances| {
+            instances
+                .iter_mut()
+                .enumerate()
+                .for_each(|(i, instance)| instance.set_transformation(placement(i, 1.5)));
+        });
+        check(&top_level, 1.5);
+        assert!(
+            top_level
+                .get_instances()
+                .iter()
+                .all(|instance| Arc::ptr_eq(instance.get_instance(), &prototype))
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! Writers of rendered images, which convert them from the working space of the camera to the
+//! space of the file.
+//!
+//! Images are given as returned by the camera, whose first row is the bottom one.
+
+use std::io::{self, Write};
+
+use crate::colour::{Colour, ColourSpace, SampledColour};
+
+fn pixels(
+    image: &[Vec<SampledColour>],
+    working: ColourSpace,
+    output: ColourSpace,
+) -> impl Iterator<Item = impl Iterator<Item = Colour>> {
+    image.iter().rev().map(move |row| {
+        row.iter()
+            .map(move |pixel| pixel.average().fix_nan().convert(working, output))
+    })
+}
+
+/// Writes an ASCII PPM with the transfer function of `output`, clipping what's outside of
+/// `[0, 1]`.
+pub fn write_ppm(
+    mut writer: impl Write,
+    image: &[Vec<SampledColour>],
+    working: ColourSpace,
+    output: ColourSpace,
+) -> io::Result<()> {
+    let (width, height) = (image.first().map_or(0, Vec::len), image.len());
+    writeln!(writer, "P3\n{width} {height}\n255")?;
+    for row in pixels(image, working, output) {
+        for pixel in row {
+            let [r, g, b] = pixel
+                .into_inner()
+                .to_array()
+                .map(|v| (255. * output.encode(v.clamp(0., 1.))).round() as u8);
+            writeln!(writer, "{r} {g} {b}")?;
+        }
+    }
+    Ok(())
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    for text in [name, kind] {
+        header.extend(text.as_bytes());
+        header.push(0);
+    }
+    header.extend((value.len() as i32).to_le_bytes());
+    header.extend(value);
+}
+
+/// Writes an uncompressed OpenEXR file of 32 bit floats. It stays linear, and the
+/// chromaticities of `output` are stored so other programs know which space it's in.
+pub fn write_exr(
+    mut writer: impl Write,
+    image: &[Vec<SampledColour>],
+    working: ColourSpace,
+    output: ColourSpace,
+) -> io::Result<()> {
+    const MAGIC: u32 = 20_000_630;
+    const VERSION: u32 = 2;
+    const FLOAT: i32 = 2;
+
+    let (width, height) = (image.first().map_or(0, Vec::len), image.len());
+    let mut header = Vec::new();
+    header.extend(MAGIC.to_le_bytes());
+    header.extend(VERSION.to_le_bytes());
+    // Channels are sorted by name
+    let channels: Vec<u8> = ["B", "G", "R"]
+        .iter()
+        .flat_map(|name| {
+            [name.as_bytes(), &[0], &FLOAT.to_le_bytes(), &[0; 4]]
+                .concat()
+                .into_iter()
+                .chain([1i32, 1].into_iter().flat_map(i32::to_le_bytes))
+        })
+        .chain([0])
+        .collect();
+    attribute(&mut header, "channels", "chlist", &channels);
+    attribute(&mut header, "compression", "compression", &[0]);
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .into_iter()
+        .flat_map(i32::to_le_bytes)
+        .collect();
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    let chromaticities: Vec<u8> = output
+        .chromaticities()
+        .into_iter()
+        .flatten()
+        .flat_map(|v| (v as f32).to_le_bytes())
+        .collect();
+    attribute(
+        &mut header,
+        "chromaticities",
+        "chromaticities",
+        &chromaticities,
+    );
+    if output == ColourSpace::Aces2065 {
+        attribute(
+            &mut header,
+            "acesImageContainerFlag",
+            "int",
+            &1i32.to_le_bytes(),
+        );
+    }
+    header.push(0);
+
+    // A block per scanline, each with its row and size before the pixels
+    let block_size = 8 + width * 3 * 4;
+    let first_block = header.len() + height * 8;
+    for row in 0..height {
+        header.extend(((first_block + row * block_size) as u64).to_le_bytes());
+    }
+    writer.write_all(&header)?;
+
+    for (y, row) in pixels(image, working, output).enumerate() {
+        let row: Vec<_> = row.map(|pixel| pixel.into_inner().to_array()).collect();
+        writer.write_all(&(y as i32).to_le_bytes())?;
+        writer.write_all(&((block_size - 8) as i32).to_le_bytes())?;
+        for channel in [2, 1, 0] {
+            for pixel in &row {
+                writer.write_all(&(pixel[channel] as f32).to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_exr, write_ppm};
+    use crate::colour::{Colour, ColourSpace, SampledColour};
+
+    fn image() -> Vec<Vec<SampledColour>> {
+        // Bottom row first
+        [Colour::new(2., 2., 2.), Colour::new(0., 0., 4.)]
+            .map(|colour| (0..3).map(|_| SampledColour::from((colour, 2))).collect())
+            .into()
+    }
+
+    #[test]
+    fn ppm_is_encoded() {
+        let mut out = Vec::new();
+        write_ppm(&mut out, &image(), ColourSpace::Srgb, ColourSpace::Srgb).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines[..3], ["P3", "3 2", "255"]);
+        assert_eq!(lines[3], "0 0 255");
+        assert_eq!(lines[6], "255 255 255");
+    }
+
+    #[test]
+    fn exr_has_the_expected_layout() {
+        let mut out = Vec::new();
+        write_exr(&mut out, &image(), ColourSpace::Srgb, ColourSpace::Aces2065).unwrap();
+        assert_eq!(out[..4], [0x76, 0x2f, 0x31, 0x01]);
+        // Offset of the last scanline, which takes up the end of the file
+        let header_end = out.len() - 2 * (8 + 3 * 3 * 4);
+        let offsets = &out[header_end - 16..header_end];
+        let last = u64::from_le_bytes(offsets[8..].try_into().unwrap()) as usize;
+        assert_eq!(last, out.len() - (8 + 3 * 3 * 4));
+        // White stays white after adapting it to the ACES white
+        let red = f32::from_le_bytes(out[out.len() - 4..].try_into().unwrap());
+        assert!((red - 1.).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,217 @@
+mod ambient_occlusion;
+mod bdpt;
+mod debug;
+mod direct;
+mod guiding;
+mod metropolis;
+mod path;
+mod photon_mapping;
+mod ris;
+mod whitted;
+
+use std::fmt::Debug;
+
+use geometry::vec3::Point3;
+
+use crate::{
+    camera::{Camera, Film},
+    colour::Colour,
+    environment::Background,
+    hittable::{HitRecord, Hittable},
+    lights::PunctualLight,
+    material::ScatterRecord,
+    pdf::{HittablePdf, MixturePdf, Pdf},
+    ray::Ray,
+    spectrum::{PathColours, SampledWavelengths},
+};
+
+pub use ambient_occlusion::AmbientOcclusion;
+pub use bdpt::BidirectionalPathTracer;
+pub use debug::DebugView;
+pub use direct::DirectLighting;
+pub use guiding::GuidedPathTracer;
+pub use metropolis::{MetropolisLightTransport, PrimarySampleVector};
+#[cfg(feature = "hit_counters")]
+pub(crate) use path::HIT_COUNTER;
+pub use path::PathTracer;
+pub use photon_mapping::ProgressivePhotonMapper;
+pub use ris::LightingStrategy;
+pub(crate) use ris::{Reservoir, ShadingPoint};
+pub use whitted::Whitted;
+
+/// Light transport algorithm used by the [`Camera`](crate::camera::Camera) to shade its rays.
+pub trait Integrator: Sync + Send + Debug {
+    /// Light arriving at the origin of `r` from its direction, following at most `depth`
+    /// bounces. Light landing on other pixels can be splatted onto the `film`.
+    ///
+    /// Integrators which need more than a ray at a time, like the [`ProgressivePhotonMapper`],
+    /// only use it as a single-ray fallback and do their actual work in
+    /// [`render`](Self::render).
+    fn radiance(
+        &self,
+        r: &Ray,
+        lighting: &Lighting<'_>,
+        film: &Film,
+        rng: &mut dyn rand::RngCore,
+        depth: u32,
+    ) -> Colour;
+
+    /// Renders the image seen by the `camera` onto the `film`, summing the samples of every
+    /// pixel. By default each sample traces a single ray with [`radiance`](Self::radiance).
+    fn render(&self, camera: &Camera, lighting: &Lighting<'_>, film: &Film) {
+        camera.render_pixels(self, lighting, film);
+    }
+}
+
+/// What rays are traced against, how the scene is lit and what colours the path carries.
+#[derive(Clone, Copy)]
+pub struct Lighting<'a> {
+    world: &'a dyn Hittable,
+    /// `None` if there's nothing to sample, in which case only the material is sampled
+    lights: Option<&'a dyn Hittable>,
+    punctual_lights: &'a [Box<dyn PunctualLight>],
+    background: &'a Background,
+    strategy: LightingStrategy,
+    camera: &'a Camera,
+    /// Of the path being traced
+    colours: PathColours,
+}
+
+impl<'a> Lighting<'a> {
+    pub(crate) const fn new(
+        world: &'a dyn Hittable,
+        lights: Option<&'a dyn Hittable>,
+        punctual_lights: &'a [Box<dyn PunctualLight>],
+        background: &'a Background,
+        strategy: LightingStrategy,
+        camera: &'a Camera,
+    ) -> Self {
+        Self {
+            world,
+            lights,
+            punctual_lights,
+            background,
+            strategy,
+            camera,
+            colours: PathColours::new(camera.get_working_space(), None),
+        }
+    }
+
+    /// The same lighting for a path carrying `wavelengths`.
+    pub(crate) const fn with_wavelengths(self, wavelengths: Option<SampledWavelengths>) -> Self {
+        Self {
+            colours: PathColours::new(self.colours.get_working_space(), wavelengths),
+            ..self
+        }
+    }
+
+    pub const fn get_world(&self) -> &'a dyn Hittable {
+        self.world
+    }
+
+    pub const fn get_lights(&self) -> Option<&'a dyn Hittable> {
+        self.lights
+    }
+
+    pub const fn get_strategy(&self) -> LightingStrategy {
+        self.strategy
+    }
+
+    pub const fn get_camera(&self) -> &'a Camera {
+        self.camera
+    }
+
+    pub const fn get_colours(&self) -> PathColours {
+        self.colours
+    }
+
+    /// Turns light carried by the path into RGB, see [`PathColours::to_rgb`].
+    pub fn to_rgb(&self, colour: Colour) -> Colour {
+        self.colours.to_rgb(colour)
+    }
+
+    /// Attenuation of a path scattered as in `srec`. If the material splits the wavelengths only
+    /// the hero one is followed from now on, see [`SampledWavelengths::terminate_secondary`].
+    pub fn attenuation(&mut self, srec: &ScatterRecord) -> Colour {
+        if srec.terminates_secondary {
+            srec.attenuation * self.colours.terminate_secondary()
+        } else {
+            srec.attenuation
+        }
+    }
+
+    pub fn background_colour(&self, r: &Ray) -> Colour {
+        self.background
+            .get_colour(r.get_direction())
+            .to_illuminant(self.colours)
+    }
+
+    /// Light emitted towards the origin of `r` by the first thing it hits, or the background if
+    /// it doesn't hit anything.
+    pub fn incoming_emission(&self, r: &Ray) -> Colour {
+        match self.world.hit(r, (f64::EPSILON)..=f64::INFINITY) {
+            Some(rec) => rec.get_material().emitted_rec(r, &rec, self.colours),
+            None => self.background_colour(r),
+        }
+    }
+
+    /// Whether emission seen by `r` was already accounted for by sampling the lights at its
+    /// origin.
+    pub fn is_light_sampled(&self, r: &Ray) -> bool {
+        self.lights
+            .is_some_and(|lights| lights.pdf_value(r.get_origin(), r.get_direction()) > 0.)
+    }
+
+    /// Samples the next direction of the path from the material only.
+    pub fn sample_material(
+        material_pdf: &dyn Pdf,
+        p: Point3,
+        rng: &mut dyn rand::RngCore,
+    ) -> (Ray, f64) {
+        let scattered_ray = Ray::new(p, material_pdf.generate(rng));
+        let pdf_value = material_pdf.value(&scattered_ray.get_direction());
+        (scattered_ray, pdf_value)
+    }
+
+    /// Samples the next direction of the path, returning the ray and its pdf.
+    pub fn sample_direction(
+        &self,
+        rec: &HitRecord<'_>,
+        material_pdf: &dyn Pdf,
+        rng: &mut dyn rand::RngCore,
+    ) -> (Ray, f64) {
+        match self.lights {
+            Some(lights) => {
+                let light_pdf = HittablePdf::new(lights, rec.get_p());
+                let p = MixturePdf::new(&light_pdf, material_pdf);
+                let scattered_ray = Ray::new(rec.get_p(), p.generate(rng));
+                let pdf_value = p.value(&scattered_ray.get_direction());
+                (scattered_ray, pdf_value)
+            }
+            None => Self::sample_material(material_pdf, rec.get_p(), rng),
+        }
+    }
+
+    /// Direct light from the punctual lights, which can only be reached with shadow rays.
+    pub fn punctual_light_colour(
+        &self,
+        r: &Ray,
+        rec: &HitRecord<'_>,
+        attenuation: Colour,
+    ) -> Colour {
+        self.punctual_lights
+            .iter()
+            .filter_map(|light| light.sample(rec.get_p()))
+            .filter_map(|sample| {
+                let shadow_ray = Ray::new(rec.get_p(), sample.direction);
+                self.world
+                    .hit(&shadow_ray, (f64::EPSILON)..=sample.distance)
+                    .is_none()
+                    .then(|| {
+                        let scattering_pdf = rec.get_material().scattering_pdf(r, rec, &shadow_ray);
+                        attenuation * sample.irradiance.to_illuminant(self.colours) * scattering_pdf
+                    })
+            })
+            .fold(Colour::default(), |acc, val| acc + val)
+    }
+}
@@ -0,0 +1,60 @@
+use crate::{
+    camera::Film,
+    colour::Colour,
+    pdf::{CosinePdf, Pdf as _},
+    ray::Ray,
+};
+
+use super::{Integrator, Lighting};
+
+/// Fraction of the hemisphere around the first hit which isn't blocked by anything closer than
+/// `max_distance`, weighted by the cosine.
+#[derive(Debug, Clone, Copy)]
+pub struct AmbientOcclusion {
+    max_distance: f64,
+    samples: u16,
+}
+
+impl AmbientOcclusion {
+    pub const fn new(max_distance: f64) -> Self {
+        Self {
+            max_distance,
+            samples: 1,
+        }
+    }
+
+    /// Occlusion rays traced per camera ray.
+    pub const fn with_samples(self, samples: u16) -> Self {
+        Self { samples, ..self }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance(
+        &self,
+        r: &Ray,
+        lighting: &Lighting<'_>,
+        _film: &Film,
+        rng: &mut dyn rand::RngCore,
+        depth: u32,
+    ) -> Colour {
+        if depth == 0 || self.samples == 0 {
+            return Colour::default();
+        }
+        let Some(rec) = lighting.world.hit(r, (f64::EPSILON)..=f64::INFINITY) else {
+            return Colour::default();
+        };
+        let pdf = CosinePdf::new(rec.get_normal());
+        let unoccluded = (0..self.samples)
+            .filter(|_| {
+                let ray = Ray::new(rec.get_p(), pdf.generate(rng));
+                lighting
+                    .world
+                    .hit(&ray, (f64::EPSILON)..=self.max_distance)
+                    .is_none()
+            })
+            .count();
+        let visibility = unoccluded as f64 / f64::from(self.samples);
+        Colour::new(visibility, visibility, visibility)
+    }
+}
@@ -0,0 +1,617 @@
+use std::f64::consts::PI;
+
+use rand::Rng as _;
+
+use geometry::vec3::{Point3, Vec3};
+
+use crate::{
+    camera::Film,
+    colour::Colour,
+    hittable::HitRecord,
+    material::ScatterReflect,
+    pdf::{CosinePdf, Pdf},
+    ray::Ray,
+};
+
+use super::{Integrator, Lighting};
+
+/// Fraction of the distance between two vertices which is skipped at both ends of a shadow ray,
+/// so it doesn't hit the surfaces the vertices are on.
+const SHADOW_EPSILON: f64 = 1e-4;
+
+/// Bidirectional path tracer, see "Robust Monte Carlo methods for light transport simulation"
+/// by Veach.
+///
+/// Subpaths are traced from the camera and from the lights, and every pair of their vertices is
+/// connected, weighting each way of building a path with the balance heuristic. Paths connected
+/// straight to the camera are splatted onto the film, which finds caustics the path tracer
+/// can't.
+///
+/// Light subpaths start at a point on the lights sampled with
+/// [`Hittable::sample_surface`](crate::hittable::Hittable::sample_surface), so brighter lights
+/// are picked more often.
+/// Cameras without [`Camera::importance`](crate::camera::Camera::importance) only use the
+/// strategies which start at the camera.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BidirectionalPathTracer;
+
+impl Integrator for BidirectionalPathTracer {
+    fn radiance(
+        &self,
+        r: &Ray,
+        lighting: &Lighting<'_>,
+        film: &Film,
+        rng: &mut dyn rand::RngCore,
+        depth: u32,
+    ) -> Colour {
+        if depth == 0 {
+            return Colour::default();
+        }
+        let depth = depth as usize;
+        let mut colour = Colour::default();
+        // Both subpaths stop following the secondary wavelengths once either of them does
+        let mut lighting = *lighting;
+
+        let importance = lighting.camera.importance(r);
+        let mut camera_path = Vec::with_capacity(depth + 1);
+        camera_path.push(Vertex {
+            // Without importance nothing can connect to the camera
+            delta: importance.is_none(),
+            ..Vertex::new(Kind::Camera, r.get_origin(), Vec3::new(0., 0., 0.), ONE)
+        });
+        let pdf_direction = importance.map_or(0., |importance| importance.pdf_direction);
+        if let Some((ray, beta)) = random_walk(
+            r.clone(),
+            ONE,
+            pdf_direction,
+            &mut lighting,
+            rng,
+            depth + 1,
+            &mut camera_path,
+        ) {
+            // Nothing else samples the background, so it gets all the weight
+            colour += beta * lighting.background_colour(&ray);
+        }
+        for vertex in &camera_path[1..] {
+            if let (Some(rec), Some(_)) = (&vertex.rec, &vertex.material_pdf) {
+                colour += vertex.beta
+                    * lighting.punctual_light_colour(&vertex.ray_in, rec, vertex.attenuation);
+            }
+        }
+
+        let mut light_path = Vec::with_capacity(depth);
+        light_subpath(&mut lighting, rng, depth, &mut light_path);
+
+        for t in 1..=camera_path.len() {
+            for s in 0..=light_path.len() {
+                // Paths with a single edge are only found by the camera
+                if s + t < 2 || (s == 1 && t == 1) || s + t - 1 > depth {
+                    continue;
+                }
+                colour += connect(&lighting, film, &light_path, &camera_path, s, t, rng);
+            }
+        }
+        colour
+    }
+}
+
+const ONE: Colour = Colour::new(1., 1., 1.);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Camera,
+    /// Start of a light subpath
+    Light,
+    Surface,
+}
+
+struct Vertex<'a> {
+    kind: Kind,
+    p: Point3,
+    normal: Vec3,
+    /// `None` for the camera
+    rec: Option<HitRecord<'a>>,
+    ray_in: Ray,
+    attenuation: Colour,
+    /// `None` if the material doesn't scatter or only scatters in a single direction
+    material_pdf: Option<Box<dyn Pdf>>,
+    /// Throughput of the subpath up to this vertex
+    beta: Colour,
+    /// Density of sampling this vertex from the previous one of its subpath, in area measure
+    pdf_fwd: f64,
+    /// Density of sampling this vertex from the next one of its subpath, in area measure
+    pdf_rev: f64,
+    delta: bool,
+}
+
+impl<'a> Vertex<'a> {
+    fn new(kind: Kind, p: Point3, normal: Vec3, beta: Colour) -> Self {
+        Self {
+            kind,
+            p,
+            normal,
+            rec: None,
+            ray_in: Ray::default(),
+            attenuation: Colour::default(),
+            material_pdf: None,
+            beta,
+            pdf_fwd: 0.,
+            pdf_rev: 0.,
+            delta: false,
+        }
+    }
+
+    fn from_rec(kind: Kind, rec: HitRecord<'a>, ray_in: Ray, beta: Colour) -> Self {
+        Self {
+            ray_in,
+            ..Self::new(kind, rec.get_p(), rec.get_normal(), beta)
+        }
+        .with_rec(rec)
+    }
+
+    fn with_rec(self, rec: HitRecord<'a>) -> Self {
+        Self {
+            rec: Some(rec),
+            ..self
+        }
+    }
+
+    /// Whether paths can be joined at this vertex.
+    fn is_connectible(&self) -> bool {
+        !self.delta && (self.kind != Kind::Surface || self.material_pdf.is_some())
+    }
+
+    /// Converts a density in solid angle at this vertex to area measure at `next`.
+    fn convert_density(&self, pdf: f64, next: &Vertex<'_>) -> f64 {
+        let w = next.p - self.p;
+        let distance_squared = w.square_length();
+        if distance_squared == 0. {
+            return 0.;
+        }
+        let cos_theta = if next.kind == Kind::Camera {
+            1.
+        } else {
+            next.normal.dot(w).abs() / distance_squared.sqrt()
+        };
+        pdf * cos_theta / distance_squared
+    }
+
+    /// Material response towards `next`, including the cosine.
+    fn f_cos(&self, next: &Vertex<'_>) -> Colour {
+        match (&self.rec, &self.material_pdf) {
+            (Some(rec), Some(_)) => {
+                let scattered = Ray::new(self.p, next.p - self.p);
+                self.attenuation
+                    * rec
+                        .get_material()
+                        .scattering_pdf(&self.ray_in, rec, &scattered)
+            }
+            _ => Colour::default(),
+        }
+    }
+
+    /// Density of sampling `next` from this vertex, in area measure. The densities of the
+    /// materials don't depend on where the path came from, so the previous vertex isn't needed.
+    fn pdf(&self, lighting: &Lighting<'_>, next: &Vertex<'_>) -> f64 {
+        let direction = next.p - self.p;
+        let pdf = match (self.kind, &self.rec, &self.material_pdf) {
+            (Kind::Camera, ..) => lighting
+                .camera
+                .importance(&Ray::new(self.p, direction))
+                .map_or(0., |importance| importance.pdf_direction),
+            (Kind::Light, Some(rec), _) => emission_pdf(rec, direction),
+            (Kind::Surface, _, Some(pdf)) => pdf.value(&direction),
+            _ => 0.,
+        };
+        self.convert_density(pdf, next)
+    }
+
+    /// Density of a light subpath emitting from this vertex towards `next`, in area measure.
+    fn pdf_light(&self, next: &Vertex<'_>) -> f64 {
+        self.rec.as_ref().map_or(0., |rec| {
+            self.convert_density(emission_pdf(rec, next.p - self.p), next)
+        })
+    }
+
+    /// Density of a light subpath starting at this vertex, in area measure.
+    fn pdf_light_origin(&self, lighting: &Lighting<'_>) -> f64 {
+        self.rec
+            .as_ref()
+            .map_or(0., |rec| light_position_pdf(lighting, rec))
+    }
+}
+
+/// Normal on the side one sided lights emit from.
+fn outward_normal(rec: &HitRecord<'_>) -> Vec3 {
+    if rec.is_front_face() {
+        rec.get_normal()
+    } else {
+        -rec.get_normal()
+    }
+}
+
+/// Light emitted by the surface at `rec` in `direction`.
+fn emitted(lighting: &Lighting<'_>, rec: &HitRecord<'_>, direction: Vec3) -> Colour {
+    let direction = direction.normalize();
+    let ray = Ray::new(rec.get_p() + direction, -direction);
+    let towards = HitRecord::new(
+        &ray,
+        1.,
+        outward_normal(rec),
+        rec.get_u(),
+        rec.get_v(),
+        rec.get_material(),
+    );
+    rec.get_material()
+        .emitted_rec(&ray, &towards, lighting.get_colours())
+}
+
+/// Density of emitting in `direction`, cosine weighted on every side which emits.
+fn emission_pdf(rec: &HitRecord<'_>, direction: Vec3) -> f64 {
+    let cos_theta = direction.normalize().dot(outward_normal(rec));
+    if rec.get_material().is_two_sided() {
+        cos_theta.abs() / (2. * PI)
+    } else {
+        cos_theta.max(0.) / PI
+    }
+}
+
+/// Density of the light subpaths starting at the point of `rec`, in area measure. Lights are
+/// picked by power and sampled uniformly by area, so it's the power emitted per area over the
+/// power of every light.
+fn light_position_pdf(lighting: &Lighting<'_>, rec: &HitRecord<'_>) -> f64 {
+    let Some(lights) = lighting.lights else {
+        return 0.;
+    };
+    let Some(power) = lights.light_power().filter(|&power| power > 0.) else {
+        return 0.;
+    };
+    // Emissive surfaces found by the camera might not be part of the lights
+    let normal = rec.get_normal();
+    let on_lights = lights
+        .hit(
+            &Ray::new(rec.get_p() + normal, -normal),
+            (1. - SHADOW_EPSILON)..=(1. + SHADOW_EPSILON),
+        )
+        .is_some();
+    if !on_lights {
+        return 0.;
+    }
+    rec.get_material().emitted_power() / power
+}
+
+/// Whether nothing blocks the segment between `from` and `to`.
+fn unoccluded(lighting: &Lighting<'_>, from: Point3, to: Point3) -> bool {
+    lighting
+        .world
+        .hit(
+            &Ray::new(from, to - from),
+            SHADOW_EPSILON..=(1. - SHADOW_EPSILON),
+        )
+        .is_none()
+}
+
+/// Light leaving a point on the lights, see [`sample_emission`].
+pub(crate) struct Emission<'a> {
+    pub(crate) rec: HitRecord<'a>,
+    pub(crate) direction: Vec3,
+    pub(crate) emitted: Colour,
+    /// In area measure
+    pub(crate) pdf_position: f64,
+    /// In solid angle
+    pub(crate) pdf_direction: f64,
+}
+
+impl Emission<'_> {
+    /// Flux carried by the ray leaving the light.
+    pub(crate) fn beta(&self) -> Colour {
+        self.emitted
+            * (self.rec.get_normal().dot(self.direction).abs()
+                / (self.pdf_position * self.pdf_direction))
+    }
+}
+
+/// Samples a point on the whole surface of the lights with
+/// [`Hittable::sample_surface`](crate::hittable::Hittable::sample_surface), and a cosine weighted
+/// direction for the light leaving it.
+pub(crate) fn sample_emission<'a>(
+    lighting: &Lighting<'a>,
+    rng: &mut dyn rand::RngCore,
+) -> Option<Emission<'a>> {
+    let rec = lighting.lights?.sample_surface(rng)?;
+    let pdf_position = light_position_pdf(lighting, &rec);
+    if pdf_position <= 0. {
+        return None;
+    }
+    let normal = if rec.get_material().is_two_sided() && rng.r#gen::<bool>() {
+        -outward_normal(&rec)
+    } else {
+        outward_normal(&rec)
+    };
+    let direction = CosinePdf::new(normal).generate(rng);
+    Some(Emission {
+        pdf_direction: emission_pdf(&rec, direction),
+        emitted: emitted(lighting, &rec, direction),
+        rec,
+        direction,
+        pdf_position,
+    })
+}
+
+/// Samples a point on the lights and follows the light it emits, adding at most
+/// `max_vertices` vertices to `path`.
+fn light_subpath<'a>(
+    lighting: &mut Lighting<'a>,
+    rng: &mut dyn rand::RngCore,
+    max_vertices: usize,
+    path: &mut Vec<Vertex<'a>>,
+) {
+    if max_vertices == 0 {
+        return;
+    }
+    let Some(emission) = sample_emission(lighting, rng) else {
+        return;
+    };
+    let beta = emission.beta();
+    let Emission {
+        rec,
+        direction,
+        emitted,
+        pdf_position,
+        pdf_direction,
+    } = emission;
+    let p = rec.get_p();
+    path.push(Vertex {
+        pdf_fwd: pdf_position,
+        ..Vertex::from_rec(Kind::Light, rec, Ray::default(), emitted / pdf_position)
+    });
+    if pdf_direction <= 0. || emitted.luminance() <= 0. {
+        return;
+    }
+    random_walk(
+        Ray::new(p, direction),
+        beta,
+        pdf_direction,
+        lighting,
+        rng,
+        max_vertices,
+        path,
+    );
+}
+
+/// Extends `path` by following the materials until it has `max_vertices` vertices, returns the
+/// ray which left the scene and its throughput if there's one.
+fn random_walk<'a>(
+    mut ray: Ray,
+    mut beta: Colour,
+    mut pdf_fwd: f64,
+    lighting: &mut Lighting<'a>,
+    rng: &mut dyn rand::RngCore,
+    max_vertices: usize,
+    path: &mut Vec<Vertex<'a>>,
+) -> Option<(Ray, Colour)> {
+    while path.len() < max_vertices {
+        let Some(rec) = lighting.world.hit(&ray, (f64::EPSILON)..=f64::INFINITY) else {
+            return Some((ray, beta));
+        };
+        let srec = rec
+            .get_material()
+            .scatter(&ray, &rec, lighting.get_colours(), rng);
+        let prev = path.last_mut().expect("Walks start from a vertex");
+        let mut vertex = Vertex::from_rec(Kind::Surface, rec, ray.clone(), beta);
+        vertex.pdf_fwd = prev.convert_density(pdf_fwd, &vertex);
+        let Some(srec) = srec else {
+            path.push(vertex);
+            return None;
+        };
+        let attenuation = lighting.attenuation(&srec);
+        vertex.attenuation = attenuation;
+        match srec.scatter_reflect {
+            ScatterReflect::Reflect(reflected) => {
+                vertex.delta = true;
+                prev.pdf_rev = 0.;
+                path.push(vertex);
+                beta *= attenuation;
+                pdf_fwd = 0.;
+                ray = reflected;
+            }
+            ScatterReflect::Scatter(pdf) => {
+                let direction = pdf.generate(rng);
+                let scattered = Ray::new(vertex.p, direction);
+                pdf_fwd = pdf.value(&direction);
+                let pdf_rev = pdf.value(&-ray.get_direction());
+                prev.pdf_rev = vertex.convert_density(pdf_rev, prev);
+                let scattering_pdf = vertex.rec.as_ref().map_or(0., |rec| {
+                    rec.get_material().scattering_pdf(&ray, rec, &scattered)
+                });
+                vertex.material_pdf = Some(pdf);
+                path.push(vertex);
+                if pdf_fwd <= 0. {
+                    return None;
+                }
+                beta *= attenuation * scattering_pdf / pdf_fwd;
+                if beta.luminance() <= 0. {
+                    return None;
+                }
+                ray = scattered;
+            }
+        }
+    }
+    None
+}
+
+/// Light carried by the path made of the first `s` vertices of `light_path` and the first `t`
+/// of `camera_path`. Paths connected to the camera are splatted instead of returned.
+fn connect(
+    lighting: &Lighting<'_>,
+    film: &Film,
+    light_path: &[Vertex<'_>],
+    camera_path: &[Vertex<'_>],
+    s: usize,
+    t: usize,
+    rng: &mut dyn rand::RngCore,
+) -> Colour {
+    let pt = &camera_path[t - 1];
+    if s == 0 {
+        let Some(rec) = &pt.rec else {
+            return Colour::default();
+        };
+        let emitted = rec
+            .get_material()
+            .emitted_rec(&pt.ray_in, rec, lighting.get_colours());
+        if emitted.luminance() <= 0. {
+            return Colour::default();
+        }
+        let weight = mis_weight(lighting, light_path, camera_path, None, s, t);
+        return pt.beta * emitted * weight;
+    }
+    let qs = &light_path[s - 1];
+    if t == 1 {
+        if !qs.is_connectible() {
+            return Colour::default();
+        }
+        let Some(sample) = lighting.camera.sample_importance(qs.p, rng) else {
+            return Colour::default();
+        };
+        let camera = Vertex::new(Kind::Camera, sample.lens_point, Vec3::new(0., 0., 0.), ONE);
+        let colour = qs.beta * qs.f_cos(&camera) * sample.weight;
+        if colour.luminance() <= 0. || !unoccluded(lighting, qs.p, camera.p) {
+            return Colour::default();
+        }
+        let weight = mis_weight(lighting, light_path, camera_path, Some(&camera), s, t);
+        film.add_splat(sample.pixel, lighting.to_rgb(colour * weight));
+        return Colour::default();
+    }
+    if !pt.is_connectible() {
+        return Colour::default();
+    }
+    if s == 1 {
+        // Sampled like the start of a light subpath, so the densities of both match
+        let Some(rec) = lighting
+            .lights
+            .and_then(|lights| lights.sample_surface(rng))
+        else {
+            return Colour::default();
+        };
+        let pdf = light_position_pdf(lighting, &rec);
+        let to_camera = pt.p - rec.get_p();
+        let emitted = emitted(lighting, &rec, to_camera);
+        if pdf <= 0. || emitted.luminance() <= 0. {
+            return Colour::default();
+        }
+        let light = Vertex {
+            pdf_fwd: pdf,
+            ..Vertex::from_rec(Kind::Light, rec, Ray::default(), emitted / pdf)
+        };
+        let distance_squared = to_camera.square_length();
+        let cos_theta = light.normal.dot(to_camera).abs() / distance_squared.sqrt();
+        let colour = pt.beta * pt.f_cos(&light) * light.beta * (cos_theta / distance_squared);
+        if colour.luminance() <= 0. || !unoccluded(lighting, pt.p, light.p) {
+            return Colour::default();
+        }
+        let weight = mis_weight(lighting, light_path, camera_path, Some(&light), s, t);
+        return colour * weight;
+    }
+    if !qs.is_connectible() {
+        return Colour::default();
+    }
+    let distance_squared = (pt.p - qs.p).square_length();
+    let colour = qs.beta * qs.f_cos(pt) * pt.f_cos(qs) * pt.beta / distance_squared;
+    if colour.luminance() <= 0. || !unoccluded(lighting, qs.p, pt.p) {
+        return Colour::default();
+    }
+    colour * mis_weight(lighting, light_path, camera_path, None, s, t)
+}
+
+/// Balance heuristic weight of the strategy which connects `s` light vertices to `t` camera
+/// vertices, `sampled` replaces the last light vertex if `s == 1` and the camera if `t == 1`.
+fn mis_weight(
+    lighting: &Lighting<'_>,
+    light_path: &[Vertex<'_>],
+    camera_path: &[Vertex<'_>],
+    sampled: Option<&Vertex<'_>>,
+    s: usize,
+    t: usize,
+) -> f64 {
+    if s + t == 2 {
+        return 1.;
+    }
+    let qs = match (s, sampled) {
+        (0, _) => None,
+        (1, Some(sampled)) if t != 1 => Some(sampled),
+        _ => Some(&light_path[s - 1]),
+    };
+    let pt = match sampled {
+        Some(sampled) if t == 1 => sampled,
+        _ => &camera_path[t - 1],
+    };
+    let qs_minus = (s > 1).then(|| &light_path[s - 2]);
+    let pt_minus = (t > 1).then(|| &camera_path[t - 2]);
+
+    // Densities which change because of the connection
+    let pt_pdf_rev = match qs {
+        Some(qs) => qs.pdf(lighting, pt),
+        None => pt.pdf_light_origin(lighting),
+    };
+    let pt_minus_pdf_rev = pt_minus.map_or(0., |pt_minus| match qs {
+        Some(_) => pt.pdf(lighting, pt_minus),
+        None => pt.pdf_light(pt_minus),
+    });
+    let qs_pdf_rev = qs.map_or(0., |qs| pt.pdf(lighting, qs));
+    let qs_minus_pdf_rev = qs
+        .zip(qs_minus)
+        .map_or(0., |(qs, qs_minus)| qs.pdf(lighting, qs_minus));
+
+    // The zero densities next to delta vertices cancel out. Any other zero density comes from a
+    // strategy which can't find the path, which gets no weight, or from a degenerate path whose
+    // vertices are on top of each other, which is skipped
+    let remap = |pdf: f64, delta: bool| if delta && pdf == 0. { 1. } else { pdf };
+    let mut sum = 0.;
+
+    let mut ratio = 1.;
+    for i in (1..t).rev() {
+        let (vertex, pdf_rev, delta) = if i == t - 1 {
+            (pt, pt_pdf_rev, false)
+        } else if i == t - 2 {
+            (&camera_path[i], pt_minus_pdf_rev, camera_path[i].delta)
+        } else {
+            (
+                &camera_path[i],
+                camera_path[i].pdf_rev,
+                camera_path[i].delta,
+            )
+        };
+        let pdf_fwd = remap(vertex.pdf_fwd, camera_path[i - 1].delta);
+        if pdf_fwd == 0. {
+            return 0.;
+        }
+        let delta_after = i + 2 < t && camera_path[i + 1].delta;
+        ratio *= remap(pdf_rev, delta_after) / pdf_fwd;
+        if !delta && !camera_path[i - 1].delta {
+            sum += ratio;
+        }
+    }
+
+    let mut ratio = 1.;
+    for i in (0..s).rev() {
+        let (vertex, pdf_rev, delta) = if i == s - 1 {
+            (qs.unwrap_or(&light_path[i]), qs_pdf_rev, false)
+        } else if i == s - 2 {
+            (&light_path[i], qs_minus_pdf_rev, light_path[i].delta)
+        } else {
+            (&light_path[i], light_path[i].pdf_rev, light_path[i].delta)
+        };
+        // Lights are never delta, as punctual lights don't start light subpaths
+        let delta_before = i > 0 && light_path[i - 1].delta;
+        let pdf_fwd = remap(vertex.pdf_fwd, delta_before);
+        if pdf_fwd == 0. {
+            return 0.;
+        }
+        let delta_after = i + 2 < s && light_path[i + 1].delta;
+        ratio *= remap(pdf_rev, delta_after) / pdf_fwd;
+        if !delta && !delta_before {
+            sum += ratio;
+        }
+    }
+    (1. + sum).recip()
+}
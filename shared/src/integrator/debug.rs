@@ -0,0 +1,71 @@
+use crate::{camera::Film, colour::Colour, ray::Ray};
+
+use super::{Integrator, Lighting};
+
+/// Shows a property of the first hit instead of the light in the scene.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugView {
+    /// Normal facing the ray, mapped from `[-1, 1]` to `[0, 1]`
+    Normal,
+    /// Texture coordinates in the red and green channels
+    Uv,
+    /// Attenuation of the material, or the emitted light if it doesn't scatter
+    Albedo,
+    /// Distance to the first hit, white at the camera and black at `max_distance`
+    Depth { max_distance: f64 },
+    /// Bounding boxes and primitives tested by the ray, from blue at none to red at `max_cost`
+    BvhCost { max_cost: u32 },
+}
+
+impl Integrator for DebugView {
+    fn radiance(
+        &self,
+        r: &Ray,
+        lighting: &Lighting<'_>,
+        _film: &Film,
+        rng: &mut dyn rand::RngCore,
+        _depth: u32,
+    ) -> Colour {
+        let range = (f64::EPSILON)..=f64::INFINITY;
+        let hit = || lighting.world.hit(r, range.clone());
+        match *self {
+            Self::Normal => hit().map(|rec| {
+                let normal = rec.get_normal();
+                Colour::new(
+                    (normal.x + 1.) / 2.,
+                    (normal.y + 1.) / 2.,
+                    (normal.z + 1.) / 2.,
+                )
+            }),
+            Self::Uv => hit().map(|rec| Colour::new(rec.get_u(), rec.get_v(), 0.)),
+            Self::Albedo => hit().map(|rec| {
+                let colours = lighting.get_colours();
+                rec.get_material()
+                    .scatter(r, &rec, colours, rng)
+                    .map_or_else(
+                        || rec.get_material().emitted_rec(r, &rec, colours),
+                        |srec| srec.attenuation,
+                    )
+            }),
+            Self::Depth { max_distance } => hit().map(|rec| {
+                let depth = 1. - ((rec.get_p() - r.get_origin()).length() / max_distance).min(1.);
+                Colour::new(depth, depth, depth)
+            }),
+            Self::BvhCost { max_cost } => {
+                let cost = lighting.world.hit_cost(r, range.clone());
+                Some(heatmap(f64::from(cost) / f64::from(max_cost.max(1))))
+            }
+        }
+        .unwrap_or_default()
+    }
+}
+
+/// Maps `[0, 1]` to blue, green and then red.
+fn heatmap(t: f64) -> Colour {
+    let t = t.clamp(0., 1.);
+    Colour::new(
+        (2. * t - 1.).max(0.),
+        1. - (2. * t - 1.).abs(),
+        (1. - 2. * t).max(0.),
+    )
+}
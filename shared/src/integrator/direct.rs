@@ -0,0 +1,86 @@
+use crate::{
+    camera::Film, colour::Colour, hittable::HitRecord, material::ScatterReflect, pdf::Pdf, ray::Ray,
+};
+
+use super::{Integrator, Lighting};
+
+/// Only light arriving after a single diffuse bounce, mirrors and glass are still followed.
+///
+/// Direct light is estimated with a light and a material sample, combined with multiple
+/// importance sampling.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DirectLighting;
+
+impl Integrator for DirectLighting {
+    fn radiance(
+        &self,
+        r: &Ray,
+        lighting: &Lighting<'_>,
+        _film: &Film,
+        rng: &mut dyn rand::RngCore,
+        depth: u32,
+    ) -> Colour {
+        let mut lighting = *lighting;
+        let mut r = r.clone();
+        let mut mult = Colour::from_array([1., 1., 1.]);
+        for _ in 0..depth {
+            let Some(rec) = lighting.world.hit(&r, (f64::EPSILON)..=f64::INFINITY) else {
+                return mult * lighting.background_colour(&r);
+            };
+            let colours = lighting.get_colours();
+            let colour_from_emission = rec.get_material().emitted_rec(&r, &rec, colours);
+            let Some(srec) = rec.get_material().scatter(&r, &rec, colours, rng) else {
+                return mult * colour_from_emission;
+            };
+            let attenuation = lighting.attenuation(&srec);
+            match srec.scatter_reflect {
+                ScatterReflect::Reflect(ray) => {
+                    mult *= attenuation;
+                    r = ray;
+                }
+                ScatterReflect::Scatter(pdf) => {
+                    let colour_from_punctual =
+                        lighting.punctual_light_colour(&r, &rec, attenuation);
+                    let colour_from_lights =
+                        Self::estimate_direct(&r, &rec, attenuation, pdf.as_ref(), &lighting, rng);
+                    return mult
+                        * (colour_from_emission + colour_from_punctual + colour_from_lights);
+                }
+            }
+        }
+        Colour::default()
+    }
+}
+
+impl DirectLighting {
+    /// Light reaching `rec` directly, weighting the light and material samples with the balance
+    /// heuristic.
+    pub(crate) fn estimate_direct(
+        r: &Ray,
+        rec: &HitRecord<'_>,
+        attenuation: Colour,
+        material_pdf: &dyn Pdf,
+        lighting: &Lighting<'_>,
+        rng: &mut dyn rand::RngCore,
+    ) -> Colour {
+        let p = rec.get_p();
+        let contribution = |direction| {
+            let scattered_ray = Ray::new(p, direction);
+            let light_pdf = lighting
+                .lights
+                .map_or(0., |lights| lights.pdf_value(p, direction));
+            let pdf_value = light_pdf + material_pdf.value(&direction);
+            if pdf_value <= 0. {
+                return Colour::default();
+            }
+            let scattering_pdf = rec.get_material().scattering_pdf(r, rec, &scattered_ray);
+            (attenuation * scattering_pdf * lighting.incoming_emission(&scattered_ray) / pdf_value)
+                .fix_nan()
+        };
+        let from_material = contribution(material_pdf.generate(rng));
+        let from_lights = lighting.lights.map_or(Colour::default(), |lights| {
+            contribution(lights.random(p, rng))
+        });
+        from_material + from_lights
+    }
+}
@@ -0,0 +1,360 @@
+use std::{
+    collections::HashMap,
+    f64::consts::{PI, TAU},
+};
+
+use rand::Rng as _;
+use rayon::iter::{IntoParallelIterator as _, ParallelIterator as _};
+
+use geometry::vec3::{Point3, Vec3};
+
+use crate::{
+    camera::{Camera, Film},
+    colour::Colour,
+    material::ScatterReflect,
+    pdf::{MixturePdf, Pdf},
+    ray::Ray,
+};
+
+use super::{Integrator, Lighting};
+
+/// Bins of the directional histograms along the `z` axis.
+const Z_BINS: usize = 8;
+/// Bins of the directional histograms around the `z` axis.
+const PHI_BINS: usize = 16;
+const BINS: usize = Z_BINS * PHI_BINS;
+
+/// Path tracer which learns where the light comes from, see "Practical path guiding for
+/// efficient light-transport simulation" by Müller et al. for a fancier version.
+///
+/// Space is split into a grid of cells, each with a histogram of the light arriving from every
+/// direction. The histograms are trained with the paths of the first passes and mixed with the
+/// material when sampling, so indirect light is found more often. The lights are sampled as with
+/// [`LightingStrategy::Mixture`](super::LightingStrategy::Mixture).
+///
+/// The camera renders it one pass per sample, which can be reproduced with
+/// [`CameraBuilder::with_seed`](crate::camera::CameraBuilder::with_seed). Single rays are shaded
+/// without guiding.
+#[derive(Debug, Clone, Copy)]
+pub struct GuidedPathTracer {
+    training_passes: u16,
+    resolution: u32,
+}
+
+impl GuidedPathTracer {
+    pub const fn new() -> Self {
+        Self {
+            training_passes: 4,
+            resolution: 16,
+        }
+    }
+
+    /// Passes whose paths train the guide, the rest only use it.
+    pub const fn with_training_passes(self, training_passes: u16) -> Self {
+        Self {
+            training_passes,
+            ..self
+        }
+    }
+
+    /// Cells of the grid along the longest side of the region the first pass reaches.
+    pub const fn with_resolution(self, resolution: u32) -> Self {
+        Self { resolution, ..self }
+    }
+}
+
+impl Default for GuidedPathTracer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Integrator for GuidedPathTracer {
+    /// Single-ray fallback, the guide is only trained when rendering the whole image so the ray
+    /// is traced without guiding.
+    fn radiance(
+        &self,
+        r: &Ray,
+        lighting: &Lighting<'_>,
+        _film: &Film,
+        rng: &mut dyn rand::RngCore,
+        depth: u32,
+    ) -> Colour {
+        Self::guided_radiance(r, lighting, None, rng, depth, None)
+    }
+
+    /// Renders one sample of every pixel at a time, training the guide with the first passes.
+    fn render(&self, camera: &Camera, lighting: &Lighting<'_>, film: &Film) {
+        let (width, height) = camera.get_image_size();
+        let mut guide = None;
+
+        for pass in 0..camera.get_samples_per_pixel() {
+            let training = self.is_training(pass);
+            let samples: Vec<_> = (0..width * height)
+                .into_par_iter()
+                .map(|index| {
+                    let (i, j) = (index % width, index / width);
+                    let mut rng = camera.pixel_rng(pass.into(), index);
+                    let mut samples = Vec::new();
+                    if let Some((r, weight)) = camera.sample_ray(i, j, &mut rng) {
+                        let colour = camera.trace_sample(&mut rng, lighting, |rng, lighting| {
+                            Self::guided_radiance(
+                                &r,
+                                lighting,
+                                guide.as_ref(),
+                                rng,
+                                camera.get_max_depth(),
+                                training.then_some(&mut samples),
+                            )
+                        });
+                        film.add_pixel([i, j], colour * weight);
+                    }
+                    samples
+                })
+                .collect();
+            // In pixel order, so a seeded render always learns the same guide
+            if training {
+                let samples: Vec<GuideSample> = samples.into_iter().flatten().collect();
+                self.train(&mut guide, &samples);
+            }
+        }
+    }
+}
+
+/// Light arriving at a point of a path from the direction the path continued in.
+struct GuideSample {
+    p: Point3,
+    direction: Vec3,
+    /// Luminance divided by the density of the direction
+    weight: f64,
+}
+
+impl GuidedPathTracer {
+    /// Whether the paths of the `pass`-th pass train the guide.
+    const fn is_training(&self, pass: u16) -> bool {
+        pass < self.training_passes
+    }
+
+    /// Adds a pass worth of `samples` to `guide`, creating it if it's the first pass.
+    fn train(&self, guide: &mut Option<Guide>, samples: &[GuideSample]) {
+        let guide = match guide {
+            Some(guide) => guide,
+            None => guide.insert(Guide::new(self.resolution, samples)),
+        };
+        for sample in samples {
+            guide.add(sample);
+        }
+        guide.refresh();
+    }
+
+    /// Light arriving at the origin of `r`, sampling directions from `guide` if there's one.
+    /// The light found in the direction each path continued in is added to `samples`.
+    fn guided_radiance(
+        r: &Ray,
+        lighting: &Lighting<'_>,
+        guide: Option<&Guide>,
+        rng: &mut dyn rand::RngCore,
+        depth: u32,
+        mut samples: Option<&mut Vec<GuideSample>>,
+    ) -> Colour {
+        let mut lighting = *lighting;
+        let mut r = r.clone();
+        let mut mult = Colour::from_array([1., 1., 1.]);
+        let mut res = Colour::default();
+        // Light found before each scattering, and the throughput after it
+        let mut vertices = Vec::new();
+        for _ in 0..depth {
+            let Some(rec) = lighting.world.hit(&r, (f64::EPSILON)..=f64::INFINITY) else {
+                res += mult * lighting.background_colour(&r);
+                break;
+            };
+            let colours = lighting.get_colours();
+            res += mult * rec.get_material().emitted_rec(&r, &rec, colours);
+            let Some(srec) = rec.get_material().scatter(&r, &rec, colours, rng) else {
+                break;
+            };
+            let attenuation = lighting.attenuation(&srec);
+            let material_pdf = match srec.scatter_reflect {
+                ScatterReflect::Reflect(ray) => {
+                    mult *= attenuation;
+                    r = ray;
+                    continue;
+                }
+                ScatterReflect::Scatter(pdf) => pdf,
+            };
+            res += mult * lighting.punctual_light_colour(&r, &rec, attenuation);
+
+            let p = rec.get_p();
+            let guide_pdf = guide.and_then(|guide| guide.pdf(p));
+            let mixture;
+            let pdf = match &guide_pdf {
+                Some(guide_pdf) => {
+                    mixture = MixturePdf::new(guide_pdf, material_pdf.as_ref());
+                    &mixture as &dyn Pdf
+                }
+                None => material_pdf.as_ref(),
+            };
+            let (scattered_ray, pdf_value) = lighting.sample_direction(&rec, pdf, rng);
+            if pdf_value <= 0. {
+                break;
+            }
+            let scattering_pdf = rec.get_material().scattering_pdf(&r, &rec, &scattered_ray);
+            mult *= attenuation * scattering_pdf / pdf_value;
+            if samples.is_some() {
+                vertices.push((p, scattered_ray.get_direction(), pdf_value, res, mult));
+            }
+            r = scattered_ray;
+        }
+
+        if let Some(samples) = &mut samples {
+            samples.extend(
+                vertices
+                    .into_iter()
+                    .filter_map(|(p, direction, pdf_value, res_before, mult)| {
+                        let throughput = mult.luminance();
+                        (throughput > 0.).then(|| GuideSample {
+                            p,
+                            direction: direction.normalize(),
+                            weight: (res.luminance() - res_before.luminance())
+                                / throughput
+                                / pdf_value,
+                        })
+                    })
+                    .filter(|sample| sample.weight.is_finite() && sample.weight > 0.),
+            );
+        }
+        res
+    }
+}
+
+/// Histograms of the light arriving at each cell of a grid.
+struct Guide {
+    origin: Point3,
+    cell_size: f64,
+    cells: HashMap<[i64; 3], Histogram>,
+}
+
+impl Guide {
+    /// Fits the grid to the region reached by `samples`.
+    fn new(resolution: u32, samples: &[GuideSample]) -> Self {
+        let (min, max) = samples.iter().fold(
+            (
+                Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+                Point3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            ),
+            |(min, max), GuideSample { p, .. }| {
+                (
+                    Point3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                    Point3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+                )
+            },
+        );
+        let extent = max - min;
+        let longest = extent.x.max(extent.y).max(extent.z);
+        Self {
+            origin: if longest.is_finite() {
+                min
+            } else {
+                Point3::new(0., 0., 0.)
+            },
+            cell_size: if longest.is_finite() && longest > 0. {
+                longest / f64::from(resolution.max(1))
+            } else {
+                1.
+            },
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, p: Point3) -> [i64; 3] {
+        let p = (p - self.origin) / self.cell_size;
+        [p.x, p.y, p.z].map(|coordinate| coordinate.floor() as i64)
+    }
+
+    fn add(&mut self, sample: &GuideSample) {
+        let cell = self.cell(sample.p);
+        self.cells
+            .entry(cell)
+            .or_default()
+            .add(sample.direction, sample.weight);
+    }
+
+    fn refresh(&mut self) {
+        self.cells.values_mut().for_each(Histogram::refresh);
+    }
+
+    /// Directions the light arrives from at `p`, `None` if nothing was learnt there.
+    fn pdf(&self, p: Point3) -> Option<GuidePdf<'_>> {
+        self.cells
+            .get(&self.cell(p))
+            .filter(|histogram| histogram.total > 0.)
+            .map(|histogram| GuidePdf { histogram })
+    }
+}
+
+/// Equal area bins over the sphere, split by the `z` coordinate and the angle around `z`.
+#[derive(Debug)]
+struct Histogram {
+    bins: [f64; BINS],
+    /// Normalized running sum of `bins`, as of the last refresh
+    cdf: [f64; BINS],
+    total: f64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            bins: [0.; BINS],
+            cdf: [0.; BINS],
+            total: 0.,
+        }
+    }
+}
+
+impl Histogram {
+    fn bin(direction: Vec3) -> usize {
+        let z = ((direction.z + 1.) / 2. * Z_BINS as f64) as usize;
+        let phi = (direction.y.atan2(direction.x) + PI) / TAU * PHI_BINS as f64;
+        z.min(Z_BINS - 1) * PHI_BINS + (phi as usize).min(PHI_BINS - 1)
+    }
+
+    fn add(&mut self, direction: Vec3, weight: f64) {
+        self.bins[Self::bin(direction)] += weight;
+    }
+
+    fn refresh(&mut self) {
+        self.total = self.bins.iter().sum();
+        let mut acc = 0.;
+        for (cdf, bin) in self.cdf.iter_mut().zip(self.bins) {
+            acc += bin;
+            *cdf = acc / self.total;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct GuidePdf<'a> {
+    histogram: &'a Histogram,
+}
+
+impl Pdf for GuidePdf<'_> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let bin = Histogram::bin(direction.normalize());
+        let probability = self.histogram.bins[bin] / self.histogram.total;
+        probability * BINS as f64 / (4. * PI)
+    }
+
+    fn generate(&self, rng: &mut dyn rand::RngCore) -> Vec3 {
+        let u: f64 = rng.r#gen();
+        let bin = self
+            .histogram
+            .cdf
+            .partition_point(|&p| p <= u)
+            .min(BINS - 1);
+        let (z_bin, phi_bin) = (bin / PHI_BINS, bin % PHI_BINS);
+        let z = (z_bin as f64 + rng.r#gen::<f64>()) / Z_BINS as f64 * 2. - 1.;
+        let phi = (phi_bin as f64 + rng.r#gen::<f64>()) / PHI_BINS as f64 * TAU - PI;
+        let r = (1. - z * z).max(0.).sqrt();
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+}
@@ -0,0 +1,371 @@
+use std::f64::consts::TAU;
+
+use rand::{Rng as _, RngCore, SeedableRng as _, rngs::SmallRng};
+use rayon::iter::{IntoParallelIterator as _, ParallelIterator as _};
+
+use crate::{
+    camera::{Camera, Film},
+    colour::Colour,
+    ray::Ray,
+};
+
+use super::{Integrator, Lighting, PathTracer};
+
+/// Primary sample space Metropolis light transport, see "A simple and robust mutation strategy
+/// for the Metropolis light transport algorithm" by Kelemen et al.
+///
+/// Paths are traced by the [`PathTracer`], but instead of fresh random numbers they are built
+/// from a [`PrimarySampleVector`] which is mutated, so once a path carrying a lot of light is
+/// found the ones near it are explored too. The pixel of each path is picked by its first two
+/// samples.
+///
+/// The camera renders it with as many mutations as samples, while single rays are shaded by the
+/// [`PathTracer`].
+#[derive(Debug, Clone, Copy)]
+pub struct MetropolisLightTransport {
+    large_step_probability: f64,
+    sigma: f64,
+    bootstrap_samples: u32,
+    chains: u32,
+}
+
+impl MetropolisLightTransport {
+    pub const fn new() -> Self {
+        Self {
+            large_step_probability: 0.3,
+            sigma: 0.01,
+            bootstrap_samples: 100_000,
+            chains: 1000,
+        }
+    }
+
+    /// Probability of replacing every sample instead of perturbing them.
+    pub const fn with_large_step_probability(self, large_step_probability: f64) -> Self {
+        Self {
+            large_step_probability,
+            ..self
+        }
+    }
+
+    /// Standard deviation of the perturbation of a small step.
+    pub const fn with_sigma(self, sigma: f64) -> Self {
+        Self { sigma, ..self }
+    }
+
+    /// Paths traced to estimate the brightness of the image and to start the chains from.
+    pub const fn with_bootstrap_samples(self, bootstrap_samples: u32) -> Self {
+        Self {
+            bootstrap_samples,
+            ..self
+        }
+    }
+
+    /// Markov chains run in parallel, there are never more of them than mutations.
+    pub const fn with_chains(self, chains: u32) -> Self {
+        Self { chains, ..self }
+    }
+}
+
+impl Default for MetropolisLightTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Integrator for MetropolisLightTransport {
+    fn radiance(
+        &self,
+        r: &Ray,
+        lighting: &Lighting<'_>,
+        film: &Film,
+        rng: &mut dyn rand::RngCore,
+        depth: u32,
+    ) -> Colour {
+        PathTracer.radiance(r, lighting, film, rng, depth)
+    }
+
+    /// Renders as many mutations as samples.
+    fn render(&self, camera: &Camera, lighting: &Lighting<'_>, film: &Film) {
+        let (width, height) = camera.get_image_size();
+        let path = |sampler: &mut PrimarySampleVector| {
+            let i = ((sampler.next_f64() * width as f64) as usize).min(width - 1);
+            let j = ((sampler.next_f64() * height as f64) as usize).min(height - 1);
+            let (r, weight) = camera.sample_ray(i, j, sampler)?;
+            let colour = camera.trace_sample(sampler, lighting, |sampler, lighting| {
+                PathTracer.radiance(&r, lighting, film, sampler, camera.get_max_depth())
+            });
+            Some(([i, j], colour * weight))
+        };
+        let mutations = (width * height) as u64 * u64::from(camera.get_samples_per_pixel());
+        self.run_chains(film, mutations, |index| camera.pixel_rng(0, index), path);
+    }
+}
+
+impl MetropolisLightTransport {
+    /// Runs `mutations` mutations split over the chains, splatting the paths traced by `path` onto
+    /// `film`. `path` returns the pixel and light of the path built from the samples it's given.
+    ///
+    /// The splats add up to the image times the mutations per pixel. The bootstrap draws its
+    /// random numbers from `rng` of 0 and each chain from `rng` of its index plus one.
+    fn run_chains(
+        &self,
+        film: &Film,
+        mutations: u64,
+        rng: impl Fn(usize) -> SmallRng + Sync,
+        path: impl Fn(&mut PrimarySampleVector) -> Option<([usize; 2], Colour)> + Sync,
+    ) {
+        let contribution = |sampler: &mut PrimarySampleVector| {
+            path(sampler).map_or(([0, 0], Colour::default(), 0.), |(pixel, colour)| {
+                (pixel, colour, colour.luminance())
+            })
+        };
+
+        // Bootstrap paths are replayed from their seeds
+        let first_seed: u64 = rng(0).r#gen();
+        let sampler = |index: usize| {
+            PrimarySampleVector::new(
+                first_seed.wrapping_add(index as u64),
+                self.large_step_probability,
+                self.sigma,
+            )
+        };
+        let weights: Vec<_> = (0..self.bootstrap_samples as usize)
+            .into_par_iter()
+            .map(|index| contribution(&mut sampler(index)).2)
+            .collect();
+        let total: f64 = weights.iter().sum();
+        // Chains without mutations would only add their first path
+        let chains = u64::from(self.chains).min(mutations);
+        if total <= 0. || chains == 0 {
+            return;
+        }
+        // Average brightness of the image
+        let normalization = total / f64::from(self.bootstrap_samples);
+        let cdf: Vec<_> = weights
+            .iter()
+            .scan(0., |acc, weight| {
+                *acc += weight / total;
+                Some(*acc)
+            })
+            .collect();
+
+        (0..chains).into_par_iter().for_each(|chain| {
+            // Every mutation is run even when they don't split evenly
+            let mutations = (chain + 1) * mutations / chains - chain * mutations / chains;
+            let mut rng = rng(chain as usize + 1);
+            // Start from a bootstrap path, picked by how much light it carries
+            let u: f64 = rng.r#gen();
+            let mut sampler = sampler(cdf.partition_point(|&p| p < u).min(cdf.len() - 1));
+            let mut current = contribution(&mut sampler);
+            for _ in 0..mutations {
+                sampler.start_iteration();
+                let proposed = contribution(&mut sampler);
+                let accept = if current.2 > 0. {
+                    (proposed.2 / current.2).min(1.)
+                } else {
+                    1.
+                };
+                // Both paths are splatted, weighted by their odds of being the next state
+                if proposed.2 > 0. {
+                    film.add_splat(
+                        proposed.0,
+                        proposed.1 * (accept * normalization / proposed.2),
+                    );
+                }
+                if current.2 > 0. {
+                    film.add_splat(
+                        current.0,
+                        current.1 * ((1. - accept) * normalization / current.2),
+                    );
+                }
+                if rng.r#gen::<f64>() < accept {
+                    current = proposed;
+                    sampler.accept();
+                } else {
+                    sampler.reject();
+                }
+            }
+        });
+    }
+}
+
+/// Random numbers used to build a path, which can be replayed and mutated.
+///
+/// It implements [`RngCore`] so it can be handed to the camera, pdfs and materials in place of
+/// a random number generator. Samples are mutated lazily, only when they are used.
+#[derive(Debug, Clone)]
+pub struct PrimarySampleVector {
+    /// Source of new samples and perturbations
+    rng: SmallRng,
+    samples: Vec<PrimarySample>,
+    /// Next sample to use
+    index: usize,
+    iteration: u64,
+    last_large_step: u64,
+    large_step: bool,
+    large_step_probability: f64,
+    sigma: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PrimarySample {
+    value: f64,
+    last_modified: u64,
+    /// Value before the current iteration, restored if it's rejected
+    backup: f64,
+    backup_modified: u64,
+}
+
+impl PrimarySampleVector {
+    /// The same `seed` replays the same samples until the first mutation.
+    pub fn new(seed: u64, large_step_probability: f64, sigma: f64) -> Self {
+        Self {
+            rng: SmallRng::seed_from_u64(seed),
+            samples: Vec::new(),
+            index: 0,
+            iteration: 0,
+            last_large_step: 0,
+            large_step: true,
+            large_step_probability,
+            sigma,
+        }
+    }
+
+    /// Mutates the samples, which are then used from the start.
+    pub fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.rng.r#gen::<f64>() < self.large_step_probability;
+        self.index = 0;
+    }
+
+    /// Keeps the samples of the current iteration.
+    pub fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    /// Restores the samples from before the current iteration.
+    pub fn reject(&mut self) {
+        for sample in &mut self.samples {
+            if sample.last_modified == self.iteration {
+                sample.value = sample.backup;
+                sample.last_modified = sample.backup_modified;
+            }
+        }
+        self.iteration -= 1;
+    }
+
+    /// Next sample, in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        if self.index == self.samples.len() {
+            // As if it was drawn by the last accepted large step
+            self.samples.push(PrimarySample {
+                value: self.rng.r#gen(),
+                last_modified: self.last_large_step,
+                ..PrimarySample::default()
+            });
+        }
+        let mut sample = self.samples[self.index];
+        // Catch up with the last accepted large step
+        if sample.last_modified < self.last_large_step {
+            sample.value = self.rng.r#gen();
+            sample.last_modified = self.last_large_step;
+        }
+        sample.backup = sample.value;
+        sample.backup_modified = sample.last_modified;
+        if self.large_step {
+            sample.value = self.rng.r#gen();
+        } else {
+            // The small steps it missed add up to a single wider one
+            let steps = (self.iteration - sample.last_modified) as f64;
+            let normal = (-2. * (1. - self.rng.r#gen::<f64>()).ln()).sqrt()
+                * (TAU * self.rng.r#gen::<f64>()).cos();
+            sample.value += normal * self.sigma * steps.sqrt();
+            sample.value -= sample.value.floor();
+        }
+        sample.last_modified = self.iteration;
+        self.samples[self.index] = sample;
+        self.index += 1;
+        sample.value
+    }
+}
+
+impl RngCore for PrimarySampleVector {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Inverse of how [`rand`] turns bits into an `f64`, so the samples reach the consumers
+    /// unchanged.
+    fn next_u64(&mut self) -> u64 {
+        ((self.next_f64() * (1u64 << 53) as f64) as u64) << 11
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng as _, rngs::SmallRng};
+
+    use crate::{camera::Film, colour::Colour};
+
+    use super::{MetropolisLightTransport, PrimarySampleVector};
+
+    #[test]
+    fn samples_are_replayed_and_restored() {
+        let mut sampler = PrimarySampleVector::new(7, 0.3, 0.01);
+        let first: Vec<_> = (0..8).map(|_| sampler.next_f64()).collect();
+        let mut replayed = PrimarySampleVector::new(7, 0.3, 0.01);
+        assert!((0..8).all(|i| replayed.next_f64() == first[i]));
+        assert!(first.iter().all(|sample| (0. ..1.).contains(sample)));
+
+        for _ in 0..100 {
+            sampler.start_iteration();
+            let mutated: Vec<_> = (0..8).map(|_| sampler.next_f64()).collect();
+            assert!(mutated.iter().all(|sample| (0. ..1.).contains(sample)));
+            sampler.reject();
+        }
+        // Rejected mutations leave the samples as they were
+        assert!(
+            sampler
+                .samples
+                .iter()
+                .zip(&first)
+                .all(|(sample, &first)| sample.value == first)
+        );
+    }
+
+    #[test]
+    fn every_mutation_is_run() {
+        // Fewer mutations than chains, which don't split evenly either
+        for mutations in [60_u32, 1001] {
+            let film = Film::new(1, 1);
+            MetropolisLightTransport::new()
+                .with_bootstrap_samples(100)
+                .run_chains(
+                    &film,
+                    mutations.into(),
+                    |index| SmallRng::seed_from_u64(index as u64),
+                    |sampler| {
+                        sampler.next_f64();
+                        Some(([0, 0], Colour::new(1., 1., 1.)))
+                    },
+                );
+            // Every mutation splats the average brightness
+            let splat = film.get_pixel(0, 0).into_inner();
+            assert!((splat.x - f64::from(mutations)).abs() < 1e-6, "{splat:?}");
+        }
+    }
+}
@@ -0,0 +1,376 @@
+#[cfg(feature = "hit_counters")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rand::Rng as _;
+use rayon::iter::{
+    IndexedParallelIterator as _, IntoParallelIterator as _, IntoParallelRefIterator as _,
+    IntoParallelRefMutIterator as _, ParallelIterator as _,
+};
+
+use crate::{
+    camera::{Camera, Film},
+    colour::Colour,
+    hittable::Hittable,
+    material::ScatterReflect,
+    pdf::Pdf,
+    ray::Ray,
+};
+
+use super::{Integrator, Lighting, LightingStrategy, Reservoir, ShadingPoint};
+
+#[cfg(feature = "hit_counters")]
+pub(crate) static HIT_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Unidirectional path tracer, direct light is estimated as set by the [`LightingStrategy`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PathTracer;
+
+impl Integrator for PathTracer {
+    fn radiance(
+        &self,
+        r: &Ray,
+        lighting: &Lighting<'_>,
+        _film: &Film,
+        rng: &mut dyn rand::RngCore,
+        depth: u32,
+    ) -> Colour {
+        Self::ray_colour_tail_call(
+            r.clone(),
+            lighting,
+            rng,
+            Colour::from_array([1., 1., 1.]),
+            Colour::default(),
+            depth,
+            false,
+        )
+    }
+
+    fn render(&self, camera: &Camera, lighting: &Lighting<'_>, film: &Film) {
+        match (lighting.get_strategy(), lighting.get_lights()) {
+            (
+                LightingStrategy::Ris {
+                    candidates,
+                    spatial_reuse: true,
+                },
+                Some(lights),
+            ) => Self::render_spatial_reuse(camera, lighting, lights, candidates, film),
+            _ => camera.render_pixels(self, lighting, film),
+        }
+    }
+}
+
+impl PathTracer {
+    /// Renders one sample of every pixel at a time, so the first hits can share their light
+    /// samples with their neighbours before being shaded.
+    fn render_spatial_reuse(
+        camera: &Camera,
+        lighting: &Lighting<'_>,
+        lights: &dyn Hittable,
+        candidates: u16,
+        film: &Film,
+    ) {
+        const NEIGHBOURS: usize = 4;
+        /// In pixels
+        const RADIUS: isize = 8;
+
+        let (width, height) = camera.get_image_size();
+        let max_depth = camera.get_max_depth();
+        let mut rngs: Vec<_> = (0..width * height)
+            .map(|index| camera.pixel_rng(0, index))
+            .collect();
+
+        for _ in 0..camera.get_samples_per_pixel() {
+            let first_hits: Vec<_> = rngs
+                .par_iter_mut()
+                .enumerate()
+                .map(|(index, rng)| {
+                    let Some((r, weight)) = camera.sample_ray(index % width, index / width, rng)
+                    else {
+                        return FirstHit::Done(Colour::default());
+                    };
+                    let lighting = lighting.with_wavelengths(camera.sample_wavelengths(rng));
+                    Self::first_hit(r, weight, &lighting, lights, candidates, max_depth, rng)
+                })
+                .collect();
+            let reservoirs: Vec<_> = first_hits
+                .par_iter()
+                .zip(rngs.par_iter_mut())
+                .enumerate()
+                .map(|(index, (first_hit, rng))| {
+                    let FirstHit::Shading {
+                        point, reservoir, ..
+                    } = first_hit
+                    else {
+                        return None;
+                    };
+                    let (i, j) = ((index % width) as isize, (index / width) as isize);
+                    let neighbours: Vec<_> = (0..NEIGHBOURS)
+                        .filter_map(|_| {
+                            let i = usize::try_from(i + rng.gen_range(-RADIUS..=RADIUS)).ok()?;
+                            let j = usize::try_from(j + rng.gen_range(-RADIUS..=RADIUS)).ok()?;
+                            (i < width && j < height).then_some(())?;
+                            match &first_hits[j * width + i] {
+                                // Only reuse samples from similar surfaces
+                                FirstHit::Shading {
+                                    point: other,
+                                    reservoir,
+                                    ..
+                                } if other.rec.get_normal().dot(point.rec.get_normal()) > 0.9
+                                    && (other.rec.get_t() - point.rec.get_t()).abs()
+                                        < 0.1 * point.rec.get_t() =>
+                                {
+                                    Some(reservoir)
+                                }
+                                _ => None,
+                            }
+                        })
+                        .collect();
+                    Some(point.reuse(lights, std::iter::once(reservoir).chain(neighbours), rng))
+                })
+                .collect();
+            first_hits
+                .into_par_iter()
+                .zip(reservoirs)
+                .zip(rngs.par_iter_mut())
+                .enumerate()
+                .for_each(|(index, ((first_hit, reservoir), rng))| {
+                    let colour =
+                        Self::finish_first_hit(first_hit, reservoir, lighting, rng, max_depth);
+                    film.add_pixel([index % width, index / width], colour);
+                });
+        }
+    }
+
+    /// `sampled_lights` is whether the lights were sampled directly at the origin of `r`, in
+    /// which case the light they cover was already added.
+    fn ray_colour_tail_call(
+        r: Ray,
+        lighting: &Lighting<'_>,
+        rng: &mut dyn rand::RngCore,
+        mult: Colour,
+        res: Colour,
+        depth: u32,
+        sampled_lights: bool,
+    ) -> Colour {
+        if depth == 0 {
+            return Colour::default() + res;
+        }
+        let counts_emission = !sampled_lights || !lighting.is_light_sampled(&r);
+        let Some(rec) = lighting.world.hit(&r, (f64::EPSILON)..=f64::INFINITY) else {
+            if !counts_emission {
+                return res;
+            }
+            return mult * lighting.background_colour(&r) + res;
+        };
+
+        #[cfg(feature = "hit_counters")]
+        HIT_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+        let colours = lighting.get_colours();
+        let colour_from_emission = if counts_emission {
+            rec.get_material().emitted_rec(&r, &rec, colours)
+        } else {
+            Colour::default()
+        };
+
+        let Some(srec) = rec.get_material().scatter(&r, &rec, colours, rng) else {
+            return mult * colour_from_emission + res;
+        };
+        let mut lighting = *lighting;
+        let attenuation = lighting.attenuation(&srec);
+        let lighting = &lighting;
+
+        let pdf_ptr = match srec.scatter_reflect {
+            ScatterReflect::Reflect(ray) => {
+                return Self::ray_colour_tail_call(
+                    ray,
+                    lighting,
+                    rng,
+                    mult * attenuation,
+                    res,
+                    depth - 1,
+                    false,
+                );
+            }
+            ScatterReflect::Scatter(pdf) => pdf,
+        };
+
+        let colour_from_punctual = lighting.punctual_light_colour(&r, &rec, attenuation);
+
+        if let (LightingStrategy::Ris { candidates, .. }, Some(lights)) =
+            (lighting.strategy, lighting.lights)
+        {
+            let shading_point = ShadingPoint {
+                ray_in: r,
+                rec,
+                attenuation,
+                colours: lighting.get_colours(),
+            };
+            let reservoir = shading_point.resample(lights, candidates, rng);
+            let colour_from_lights = shading_point.shade(lighting, &reservoir);
+            return Self::continue_from(
+                &shading_point,
+                pdf_ptr.as_ref(),
+                lighting,
+                rng,
+                mult,
+                res + mult * (colour_from_emission + colour_from_punctual + colour_from_lights),
+                depth,
+            );
+        }
+
+        let (scattered_ray, pdf_value) = lighting.sample_direction(&rec, pdf_ptr.as_ref(), rng);
+
+        let scattering_pdf = rec.get_material().scattering_pdf(&r, &rec, &scattered_ray);
+
+        Self::ray_colour_tail_call(
+            scattered_ray,
+            lighting,
+            rng,
+            mult * (attenuation * scattering_pdf / pdf_value),
+            res + mult * (colour_from_emission + colour_from_punctual),
+            depth - 1,
+            false,
+        )
+    }
+
+    /// Continues a path from a point where the lights were already sampled, following the
+    /// material only.
+    fn continue_from(
+        shading_point: &ShadingPoint<'_>,
+        material_pdf: &dyn Pdf,
+        lighting: &Lighting<'_>,
+        rng: &mut dyn rand::RngCore,
+        mult: Colour,
+        res: Colour,
+        depth: u32,
+    ) -> Colour {
+        let ShadingPoint {
+            ray_in,
+            rec,
+            attenuation,
+            ..
+        } = shading_point;
+        let (scattered_ray, pdf_value) = Lighting::sample_material(material_pdf, rec.get_p(), rng);
+        let scattering_pdf = rec
+            .get_material()
+            .scattering_pdf(ray_in, rec, &scattered_ray);
+        Self::ray_colour_tail_call(
+            scattered_ray,
+            lighting,
+            rng,
+            mult * (*attenuation * scattering_pdf / pdf_value),
+            res,
+            depth - 1,
+            true,
+        )
+    }
+
+    /// Traces a camera ray up to its first diffuse hit, where the lights are resampled but not
+    /// yet shaded.
+    fn first_hit<'a>(
+        r: Ray,
+        weight: f64,
+        lighting: &Lighting<'a>,
+        lights: &dyn Hittable,
+        candidates: u16,
+        depth: u32,
+        rng: &mut dyn rand::RngCore,
+    ) -> FirstHit<'a> {
+        if depth == 0 {
+            return FirstHit::Done(Colour::default());
+        }
+        let Some(rec) = lighting.world.hit(&r, (f64::EPSILON)..=f64::INFINITY) else {
+            return FirstHit::Done(lighting.to_rgb(lighting.background_colour(&r) * weight));
+        };
+        let colours = lighting.get_colours();
+        let colour_from_emission = rec.get_material().emitted_rec(&r, &rec, colours);
+        let Some(srec) = rec.get_material().scatter(&r, &rec, colours, rng) else {
+            return FirstHit::Done(lighting.to_rgb(colour_from_emission * weight));
+        };
+        let mut lighting = *lighting;
+        let attenuation = lighting.attenuation(&srec);
+        match srec.scatter_reflect {
+            ScatterReflect::Reflect(ray) => FirstHit::Done(lighting.to_rgb(
+                Self::ray_colour_tail_call(
+                    ray,
+                    &lighting,
+                    rng,
+                    attenuation,
+                    Colour::default(),
+                    depth - 1,
+                    false,
+                ) * weight,
+            )),
+            ScatterReflect::Scatter(pdf) => {
+                let colour =
+                    colour_from_emission + lighting.punctual_light_colour(&r, &rec, attenuation);
+                let point = ShadingPoint {
+                    ray_in: r,
+                    rec,
+                    attenuation,
+                    colours: lighting.get_colours(),
+                };
+                let reservoir = point.resample(lights, candidates, rng);
+                FirstHit::Shading {
+                    point,
+                    pdf,
+                    reservoir,
+                    colour,
+                    weight,
+                }
+            }
+        }
+    }
+
+    /// Shades a [`FirstHit`] with the light sample of `reservoir`, or its own if it's `None`,
+    /// and follows the rest of the path. Returns the light of the sample in RGB.
+    fn finish_first_hit(
+        first_hit: FirstHit<'_>,
+        reservoir: Option<Reservoir>,
+        lighting: &Lighting<'_>,
+        rng: &mut dyn rand::RngCore,
+        depth: u32,
+    ) -> Colour {
+        match first_hit {
+            FirstHit::Done(colour) => colour,
+            FirstHit::Shading {
+                point,
+                pdf,
+                reservoir: own,
+                colour,
+                weight,
+            } => {
+                let lighting = lighting.with_wavelengths(point.colours.get_wavelengths());
+                let colour_from_lights = point.shade(&lighting, &reservoir.unwrap_or(own));
+                let colour = Self::continue_from(
+                    &point,
+                    pdf.as_ref(),
+                    &lighting,
+                    rng,
+                    Colour::from_array([1., 1., 1.]),
+                    colour + colour_from_lights,
+                    depth,
+                ) * weight;
+                lighting.to_rgb(colour)
+            }
+        }
+    }
+}
+
+/// First hit of a camera ray when rendering with spatial reuse.
+// There's only one per pixel at a time, so boxing wouldn't save much
+#[allow(clippy::large_enum_variant)]
+enum FirstHit<'a> {
+    /// The path didn't need to resample any light, its light is in RGB
+    Done(Colour),
+    Shading {
+        point: ShadingPoint<'a>,
+        pdf: Box<dyn Pdf>,
+        reservoir: Reservoir,
+        /// Light which doesn't come from the reservoir
+        colour: Colour,
+        /// Weight of the camera sample
+        weight: f64,
+    },
+}
@@ -0,0 +1,369 @@
+use std::{
+    collections::HashMap,
+    f64::consts::PI,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use rand::rngs::SmallRng;
+use rayon::iter::{
+    IndexedParallelIterator as _, IntoParallelIterator as _, IntoParallelRefMutIterator as _,
+    ParallelIterator as _,
+};
+
+use geometry::vec3::{Point3, Vec3};
+
+use crate::{
+    camera::{Camera, Film},
+    colour::Colour,
+    material::ScatterReflect,
+    ray::Ray,
+};
+
+use super::{
+    DirectLighting, Integrator, Lighting, PathTracer, ShadingPoint, bdpt::sample_emission,
+};
+
+/// Stochastic progressive photon mapping, see "Stochastic progressive photon mapping" by
+/// Hachisuka and Jensen.
+///
+/// Every pass traces a ray per pixel through mirrors and glass until it lands on a diffuse
+/// surface, where the direct light is sampled as usual. Photons are then shot from the lights
+/// and the ones landing close to those points are gathered. The gather radius of each pixel
+/// shrinks as it collects photons, so caustics converge without having to find the lights
+/// through the glass.
+///
+/// The camera renders it one pass per sample, while single rays are shaded by the
+/// [`PathTracer`]. Light from the background only reaches the camera directly or through a
+/// single diffuse bounce. When rendering spectrally all the pixels and photons of a pass carry
+/// the same wavelengths.
+#[derive(Debug, Clone, Copy)]
+pub struct ProgressivePhotonMapper {
+    initial_radius: f64,
+    photons_per_pass: u32,
+    alpha: f64,
+}
+
+impl ProgressivePhotonMapper {
+    pub const fn new(initial_radius: f64) -> Self {
+        Self {
+            initial_radius,
+            photons_per_pass: 100_000,
+            alpha: 2. / 3.,
+        }
+    }
+
+    pub const fn with_photons_per_pass(self, photons_per_pass: u32) -> Self {
+        Self {
+            photons_per_pass,
+            ..self
+        }
+    }
+
+    /// Fraction of the photons of each pass which are kept when shrinking the radius, lower
+    /// values shrink it faster.
+    pub const fn with_alpha(self, alpha: f64) -> Self {
+        Self { alpha, ..self }
+    }
+}
+
+impl Integrator for ProgressivePhotonMapper {
+    /// Single-ray fallback, photons are only gathered when rendering the whole image so the ray
+    /// is shaded by the [`PathTracer`].
+    fn radiance(
+        &self,
+        r: &Ray,
+        lighting: &Lighting<'_>,
+        film: &Film,
+        rng: &mut dyn rand::RngCore,
+        depth: u32,
+    ) -> Colour {
+        PathTracer.radiance(r, lighting, film, rng, depth)
+    }
+
+    /// Renders a pass of photon mapping per sample.
+    fn render(&self, camera: &Camera, lighting: &Lighting<'_>, film: &Film) {
+        let (width, height) = camera.get_image_size();
+        let max_depth = camera.get_max_depth();
+        let mut rngs: Vec<_> = (0..width * height)
+            .map(|index| camera.pixel_rng(0, index))
+            .collect();
+        let mut pixels = self.pixel_statistics(width * height);
+
+        for pass in 0..camera.get_samples_per_pixel() {
+            // Shared by the pixels and the photons, so any photon can land on any pixel
+            let lighting = &lighting.with_wavelengths(camera.sample_wavelengths(&mut rngs[0]));
+            let visible_points: Vec<Option<VisiblePoint<'_>>> = rngs
+                .par_iter_mut()
+                .zip(pixels.par_iter_mut())
+                .enumerate()
+                .map(|(index, (rng, pixel))| {
+                    let (r, weight) = camera.sample_ray(index % width, index / width, rng)?;
+                    let (colour, visible_point) =
+                        Self::visible_point(r, weight, lighting, rng, max_depth);
+                    pixel.direct += lighting.to_rgb(colour);
+                    visible_point
+                })
+                .collect();
+            self.photon_pass(
+                lighting,
+                &visible_points,
+                &mut pixels,
+                width,
+                max_depth,
+                // The pixels use the first pass
+                |photon| camera.pixel_rng(u64::from(pass) + 1, photon as usize),
+            );
+        }
+        for (index, pixel) in pixels.iter().enumerate() {
+            film.add_pixel([index % width, index / width], self.colour(pixel));
+        }
+    }
+}
+
+/// Where a pixel gathers photons during a pass.
+struct VisiblePoint<'a> {
+    point: ShadingPoint<'a>,
+    /// Throughput from the camera
+    beta: Colour,
+}
+
+/// Photons gathered by a pixel over all the passes.
+struct PixelStatistics {
+    radius: f64,
+    /// Photons kept so far
+    photons: f64,
+    /// Flux of the kept photons, in RGB
+    tau: Colour,
+    /// Light which doesn't come from photons summed over the passes, in RGB
+    direct: Colour,
+}
+
+impl ProgressivePhotonMapper {
+    fn pixel_statistics(&self, pixels: usize) -> Vec<PixelStatistics> {
+        (0..pixels)
+            .map(|_| PixelStatistics {
+                radius: self.initial_radius,
+                photons: 0.,
+                tau: Colour::default(),
+                direct: Colour::default(),
+            })
+            .collect()
+    }
+
+    /// Sum of the light reaching a pixel over all passes.
+    fn colour(&self, pixel: &PixelStatistics) -> Colour {
+        let area = PI * pixel.radius * pixel.radius;
+        pixel.direct + pixel.tau / (f64::from(self.photons_per_pass) * area)
+    }
+
+    /// Traces a camera ray up to its first diffuse hit, returns the light found on the way and
+    /// where the photons are gathered.
+    fn visible_point<'a>(
+        mut r: Ray,
+        weight: f64,
+        lighting: &Lighting<'a>,
+        rng: &mut dyn rand::RngCore,
+        depth: u32,
+    ) -> (Colour, Option<VisiblePoint<'a>>) {
+        let mut lighting = *lighting;
+        let mut beta = Colour::new(weight, weight, weight);
+        let mut colour = Colour::default();
+        for _ in 0..depth {
+            let Some(rec) = lighting.world.hit(&r, (f64::EPSILON)..=f64::INFINITY) else {
+                return (colour + beta * lighting.background_colour(&r), None);
+            };
+            let colours = lighting.get_colours();
+            colour += beta * rec.get_material().emitted_rec(&r, &rec, colours);
+            let Some(srec) = rec.get_material().scatter(&r, &rec, colours, rng) else {
+                return (colour, None);
+            };
+            let attenuation = lighting.attenuation(&srec);
+            match srec.scatter_reflect {
+                ScatterReflect::Reflect(ray) => {
+                    beta *= attenuation;
+                    r = ray;
+                }
+                ScatterReflect::Scatter(pdf) => {
+                    let colour_from_punctual =
+                        lighting.punctual_light_colour(&r, &rec, attenuation);
+                    let colour_from_lights = DirectLighting::estimate_direct(
+                        &r,
+                        &rec,
+                        attenuation,
+                        pdf.as_ref(),
+                        &lighting,
+                        rng,
+                    );
+                    colour += beta * (colour_from_punctual + colour_from_lights);
+                    let point = ShadingPoint {
+                        ray_in: r,
+                        rec,
+                        attenuation,
+                        colours: lighting.get_colours(),
+                    };
+                    return (colour, Some(VisiblePoint { point, beta }));
+                }
+            }
+        }
+        (colour, None)
+    }
+
+    /// Shoots the photons of a pass, gathers them at the `visible_points` and shrinks the radius
+    /// of the `pixels` which found any. Both are stored row by row, `width` pixels at a time.
+    /// The photons carry the wavelengths of `lighting`, like the visible points.
+    ///
+    /// Each photon draws its random numbers from `photon_rng` of its index.
+    fn photon_pass(
+        &self,
+        lighting: &Lighting<'_>,
+        visible_points: &[Option<VisiblePoint<'_>>],
+        pixels: &mut [PixelStatistics],
+        width: usize,
+        depth: u32,
+        photon_rng: impl Fn(u32) -> SmallRng + Sync,
+    ) {
+        let grid = Grid::new(visible_points, pixels);
+        let pixels_ref = &*pixels;
+        let flux = Film::new(width, pixels.len() / width);
+        let counts: Vec<_> = (0..pixels.len()).map(|_| AtomicU64::new(0)).collect();
+
+        (0..self.photons_per_pass)
+            .into_par_iter()
+            .for_each(|photon| {
+                let rng = &mut photon_rng(photon);
+                let mut lighting = *lighting;
+                let Some(emission) = sample_emission(&lighting, rng) else {
+                    return;
+                };
+                let mut beta = emission.beta();
+                let mut r = Ray::new(emission.rec.get_p(), emission.direction);
+                for bounce in 0..depth {
+                    let Some(rec) = lighting.world.hit(&r, (f64::EPSILON)..=f64::INFINITY) else {
+                        return;
+                    };
+                    let Some(srec) =
+                        rec.get_material()
+                            .scatter(&r, &rec, lighting.get_colours(), rng)
+                    else {
+                        return;
+                    };
+                    let attenuation = lighting.attenuation(&srec);
+                    let pdf = match srec.scatter_reflect {
+                        ScatterReflect::Reflect(ray) => {
+                            beta *= attenuation;
+                            r = ray;
+                            continue;
+                        }
+                        ScatterReflect::Scatter(pdf) => pdf,
+                    };
+                    // Direct light is sampled at the visible points
+                    if bounce > 0 {
+                        let p = rec.get_p();
+                        for &index in grid.get(p) {
+                            let Some(visible_point) = &visible_points[index] else {
+                                continue;
+                            };
+                            let radius = pixels_ref[index].radius;
+                            if (visible_point.point.rec.get_p() - p).square_length()
+                                > radius * radius
+                            {
+                                continue;
+                            }
+                            let colour = visible_point.beta * visible_point.f(&r) * beta;
+                            flux.add_splat([index % width, index / width], colour);
+                            counts[index].fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                    let direction = pdf.generate(rng);
+                    let scattered = Ray::new(rec.get_p(), direction);
+                    let pdf_value = pdf.value(&direction);
+                    if pdf_value <= 0. {
+                        return;
+                    }
+                    let scattering_pdf = rec.get_material().scattering_pdf(&r, &rec, &scattered);
+                    beta *= attenuation * scattering_pdf / pdf_value;
+                    r = scattered;
+                }
+            });
+
+        for (index, (pixel, count)) in pixels.iter_mut().zip(counts).enumerate() {
+            let count = count.into_inner() as f64;
+            if count == 0. {
+                continue;
+            }
+            let photons = pixel.photons + self.alpha * count;
+            let radius = pixel.radius * (photons / (pixel.photons + count)).sqrt();
+            let flux = lighting.to_rgb(flux.get_pixel(index % width, index / width));
+            let shrink = (radius / pixel.radius).powi(2);
+            pixel.tau = (pixel.tau + flux) * shrink;
+            pixel.photons = photons;
+            pixel.radius = radius;
+        }
+    }
+}
+
+impl VisiblePoint<'_> {
+    /// Material response to a photon arriving along `photon`, without the cosine.
+    fn f(&self, photon: &Ray) -> Colour {
+        let ShadingPoint {
+            ray_in,
+            rec,
+            attenuation,
+            ..
+        } = &self.point;
+        let towards_photon = Ray::new(rec.get_p(), -photon.get_direction());
+        let cos_theta = rec
+            .get_normal()
+            .dot(towards_photon.get_direction().normalize());
+        if cos_theta <= 0. {
+            return Colour::default();
+        }
+        *attenuation
+            * rec
+                .get_material()
+                .scattering_pdf(ray_in, rec, &towards_photon)
+            / cos_theta
+    }
+}
+
+/// Visible points bucketed by the cells their gather spheres overlap.
+struct Grid {
+    cell_size: f64,
+    cells: HashMap<[i64; 3], Vec<usize>>,
+}
+
+impl Grid {
+    fn new(visible_points: &[Option<VisiblePoint<'_>>], pixels: &[PixelStatistics]) -> Self {
+        let max_radius = pixels.iter().map(|pixel| pixel.radius).fold(0., f64::max);
+        let mut grid = Self {
+            cell_size: (2. * max_radius).max(f64::EPSILON),
+            cells: HashMap::new(),
+        };
+        for (index, (visible_point, pixel)) in visible_points.iter().zip(pixels).enumerate() {
+            let Some(visible_point) = visible_point else {
+                continue;
+            };
+            let p = visible_point.point.rec.get_p();
+            let offset = Vec3::new(pixel.radius, pixel.radius, pixel.radius);
+            let [min_x, min_y, min_z] = grid.cell(p - offset);
+            let [max_x, max_y, max_z] = grid.cell(p + offset);
+            for x in min_x..=max_x {
+                for y in min_y..=max_y {
+                    for z in min_z..=max_z {
+                        grid.cells.entry([x, y, z]).or_default().push(index);
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    fn cell(&self, p: Point3) -> [i64; 3] {
+        [p.x, p.y, p.z].map(|coordinate| (coordinate / self.cell_size).floor() as i64)
+    }
+
+    /// Visible points which might be close enough to gather a photon at `p`.
+    fn get(&self, p: Point3) -> &[usize] {
+        self.cells.get(&self.cell(p)).map_or(&[], Vec::as_slice)
+    }
+}
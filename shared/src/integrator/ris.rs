@@ -0,0 +1,200 @@
+use rand::{Rng as _, distributions::Standard};
+
+use geometry::vec3::{Point3, Vec3};
+
+use crate::{
+    colour::Colour,
+    hittable::{HitRecord, Hittable},
+    ray::Ray,
+    spectrum::PathColours,
+};
+
+use super::Lighting;
+
+/// How the light coming directly from the lights is estimated at diffuse surfaces.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LightingStrategy {
+    /// Follows a single direction, sampled from the lights or the material with equal
+    /// probability
+    #[default]
+    Mixture,
+    /// Resampled importance sampling, `candidates` light samples are drawn and one is picked
+    /// proportionally to its unshadowed contribution, so only it needs a shadow ray.
+    ///
+    /// With `spatial_reuse` the first hit of each pixel also resamples the candidates picked by
+    /// its neighbours, which removes a lot of noise at the cost of a slight bias.
+    Ris {
+        candidates: u16,
+        spatial_reuse: bool,
+    },
+}
+
+/// A light sample that can be shared between shading points.
+#[derive(Debug, Clone, Copy)]
+enum LightPoint {
+    /// Point on the surface of a light
+    Point(Point3),
+    /// Direction towards a light infinitely far away
+    Direction(Vec3),
+}
+
+/// Weighted reservoir holding a single light sample, see "Spatiotemporal reservoir resampling
+/// for real-time ray tracing with dynamic direct lighting" by Bitterli et al.
+///
+/// Weights are in area measure for points on lights and in solid angle for directions, so they
+/// stay valid when reused at another point.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Reservoir {
+    sample: Option<LightPoint>,
+    /// Target function of `sample` at the point the reservoir belongs to
+    target: f64,
+    weight_sum: f64,
+    count: u32,
+}
+
+impl Reservoir {
+    fn update(
+        &mut self,
+        sample: LightPoint,
+        target: f64,
+        weight: f64,
+        count: u32,
+        rng: &mut dyn rand::RngCore,
+    ) {
+        self.count += count;
+        if !(weight > 0. && weight.is_finite()) {
+            return;
+        }
+        self.weight_sum += weight;
+        let u: f64 = rng.sample(Standard);
+        if u * self.weight_sum < weight {
+            self.sample = Some(sample);
+            self.target = target;
+        }
+    }
+
+    /// Weight that makes `f(sample) * contribution_weight()` an estimate of the integral.
+    fn contribution_weight(&self) -> f64 {
+        if self.target > 0. && self.count > 0 {
+            self.weight_sum / (f64::from(self.count) * self.target)
+        } else {
+            0.
+        }
+    }
+}
+
+/// Shading point at which light is resampled.
+pub(crate) struct ShadingPoint<'a> {
+    pub(crate) ray_in: Ray,
+    pub(crate) rec: HitRecord<'a>,
+    pub(crate) attenuation: Colour,
+    /// Of the path reaching the point, see [`Lighting::get_colours`]
+    pub(crate) colours: PathColours,
+}
+
+/// Light sample as seen from a [`ShadingPoint`].
+struct Evaluation {
+    shadow_ray: Ray,
+    /// Converts from solid angle to the measure of the sample
+    geometry: f64,
+    /// Material response towards the sample, including the attenuation
+    response: Colour,
+    target: f64,
+}
+
+impl ShadingPoint<'_> {
+    fn evaluate(&self, lights: &dyn Hittable, sample: LightPoint) -> Evaluation {
+        let p = self.rec.get_p();
+        let (direction, distance_squared) = match sample {
+            LightPoint::Point(point) => {
+                let to_light = point - p;
+                (to_light.normalize(), to_light.square_length())
+            }
+            LightPoint::Direction(direction) => (direction, 1.),
+        };
+        let shadow_ray = Ray::new(p, direction);
+        let response = self.attenuation
+            * self
+                .rec
+                .get_material()
+                .scattering_pdf(&self.ray_in, &self.rec, &shadow_ray);
+        let light = lights.hit(&shadow_ray, (f64::EPSILON)..=f64::INFINITY);
+        let geometry = match (sample, &light) {
+            (LightPoint::Point(_), Some(light)) => {
+                direction.dot(light.get_normal()).abs() / distance_squared
+            }
+            (LightPoint::Point(_), None) => distance_squared.recip(),
+            (LightPoint::Direction(_), _) => 1.,
+        };
+        let target = match &light {
+            Some(light) if light.get_material().is_emissive() => {
+                let emitted = light
+                    .get_material()
+                    .emitted_rec(&shadow_ray, light, self.colours);
+                (response * emitted).luminance()
+            }
+            // Guides like glass spheres don't emit anything themselves
+            _ => response.luminance(),
+        };
+        Evaluation {
+            shadow_ray,
+            geometry,
+            response,
+            target: target.max(0.) * geometry,
+        }
+    }
+
+    /// Draws `candidates` samples from the lights and keeps one of them.
+    pub(crate) fn resample(
+        &self,
+        lights: &dyn Hittable,
+        candidates: u16,
+        rng: &mut dyn rand::RngCore,
+    ) -> Reservoir {
+        let p = self.rec.get_p();
+        let mut reservoir = Reservoir::default();
+        for _ in 0..candidates {
+            let direction = lights.random(p, rng).normalize();
+            let pdf = lights.pdf_value(p, direction);
+            let sample = match lights.hit(&Ray::new(p, direction), (f64::EPSILON)..=f64::INFINITY) {
+                Some(light) => LightPoint::Point(light.get_p()),
+                None => LightPoint::Direction(direction),
+            };
+            let evaluation = self.evaluate(lights, sample);
+            let weight = evaluation.target / (pdf * evaluation.geometry);
+            reservoir.update(sample, evaluation.target, weight, 1, rng);
+        }
+        reservoir
+    }
+
+    /// Merges the reservoirs of neighbouring points into this point's reservoir.
+    pub(crate) fn reuse<'r>(
+        &self,
+        lights: &dyn Hittable,
+        reservoirs: impl IntoIterator<Item = &'r Reservoir>,
+        rng: &mut dyn rand::RngCore,
+    ) -> Reservoir {
+        let mut out = Reservoir::default();
+        for reservoir in reservoirs {
+            let Some(sample) = reservoir.sample else {
+                out.count += reservoir.count;
+                continue;
+            };
+            let target = self.evaluate(lights, sample).target;
+            let weight = target * reservoir.contribution_weight() * f64::from(reservoir.count);
+            out.update(sample, target, weight, reservoir.count, rng);
+        }
+        out
+    }
+
+    /// Light arriving from the sample of the reservoir, with a shadow ray against the world.
+    pub(crate) fn shade(&self, lighting: &Lighting<'_>, reservoir: &Reservoir) -> Colour {
+        let (Some(lights), Some(sample)) = (lighting.lights, reservoir.sample) else {
+            return Colour::default();
+        };
+        let evaluation = self.evaluate(lights, sample);
+        // The first thing hit is the light unless something is blocking it
+        let emitted = lighting.incoming_emission(&evaluation.shadow_ray);
+        evaluation.response * emitted * (evaluation.geometry * reservoir.contribution_weight())
+    }
+}
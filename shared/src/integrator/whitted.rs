@@ -0,0 +1,70 @@
+use crate::{camera::Film, colour::Colour, material::ScatterReflect, ray::Ray};
+
+use super::{Integrator, Lighting};
+
+/// Classic Whitted style ray tracing, mirrors and glass are followed while diffuse surfaces are
+/// only lit by the punctual lights, a single shadow ray towards the lights and a constant
+/// ambient term.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Whitted {
+    ambient: Colour,
+}
+
+impl Whitted {
+    pub const fn new() -> Self {
+        Self {
+            ambient: Colour::new(0., 0., 0.),
+        }
+    }
+
+    /// Light added to every diffuse surface to make up for the missing indirect light.
+    pub const fn with_ambient(self, ambient: Colour) -> Self {
+        Self { ambient }
+    }
+}
+
+impl Integrator for Whitted {
+    fn radiance(
+        &self,
+        r: &Ray,
+        lighting: &Lighting<'_>,
+        _film: &Film,
+        rng: &mut dyn rand::RngCore,
+        depth: u32,
+    ) -> Colour {
+        let mut lighting = *lighting;
+        let mut r = r.clone();
+        let mut mult = Colour::from_array([1., 1., 1.]);
+        for _ in 0..depth {
+            let Some(rec) = lighting.world.hit(&r, (f64::EPSILON)..=f64::INFINITY) else {
+                return mult * lighting.background_colour(&r);
+            };
+            let colours = lighting.get_colours();
+            let colour_from_emission = rec.get_material().emitted_rec(&r, &rec, colours);
+            let Some(srec) = rec.get_material().scatter(&r, &rec, colours, rng) else {
+                return mult * colour_from_emission;
+            };
+            let attenuation = lighting.attenuation(&srec);
+            let ScatterReflect::Reflect(ray) = srec.scatter_reflect else {
+                let p = rec.get_p();
+                let colour_from_punctual = lighting.punctual_light_colour(&r, &rec, attenuation);
+                let colour_from_lights = lighting.lights.map_or(Colour::default(), |lights| {
+                    let shadow_ray = Ray::new(p, lights.random(p, rng));
+                    let pdf_value = lights.pdf_value(p, shadow_ray.get_direction());
+                    let scattering_pdf = rec.get_material().scattering_pdf(&r, &rec, &shadow_ray);
+                    (attenuation * scattering_pdf * lighting.incoming_emission(&shadow_ray)
+                        / pdf_value)
+                        .fix_nan()
+                });
+                return mult
+                    * (colour_from_emission
+                        + colour_from_punctual
+                        + colour_from_lights
+                        + attenuation * self.ambient);
+            };
+            mult *= attenuation;
+            r = ray;
+        }
+        Colour::default()
+    }
+}
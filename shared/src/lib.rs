@@ -1,12 +1,19 @@
 // #![feature(explicit_tail_calls)]
+pub mod animation;
 pub mod camera;
 pub mod colour;
 pub mod entities;
+pub mod environment;
 pub mod hittable;
 pub mod hittable_collections;
+pub mod image;
+pub mod integrator;
+pub mod lights;
 pub mod material;
 pub mod pdf;
 pub mod perlin;
 pub mod ray;
+pub mod scene;
+pub mod spectrum;
 pub mod texture;
 pub mod utils;
@@ -0,0 +1,209 @@
+mod ies;
+mod photometry;
+pub use ies::IesProfile;
+pub use photometry::{Brightness, Emission};
+
+use std::{
+    f64::consts::{PI, TAU},
+    fmt::Debug,
+    sync::Arc,
+};
+
+use geometry::{
+    onb::Onb,
+    vec3::{Point3, Vec3},
+};
+
+use crate::colour::Colour;
+
+/// Light arriving at a point from a [`PunctualLight`].
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    /// Unit vector pointing towards the light
+    pub direction: Vec3,
+    pub distance: f64,
+    /// Irradiance on a surface facing the light
+    pub irradiance: Colour,
+}
+
+/// Lights without area, which can't be hit by rays and so must be sampled explicitly.
+pub trait PunctualLight: Sync + Send + Debug {
+    fn sample(&self, point: Point3) -> Option<LightSample>;
+}
+
+/// Light emitted equally in every direction, unless it has an [`IesProfile`] in which case
+/// the profile points down (`-y`).
+#[derive(Debug, Clone)]
+pub struct PointLight {
+    position: Point3,
+    intensity: Colour,
+    profile: Option<Arc<IesProfile>>,
+}
+
+impl PointLight {
+    pub const fn new(position: Point3, intensity: Colour) -> Self {
+        Self {
+            position,
+            intensity,
+            profile: None,
+        }
+    }
+
+    /// `None` unless the brightness is in candela or a flux, which is spread evenly over every
+    /// direction.
+    pub fn new_with_emission(position: Point3, emission: Emission) -> Option<Self> {
+        Some(Self::new(position, emission.intensity(4. * PI)?))
+    }
+
+    pub fn with_profile(self, profile: Arc<IesProfile>) -> Self {
+        Self {
+            profile: Some(profile),
+            ..self
+        }
+    }
+}
+
+impl PunctualLight for PointLight {
+    fn sample(&self, point: Point3) -> Option<LightSample> {
+        let to_light = self.position - point;
+        let distance = to_light.length();
+        let direction = to_light / distance;
+        let profile = self.profile.as_ref().map_or(1., |profile| {
+            let emitted = -direction;
+            profile.evaluate(
+                (-emitted.y).clamp(-1., 1.).acos().to_degrees(),
+                libm::atan2(emitted.z, emitted.x).to_degrees(),
+            )
+        });
+        (profile > 0.).then(|| LightSample {
+            direction,
+            distance,
+            irradiance: self.intensity * (profile / (distance * distance)),
+        })
+    }
+}
+
+/// Point light restricted to a cone, the falloff goes smoothly from the inner to the outer
+/// angle.
+#[derive(Debug, Clone)]
+pub struct SpotLight {
+    position: Point3,
+    axis: Onb,
+    intensity: Colour,
+    cos_inner: f64,
+    cos_outer: f64,
+    profile: Option<Arc<IesProfile>>,
+}
+
+impl SpotLight {
+    /// Angles are in degrees, measured from the direction to the edge of the cone.
+    pub fn new(
+        position: Point3,
+        direction: Vec3,
+        intensity: Colour,
+        inner_angle: f64,
+        outer_angle: f64,
+    ) -> Self {
+        let outer_angle = outer_angle.max(inner_angle);
+        Self {
+            position,
+            axis: Onb::new(direction.normalize()),
+            intensity,
+            cos_inner: inner_angle.to_radians().cos(),
+            cos_outer: outer_angle.to_radians().cos(),
+            profile: None,
+        }
+    }
+
+    /// `None` unless the brightness is in candela or a flux, which is spread over the cone
+    /// halfway between both angles.
+    pub fn new_with_emission(
+        position: Point3,
+        direction: Vec3,
+        emission: Emission,
+        inner_angle: f64,
+        outer_angle: f64,
+    ) -> Option<Self> {
+        let half_angle = (inner_angle + outer_angle.max(inner_angle)) / 2.;
+        let solid_angle = TAU * (1. - half_angle.to_radians().cos());
+        Some(Self::new(
+            position,
+            direction,
+            emission.intensity(solid_angle)?,
+            inner_angle,
+            outer_angle,
+        ))
+    }
+
+    /// The profile replaces the falloff of the cone, pointing along the direction of the light.
+    pub fn with_profile(self, profile: Arc<IesProfile>) -> Self {
+        Self {
+            profile: Some(profile),
+            ..self
+        }
+    }
+}
+
+impl PunctualLight for SpotLight {
+    fn sample(&self, point: Point3) -> Option<LightSample> {
+        let to_light = self.position - point;
+        let distance = to_light.length();
+        let direction = to_light / distance;
+        let emitted = -direction;
+        let cos_theta = emitted.dot(self.axis.get_w());
+        let falloff = match &self.profile {
+            Some(profile) => profile.evaluate(
+                cos_theta.clamp(-1., 1.).acos().to_degrees(),
+                libm::atan2(
+                    emitted.dot(self.axis.get_v()),
+                    emitted.dot(self.axis.get_u()),
+                )
+                .to_degrees(),
+            ),
+            None if cos_theta >= self.cos_inner => 1.,
+            None if self.cos_inner > self.cos_outer => {
+                let t = ((cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer))
+                    .clamp(0., 1.);
+                t * t * (3. - 2. * t)
+            }
+            None => 0.,
+        };
+        (falloff > 0.).then(|| LightSample {
+            direction,
+            distance,
+            irradiance: self.intensity * (falloff / (distance * distance)),
+        })
+    }
+}
+
+/// Light coming from infinitely far away in a single direction, like the sun.
+#[derive(Debug, Clone, Copy)]
+pub struct DirectionalLight {
+    /// Direction the light travels in
+    direction: Vec3,
+    irradiance: Colour,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, irradiance: Colour) -> Self {
+        Self {
+            direction: direction.normalize(),
+            irradiance,
+        }
+    }
+
+    /// `None` unless the brightness is in lux.
+    pub fn new_with_emission(direction: Vec3, emission: Emission) -> Option<Self> {
+        Some(Self::new(direction, emission.irradiance()?))
+    }
+}
+
+impl PunctualLight for DirectionalLight {
+    fn sample(&self, _point: Point3) -> Option<LightSample> {
+        Some(LightSample {
+            direction: -self.direction,
+            distance: f64::INFINITY,
+            irradiance: self.irradiance,
+        })
+    }
+}
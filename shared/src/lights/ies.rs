@@ -0,0 +1,116 @@
+use std::{fs::read_to_string, io, path::Path};
+
+/// Photometric web from an IESNA LM-63 file, only type C photometry is supported.
+///
+/// Values are normalized so the brightest direction has a value of 1.
+#[derive(Debug, Clone)]
+pub struct IesProfile {
+    /// In degrees, 0 points down the axis of the luminaire
+    vertical_angles: Vec<f64>,
+    /// In degrees
+    horizontal_angles: Vec<f64>,
+    /// One row of vertical values per horizontal angle
+    candela: Vec<Vec<f64>>,
+}
+
+impl IesProfile {
+    pub fn parse(contents: &str) -> Option<Self> {
+        let (_, data) = contents.split_once("TILT=")?;
+        let (tilt, data) = data.split_once('\n')?;
+        // Tilt tables are only used with some lamps, they are rare enough to not support them
+        (tilt.trim() == "NONE").then_some(())?;
+        let mut numbers = data.split_whitespace().map(str::parse::<f64>);
+        let mut next = || numbers.next()?.ok();
+
+        let [_lamps, _lumens, multiplier, vertical, horizontal] = [(); 5].map(|_| next());
+        let (multiplier, vertical, horizontal) =
+            (multiplier?, vertical? as usize, horizontal? as usize);
+        // Photometric type, units, luminous opening, ballast factor, unused and input watts
+        (0..8).try_for_each(|_| next().map(|_| ()))?;
+
+        let vertical_angles = (0..vertical).map(|_| next()).collect::<Option<Vec<_>>>()?;
+        let horizontal_angles = (0..horizontal)
+            .map(|_| next())
+            .collect::<Option<Vec<_>>>()?;
+        let candela = (0..horizontal)
+            .map(|_| {
+                (0..vertical)
+                    .map(|_| next().map(|v| v * multiplier))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Self::new(vertical_angles, horizontal_angles, candela)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse(&read_to_string(path)?)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid IES file"))
+    }
+
+    /// `candela` has one row of `vertical_angles.len()` values for each horizontal angle.
+    pub fn new(
+        vertical_angles: Vec<f64>,
+        horizontal_angles: Vec<f64>,
+        candela: Vec<Vec<f64>>,
+    ) -> Option<Self> {
+        (!vertical_angles.is_empty()
+            && !horizontal_angles.is_empty()
+            && candela.len() == horizontal_angles.len()
+            && candela.iter().all(|row| row.len() == vertical_angles.len()))
+        .then_some(())?;
+        let max = candela.iter().flatten().copied().fold(0., f64::max);
+        (max > 0.).then(|| Self {
+            vertical_angles,
+            horizontal_angles,
+            candela: candela
+                .into_iter()
+                .map(|row| row.into_iter().map(|v| v / max).collect())
+                .collect(),
+        })
+    }
+
+    /// Relative intensity, `theta` is the angle from the axis of the luminaire and `phi` the
+    /// angle around it, both in degrees.
+    pub fn evaluate(&self, theta: f64, phi: f64) -> f64 {
+        let phi = phi.rem_euclid(360.);
+        // The last horizontal angle tells which symmetry the luminaire has
+        let phi = match self.horizontal_angles.last() {
+            Some(&last) if last <= 0. => 0.,
+            Some(&last) if last <= 90. => {
+                let phi = phi % 180.;
+                if phi > 90. { 180. - phi } else { phi }
+            }
+            Some(&last) if last <= 180. && phi > 180. => 360. - phi,
+            _ => phi,
+        };
+        let phi = phi.clamp(
+            self.horizontal_angles[0],
+            self.horizontal_angles[self.horizontal_angles.len() - 1],
+        );
+        let Some((h0, h1, t)) = interpolation(&self.horizontal_angles, phi) else {
+            return 0.;
+        };
+        let vertical = |row: &[f64]| {
+            interpolation(&self.vertical_angles, theta)
+                .map_or(0., |(v0, v1, s)| row[v0] * (1. - s) + row[v1] * s)
+        };
+        vertical(&self.candela[h0]) * (1. - t) + vertical(&self.candela[h1]) * t
+    }
+}
+
+/// Indices around `x` in the sorted `values` and how far between them `x` is, `None` if `x` is
+/// outside of the values.
+fn interpolation(values: &[f64], x: f64) -> Option<(usize, usize, f64)> {
+    if values.len() == 1 {
+        return Some((0, 0, 0.));
+    }
+    if x < values[0] || x > values[values.len() - 1] {
+        return None;
+    }
+    let i = values
+        .partition_point(|&v| v <= x)
+        .clamp(1, values.len() - 1);
+    let (a, b) = (values[i - 1], values[i]);
+    let t = if b > a { (x - a) / (b - a) } else { 0. };
+    Some((i - 1, i, t))
+}
@@ -0,0 +1,77 @@
+use std::f64::consts::PI;
+
+use crate::colour::{Colour, luminous_efficacy};
+
+/// How bright a light is. A unit of length is taken as a metre, so a luminance of 1 is a nit.
+#[derive(Debug, Clone, Copy)]
+pub enum Brightness {
+    /// Luminance of a surface, in cd/m²
+    Nits(f64),
+    /// Luminous intensity of a punctual light, in cd
+    Candela(f64),
+    /// Luminous flux over every direction the light emits in, in lm
+    Lumens(f64),
+    /// Radiant flux over every direction the light emits in, including what the eye can't see,
+    /// in W
+    Watts(f64),
+    /// Illuminance on a surface facing a directional light, in lx
+    Lux(f64),
+}
+
+/// Light given by its colour temperature and brightness instead of RGB.
+#[derive(Debug, Clone, Copy)]
+pub struct Emission {
+    /// In Kelvin
+    pub temperature: f64,
+    pub brightness: Brightness,
+}
+
+impl Emission {
+    pub const fn new(temperature: f64, brightness: Brightness) -> Self {
+        Self {
+            temperature,
+            brightness,
+        }
+    }
+
+    fn colour(&self, luminance: f64) -> Colour {
+        Colour::from_temperature(self.temperature) * luminance
+    }
+
+    /// Luminous flux, if the brightness is a flux.
+    fn lumens(&self) -> Option<f64> {
+        match self.brightness {
+            Brightness::Lumens(lumens) => Some(lumens),
+            Brightness::Watts(watts) => Some(watts * luminous_efficacy(self.temperature)),
+            _ => None,
+        }
+    }
+
+    /// Radiance of a diffuse surface emitting it over `area`, which counts every side that
+    /// emits. `None` unless the brightness is in nits or a flux.
+    pub fn radiance(&self, area: f64) -> Option<Colour> {
+        let luminance = match self.brightness {
+            Brightness::Nits(nits) => nits,
+            _ => self.lumens()? / (PI * area),
+        };
+        Some(self.colour(luminance))
+    }
+
+    /// Intensity of a punctual light emitting it over `solid_angle` steradians. `None` unless
+    /// the brightness is in candela or a flux.
+    pub fn intensity(&self, solid_angle: f64) -> Option<Colour> {
+        let intensity = match self.brightness {
+            Brightness::Candela(candela) => candela,
+            _ => self.lumens()? / solid_angle,
+        };
+        Some(self.colour(intensity))
+    }
+
+    /// Irradiance of a directional light, `None` unless the brightness is in lux.
+    pub fn irradiance(&self) -> Option<Colour> {
+        match self.brightness {
+            Brightness::Lux(lux) => Some(self.colour(lux)),
+            _ => None,
+        }
+    }
+}
@@ -11,8 +11,10 @@ use geometry::vec3::Vec3Ext as _;
 use crate::{
     colour::Colour,
     hittable::HitRecord,
+    lights::{Emission, IesProfile},
     pdf::{CosinePdf, Pdf, SpherePdf},
     ray::Ray,
+    spectrum::PathColours,
     texture::{SolidColour, Texture},
     utils::random_utils::UnitSphere,
 };
@@ -27,25 +29,57 @@ pub enum ScatterReflect {
 pub struct ScatterRecord {
     pub attenuation: Colour,
     pub scatter_reflect: ScatterReflect,
+    /// Whether the wavelengths of the path are split, so only the hero one can be followed, see
+    /// [`SampledWavelengths::terminate_secondary`](crate::spectrum::SampledWavelengths::terminate_secondary)
+    pub terminates_secondary: bool,
 }
 
+/// Colours are carried as the `colours` of the path, in the working space or at its
+/// wavelengths when rendering spectrally, see [`Colour::to_reflectance`].
 pub trait Material: Sync + Send + Debug {
     fn scatter(
         &self,
         _ray_in: &Ray,
         _rec: &HitRecord<'_>,
+        _colours: PathColours,
         _rng: &mut dyn rand::RngCore,
     ) -> Option<ScatterRecord> {
         None
     }
 
-    fn emitted(&self, _u: f64, _v: f64, _point: Point3) -> Colour {
+    fn emitted(&self, _u: f64, _v: f64, _point: Point3, _colours: PathColours) -> Colour {
         Colour::new(0., 0., 0.)
     }
 
+    /// Emission seen by `ray_in`, for materials whose emission depends on the direction.
+    fn emitted_rec(&self, _ray_in: &Ray, rec: &HitRecord<'_>, colours: PathColours) -> Colour {
+        self.emitted(rec.get_u(), rec.get_v(), rec.get_p(), colours)
+    }
+
+    /// Whether objects with this material should be sampled as lights.
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// Rough luminance emitted per unit area, used to pick which lights to sample more often.
+    fn emitted_power(&self) -> f64 {
+        0.
+    }
+
+    /// Whether light is emitted on both sides of the surface.
+    fn is_two_sided(&self) -> bool {
+        true
+    }
+
     fn scattering_pdf(&self, _ray_in: &Ray, _rec: &HitRecord<'_>, _scattered: &Ray) -> f64 {
         0.
     }
+
+    /// Whether the material is taken from the enclosing node of a
+    /// [`SceneNode`](crate::scene::SceneNode), see [`INHERITED_PTR`].
+    fn is_inherited(&self) -> bool {
+        false
+    }
 }
 
 mod dyn_util {
@@ -58,7 +92,7 @@ mod dyn_util {
 
         use geometry::vec3::Point3;
 
-        use crate::{colour::Colour, hittable::HitRecord, ray::Ray};
+        use crate::{colour::Colour, hittable::HitRecord, ray::Ray, spectrum::PathColours};
 
         use super::super::{Material, ScatterRecord};
 
@@ -206,6 +240,7 @@ mod dyn_util {
                 &self,
                 ray_in: &Ray,
                 rec: &HitRecord<'_>,
+                colours: PathColours,
                 rng: &mut dyn rand::RngCore,
             ) -> Option<ScatterRecord> {
                 unsafe {
@@ -213,17 +248,62 @@ mod dyn_util {
                         .as_ref()
                         .unwrap()
                 }
-                .scatter(ray_in, rec, rng)
+                .scatter(ray_in, rec, colours, rng)
+            }
+
+            #[inline]
+            fn emitted(&self, u: f64, v: f64, point: Point3, colours: PathColours) -> Colour {
+                unsafe {
+                    (self.into_material.into_material)(&self.bytes as _)
+                        .as_ref()
+                        .unwrap()
+                }
+                .emitted(u, v, point, colours)
+            }
+
+            #[inline]
+            fn emitted_rec(
+                &self,
+                ray_in: &Ray,
+                rec: &HitRecord<'_>,
+                colours: PathColours,
+            ) -> Colour {
+                unsafe {
+                    (self.into_material.into_material)(&self.bytes as _)
+                        .as_ref()
+                        .unwrap()
+                }
+                .emitted_rec(ray_in, rec, colours)
             }
 
             #[inline]
-            fn emitted(&self, u: f64, v: f64, point: Point3) -> Colour {
+            fn is_emissive(&self) -> bool {
                 unsafe {
                     (self.into_material.into_material)(&self.bytes as _)
                         .as_ref()
                         .unwrap()
                 }
-                .emitted(u, v, point)
+                .is_emissive()
+            }
+
+            #[inline]
+            fn emitted_power(&self) -> f64 {
+                unsafe {
+                    (self.into_material.into_material)(&self.bytes as _)
+                        .as_ref()
+                        .unwrap()
+                }
+                .emitted_power()
+            }
+
+            #[inline]
+            fn is_two_sided(&self) -> bool {
+                unsafe {
+                    (self.into_material.into_material)(&self.bytes as _)
+                        .as_ref()
+                        .unwrap()
+                }
+                .is_two_sided()
             }
 
             #[inline]
@@ -235,6 +315,16 @@ mod dyn_util {
                 }
                 .scattering_pdf(ray_in, rec, scattered)
             }
+
+            #[inline]
+            fn is_inherited(&self) -> bool {
+                unsafe {
+                    (self.into_material.into_material)(&self.bytes as _)
+                        .as_ref()
+                        .unwrap()
+                }
+                .is_inherited()
+            }
         }
     }
 
@@ -243,7 +333,10 @@ mod dyn_util {
 
         use geometry::vec3::Point3;
 
-        use crate::{colour::Colour, hittable::HitRecord, material::ScatterRecord, ray::Ray};
+        use crate::{
+            colour::Colour, hittable::HitRecord, material::ScatterRecord, ray::Ray,
+            spectrum::PathColours,
+        };
 
         use super::super::Material;
 
@@ -282,18 +375,52 @@ mod dyn_util {
                 &self,
                 ray_in: &Ray,
                 rec: &HitRecord<'_>,
+                colours: PathColours,
                 rng: &mut dyn rand::RngCore,
             ) -> Option<ScatterRecord> {
                 match self {
-                    DynMaterial::Ref(material) => material.scatter(ray_in, rec, rng),
-                    DynMaterial::Arc(material) => material.scatter(ray_in, rec, rng),
+                    DynMaterial::Ref(material) => material.scatter(ray_in, rec, colours, rng),
+                    DynMaterial::Arc(material) => material.scatter(ray_in, rec, colours, rng),
+                }
+            }
+
+            fn emitted(&self, u: f64, v: f64, point: Point3, colours: PathColours) -> Colour {
+                match self {
+                    DynMaterial::Ref(material) => material.emitted(u, v, point, colours),
+                    DynMaterial::Arc(material) => material.emitted(u, v, point, colours),
+                }
+            }
+
+            fn emitted_rec(
+                &self,
+                ray_in: &Ray,
+                rec: &HitRecord<'_>,
+                colours: PathColours,
+            ) -> Colour {
+                match self {
+                    DynMaterial::Ref(material) => material.emitted_rec(ray_in, rec, colours),
+                    DynMaterial::Arc(material) => material.emitted_rec(ray_in, rec, colours),
                 }
             }
 
-            fn emitted(&self, u: f64, v: f64, point: Point3) -> Colour {
+            fn is_emissive(&self) -> bool {
                 match self {
-                    DynMaterial::Ref(material) => material.emitted(u, v, point),
-                    DynMaterial::Arc(material) => material.emitted(u, v, point),
+                    DynMaterial::Ref(material) => material.is_emissive(),
+                    DynMaterial::Arc(material) => material.is_emissive(),
+                }
+            }
+
+            fn emitted_power(&self) -> f64 {
+                match self {
+                    DynMaterial::Ref(material) => material.emitted_power(),
+                    DynMaterial::Arc(material) => material.emitted_power(),
+                }
+            }
+
+            fn is_two_sided(&self) -> bool {
+                match self {
+                    DynMaterial::Ref(material) => material.is_two_sided(),
+                    DynMaterial::Arc(material) => material.is_two_sided(),
                 }
             }
 
@@ -303,6 +430,13 @@ mod dyn_util {
                     DynMaterial::Arc(material) => material.scattering_pdf(ray_in, rec, scattered),
                 }
             }
+
+            fn is_inherited(&self) -> bool {
+                match self {
+                    DynMaterial::Ref(material) => material.is_inherited(),
+                    DynMaterial::Arc(material) => material.is_inherited(),
+                }
+            }
         }
 
         impl AsRef<dyn Material> for DynMaterial {
@@ -324,6 +458,19 @@ pub const INVISIBLE_PTR: &dyn Material = &Invisible;
 
 impl Material for Invisible {}
 
+/// Placeholder for the material of the enclosing node of a
+/// [`SceneNode`](crate::scene::SceneNode), objects using it take the closest material set on
+/// their node or its ancestors.
+#[derive(Debug, Clone, Copy)]
+pub struct Inherited;
+pub const INHERITED_PTR: &dyn Material = &Inherited;
+
+impl Material for Inherited {
+    fn is_inherited(&self) -> bool {
+        true
+    }
+}
+
 pub struct Lambertian {
     texture: Arc<dyn Texture>,
 }
@@ -359,13 +506,16 @@ impl Material for Lambertian {
         &self,
         _ray_in: &Ray,
         rec: &HitRecord<'_>,
+        colours: PathColours,
         _rng: &mut dyn rand::RngCore,
     ) -> Option<ScatterRecord> {
         Some(ScatterRecord {
             attenuation: self
                 .texture
-                .get_colour(rec.get_u(), rec.get_v(), rec.get_p()),
+                .get_colour(rec.get_u(), rec.get_v(), rec.get_p())
+                .to_reflectance(colours),
             scatter_reflect: ScatterReflect::Scatter(Box::new(CosinePdf::new(rec.get_normal()))),
+            terminates_secondary: false,
         })
     }
 
@@ -409,25 +559,29 @@ impl Material for Metal {
         &self,
         ray_in: &Ray,
         rec: &HitRecord<'_>,
+        colours: PathColours,
         rng: &mut dyn rand::RngCore,
     ) -> Option<ScatterRecord> {
         let reflected = ray_in.get_direction().normalize().reflect(rec.get_normal());
         let reflected = Ray::new(rec.get_p(), reflected + rng.sample(UnitSphere) * self.fuzz);
         (reflected.get_direction().dot(rec.get_normal()) > 0.).then_some(ScatterRecord {
-            attenuation: self.albedo,
+            attenuation: self.albedo.to_reflectance(colours),
             scatter_reflect: ScatterReflect::Reflect(reflected),
+            terminates_secondary: false,
         })
     }
 }
 
 pub struct Dialectric {
     index_of_refraction: f64,
+    dispersion: Option<Dispersion>,
 }
 
 impl Debug for Dialectric {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Dialectric")
             .field("index_of_refraction", &self.index_of_refraction)
+            .field("dispersion", &self.dispersion)
             .finish()
     }
 }
@@ -436,6 +590,7 @@ impl Clone for Dialectric {
     fn clone(&self) -> Self {
         Self {
             index_of_refraction: self.index_of_refraction,
+            dispersion: self.dispersion,
         }
     }
 }
@@ -444,8 +599,19 @@ impl Dialectric {
     pub fn new(index_of_refraction: f64) -> Self {
         Self {
             index_of_refraction,
+            dispersion: None,
         }
     }
+
+    /// Makes the index of refraction depend on the wavelength when rendering spectrally, the
+    /// one at the sodium D line (587.6 nm) is used when rendering in RGB.
+    pub fn with_dispersion(self, dispersion: Dispersion) -> Self {
+        Self {
+            index_of_refraction: dispersion.index_of_refraction(587.6),
+            dispersion: Some(dispersion),
+        }
+    }
+
     #[inline]
     fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
         let r0 = (1. - ref_idx) / (1. + ref_idx);
@@ -459,12 +625,21 @@ impl Material for Dialectric {
         &self,
         ray_in: &Ray,
         rec: &HitRecord<'_>,
+        colours: PathColours,
         rng: &mut dyn rand::RngCore,
     ) -> Option<ScatterRecord> {
+        // Each wavelength would be bent differently, so only the hero one is followed
+        let (index_of_refraction, terminates_secondary) =
+            match (self.dispersion, colours.get_wavelengths()) {
+                (Some(dispersion), Some(wavelengths)) => {
+                    (dispersion.index_of_refraction(wavelengths.hero()), true)
+                }
+                _ => (self.index_of_refraction, false),
+            };
         let refraction_ratio = if rec.is_front_face() {
-            self.index_of_refraction.recip()
+            index_of_refraction.recip()
         } else {
-            self.index_of_refraction
+            index_of_refraction
         };
         let unit_direction = ray_in.get_direction().normalize();
 
@@ -483,13 +658,51 @@ impl Material for Dialectric {
         Some(ScatterRecord {
             attenuation: Colour::new(1., 1., 1.),
             scatter_reflect: ScatterReflect::Reflect(Ray::new(rec.get_p(), direction)),
+            terminates_secondary,
         })
     }
 }
 
+/// Index of refraction as a function of the wavelength, which is given in micrometres.
+#[derive(Debug, Clone, Copy)]
+pub enum Dispersion {
+    /// `n = a + b / λ²`
+    Cauchy { a: f64, b: f64 },
+    /// `n² = 1 + Σ bᵢ λ² / (λ² - cᵢ)`
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    /// Borosilicate crown glass, the usual glass of lenses.
+    pub const BK7: Self = Self::Sellmeier {
+        b: [1.03961212, 0.231792344, 1.01046945],
+        c: [0.00600069867, 0.0200179144, 103.560653],
+    };
+    /// Dense flint glass, which disperses light far more than crown glass.
+    pub const SF11: Self = Self::Sellmeier {
+        b: [1.73759695, 0.313747346, 1.89878101],
+        c: [0.013188707, 0.0623068142, 155.23629],
+    };
+
+    /// `wavelength` is in nanometres.
+    pub fn index_of_refraction(&self, wavelength: f64) -> f64 {
+        let lambda2 = (wavelength / 1000.).powi(2);
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / lambda2,
+            Dispersion::Sellmeier { b, c } => (1.
+                + (0..3)
+                    .map(|i| b[i] * lambda2 / (lambda2 - c[i]))
+                    .sum::<f64>())
+            .sqrt(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DiffuseLight {
     texture: Arc<dyn Texture>,
+    two_sided: bool,
+    profile: Option<Arc<IesProfile>>,
 }
 
 #[cfg(feature = "hit_counters")]
@@ -497,19 +710,74 @@ pub(crate) static LIGHT_HIT_COUNTER: AtomicU32 = AtomicU32::new(0);
 
 impl DiffuseLight {
     pub fn new(texture: Arc<dyn Texture>) -> Self {
-        Self { texture }
+        Self {
+            texture,
+            two_sided: true,
+            profile: None,
+        }
     }
 
     pub fn new_with_colour(colour: Colour) -> Self {
         Self::new(Arc::new(SolidColour(colour)))
     }
+
+    /// Fluxes are spread over `area`, which counts both sides of two sided lights. `None`
+    /// unless the brightness is in nits or a flux.
+    pub fn new_with_emission(emission: Emission, area: f64) -> Option<Self> {
+        Some(Self::new_with_colour(emission.radiance(area)?))
+    }
+
+    /// One sided lights only emit on the side the outward normal points to.
+    pub fn with_two_sided(self, two_sided: bool) -> Self {
+        Self { two_sided, ..self }
+    }
+
+    /// Scales the emission by the vertical angles of the profile, measured from the normal.
+    pub fn with_profile(self, profile: Arc<IesProfile>) -> Self {
+        Self {
+            profile: Some(profile),
+            ..self
+        }
+    }
 }
 
 impl Material for DiffuseLight {
-    fn emitted(&self, u: f64, v: f64, point: Point3) -> Colour {
+    fn emitted(&self, u: f64, v: f64, point: Point3, colours: PathColours) -> Colour {
         #[cfg(feature = "hit_counters")]
         LIGHT_HIT_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        self.texture.get_colour(u, v, point)
+        self.texture.get_colour(u, v, point).to_illuminant(colours)
+    }
+
+    fn emitted_rec(&self, ray_in: &Ray, rec: &HitRecord<'_>, colours: PathColours) -> Colour {
+        if !self.two_sided && !rec.is_front_face() {
+            return Colour::default();
+        }
+        let emitted = self.emitted(rec.get_u(), rec.get_v(), rec.get_p(), colours);
+        match &self.profile {
+            Some(profile) => {
+                let cos_theta = -ray_in.get_direction().normalize().dot(rec.get_normal());
+                emitted * profile.evaluate(cos_theta.clamp(-1., 1.).acos().to_degrees(), 0.)
+            }
+            None => emitted,
+        }
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+
+    /// Textures are only sampled at their center, which is good enough to rank lights.
+    fn emitted_power(&self) -> f64 {
+        let sides = if self.two_sided { 2. } else { 1. };
+        sides
+            * self
+                .texture
+                .get_colour(0.5, 0.5, Point3::new(0., 0., 0.))
+                .luminance()
+    }
+
+    fn is_two_sided(&self) -> bool {
+        self.two_sided
     }
 }
 
@@ -534,17 +802,20 @@ impl Material for Isotropic {
         &self,
         _ray_in: &Ray,
         rec: &HitRecord<'_>,
+        colours: PathColours,
         _rng: &mut dyn rand::RngCore,
     ) -> Option<ScatterRecord> {
         Some(ScatterRecord {
             attenuation: self
                 .texture
-                .get_colour(rec.get_u(), rec.get_v(), rec.get_p()),
+                .get_colour(rec.get_u(), rec.get_v(), rec.get_p())
+                .to_reflectance(colours),
             scatter_reflect: ScatterReflect::Scatter(Box::new(SpherePdf)),
+            terminates_secondary: false,
         })
     }
 
-    fn emitted(&self, _u: f64, _v: f64, _point: Point3) -> Colour {
+    fn emitted(&self, _u: f64, _v: f64, _point: Point3, _colours: PathColours) -> Colour {
         Colour::new(0., 0., 0.)
     }
 
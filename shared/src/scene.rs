@@ -0,0 +1,115 @@
+mod animation;
+mod graph;
+pub use animation::AnimatedScene;
+pub use graph::SceneNode;
+
+use crate::{
+    hittable::{BoundedHittable, Hittable},
+    hittable_collections::{light_list::LightList, light_tree::LightTree},
+    lights::PunctualLight,
+};
+
+/// Everything [`Camera::render_scene`](crate::camera::Camera::render_scene) needs besides the
+/// camera itself.
+#[derive(Debug)]
+pub struct Scene {
+    world: Box<dyn BoundedHittable>,
+    lights: Option<Box<dyn BoundedHittable>>,
+    punctual_lights: Vec<Box<dyn PunctualLight>>,
+    light_sampling: LightSampling,
+}
+
+/// How the emissive objects of a [`Scene`] without explicit lights are sampled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LightSampling {
+    /// Picks lights proportionally to their power
+    #[default]
+    Power,
+    /// Picks lights with a [`LightTree`], which takes into account where they are, better for
+    /// scenes with many lights
+    Tree,
+}
+
+impl Scene {
+    /// Without explicit lights the emissive objects of `world` are sampled.
+    pub fn new(world: Box<dyn BoundedHittable>) -> Self {
+        Self {
+            world,
+            lights: None,
+            punctual_lights: Vec::new(),
+            light_sampling: LightSampling::Power,
+        }
+    }
+
+    /// Objects sampled as lights instead of the emissive objects of the world, they can also be
+    /// invisible guides like glass spheres.
+    pub fn with_lights(self, lights: Box<dyn BoundedHittable>) -> Self {
+        Self {
+            lights: Some(lights),
+            ..self
+        }
+    }
+
+    pub fn with_light_sampling(self, light_sampling: LightSampling) -> Self {
+        Self {
+            light_sampling,
+            ..self
+        }
+    }
+
+    pub fn with_punctual_light<T>(mut self, light: T) -> Self
+    where
+        T: PunctualLight + 'static,
+    {
+        self.punctual_lights.push(Box::new(light));
+        self
+    }
+
+    pub fn get_world(&self) -> &dyn BoundedHittable {
+        self.world.as_ref()
+    }
+
+    pub fn get_punctual_lights(&self) -> &[Box<dyn PunctualLight>] {
+        &self.punctual_lights
+    }
+
+    /// The lights to sample, `None` if there's nothing to sample.
+    pub fn get_lights(&self) -> Option<SceneLights<'_>> {
+        match &self.lights {
+            Some(lights) => Some(SceneLights::Explicit(lights.as_ref())),
+            None => match self.light_sampling {
+                LightSampling::Power => {
+                    let lights = LightList::from_world(self.world.as_ref());
+                    (!lights.is_empty()).then_some(SceneLights::Derived(lights))
+                }
+                LightSampling::Tree => {
+                    let lights = LightTree::from_world(self.world.as_ref());
+                    (!lights.is_empty()).then_some(SceneLights::Tree(lights))
+                }
+            },
+        }
+    }
+}
+
+impl From<(Box<dyn BoundedHittable>, Box<dyn BoundedHittable>)> for Scene {
+    fn from((world, lights): (Box<dyn BoundedHittable>, Box<dyn BoundedHittable>)) -> Self {
+        Self::new(world).with_lights(lights)
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneLights<'a> {
+    Explicit(&'a dyn BoundedHittable),
+    Derived(LightList<'a>),
+    Tree(LightTree<'a>),
+}
+
+impl SceneLights<'_> {
+    pub fn as_hittable(&self) -> &dyn Hittable {
+        match self {
+            SceneLights::Explicit(lights) => *lights,
+            SceneLights::Derived(lights) => lights,
+            SceneLights::Tree(lights) => lights,
+        }
+    }
+}
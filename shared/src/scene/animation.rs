@@ -0,0 +1,198 @@
+use std::{
+    fmt::{self, Debug},
+    sync::{Arc, OnceLock},
+};
+
+use crate::{
+    animation::AnimatedCamera,
+    camera::CameraBuilder,
+    hittable::BoundedHittable,
+    hittable_collections::{bvh::BoundedVolumeHierarchy, hittable_list::HittableList},
+    scene::Scene,
+};
+
+type AnimatedObject = Box<dyn Fn(f64) -> Arc<dyn BoundedHittable> + Send + Sync>;
+
+/// Scene changing over time, made of static objects and of objects built again for each frame.
+///
+/// By default the hierarchy of the static objects is built for the first frame and then reused,
+/// so only the animated objects are rebuilt, see
+/// [`with_static_reuse`](AnimatedScene::with_static_reuse).
+pub struct AnimatedScene {
+    static_objects: Vec<Arc<dyn BoundedHittable>>,
+    static_world: OnceLock<Arc<dyn BoundedHittable>>,
+    reuse_static: bool,
+    animated: Vec<AnimatedObject>,
+    lights: Option<Arc<dyn BoundedHittable>>,
+    camera: CameraBuilder,
+    camera_animation: AnimatedCamera,
+}
+
+impl AnimatedScene {
+    pub fn new(camera: CameraBuilder) -> Self {
+        Self {
+            static_objects: Vec::new(),
+            static_world: OnceLock::new(),
+            reuse_static: true,
+            animated: Vec::new(),
+            lights: None,
+            camera,
+            camera_animation: AnimatedCamera::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_static<T: BoundedHittable + 'static>(mut self, object: T) -> Self {
+        self.static_objects.push(Arc::new(object));
+        self.static_world = OnceLock::new();
+        self
+    }
+
+    /// Object given by `f` at the time of each frame, which can use
+    /// [`Animated`](crate::animation::Animated) values for its transformation or its material.
+    #[must_use]
+    pub fn with_animated<F, T>(mut self, f: F) -> Self
+    where
+        F: Fn(f64) -> T + Send + Sync + 'static,
+        T: BoundedHittable + 'static,
+    {
+        self.animated.push(Box::new(move |time| Arc::new(f(time))));
+        self
+    }
+
+    /// Objects sampled as lights in every frame, see [`Scene::with_lights`], otherwise the
+    /// emissive objects of each frame are sampled.
+    #[must_use]
+    pub fn with_lights<T: BoundedHittable + 'static>(self, lights: T) -> Self {
+        Self {
+            lights: Some(Arc::new(lights)),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_camera_animation(self, camera_animation: AnimatedCamera) -> Self {
+        Self {
+            camera_animation,
+            ..self
+        }
+    }
+
+    /// Whether the hierarchy of the static objects is kept between frames, otherwise every
+    /// frame gets a single hierarchy over all its objects.
+    #[must_use]
+    pub fn with_static_reuse(self, reuse_static: bool) -> Self {
+        Self {
+            reuse_static,
+            ..self
+        }
+    }
+
+    /// The camera before the animation is applied.
+    pub fn get_camera(&self) -> CameraBuilder {
+        self.camera.clone()
+    }
+
+    pub fn map_camera(self, f: impl FnOnce(CameraBuilder) -> CameraBuilder) -> Self {
+        Self {
+            camera: f(self.camera),
+            ..self
+        }
+    }
+
+    pub fn camera_at(&self, time: f64) -> CameraBuilder {
+        self.camera_animation.apply(self.camera.clone(), time)
+    }
+
+    pub fn get_lights(&self) -> Option<&dyn BoundedHittable> {
+        self.lights.as_deref()
+    }
+
+    /// The hierarchy of the static objects, built the first time it's needed.
+    pub fn get_static_world(&self) -> Arc<dyn BoundedHittable> {
+        self.static_world
+            .get_or_init(|| match self.static_objects.as_slice() {
+                [object] => object.clone(),
+                objects => {
+                    let mut list = HittableList::default();
+                    objects.iter().for_each(|object| list.add(object.clone()));
+                    hierarchy(list).into()
+                }
+            })
+            .clone()
+    }
+
+    pub fn world_at(&self, time: f64) -> Box<dyn BoundedHittable> {
+        let mut animated = HittableList::default();
+        self.animated
+            .iter()
+            .for_each(|object| animated.add(object(time)));
+        if !self.reuse_static {
+            self.static_objects
+                .iter()
+                .for_each(|object| animated.add(object.clone()));
+            return hierarchy(animated);
+        }
+        let static_world = self.get_static_world();
+        if animated.is_empty() {
+            return Box::new(static_world);
+        }
+        let mut world = HittableList::default();
+        world.add(static_world);
+        world.add(BoundedVolumeHierarchy::from(animated));
+        Box::new(world)
+    }
+
+    pub fn scene_at(&self, time: f64) -> Scene {
+        let scene = Scene::new(self.world_at(time));
+        match &self.lights {
+            Some(lights) => scene.with_lights(Box::new(lights.clone())),
+            None => scene,
+        }
+    }
+}
+
+/// Hierarchy over the objects of `list`, which can't be built without objects.
+fn hierarchy(list: HittableList) -> Box<dyn BoundedHittable> {
+    if list.is_empty() {
+        Box::new(list)
+    } else {
+        Box::new(BoundedVolumeHierarchy::from(list))
+    }
+}
+
+impl Debug for AnimatedScene {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AnimatedScene")
+            .field("static_objects", &self.static_objects)
+            .field("reuse_static", &self.reuse_static)
+            .field("animated", &self.animated.len())
+            .field("lights", &self.lights)
+            .field("camera", &self.camera)
+            .field("camera_animation", &self.camera_animation)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A still scene, whose world is kept as it is in every frame.
+impl
+    From<(
+        Box<dyn BoundedHittable>,
+        Box<dyn BoundedHittable>,
+        CameraBuilder,
+    )> for AnimatedScene
+{
+    fn from(
+        (world, lights, camera): (
+            Box<dyn BoundedHittable>,
+            Box<dyn BoundedHittable>,
+            CameraBuilder,
+        ),
+    ) -> Self {
+        Self {
+            static_objects: vec![Arc::from(world)],
+            lights: Some(Arc::from(lights)),
+            ..Self::new(camera)
+        }
+    }
+}
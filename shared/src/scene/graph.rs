@@ -0,0 +1,246 @@
+use std::{fmt::Debug, ops::RangeInclusive, sync::Arc};
+
+use geometry::{
+    aabox::AABBox,
+    bounded::Bounded,
+    transformations::{Instance, Transformation},
+    vec3::{Point3, Vec3},
+};
+
+use crate::{
+    hittable::{BoundedHittable, HitRecord, Hittable},
+    hittable_collections::{
+        bvh::BoundedVolumeHierarchy, hittable_list::HittableList, light_tree::LightBounds,
+    },
+    material::{DynMaterial, Material},
+    ray::Ray,
+};
+
+/// Named node of a scene graph, with objects and child nodes placed by its transformation.
+///
+/// Transformations are relative to the parent node, and objects made with
+/// [`INHERITED_PTR`](crate::material::INHERITED_PTR) as their material take the closest one set
+/// on the node or its ancestors. The graph is flattened into a [`BoundedVolumeHierarchy`] by
+/// [`build`](SceneNode::build), so it can still be changed and built again.
+#[derive(Debug, Default, Clone)]
+pub struct SceneNode {
+    name: String,
+    transformation: Transformation,
+    material: Option<DynMaterial>,
+    objects: Vec<Arc<dyn BoundedHittable>>,
+    children: Vec<SceneNode>,
+}
+
+impl SceneNode {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    #[must_use]
+    pub fn with_transformation<T: Into<Transformation>>(mut self, transformation: T) -> Self {
+        self.set_transformation(transformation);
+        self
+    }
+
+    /// Applies `transformation` after the current one.
+    #[must_use]
+    pub fn transform<T: Into<Transformation>>(mut self, transformation: T) -> Self {
+        self.transformation = self.transformation.then(&transformation.into());
+        self
+    }
+
+    /// # Panics
+    /// If the material can't be turned into a [`DynMaterial`]
+    #[must_use]
+    pub fn with_material<T>(mut self, material: T) -> Self
+    where
+        T: TryInto<DynMaterial>,
+        <T as TryInto<DynMaterial>>::Error: Debug,
+    {
+        self.set_material(material);
+        self
+    }
+
+    #[must_use]
+    pub fn with_object<T: BoundedHittable + 'static>(mut self, object: T) -> Self {
+        self.add_object(object);
+        self
+    }
+
+    #[must_use]
+    pub fn with_child(mut self, child: SceneNode) -> Self {
+        self.add_child(child);
+        self
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub const fn get_transformation(&self) -> Transformation {
+        self.transformation
+    }
+
+    pub fn set_transformation<T: Into<Transformation>>(&mut self, transformation: T) {
+        self.transformation = transformation.into();
+    }
+
+    pub const fn get_material(&self) -> Option<&DynMaterial> {
+        self.material.as_ref()
+    }
+
+    /// # Panics
+    /// If the material can't be turned into a [`DynMaterial`]
+    pub fn set_material<T>(&mut self, material: T)
+    where
+        T: TryInto<DynMaterial>,
+        <T as TryInto<DynMaterial>>::Error: Debug,
+    {
+        self.material = Some(material.try_into().unwrap());
+    }
+
+    pub fn add_object<T: BoundedHittable + 'static>(&mut self, object: T) {
+        self.objects.push(Arc::new(object));
+    }
+
+    pub fn add_child(&mut self, child: SceneNode) {
+        self.children.push(child);
+    }
+
+    pub fn get_children(&self) -> &[SceneNode] {
+        &self.children
+    }
+
+    /// First node called `name`, looking at a node before its children.
+    pub fn find(&self, name: &str) -> Option<&SceneNode> {
+        if self.name == name {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(name))
+    }
+
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut SceneNode> {
+        if self.name == name {
+            return Some(self);
+        }
+        self.children
+            .iter_mut()
+            .find_map(|child| child.find_mut(name))
+    }
+
+    /// Flattens the objects of this node and its descendants into a single hierarchy, the
+    /// objects themselves are shared and not copied.
+    pub fn build(&self) -> BoundedVolumeHierarchy {
+        let mut list = HittableList::default();
+        self.flatten(Transformation::default(), None, &mut list);
+        BoundedVolumeHierarchy::from(list)
+    }
+
+    fn flatten(
+        &self,
+        parent: Transformation,
+        inherited: Option<&DynMaterial>,
+        list: &mut HittableList,
+    ) {
+        let transformation = self.transformation.then(&parent);
+        let material = self.material.as_ref().or(inherited);
+        for object in &self.objects {
+            match material {
+                Some(material) => list.add(Instance::instance(
+                    &Arc::new(WithMaterial {
+                        object: object.clone(),
+                        material: material.clone(),
+                    }),
+                    transformation,
+                )),
+                None => list.add(Instance::instance(object, transformation)),
+            }
+        }
+        for child in &self.children {
+            child.flatten(transformation, material, list);
+        }
+    }
+}
+
+/// Object whose parts made with [`INHERITED_PTR`](crate::material::INHERITED_PTR) use `material`.
+#[derive(Debug)]
+struct WithMaterial {
+    object: Arc<dyn BoundedHittable>,
+    material: DynMaterial,
+}
+
+impl Hittable for WithMaterial {
+    fn hit(&self, r: &Ray, range: RangeInclusive<f64>) -> Option<HitRecord<'_>> {
+        self.object.hit(r, range).map(|mut rec| {
+            if rec.get_material().is_inherited() {
+                rec.set_material(self.material.as_ref());
+            }
+            rec
+        })
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3, rng: &mut dyn rand::RngCore) -> Vec3 {
+        self.object.random(origin, rng)
+    }
+
+    fn sample_surface(&self, rng: &mut dyn rand::RngCore) -> Option<HitRecord<'_>> {
+        self.object.sample_surface(rng).map(|mut rec| {
+            if rec.get_material().is_inherited() {
+                rec.set_material(self.material.as_ref());
+            }
+            rec
+        })
+    }
+
+    fn emissive_objects<'a>(&'a self, lights: &mut Vec<&'a dyn Hittable>) {
+        // The parts with their own material aren't affected, the whole object is sampled if the
+        // inherited material emits light
+        if self.material.is_emissive() {
+            lights.push(self);
+        } else {
+            self.object.emissive_objects(lights);
+        }
+    }
+
+    fn light_power(&self) -> Option<f64> {
+        if self.material.is_emissive() {
+            Some(self.material.emitted_power() * self.object.get_surface_area())
+        } else {
+            self.object.light_power()
+        }
+    }
+
+    fn light_bounds(&self) -> Option<LightBounds> {
+        if self.material.is_emissive() {
+            Some(LightBounds::omnidirectional(
+                self.get_aabbox(),
+                self.light_power()?,
+            ))
+        } else {
+            self.object.light_bounds()
+        }
+    }
+
+    fn hit_cost(&self, r: &Ray, range: RangeInclusive<f64>) -> u32 {
+        self.object.hit_cost(r, range)
+    }
+}
+
+impl Bounded for WithMaterial {
+    fn get_aabbox(&self) -> AABBox {
+        self.object.get_aabbox()
+    }
+
+    fn get_surface_area(&self) -> f64 {
+        self.object.get_surface_area()
+    }
+}
+
+impl BoundedHittable for WithMaterial {}
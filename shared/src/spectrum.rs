@@ -0,0 +1,275 @@
+//! Spectral rendering, where the three channels of a [`Colour`] carry the light of three
+//! wavelengths instead of red, green and blue.
+//!
+//! The wavelengths of a sample are carried by the [`PathColours`] of the
+//! [`Lighting`](crate::integrator::Lighting) its path is traced with, and RGB colours are turned
+//! into spectra where they enter the path with [`Colour::to_reflectance`] and
+//! [`Colour::to_illuminant`], which only convert them to the working space outside of spectral
+//! rendering.
+
+use std::sync::LazyLock;
+
+use crate::colour::{Colour, ColourSpace};
+
+/// Shortest wavelength sampled, in nanometres
+pub const MIN_WAVELENGTH: f64 = 360.;
+/// Longest wavelength sampled, in nanometres
+pub const MAX_WAVELENGTH: f64 = 830.;
+const WAVELENGTH_RANGE: f64 = MAX_WAVELENGTH - MIN_WAVELENGTH;
+
+/// Wavelengths carried by a path, see "Hero wavelength spectral sampling" by Wilkie et al.
+///
+/// The hero wavelength is sampled uniformly and the other two are spread evenly after it,
+/// wrapping around the visible range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampledWavelengths {
+    /// In nanometres
+    lambda: [f64; 3],
+    /// Whether only the hero wavelength is still followed
+    hero_only: bool,
+}
+
+impl SampledWavelengths {
+    /// `u` is a random number in `[0, 1)`.
+    pub fn sample(u: f64) -> Self {
+        Self {
+            lambda: std::array::from_fn(|i| {
+                let offset = (u + i as f64 / 3.).fract();
+                MIN_WAVELENGTH + offset * WAVELENGTH_RANGE
+            }),
+            hero_only: false,
+        }
+    }
+
+    pub const fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    pub const fn get_wavelengths(&self) -> [f64; 3] {
+        self.lambda
+    }
+
+    /// Turns light sampled at these wavelengths into RGB in `space`, so a flat spectrum of `1` is
+    /// white.
+    pub fn to_rgb(&self, values: Colour, space: ColourSpace) -> Colour {
+        let values = values.into_inner().to_array();
+        let xyz = self
+            .lambda
+            .iter()
+            .zip(values)
+            .fold([0.; 3], |acc, (&lambda, value)| {
+                let cmf = colour_matching(lambda);
+                std::array::from_fn(|i| acc[i] + cmf[i] * value)
+            });
+        // Each wavelength is sampled with density `1 / WAVELENGTH_RANGE`
+        let xyz = xyz.map(|v| v * WAVELENGTH_RANGE / 3.);
+        xyz_to_rgb(xyz).convert(ColourSpace::Srgb, space)
+    }
+
+    /// Stops following every wavelength but the hero one, for interactions which split them like
+    /// dispersion. Returns what the throughput of the path is multiplied by: if the others were
+    /// still being followed they are dropped and the hero wavelength makes up for them.
+    pub fn terminate_secondary(&mut self) -> Colour {
+        if self.hero_only {
+            return Colour::new(1., 1., 1.);
+        }
+        self.hero_only = true;
+        let followed = self.lambda.len() as f64;
+        Colour::new(followed, 0., 0.)
+    }
+}
+
+/// What the colours carried by a path are: RGB in the working space of the camera, or light at
+/// a few sampled wavelengths.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathColours {
+    working_space: ColourSpace,
+    /// `None` when rendering in RGB
+    wavelengths: Option<SampledWavelengths>,
+}
+
+impl PathColours {
+    pub const fn new(working_space: ColourSpace, wavelengths: Option<SampledWavelengths>) -> Self {
+        Self {
+            working_space,
+            wavelengths,
+        }
+    }
+
+    pub const fn get_working_space(&self) -> ColourSpace {
+        self.working_space
+    }
+
+    pub const fn get_wavelengths(&self) -> Option<SampledWavelengths> {
+        self.wavelengths
+    }
+
+    /// Turns light carried by the path into RGB in the working space.
+    pub fn to_rgb(&self, colour: Colour) -> Colour {
+        match self.wavelengths {
+            Some(wavelengths) => wavelengths.to_rgb(colour, self.working_space),
+            None => colour,
+        }
+    }
+
+    /// See [`SampledWavelengths::terminate_secondary`], nothing is split when rendering in RGB.
+    pub fn terminate_secondary(&mut self) -> Colour {
+        match &mut self.wavelengths {
+            Some(wavelengths) => wavelengths.terminate_secondary(),
+            None => Colour::new(1., 1., 1.),
+        }
+    }
+}
+
+/// CIE 1931 colour matching functions, fitted with Gaussians as in "Simple analytic
+/// approximations to the CIE XYZ color matching functions" by Wyman et al.
+pub(crate) fn colour_matching(lambda: f64) -> [f64; 3] {
+    let g = |mu: f64, sigma_low: f64, sigma_high: f64| {
+        let t = (lambda - mu) / if lambda < mu { sigma_low } else { sigma_high };
+        (-0.5 * t * t).exp()
+    };
+    [
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    ]
+}
+
+/// Integrates `f` over the sampled range, one nanometre at a time.
+pub(crate) fn integrate(f: impl Fn(f64) -> [f64; 3]) -> [f64; 3] {
+    (0..WAVELENGTH_RANGE as usize)
+        .map(|i| f(MIN_WAVELENGTH + i as f64 + 0.5))
+        .fold([0.; 3], |acc, v| std::array::from_fn(|i| acc[i] + v[i]))
+}
+
+/// Linear sRGB of a flat spectrum of `1`, used to make it white.
+static WHITE: LazyLock<[f64; 3]> = LazyLock::new(|| xyz_to_linear_srgb(integrate(colour_matching)));
+
+/// CIE XYZ to linear sRGB, whose white is D65.
+pub(crate) fn xyz_to_linear_srgb([x, y, z]: [f64; 3]) -> [f64; 3] {
+    [
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    ]
+}
+
+fn xyz_to_rgb(xyz: [f64; 3]) -> Colour {
+    let rgb = xyz_to_linear_srgb(xyz);
+    Colour::from_array(std::array::from_fn(|i| rgb[i] / WHITE[i]))
+}
+
+/// Smooth blue, green and red spectra which add up to a flat one, spectra are upsampled as a
+/// mix of them.
+fn basis(lambda: f64) -> [f64; 3] {
+    let sigmoid = |x: f64| 1. / (1. + (-x / 12.).exp());
+    let blue = 1. - sigmoid(lambda - 490.);
+    let red = sigmoid(lambda - 590.);
+    [red, 1. - red - blue, blue]
+}
+
+/// Turns linear sRGB into the weights of the [`basis`] spectra.
+static RGB_TO_BASIS: LazyLock<[[f64; 3]; 3]> = LazyLock::new(|| {
+    // Columns are the colours of the basis spectra
+    let columns = [0, 1, 2].map(|k| {
+        xyz_to_rgb(integrate(|lambda| {
+            colour_matching(lambda).map(|v| v * basis(lambda)[k])
+        }))
+        .into_inner()
+        .to_array()
+    });
+    invert(std::array::from_fn(|i| columns.map(|column| column[i])))
+});
+
+fn invert(m: [[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let cofactor = |i: usize, j: usize| {
+        let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+        let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let determinant: f64 = (0..3).map(|j| m[0][j] * cofactor(0, j)).sum();
+    std::array::from_fn(|i| std::array::from_fn(|j| cofactor(j, i) / determinant))
+}
+
+impl Colour {
+    /// Reflectance carried by a path with `colours` for this linear sRGB colour. When rendering
+    /// spectrally it's the spectrum at the wavelengths of the path, kept in `[0, 1]` so it still
+    /// reflects at most all of the light, otherwise it's the colour in the working space.
+    pub fn to_reflectance(self, colours: PathColours) -> Self {
+        self.upsample(colours, |weight| weight.clamp(0., 1.))
+    }
+
+    /// Like [`Colour::to_reflectance`] for light sources, whose spectra aren't bounded.
+    pub fn to_illuminant(self, colours: PathColours) -> Self {
+        self.upsample(colours, |weight| weight.max(0.))
+    }
+
+    fn upsample(self, colours: PathColours, clamp: impl Fn(f64) -> f64) -> Self {
+        let Some(wavelengths) = colours.wavelengths else {
+            return self.convert(ColourSpace::Srgb, colours.working_space);
+        };
+        let rgb = self.into_inner().to_array();
+        let weights = RGB_TO_BASIS.map(|row| clamp((0..3).map(|i| row[i] * rgb[i]).sum()));
+        Colour::from_array(wavelengths.lambda.map(|lambda| {
+            let basis = basis(lambda);
+            (0..3).map(|k| weights[k] * basis[k]).sum()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng as _, SeedableRng as _, rngs::SmallRng};
+
+    use super::{PathColours, SampledWavelengths};
+    use crate::colour::{Colour, ColourSpace};
+
+    /// Averages the RGB of `colour` upsampled at many wavelengths.
+    fn round_trip(colour: Colour) -> [f64; 3] {
+        let mut rng = SmallRng::seed_from_u64(3);
+        let samples = 100_000;
+        let sum = (0..samples).fold(Colour::default(), |acc, _| {
+            let wavelengths = SampledWavelengths::sample(rng.r#gen());
+            let colours = PathColours::new(ColourSpace::Srgb, Some(wavelengths));
+            acc + colours.to_rgb(colour.to_reflectance(colours))
+        });
+        (sum / f64::from(samples)).into_inner().to_array()
+    }
+
+    #[test]
+    fn colours_survive_upsampling() {
+        for rgb in [
+            [1., 1., 1.],
+            [0.65, 0.05, 0.05],
+            [0.12, 0.45, 0.15],
+            [0.2, 0.3, 0.8],
+        ] {
+            let out = round_trip(Colour::from_array(rgb));
+            assert!(
+                out.iter()
+                    .zip(rgb)
+                    .all(|(out, rgb)| (out - rgb).abs() < 0.02),
+                "{rgb:?} came back as {out:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn secondary_wavelengths_are_only_terminated_once() {
+        let mut wavelengths = SampledWavelengths::sample(0.3);
+        let first = wavelengths.terminate_secondary().into_inner().to_array();
+        assert_eq!(first, [3., 0., 0.]);
+        let second = wavelengths.terminate_secondary().into_inner().to_array();
+        assert_eq!(second, [1., 1., 1.]);
+    }
+
+    #[test]
+    fn rgb_is_unchanged_outside_spectral_rendering() {
+        let colours = PathColours::new(ColourSpace::Srgb, None);
+        let colour = Colour::new(0.3, 0.6, 0.9).to_reflectance(colours);
+        assert_eq!(colour.into_inner().to_array(), [0.3, 0.6, 0.9]);
+        let colours = PathColours::new(ColourSpace::AcesCg, None);
+        let colour = Colour::new(0.3, 0.6, 0.9).to_reflectance(colours);
+        assert_ne!(colour.into_inner().to_array(), [0.3, 0.6, 0.9]);
+    }
+}
@@ -6,7 +6,10 @@ use std::{
 
 use geometry::vec3::{Point3, Vec3};
 
-use crate::{colour::Colour, perlin::Perlin};
+use crate::{
+    colour::{Colour, ColourSpace},
+    perlin::Perlin,
+};
 
 pub trait Texture: Debug + Sync + Send {
     fn get_colour(&self, u: f64, v: f64, point: Point3) -> Colour;
@@ -15,6 +18,13 @@ pub trait Texture: Debug + Sync + Send {
 #[derive(Debug, Clone, Copy)]
 pub struct SolidColour(pub Colour);
 
+impl SolidColour {
+    /// `colour` is given in `space` instead of linear sRGB.
+    pub fn new_in(colour: Colour, space: ColourSpace) -> Self {
+        Self(colour.convert(space, ColourSpace::Srgb))
+    }
+}
+
 impl Texture for SolidColour {
     fn get_colour(&self, _u: f64, _v: f64, _point: Point3) -> Colour {
         self.0
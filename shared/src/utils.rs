@@ -76,6 +76,13 @@ pub mod slice {
                 .filter_map(move |obj| obj.bounded_hit(r, start..=end))
                 .min_by(|a, b| a.get_t().total_cmp(&b.get_t()))
         }
+
+        fn hit_cost(&self, r: &Ray, range: RangeInclusive<f64>) -> u32 {
+            unsafe { std::slice::from_raw_parts(self.ptr.cast_const(), self.len) }
+                .iter()
+                .map(|obj| obj.bounded_hit_cost(r, range.clone()))
+                .sum()
+        }
     }
 }
 
@@ -160,3 +167,232 @@ pub mod random_utils {
         }
     }
 }
+
+pub mod distribution {
+    /// Piecewise constant distribution over `[0, 1)`.
+    #[derive(Debug, Clone)]
+    pub struct Distribution1D {
+        func: Vec<f64>,
+        cdf: Vec<f64>,
+        integral: f64,
+    }
+
+    impl Distribution1D {
+        /// Negative values are treated as 0, if every value is 0 the distribution is uniform.
+        pub fn new(func: Vec<f64>) -> Self {
+            assert!(!func.is_empty(), "Distribution1D shouldn't be empty");
+            let n = func.len() as f64;
+            let func: Vec<_> = func.into_iter().map(|v| v.max(0.)).collect();
+            let mut cdf = Vec::with_capacity(func.len() + 1);
+            cdf.push(0.);
+            func.iter().fold(0., |accum, v| {
+                let accum = accum + v / n;
+                cdf.push(accum);
+                accum
+            });
+            let integral = *cdf.last().unwrap();
+            if integral > 0. {
+                cdf.iter_mut().for_each(|v| *v /= integral);
+            } else {
+                cdf.iter_mut()
+                    .enumerate()
+                    .for_each(|(i, v)| *v = i as f64 / n);
+            }
+            Self {
+                func,
+                cdf,
+                integral,
+            }
+        }
+
+        pub fn len(&self) -> usize {
+            self.func.len()
+        }
+
+        #[must_use]
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        pub const fn integral(&self) -> f64 {
+            self.integral
+        }
+
+        /// Maps `u` in `[0, 1)` to a sample in `[0, 1)`, returning the sample, its density and
+        /// the index of the segment it fell in.
+        pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+            let offset = (self.cdf.partition_point(|&v| v <= u).max(1) - 1).min(self.len() - 1);
+            let width = self.cdf[offset + 1] - self.cdf[offset];
+            let du = if width > 0. {
+                (u - self.cdf[offset]) / width
+            } else {
+                0.
+            };
+            let x = ((offset as f64 + du) / self.len() as f64).min(1. - f64::EPSILON);
+            (x, self.pdf(offset), offset)
+        }
+
+        /// Density of the segment `index`.
+        pub fn pdf(&self, index: usize) -> f64 {
+            if self.integral > 0. {
+                self.func[index] / self.integral
+            } else {
+                1.
+            }
+        }
+
+        /// Probability of picking the segment `index`, when using the distribution to choose
+        /// between discrete items.
+        pub fn discrete_pdf(&self, index: usize) -> f64 {
+            self.pdf(index) / self.len() as f64
+        }
+
+        pub fn index_of(&self, x: f64) -> usize {
+            ((x * self.len() as f64) as usize).min(self.len() - 1)
+        }
+    }
+
+    /// Piecewise constant distribution over `[0, 1)^2`, stored as rows along `v`.
+    #[derive(Debug, Clone)]
+    pub struct Distribution2D {
+        conditional: Vec<Distribution1D>,
+        marginal: Distribution1D,
+    }
+
+    impl Distribution2D {
+        /// `func` is in row major order with `width` columns.
+        pub fn new(func: &[f64], width: usize) -> Self {
+            assert!(
+                width > 0 && func.len().is_multiple_of(width),
+                "Distribution2D needs complete rows"
+            );
+            let conditional: Vec<_> = func
+                .chunks_exact(width)
+                .map(|row| Distribution1D::new(row.to_vec()))
+                .collect();
+            let marginal =
+                Distribution1D::new(conditional.iter().map(Distribution1D::integral).collect());
+            Self {
+                conditional,
+                marginal,
+            }
+        }
+
+        /// Returns the sampled `(u, v)` and its density.
+        pub fn sample(&self, u: [f64; 2]) -> ([f64; 2], f64) {
+            let (v, pdf_v, row) = self.marginal.sample(u[1]);
+            let (u, pdf_u, _) = self.conditional[row].sample(u[0]);
+            ([u, v], pdf_u * pdf_v)
+        }
+
+        pub fn pdf(&self, [u, v]: [f64; 2]) -> f64 {
+            let row = self.marginal.index_of(v);
+            let conditional = &self.conditional[row];
+            conditional.pdf(conditional.index_of(u)) * self.marginal.pdf(row)
+        }
+    }
+}
+
+pub mod polynomial {
+    use std::ops::RangeInclusive;
+
+    /// Roots of `a x^2 + b x + c` in increasing order, if there are any.
+    pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<[f64; 2]> {
+        if a.abs() < f64::EPSILON {
+            return None;
+        }
+        let discriminant = b * b - 4. * a * c;
+        (discriminant >= 0.).then_some(())?;
+        // Avoids subtracting numbers which are almost equal
+        let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+        let (x0, x1) = if q == 0. { (0., 0.) } else { (q / a, c / q) };
+        Some([x0.min(x1), x0.max(x1)])
+    }
+
+    /// Value at `x` of the polynomial whose coefficients start at the constant term.
+    pub fn evaluate(coefficients: &[f64], x: f64) -> f64 {
+        coefficients.iter().rev().fold(0., |accum, c| accum * x + c)
+    }
+
+    /// Roots in the finite `range` of the polynomial whose coefficients start at the constant
+    /// term, in increasing order.
+    ///
+    /// The roots of the derivative split the range into parts where the polynomial is
+    /// monotonic, which are bisected, so roots where it touches zero without crossing it can be
+    /// missed.
+    pub fn roots_in(coefficients: &[f64], range: RangeInclusive<f64>) -> Vec<f64> {
+        let (&start, &end) = (range.start(), range.end());
+        match coefficients {
+            [] | [_] => Vec::new(),
+            [c0, c1] => {
+                let root = -c0 / c1;
+                if range.contains(&root) {
+                    vec![root]
+                } else {
+                    Vec::new()
+                }
+            }
+            _ => {
+                let derivative: Vec<_> = coefficients
+                    .iter()
+                    .enumerate()
+                    .skip(1)
+                    .map(|(i, c)| c * i as f64)
+                    .collect();
+                let mut bounds = vec![start];
+                bounds.extend(roots_in(&derivative, range));
+                bounds.push(end);
+                let mut roots: Vec<_> = bounds
+                    .windows(2)
+                    .filter_map(|bounds| bisect(coefficients, bounds[0], bounds[1]))
+                    .collect();
+                roots.dedup();
+                roots
+            }
+        }
+    }
+
+    /// Root in `[low, high]` of a polynomial which is monotonic there.
+    fn bisect(coefficients: &[f64], mut low: f64, mut high: f64) -> Option<f64> {
+        let f_low = evaluate(coefficients, low);
+        let f_high = evaluate(coefficients, high);
+        if f_low == 0. {
+            return Some(low);
+        }
+        if f_high == 0. {
+            return Some(high);
+        }
+        (f_low.is_sign_negative() != f_high.is_sign_negative()).then_some(())?;
+        for _ in 0..100 {
+            let middle = (low + high) / 2.;
+            if middle <= low || middle >= high {
+                break;
+            }
+            if evaluate(coefficients, middle).is_sign_negative() == f_low.is_sign_negative() {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        Some((low + high) / 2.)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{roots_in, solve_quadratic};
+
+        #[test]
+        fn finds_every_root() {
+            let [x0, x1] = solve_quadratic(2., -6., 4.).unwrap();
+            assert!((x0 - 1.).abs() < 1e-12 && (x1 - 2.).abs() < 1e-12);
+            assert!(solve_quadratic(1., 0., 1.).is_none());
+            // (x + 2)(x - 0.5)(x - 1)(x - 3)
+            let roots = roots_in(&[-3., 8.5, -4., -2.5, 1.], -10. ..=10.);
+            assert_eq!(roots.len(), 4);
+            for (root, expected) in roots.iter().zip([-2., 0.5, 1., 3.]) {
+                assert!((root - expected).abs() < 1e-9, "{roots:?}");
+            }
+            assert_eq!(roots_in(&[-3., 8.5, -4., -2.5, 1.], 0. ..=0.9).len(), 1);
+        }
+    }
+}